@@ -294,6 +294,12 @@ impl errors::HttpRescue<Error> for Rescue {
         if let Some(cause) = errors::cause_ref::<inbound::policy::HttpRouteUnauthorized>(&*error) {
             return Ok(errors::SyntheticHttpResponse::permission_denied(cause));
         }
+        if let Some(cause) = errors::cause_ref::<inbound::policy::HttpRouteNotFound>(&*error) {
+            return Ok(errors::SyntheticHttpResponse::not_found(cause));
+        }
+        if let Some(cause) = errors::cause_ref::<inbound::policy::HttpRateLimited>(&*error) {
+            return Ok(errors::SyntheticHttpResponse::rate_limited(cause));
+        }
 
         tracing::warn!(error, "Unexpected error");
         Ok(errors::SyntheticHttpResponse::unexpected_error())
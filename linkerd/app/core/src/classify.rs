@@ -2,7 +2,9 @@ use crate::profiles;
 use linkerd_error::Error;
 use linkerd_http_classify as classify;
 pub use linkerd_http_classify::{CanClassify, NewClassify};
-use linkerd_proxy_http::{HasH2Reason, ResponseTimeoutError};
+use linkerd_proxy_http::{
+    balance::IsFailure, DeadlineExceededError, HasH2Reason, ResponseTimeoutError,
+};
 use std::borrow::Cow;
 use tonic as grpc;
 use tracing::trace;
@@ -144,7 +146,7 @@ impl classify::ClassifyResponse for Response {
     }
 
     fn error(self, err: &Error) -> Self::Class {
-        let msg = if err.is::<ResponseTimeoutError>() {
+        let msg = if err.is::<ResponseTimeoutError>() || err.is::<DeadlineExceededError>() {
             "timeout".into()
         } else {
             h2_error(err).into()
@@ -224,6 +226,12 @@ impl Class {
     }
 }
 
+impl IsFailure for Class {
+    fn is_failure(&self) -> bool {
+        Class::is_failure(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{Class, SuccessOrFailure};
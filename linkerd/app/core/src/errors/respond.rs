@@ -116,6 +116,24 @@ impl SyntheticHttpResponse {
         }
     }
 
+    pub fn unavailable(msg: impl ToString) -> Self {
+        Self {
+            http_status: http::StatusCode::SERVICE_UNAVAILABLE,
+            grpc_status: tonic::Code::Unavailable,
+            close_connection: false,
+            message: Cow::Owned(msg.to_string()),
+        }
+    }
+
+    pub fn deadline_exceeded(msg: impl ToString) -> Self {
+        Self {
+            http_status: http::StatusCode::GATEWAY_TIMEOUT,
+            grpc_status: tonic::Code::DeadlineExceeded,
+            close_connection: false,
+            message: Cow::Owned(msg.to_string()),
+        }
+    }
+
     pub fn unauthenticated(msg: impl ToString) -> Self {
         Self {
             http_status: http::StatusCode::FORBIDDEN,
@@ -152,6 +170,15 @@ impl SyntheticHttpResponse {
         }
     }
 
+    pub fn rate_limited(msg: impl ToString) -> Self {
+        Self {
+            http_status: http::StatusCode::TOO_MANY_REQUESTS,
+            grpc_status: tonic::Code::ResourceExhausted,
+            close_connection: false,
+            message: Cow::Owned(msg.to_string()),
+        }
+    }
+
     #[inline]
     fn message(&self) -> HeaderValue {
         match self.message {
@@ -164,7 +191,7 @@ impl SyntheticHttpResponse {
     }
 
     #[inline]
-    fn grpc_response<B: Default>(&self, emit_headers: bool) -> http::Response<B> {
+    pub(crate) fn grpc_response<B: Default>(&self, emit_headers: bool) -> http::Response<B> {
         debug!(code = %self.grpc_status, "Handling error on gRPC connection");
         let mut rsp = http::Response::builder()
             .version(http::Version::HTTP_2)
@@ -188,7 +215,7 @@ impl SyntheticHttpResponse {
     }
 
     #[inline]
-    fn http_response<B: Default>(
+    pub(crate) fn http_response<B: Default>(
         &self,
         version: http::Version,
         emit_headers: bool,
@@ -255,20 +282,13 @@ where
         let emit_headers = self.emit_headers;
 
         match req.version() {
-            http::Version::HTTP_2 => {
-                let is_grpc = req
-                    .headers()
-                    .get(http::header::CONTENT_TYPE)
-                    .and_then(|v| v.to_str().ok().map(|s| s.starts_with(GRPC_CONTENT_TYPE)))
-                    .unwrap_or(false);
-                Respond {
-                    client,
-                    rescue,
-                    is_grpc,
-                    version: http::Version::HTTP_2,
-                    emit_headers,
-                }
-            }
+            http::Version::HTTP_2 => Respond {
+                client,
+                rescue,
+                is_grpc: is_grpc(req),
+                version: http::Version::HTTP_2,
+                emit_headers,
+            },
             version => Respond {
                 client,
                 rescue,
@@ -445,6 +465,14 @@ impl<R, B> ResponseBody<R, B> {
     }
 }
 
+/// Returns true if the request has a gRPC content type.
+pub(crate) fn is_grpc<B>(req: &http::Request<B>) -> bool {
+    req.headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok().map(|s| s.starts_with(GRPC_CONTENT_TYPE)))
+        .unwrap_or(false)
+}
+
 // Copied from tonic, where it's private.
 fn code_header(code: tonic::Code) -> HeaderValue {
     use tonic::Code;
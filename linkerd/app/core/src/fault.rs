@@ -0,0 +1,282 @@
+//! Injects faults configured on a route, for chaos testing.
+//!
+//! Aborted requests are answered with a synthesized error response (rather
+//! than an error) so that they may be classified by the route's response
+//! classes and retried.
+
+use crate::{
+    errors::{respond, SyntheticHttpResponse},
+    proxy::http::{route_filter::Filter, RouteFilters},
+    svc::{self, layer, NewService, Param},
+    Error,
+};
+use futures::{future, TryFutureExt};
+use rand::{rngs::SmallRng, thread_rng, Rng, SeedableRng};
+use std::{
+    borrow::Cow,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tracing::debug;
+
+pub use crate::proxy::http::route_filter::{FaultAbort, FaultDelay, InjectFault, Ratio};
+
+#[derive(Clone, Debug)]
+pub struct NewInjectFault<N> {
+    inner: N,
+}
+
+#[derive(Clone, Debug)]
+pub struct InjectFaultService<S> {
+    faults: Arc<[InjectFault]>,
+    rng: SmallRng,
+    inner: S,
+}
+
+// === impl NewInjectFault ===
+
+impl<N> NewInjectFault<N> {
+    pub fn layer() -> impl layer::Layer<N, Service = Self> + Clone {
+        layer::mk(|inner| Self { inner })
+    }
+}
+
+impl<T, N> NewService<T> for NewInjectFault<N>
+where
+    T: Param<RouteFilters>,
+    N: NewService<T>,
+{
+    type Service = InjectFaultService<N::Service>;
+
+    fn new_service(&self, target: T) -> Self::Service {
+        let RouteFilters(filters) = target.param();
+        let faults = filters
+            .iter()
+            .filter_map(|f| match f {
+                Filter::InjectFault(fault) => Some(fault.clone()),
+                _ => None,
+            })
+            .collect();
+        InjectFaultService {
+            faults,
+            rng: SmallRng::from_rng(&mut thread_rng()).expect("RNG must initialize"),
+            inner: self.inner.new_service(target),
+        }
+    }
+}
+
+// === impl InjectFaultService ===
+
+impl<S, B, RspB> svc::Service<http::Request<B>> for InjectFaultService<S>
+where
+    S: svc::Service<http::Request<B>, Response = http::Response<RspB>>,
+    S: Clone + Send + 'static,
+    S::Error: Into<Error>,
+    S::Future: Send,
+    B: Send + 'static,
+    RspB: Default + Send + 'static,
+{
+    type Response = http::Response<RspB>;
+    type Error = Error;
+    type Future = future::Either<
+        future::MapErr<S::Future, fn(S::Error) -> Error>,
+        Pin<Box<dyn Future<Output = Result<http::Response<RspB>, Error>> + Send + 'static>>,
+    >;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let mut delay = None;
+        for InjectFault {
+            abort,
+            delay: fault_delay,
+        } in self.faults.iter()
+        {
+            if let Some(abort) = abort {
+                if sample(&mut self.rng, abort.ratio) {
+                    debug!(status = %abort.http_status, "Injecting failure");
+                    let rsp = abort_response(abort, &req);
+                    return future::Either::Right(Box::pin(future::ok(rsp)));
+                }
+            }
+
+            if let Some(FaultDelay { duration, ratio }) = fault_delay {
+                if delay.is_none() && sample(&mut self.rng, *ratio) {
+                    delay = Some(*duration);
+                }
+            }
+        }
+
+        let delay = match delay {
+            None => {
+                return future::Either::Left(
+                    self.inner
+                        .call(req)
+                        .map_err(Into::into as fn(S::Error) -> Error),
+                )
+            }
+            Some(delay) => delay,
+        };
+
+        // The request must not be dispatched until the delay elapses, so the
+        // ready service is moved into the delayed future.
+        debug!(?delay, "Injecting delay");
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        future::Either::Right(Box::pin(async move {
+            tokio::time::sleep(delay).await;
+            inner.call(req).await.map_err(Into::into)
+        }))
+    }
+}
+
+fn sample(rng: &mut SmallRng, ratio: Ratio) -> bool {
+    rng.gen_ratio(ratio.numerator(), ratio.denominator())
+}
+
+fn abort_response<B, RspB: Default>(
+    abort: &FaultAbort,
+    req: &http::Request<B>,
+) -> http::Response<RspB> {
+    let grpc_status = abort
+        .grpc_status
+        .map(|code| tonic::Code::from_i32(code.into()))
+        .unwrap_or_else(|| grpc_status_for(abort.http_status));
+    let rsp = SyntheticHttpResponse {
+        http_status: abort.http_status,
+        grpc_status,
+        close_connection: false,
+        message: Cow::Borrowed("injected fault"),
+    };
+
+    if req.version() == http::Version::HTTP_2 && respond::is_grpc(req) {
+        rsp.grpc_response(false)
+    } else {
+        rsp.http_response(req.version(), false)
+    }
+}
+
+/// Maps an HTTP status to a gRPC status as described by the [gRPC spec][spec].
+///
+/// [spec]: https://github.com/grpc/grpc/blob/master/doc/http-grpc-status-mapping.md
+fn grpc_status_for(status: http::StatusCode) -> tonic::Code {
+    match status {
+        http::StatusCode::BAD_REQUEST => tonic::Code::Internal,
+        http::StatusCode::UNAUTHORIZED => tonic::Code::Unauthenticated,
+        http::StatusCode::FORBIDDEN => tonic::Code::PermissionDenied,
+        http::StatusCode::NOT_FOUND => tonic::Code::Unimplemented,
+        http::StatusCode::TOO_MANY_REQUESTS
+        | http::StatusCode::BAD_GATEWAY
+        | http::StatusCode::SERVICE_UNAVAILABLE
+        | http::StatusCode::GATEWAY_TIMEOUT => tonic::Code::Unavailable,
+        _ => tonic::Code::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::svc::ServiceExt;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    type MockSvc = InjectFaultService<svc::stack::util::ServiceFn<MockFn>>;
+    type MockFn = fn(http::Request<()>) -> future::Ready<Result<http::Response<()>, Error>>;
+
+    fn mk_svc(fault: InjectFault) -> MockSvc {
+        InjectFaultService {
+            faults: Arc::new([fault]),
+            rng: SmallRng::seed_from_u64(0),
+            inner: svc::mk((|_| future::ok(http::Response::new(()))) as MockFn),
+        }
+    }
+
+    fn abort(grpc_status: Option<u16>, ratio: Ratio) -> InjectFault {
+        InjectFault {
+            abort: Some(FaultAbort {
+                http_status: http::StatusCode::SERVICE_UNAVAILABLE,
+                grpc_status,
+                ratio,
+            }),
+            delay: None,
+        }
+    }
+
+    fn grpc_request() -> http::Request<()> {
+        http::Request::builder()
+            .version(http::Version::HTTP_2)
+            .header(http::header::CONTENT_TYPE, "application/grpc")
+            .body(())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn aborts_http() {
+        let rsp = mk_svc(abort(None, Ratio::ALL))
+            .oneshot(http::Request::new(()))
+            .await
+            .expect("aborts must be responses");
+        assert_eq!(rsp.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn aborts_grpc() {
+        // The gRPC status is derived from the HTTP status unless it's set.
+        let rsp = mk_svc(abort(None, Ratio::ALL))
+            .oneshot(grpc_request())
+            .await
+            .expect("aborts must be responses");
+        assert_eq!(rsp.status(), http::StatusCode::OK);
+        assert_eq!(rsp.headers()["grpc-status"], "14");
+
+        let rsp = mk_svc(abort(Some(4), Ratio::ALL))
+            .oneshot(grpc_request())
+            .await
+            .expect("aborts must be responses");
+        assert_eq!(rsp.status(), http::StatusCode::OK);
+        assert_eq!(rsp.headers()["grpc-status"], "4");
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn delays() {
+        let duration = Duration::from_secs(3);
+        let svc = mk_svc(InjectFault {
+            abort: None,
+            delay: Some(FaultDelay {
+                duration,
+                ratio: Ratio::ALL,
+            }),
+        });
+        let start = Instant::now();
+        let rsp = svc
+            .oneshot(http::Request::new(()))
+            .await
+            .expect("delayed requests must be dispatched");
+        assert_eq!(rsp.status(), http::StatusCode::OK);
+        assert!(start.elapsed() >= duration);
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn zero_ratio_passes_through() {
+        let never = Ratio::new(0, 1).unwrap();
+        let svc = mk_svc(InjectFault {
+            abort: abort(None, never).abort,
+            delay: Some(FaultDelay {
+                duration: Duration::from_secs(3),
+                ratio: never,
+            }),
+        });
+        let start = Instant::now();
+        let rsp = svc
+            .oneshot(http::Request::new(()))
+            .await
+            .expect("requests must be dispatched");
+        assert_eq!(rsp.status(), http::StatusCode::OK);
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+}
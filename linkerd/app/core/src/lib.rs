@@ -37,6 +37,7 @@ pub mod config;
 pub mod control;
 pub mod dns;
 pub mod errors;
+pub mod fault;
 pub mod http_tracing;
 pub mod metrics;
 pub mod proxy;
@@ -32,6 +32,10 @@ pub type HttpProfileRoute = http_metrics::Requests<ProfileRouteLabels, Class>;
 
 pub type HttpProfileRouteRetry = http_metrics::Retries<ProfileRouteLabels>;
 
+pub type HttpProfileRouteMirror = http_metrics::Mirrors<ProfileRouteLabels>;
+
+pub type HttpBalancerEjections = http_metrics::Ejections<BalancerLabels>;
+
 pub type Stack = stack_metrics::Registry<StackLabels>;
 
 #[derive(Clone, Debug)]
@@ -46,6 +50,8 @@ pub struct Proxy {
     pub http_profile_route: HttpProfileRoute,
     pub http_profile_route_actual: HttpProfileRoute,
     pub http_profile_route_retry: HttpProfileRouteRetry,
+    pub http_profile_route_mirror: HttpProfileRouteMirror,
+    pub http_balancer_ejections: HttpBalancerEjections,
     pub http_endpoint: HttpEndpoint,
     pub transport: transport::Metrics,
     pub stack: Stack,
@@ -118,6 +124,14 @@ pub struct ProfileRouteLabels {
     labels: Option<String>,
 }
 
+/// Labels referencing the logical service over which a balancer distributes
+/// requests.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BalancerLabels {
+    direction: Direction,
+    addr: Option<profiles::LogicalAddr>,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Direction {
     In,
@@ -175,6 +189,18 @@ impl Metrics {
             (m, r)
         };
 
+        let (http_profile_route_mirror, mirror_report) = {
+            let m = metrics::Mirrors::<ProfileRouteLabels>::default();
+            let r = m.clone().into_report(retain_idle).with_prefix("route");
+            (m, r)
+        };
+
+        let (http_balancer_ejections, ejections_report) = {
+            let m = metrics::Ejections::<BalancerLabels>::default();
+            let r = m.clone().into_report(retain_idle).with_prefix("balancer");
+            (m, r)
+        };
+
         let (http_profile_route_actual, actual_report) = {
             let m = metrics::Requests::<ProfileRouteLabels, Class>::default();
             let r = m
@@ -192,6 +218,8 @@ impl Metrics {
             http_endpoint,
             http_profile_route,
             http_profile_route_retry,
+            http_profile_route_mirror,
+            http_balancer_ejections,
             http_profile_route_actual,
             stack: stack.clone(),
             transport,
@@ -208,6 +236,8 @@ impl Metrics {
         let report = endpoint_report
             .and_report(profile_route_report)
             .and_report(retry_report)
+            .and_report(mirror_report)
+            .and_report(ejections_report)
             .and_report(actual_report)
             .and_report(control_report)
             .and_report(transport_report)
@@ -275,6 +305,29 @@ impl FmtLabels for ProfileRouteLabels {
     }
 }
 
+// === impl BalancerLabels ===
+
+impl BalancerLabels {
+    pub fn outbound(addr: Option<profiles::LogicalAddr>) -> Self {
+        Self {
+            direction: Direction::Out,
+            addr,
+        }
+    }
+}
+
+impl FmtLabels for BalancerLabels {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.direction.fmt_labels(f)?;
+
+        if let Some(addr) = self.addr.as_ref() {
+            write!(f, ",dst=\"{}\"", addr)?;
+        }
+
+        Ok(())
+    }
+}
+
 // === impl EndpointLabels ===
 
 impl From<InboundEndpointLabels> for EndpointLabels {
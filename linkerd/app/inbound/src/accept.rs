@@ -136,6 +136,9 @@ mod tests {
                         name: "testsaz".into(),
                     }),
                 }]),
+                http_routes: Arc::new([]),
+                grpc_routes: Arc::new([]),
+                rate_limit: None,
                 meta: Arc::new(Meta::Resource {
                     group: "policy.linkerd.io".into(),
                     kind: "server".into(),
@@ -31,6 +31,9 @@ fn allow(protocol: Protocol) -> AllowPolicy {
         ServerPolicy {
             protocol,
             authorizations: authzs(),
+            http_routes: Arc::new([]),
+            grpc_routes: Arc::new([]),
+            rate_limit: None,
             meta: Arc::new(Meta::Resource {
                 group: "policy.linkerd.io".into(),
                 kind: "server".into(),
@@ -223,6 +223,9 @@ pub mod fuzz {
                         }),
                     }]
                     .into(),
+                    http_routes: Arc::new([]),
+                    grpc_routes: Arc::new([]),
+                    rate_limit: None,
                     meta: Arc::new(policy::Meta::Resource {
                         group: "policy.linkerd.io".into(),
                         kind: "server".into(),
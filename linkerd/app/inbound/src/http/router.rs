@@ -130,6 +130,9 @@ impl<C> Inbound<C> {
                             rt.span_sink.clone(),
                             super::trace_labels(),
                         ))
+                        // Deducts the time spent in the proxy from the deadline
+                        // sent to the application.
+                        .push(http::RewriteDeadline::layer())
                         .push(http::BoxResponse::layer())
                         // This box is needed to reduce compile times on recent
                         // (2021-10-17) nightlies, though this may be fixed by
@@ -235,7 +238,10 @@ impl<C> Inbound<C> {
                 // minimize it's type footprint with a Box.
                 .push(svc::ArcNewService::layer())
                 .push(svc::NewRouter::layer(LogicalPerRequest::from))
-                .push(policy::NewHttpPolicy::layer(rt.metrics.http_authz.clone()))
+                .push(policy::NewHttpPolicy::layer(
+                    rt.metrics.http_authz.clone(),
+                    config.priority_shedding,
+                ))
                 // Used by tap.
                 .push_http_insert_target::<tls::ConditionalServerTls>()
                 .push_http_insert_target::<Remote<ClientAddr>>()
@@ -66,7 +66,9 @@ impl<H> Inbound<H> {
                         // driven outside of the request path, so there's no need
                         // for SpawnReady
                         .push(svc::ConcurrencyLimitLayer::new(max_in_flight_requests))
-                        .push(svc::FailFast::layer("HTTP Server", dispatch_timeout)),
+                        .push(svc::FailFast::layer("HTTP Server", dispatch_timeout))
+                        // Records and enforces request deadlines.
+                        .push(http::EnforceDeadline::layer()),
                 )
                 .push(rt.metrics.http_errors.to_layer())
                 .push(ServerRescue::layer())
@@ -130,6 +132,15 @@ impl errors::HttpRescue<Error> for ServerRescue {
         if let Some(cause) = errors::cause_ref::<policy::HttpRouteUnauthorized>(&*error) {
             return Ok(errors::SyntheticHttpResponse::permission_denied(cause));
         }
+        if let Some(cause) = errors::cause_ref::<policy::HttpRouteNotFound>(&*error) {
+            return Ok(errors::SyntheticHttpResponse::not_found(cause));
+        }
+        if let Some(cause) = errors::cause_ref::<policy::HttpRateLimited>(&*error) {
+            return Ok(errors::SyntheticHttpResponse::rate_limited(cause));
+        }
+        if let Some(cause) = errors::cause_ref::<policy::HttpRequestShed>(&*error) {
+            return Ok(errors::SyntheticHttpResponse::unavailable(cause));
+        }
 
         if let Some(cause) = errors::cause_ref::<crate::GatewayDomainInvalid>(&*error) {
             return Ok(errors::SyntheticHttpResponse::not_found(cause));
@@ -143,6 +154,9 @@ impl errors::HttpRescue<Error> for ServerRescue {
         if let Some(cause) = errors::cause_ref::<errors::FailFastError>(&*error) {
             return Ok(errors::SyntheticHttpResponse::gateway_timeout(cause));
         }
+        if let Some(cause) = errors::cause_ref::<http::DeadlineExceededError>(&*error) {
+            return Ok(errors::SyntheticHttpResponse::deadline_exceeded(cause));
+        }
 
         if errors::is_caused_by::<errors::H2Error>(&*error) {
             return Err(error);
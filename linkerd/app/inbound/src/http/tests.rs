@@ -617,6 +617,9 @@ impl svc::Param<policy::AllowPolicy> for Target {
                     }),
                 }]
                 .into(),
+                http_routes: Arc::new([]),
+                grpc_routes: Arc::new([]),
+                rate_limit: None,
                 meta: Arc::new(policy::Meta::Resource {
                     group: "policy.linkerd.io".into(),
                     kind: "server".into(),
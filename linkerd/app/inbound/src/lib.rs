@@ -41,6 +41,7 @@ pub struct Config {
     pub policy: policy::Config,
     pub profile_idle_timeout: Duration,
     pub allowed_ips: transport::AllowIps,
+    pub priority_shedding: Option<policy::PriorityShedding>,
 }
 
 #[derive(Clone)]
@@ -93,7 +94,10 @@ impl<S> Inbound<S> {
     pub fn authorize_http<N>(
         &self,
     ) -> impl svc::layer::Layer<N, Service = policy::NewHttpPolicy<N>> + Clone {
-        policy::NewHttpPolicy::layer(self.runtime.metrics.http_authz.clone())
+        policy::NewHttpPolicy::layer(
+            self.runtime.metrics.http_authz.clone(),
+            self.config.priority_shedding,
+        )
     }
 
     /// A helper for gateways to instrument policy checks.
@@ -7,6 +7,7 @@ use linkerd_app_core::{
     tls,
     transport::OrigDstAddr,
 };
+use linkerd_server_policy::grpc;
 use parking_lot::Mutex;
 use std::{collections::HashMap, sync::Arc};
 
@@ -32,6 +33,18 @@ metrics! {
 #[derive(Clone, Debug, Default)]
 pub struct HttpAuthzMetrics(Arc<HttpInner>);
 
+/// Labels describing the gRPC service and method of a request that matched a
+/// gRPC route.
+///
+/// Only the parts of the request path that were explicitly matched by the
+/// route are recorded so that label cardinality is bounded by the configured
+/// routes.
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct GrpcRpcLabels {
+    service: Option<String>,
+    method: Option<String>,
+}
+
 #[derive(Clone, Debug, Default)]
 pub(crate) struct TcpAuthzMetrics(Arc<TcpInner>);
 
@@ -57,26 +70,37 @@ struct Key<L> {
 
 type ServerKey = Key<ServerLabel>;
 type ServerAuthzKey = Key<ServerAuthzLabels>;
-type RouteKey = Key<RouteLabels>;
-type RouteAuthzKey = Key<RouteAuthzLabels>;
+type RouteKey = Key<(RouteLabels, Option<GrpcRpcLabels>)>;
+type RouteAuthzKey = Key<(RouteAuthzLabels, Option<GrpcRpcLabels>)>;
 
 // === impl HttpAuthzMetrics ===
 
 impl HttpAuthzMetrics {
-    pub fn allow(&self, permit: &HttpRoutePermit, tls: tls::ConditionalServerTls) {
+    pub fn allow(
+        &self,
+        permit: &HttpRoutePermit,
+        rpc: Option<GrpcRpcLabels>,
+        tls: tls::ConditionalServerTls,
+    ) {
         self.0
             .allow
             .lock()
-            .entry(RouteAuthzKey::from_permit(permit, tls))
+            .entry(RouteAuthzKey::from_permit(permit, rpc, tls))
             .or_default()
             .incr();
     }
 
-    pub fn deny(&self, labels: RouteLabels, dst: OrigDstAddr, tls: tls::ConditionalServerTls) {
+    pub fn deny(
+        &self,
+        labels: RouteLabels,
+        rpc: Option<GrpcRpcLabels>,
+        dst: OrigDstAddr,
+        tls: tls::ConditionalServerTls,
+    ) {
         self.0
             .deny
             .lock()
-            .entry(RouteKey::new(labels, dst, tls))
+            .entry(RouteKey::new((labels, rpc), dst, tls))
             .or_default()
             .incr();
     }
@@ -113,6 +137,34 @@ impl FmtMetrics for HttpAuthzMetrics {
     }
 }
 
+// === impl GrpcRpcLabels ===
+
+impl GrpcRpcLabels {
+    pub(crate) fn new(path: &str, matched: &grpc::RouteMatch) -> Self {
+        let mut parts = path.trim_start_matches('/').splitn(2, '/');
+        let service = parts
+            .next()
+            .filter(|_| matched.route().is_service_match())
+            .map(Into::into);
+        let method = parts
+            .next()
+            .filter(|_| matched.route().is_method_match())
+            .map(Into::into);
+        Self { service, method }
+    }
+}
+
+impl FmtLabels for GrpcRpcLabels {
+    fn fmt_labels(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "grpc_service=\"{}\",grpc_method=\"{}\"",
+            self.service.as_deref().unwrap_or(""),
+            self.method.as_deref().unwrap_or(""),
+        )
+    }
+}
+
 // === impl TcpAuthzMetrics ===
 
 impl TcpAuthzMetrics {
@@ -196,8 +248,12 @@ impl ServerKey {
 }
 
 impl RouteAuthzKey {
-    fn from_permit(permit: &HttpRoutePermit, tls: tls::ConditionalServerTls) -> Self {
-        Self::new(permit.labels.clone(), permit.dst, tls)
+    fn from_permit(
+        permit: &HttpRoutePermit,
+        rpc: Option<GrpcRpcLabels>,
+        tls: tls::ConditionalServerTls,
+    ) -> Self {
+        Self::new((permit.labels.clone(), rpc), permit.dst, tls)
     }
 }
 
@@ -3,20 +3,27 @@ mod tcp;
 
 pub(crate) use self::{http::HttpErrorMetrics, tcp::TcpErrorMetrics};
 use crate::{
-    policy::{HttpRouteUnauthorized, ServerUnauthorized},
+    policy::{
+        HttpRateLimited, HttpRequestShed, HttpRouteNotFound, HttpRouteUnauthorized,
+        ServerUnauthorized,
+    },
     GatewayDomainInvalid, GatewayIdentityRequired, GatewayLoop,
 };
-use linkerd_app_core::{errors::FailFastError, metrics::FmtLabels, tls};
+use linkerd_app_core::{
+    errors::FailFastError, metrics::FmtLabels, proxy::http::DeadlineExceededError, tls,
+};
 use std::fmt;
 
 /// Inbound proxy error types.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 enum ErrorKind {
+    DeadlineExceeded,
     FailFast,
     GatewayDomainInvalid,
     GatewayIdentityRequired,
     GatewayLoop,
     Io,
+    LoadShed,
     TlsDetectTimeout,
     Unexpected,
 }
@@ -28,8 +35,16 @@ impl ErrorKind {
         if err.is::<ServerUnauthorized>() || err.is::<HttpRouteUnauthorized>() {
             // Unauthorized metrics are tracked separately.and are not considered to be errors.
             None
+        } else if err.is::<HttpRouteNotFound>() || err.is::<HttpRateLimited>() {
+            // Unmatched and rate-limited requests are policy decisions and are
+            // not considered to be errors.
+            None
         } else if err.is::<FailFastError>() {
             Some(ErrorKind::FailFast)
+        } else if err.is::<DeadlineExceededError>() {
+            Some(ErrorKind::DeadlineExceeded)
+        } else if err.is::<HttpRequestShed>() {
+            Some(ErrorKind::LoadShed)
         } else if err.is::<std::io::Error>() {
             Some(ErrorKind::Io)
         } else if err.is::<tls::server::ServerTlsTimeoutError>() {
@@ -54,12 +69,14 @@ impl FmtLabels for ErrorKind {
             f,
             "error=\"{}\"",
             match self {
+                ErrorKind::DeadlineExceeded => "deadline exceeded",
                 ErrorKind::FailFast => "failfast",
                 ErrorKind::TlsDetectTimeout => "tls detection timeout",
                 ErrorKind::GatewayIdentityRequired => "gateway identity required",
                 ErrorKind::GatewayLoop => "gateway loop",
                 ErrorKind::GatewayDomainInvalid => "gateway domain invalid",
                 ErrorKind::Io => "i/o",
+                ErrorKind::LoadShed => "load shed",
                 ErrorKind::Unexpected => "unexpected",
             }
         )
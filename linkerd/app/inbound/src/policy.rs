@@ -2,13 +2,18 @@ mod api;
 mod config;
 pub mod defaults;
 mod http;
+mod priority;
+mod rate_limit;
 mod store;
 mod tcp;
 
 pub(crate) use self::store::Store;
+pub use self::store::{PortsRx, UpdatesTx};
 pub use self::{
     config::Config,
-    http::{HttpRouteUnauthorized, NewHttpPolicy},
+    http::{HttpRouteNotFound, HttpRouteUnauthorized, NewHttpPolicy},
+    priority::{HttpRequestShed, PriorityShedding},
+    rate_limit::HttpRateLimited,
     tcp::NewTcpPolicy,
 };
 
@@ -20,7 +25,8 @@ use linkerd_app_core::{
 };
 use linkerd_cache::Cached;
 pub use linkerd_server_policy::{
-    authz::Suffix, Authentication, Authorization, Meta, Protocol, ServerPolicy,
+    authz::Suffix, http::Route as HttpRoute, Authentication, Authorization, Meta, Priority,
+    Protocol, RateLimit, RateLimitKey, RoutePolicy, ServerPolicy,
 };
 use std::sync::Arc;
 use thiserror::Error;
@@ -79,6 +85,9 @@ impl From<DefaultPolicy> for ServerPolicy {
             DefaultPolicy::Deny => ServerPolicy {
                 protocol: Protocol::Opaque,
                 authorizations: Arc::new([]),
+                http_routes: Arc::new([]),
+                grpc_routes: Arc::new([]),
+                rate_limit: None,
                 meta: Meta::new_default("deny"),
             },
         }
@@ -100,7 +109,7 @@ impl AllowPolicy {
     }
 
     #[inline]
-    pub(crate) fn borrow(&self) -> tokio::sync::watch::Ref<'_, ServerPolicy> {
+    pub fn borrow(&self) -> tokio::sync::watch::Ref<'_, ServerPolicy> {
         self.server.borrow()
     }
 
@@ -1,4 +1,4 @@
-use super::{api::Api, DefaultPolicy, GetPolicy, ServerPolicy, Store};
+use super::{api::Api, DefaultPolicy, GetPolicy, PortsRx, ServerPolicy, Store, UpdatesTx};
 use linkerd_app_core::{control, dns, identity, metrics, svc::NewService};
 use std::collections::{HashMap, HashSet};
 use tokio::time::Duration;
@@ -6,7 +6,8 @@ use tokio::time::Duration;
 /// Configures inbound policies.
 ///
 /// The proxy usually watches dynamic policies from the control plane, though it can also use
-/// 'fixed' policies configured at startup.
+/// 'fixed' policies configured at startup or policies watched from another source (e.g. a local
+/// file).
 #[derive(Clone, Debug)]
 #[allow(clippy::large_enum_variant)]
 pub enum Config {
@@ -16,17 +17,84 @@ pub enum Config {
         default: DefaultPolicy,
         cache_max_idle_age: Duration,
         ports: HashSet<u16>,
+
+        /// Policies that are used until the control plane's policies are
+        /// discovered (e.g. from a snapshot of previously discovered policies).
+        initial: HashMap<u16, ServerPolicy>,
+
+        /// Receives each discovered policy update, if set.
+        updates: Option<UpdatesTx>,
     },
     Fixed {
         default: DefaultPolicy,
         cache_max_idle_age: Duration,
         ports: HashMap<u16, ServerPolicy>,
     },
+    Watch {
+        default: DefaultPolicy,
+        cache_max_idle_age: Duration,
+        ports: PortsRx,
+
+        /// Receives each watched policy update, if set.
+        updates: Option<UpdatesTx>,
+    },
 }
 
 // === impl Config ===
 
 impl Config {
+    /// Replaces the configured policies with watched policies, retaining the
+    /// default policy and the receiver of updates.
+    pub fn into_watch(self, ports: PortsRx) -> Self {
+        let (default, cache_max_idle_age, updates) = match self {
+            Self::Discover {
+                default,
+                cache_max_idle_age,
+                updates,
+                ..
+            }
+            | Self::Watch {
+                default,
+                cache_max_idle_age,
+                updates,
+                ..
+            } => (default, cache_max_idle_age, updates),
+            Self::Fixed {
+                default,
+                cache_max_idle_age,
+                ..
+            } => (default, cache_max_idle_age, None),
+        };
+        Self::Watch {
+            default,
+            cache_max_idle_age,
+            ports,
+            updates,
+        }
+    }
+
+    /// Sets the policies that are used until policies are discovered.
+    ///
+    /// This has no effect unless policies are discovered from the control
+    /// plane.
+    pub fn with_initial(mut self, policies: HashMap<u16, ServerPolicy>) -> Self {
+        if let Self::Discover { initial, .. } = &mut self {
+            *initial = policies;
+        }
+        self
+    }
+
+    /// Sends each update to a port's policy to `tx`.
+    ///
+    /// This has no effect when policies are fixed, since they are never
+    /// updated.
+    pub fn with_updates(mut self, tx: UpdatesTx) -> Self {
+        if let Self::Discover { updates, .. } | Self::Watch { updates, .. } = &mut self {
+            *updates = Some(tx);
+        }
+        self
+    }
+
     pub(crate) fn build(
         self,
         dns: dns::Resolver,
@@ -40,19 +108,28 @@ impl Config {
                 cache_max_idle_age,
             } => Store::spawn_fixed(default, cache_max_idle_age, ports),
 
+            Self::Watch {
+                default,
+                ports,
+                cache_max_idle_age,
+                updates,
+            } => Store::spawn_watch(default, cache_max_idle_age, ports, updates),
+
             Self::Discover {
                 control,
                 ports,
                 workload,
                 default,
                 cache_max_idle_age,
+                initial,
+                updates,
             } => {
                 let watch = {
                     let backoff = control.connect.backoff;
                     let c = control.build(dns, metrics, identity).new_service(());
                     Api::new(workload, c).into_watch(backoff)
                 };
-                Store::spawn_discover(default, cache_max_idle_age, watch, ports)
+                Store::spawn_discover(default, cache_max_idle_age, watch, ports, initial, updates)
             }
         }
     }
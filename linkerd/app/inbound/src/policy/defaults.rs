@@ -2,7 +2,7 @@ use linkerd_app_core::{IpNet, Ipv4Net, Ipv6Net};
 use linkerd_server_policy::{
     authz::Suffix, Authentication, Authorization, Meta, Protocol, ServerPolicy,
 };
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 pub fn all_authenticated(timeout: Duration) -> ServerPolicy {
     mk("all-authenticated", all_nets(), authenticated(), timeout)
@@ -70,6 +70,9 @@ fn mk(
             meta: Meta::new_default(name),
         }]
         .into(),
+        http_routes: Arc::new([]),
+        grpc_routes: Arc::new([]),
+        rate_limit: None,
         meta: Meta::new_default(name),
     }
 }
@@ -1,18 +1,28 @@
+use super::{
+    priority::{self, PriorityQueue, PriorityShedding},
+    rate_limit::RateLimits,
+};
 use crate::{
-    metrics::authz::HttpAuthzMetrics,
-    policy::{AllowPolicy, HttpRoutePermit},
+    metrics::authz::{GrpcRpcLabels, HttpAuthzMetrics},
+    policy::{AllowPolicy, HttpRoutePermit, Priority},
 };
-use futures::{future, TryFutureExt};
+use futures::future;
 use linkerd_app_core::{
     metrics::{RouteAuthzLabels, RouteLabels, ServerLabel},
+    proxy::http::BoxBody,
     svc::{self, ServiceExt},
     tls,
     transport::{ClientAddr, OrigDstAddr, Remote},
     Error,
 };
-use linkerd_server_policy::{Authorization, Meta};
+use linkerd_server_policy::{
+    grpc as grpc_route, http as http_route, Authorization, Meta, Protocol, ServerPolicy,
+};
 use std::{sync::Arc, task};
 
+#[cfg(test)]
+mod tests;
+
 /// A middleware that enforces policy on each HTTP request.
 ///
 /// This enforcement is done lazily on each request so that policy updates are
@@ -23,6 +33,8 @@ use std::{sync::Arc, task};
 #[derive(Clone, Debug)]
 pub struct NewHttpPolicy<N> {
     metrics: HttpAuthzMetrics,
+    rate_limits: RateLimits,
+    priority_queue: PriorityQueue,
     inner: N,
     default_route_meta: Arc<Meta>,
 }
@@ -33,6 +45,8 @@ pub struct HttpPolicyService<T, N> {
     meta: ConnectionMeta,
     policy: AllowPolicy,
     metrics: HttpAuthzMetrics,
+    rate_limits: RateLimits,
+    priority_queue: PriorityQueue,
     inner: N,
     default_route_meta: Arc<Meta>,
 }
@@ -48,16 +62,31 @@ struct ConnectionMeta {
 #[error("unauthorized request on route {}/{}", .0.kind(), .0.name())]
 pub struct HttpRouteUnauthorized(Arc<Meta>);
 
+#[derive(Debug, thiserror::Error)]
+#[error("no route found for request")]
+pub struct HttpRouteNotFound(());
+
 // === impl NewHttpPolicy ===
 
 impl<N> NewHttpPolicy<N> {
-    pub fn layer(metrics: HttpAuthzMetrics) -> impl svc::layer::Layer<N, Service = Self> + Clone {
-        // XXX for now we use a single, synthetic route for all requests. This
-        // will change in the near future.
+    pub fn layer(
+        metrics: HttpAuthzMetrics,
+        priority_shedding: Option<PriorityShedding>,
+    ) -> impl svc::layer::Layer<N, Service = Self> + Clone {
+        // Servers that do not configure routes use a single, synthetic route
+        // for all requests.
         let default_route_meta = Meta::new_default("default");
 
+        // Rate limits are shared by all connections.
+        let rate_limits = RateLimits::default();
+
+        // Requests are prioritized across all connections.
+        let priority_queue = PriorityQueue::new(priority_shedding);
+
         svc::layer::mk(move |inner| Self {
             metrics: metrics.clone(),
+            rate_limits: rate_limits.clone(),
+            priority_queue: priority_queue.clone(),
             default_route_meta: default_route_meta.clone(),
             inner,
         })
@@ -83,6 +112,8 @@ where
             policy,
             meta: ConnectionMeta { client, dst, tls },
             metrics: self.metrics.clone(),
+            rate_limits: self.rate_limits.clone(),
+            priority_queue: self.priority_queue.clone(),
             inner: self.inner.clone(),
             default_route_meta: self.default_route_meta.clone(),
         }
@@ -95,13 +126,13 @@ impl<B, T, N, S> svc::Service<::http::Request<B>> for HttpPolicyService<T, N>
 where
     T: Clone,
     N: svc::NewService<(HttpRoutePermit, T), Service = S>,
-    S: svc::Service<::http::Request<B>>,
+    S: svc::Service<::http::Request<B>, Response = ::http::Response<BoxBody>>,
     S::Error: Into<Error>,
 {
-    type Response = S::Response;
+    type Response = ::http::Response<BoxBody>;
     type Error = Error;
     type Future = future::Either<
-        future::ErrInto<svc::stack::Oneshot<S, ::http::Request<B>>, Error>,
+        priority::Admit<svc::stack::Oneshot<S, ::http::Request<B>>>,
         future::Ready<Result<Self::Response, Error>>,
     >;
 
@@ -110,31 +141,80 @@ where
         task::Poll::Ready(Ok(()))
     }
 
-    fn call(&mut self, req: ::http::Request<B>) -> Self::Future {
+    fn call(&mut self, mut req: ::http::Request<B>) -> Self::Future {
         let server = self.policy.server.borrow();
-        let labels = ServerLabel(server.meta.clone());
+
+        // If the server configures routes, the request must match one of them
+        // and is authorized by the matched route's authorizations. Otherwise,
+        // the server's authorizations apply to all requests. gRPC servers
+        // prefer gRPC routes so that requests may be matched by service and
+        // method.
+        let (route, authzs, rpc, priority) =
+            if server.protocol == Protocol::Grpc && !server.grpc_routes.is_empty() {
+                match grpc_route::find(&*server.grpc_routes, &req) {
+                    Some((m, policy)) => (
+                        policy.meta.clone(),
+                        policy.authorizations.clone(),
+                        Some(GrpcRpcLabels::new(req.uri().path(), &m)),
+                        policy.priority,
+                    ),
+                    None => return future::Either::Right(future::err(route_not_found(&*server))),
+                }
+            } else if !server.http_routes.is_empty() {
+                match http_route::find(&*server.http_routes, &req) {
+                    Some((_, policy)) => (
+                        policy.meta.clone(),
+                        policy.authorizations.clone(),
+                        None,
+                        policy.priority,
+                    ),
+                    None => return future::Either::Right(future::err(route_not_found(&*server))),
+                }
+            } else {
+                (
+                    self.default_route_meta.clone(),
+                    server.authorizations.clone(),
+                    None,
+                    None,
+                )
+            };
 
         let labels = RouteLabels {
-            route: self.default_route_meta.clone(),
-            server: labels,
+            route,
+            server: ServerLabel(server.meta.clone()),
         };
+        let rate_limit = server.rate_limit.clone();
+        drop(server);
 
-        let permit = match Self::check_authorized(
-            &*server.authorizations,
-            &self.meta,
-            labels,
-            &self.metrics,
-        ) {
+        let permit = match Self::check_authorized(&*authzs, &self.meta, labels, rpc, &self.metrics)
+        {
             Ok(p) => p,
             Err(deny) => return future::Either::Right(future::err(deny.into())),
         };
 
-        future::Either::Left(
-            self.inner
-                .new_service((permit, self.target.clone()))
-                .oneshot(req)
-                .err_into::<Error>(),
-        )
+        // Authorized requests are subject to the server's rate limit, if any.
+        if let Some(limit) = rate_limit {
+            let server = &permit.labels.route.server.0;
+            if let Err(e) = self
+                .rate_limits
+                .check(server, &limit, self.meta.client, &self.meta.tls)
+            {
+                return future::Either::Right(future::err(e.into()));
+            }
+        }
+
+        // Routes may set the priority of their requests. Otherwise,
+        // authenticated clients may set it if the header is trusted. The
+        // header is never forwarded to the application.
+        let header = req.headers_mut().remove(priority::L5D_PRIORITY);
+        let priority = priority.unwrap_or_else(|| {
+            header_priority(header, &self.meta.tls, self.priority_queue.trusts_header())
+        });
+        let call = self
+            .inner
+            .new_service((permit, self.target.clone()))
+            .oneshot(req);
+        future::Either::Left(self.priority_queue.admit(priority, call))
     }
 }
 
@@ -143,6 +223,7 @@ impl<T, N> HttpPolicyService<T, N> {
         authzs: impl IntoIterator<Item = &'a Authorization>,
         conn: &ConnectionMeta,
         labels: RouteLabels,
+        rpc: Option<GrpcRpcLabels>,
         metrics: &HttpAuthzMetrics,
     ) -> Result<HttpRoutePermit, HttpRouteUnauthorized> {
         let authz = match authzs
@@ -163,7 +244,7 @@ impl<T, N> HttpPolicyService<T, N> {
                     "Request denied",
                 );
                 let route = labels.route.clone();
-                metrics.deny(labels, conn.dst, conn.tls.clone());
+                metrics.deny(labels, rpc, conn.dst, conn.tls.clone());
                 return Err(HttpRouteUnauthorized(route));
             }
         };
@@ -193,7 +274,37 @@ impl<T, N> HttpPolicyService<T, N> {
             }
         };
 
-        metrics.allow(&permit, conn.tls.clone());
+        metrics.allow(&permit, rpc, conn.tls.clone());
         Ok(permit)
     }
 }
+
+/// Returns the priority that a client set with the `l5d-priority` header, if
+/// the client is authenticated and the header is trusted.
+fn header_priority(
+    header: Option<::http::HeaderValue>,
+    tls: &tls::ConditionalServerTls,
+    trusted: bool,
+) -> Priority {
+    let authenticated = matches!(
+        tls,
+        tls::ConditionalServerTls::Some(tls::ServerTls::Established {
+            client_id: Some(_),
+            ..
+        })
+    );
+    header
+        .filter(|_| trusted && authenticated)
+        .and_then(|v| v.to_str().ok()?.parse().ok())
+        .unwrap_or_default()
+}
+
+fn route_not_found(server: &ServerPolicy) -> Error {
+    tracing::debug!(
+        server.group = %server.meta.group(),
+        server.kind = %server.meta.kind(),
+        server.name = %server.meta.name(),
+        "No route matched request",
+    );
+    HttpRouteNotFound(()).into()
+}
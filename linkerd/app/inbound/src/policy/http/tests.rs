@@ -0,0 +1,425 @@
+use super::*;
+use crate::policy::{
+    Authentication, Authorization, HttpRateLimited, Meta, Protocol, RateLimit, RateLimitKey,
+    RoutePolicy, ServerPolicy,
+};
+use linkerd_server_policy::{
+    grpc::{self, MatchRoute, MatchRpc},
+    http::{MatchPath, MatchRequest, Route, Rule},
+};
+
+#[tokio::test(flavor = "current_thread")]
+async fn default_route() {
+    let (svc, _tx) = mk_svc(
+        ServerPolicy {
+            protocol: Protocol::Http1,
+            authorizations: vec![Authorization {
+                authentication: Authentication::Unauthenticated,
+                networks: vec!["192.0.2.0/24".parse().unwrap()],
+                meta: mk_meta("serverauthorization", "unauth"),
+            }]
+            .into(),
+            http_routes: Arc::new([]),
+            grpc_routes: Arc::new([]),
+            rate_limit: None,
+            meta: mk_meta("server", "test"),
+        },
+        tls::ConditionalServerTls::None(tls::NoServerTls::NoClientHello),
+    );
+
+    let permit = call(svc, ::http::Method::GET, "/")
+        .await
+        .expect("request must be authorized");
+    assert_eq!(
+        permit.labels.route.route,
+        Meta::new_default("default"),
+        "the synthetic route must be used when no routes are configured"
+    );
+    assert_eq!(
+        permit.labels.authz,
+        mk_meta("serverauthorization", "unauth")
+    );
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn routes_authorize_independently() {
+    let (svc, _tx) = mk_svc(
+        routes_policy(),
+        tls::ConditionalServerTls::None(tls::NoServerTls::NoClientHello),
+    );
+
+    let permit = call(svc.clone(), ::http::Method::GET, "/healthz")
+        .await
+        .expect("health checks must be authorized");
+    assert_eq!(permit.labels.route.route, mk_meta("httproute", "healthz"));
+    assert_eq!(permit.labels.authz, mk_meta("authorizationpolicy", "all"));
+
+    let err = call(svc, ::http::Method::POST, "/admin/reset")
+        .await
+        .expect_err("unauthenticated admin requests must be denied");
+    assert!(
+        err.is::<HttpRouteUnauthorized>(),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn routes_authenticated() {
+    let (svc, _tx) = mk_svc(
+        routes_policy(),
+        tls::ConditionalServerTls::Some(tls::ServerTls::Established {
+            client_id: Some(client_id()),
+            negotiated_protocol: None,
+        }),
+    );
+
+    let permit = call(svc, ::http::Method::POST, "/admin/reset")
+        .await
+        .expect("authenticated admin requests must be authorized");
+    assert_eq!(permit.labels.route.route, mk_meta("httproute", "admin"));
+    assert_eq!(permit.labels.authz, mk_meta("authorizationpolicy", "admin"));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn routes_not_found() {
+    let (svc, _tx) = mk_svc(
+        routes_policy(),
+        tls::ConditionalServerTls::None(tls::NoServerTls::NoClientHello),
+    );
+
+    let err = call(svc.clone(), ::http::Method::POST, "/healthz")
+        .await
+        .expect_err("requests must match a route method");
+    assert!(err.is::<HttpRouteNotFound>(), "unexpected error: {}", err);
+
+    let err = call(svc, ::http::Method::POST, "/administrator")
+        .await
+        .expect_err("requests must match an entire path segment");
+    assert!(err.is::<HttpRouteNotFound>(), "unexpected error: {}", err);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn routes_updated() {
+    let (svc, tx) = mk_svc(
+        routes_policy(),
+        tls::ConditionalServerTls::None(tls::NoServerTls::NoClientHello),
+    );
+
+    call(svc.clone(), ::http::Method::GET, "/other")
+        .await
+        .expect_err("requests must match a route");
+
+    tx.send(ServerPolicy {
+        http_routes: Arc::new([]),
+        grpc_routes: Arc::new([]),
+        ..routes_policy()
+    })
+    .expect("policy must be updated");
+    call(svc, ::http::Method::GET, "/other")
+        .await
+        .expect("requests must be authorized by the server when routes are removed");
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn grpc_routes() {
+    let (svc, _tx) = mk_svc(
+        ServerPolicy {
+            protocol: Protocol::Grpc,
+            authorizations: Arc::new([]),
+            http_routes: Arc::new([]),
+            grpc_routes: Arc::new([grpc::Route {
+                hosts: vec![],
+                rules: vec![grpc::Rule {
+                    matches: vec![MatchRoute {
+                        rpc: MatchRpc {
+                            service: Some("io.linkerd.Admin".into()),
+                            method: None,
+                        },
+                        ..MatchRoute::default()
+                    }],
+                    policy: RoutePolicy {
+                        meta: mk_meta("grpcroute", "admin"),
+                        authorizations: vec![Authorization {
+                            authentication: Authentication::Unauthenticated,
+                            networks: vec!["192.0.2.0/24".parse().unwrap()],
+                            meta: mk_meta("authorizationpolicy", "all"),
+                        }]
+                        .into(),
+                        priority: None,
+                    },
+                }],
+            }]),
+            rate_limit: None,
+            meta: mk_meta("server", "test"),
+        },
+        tls::ConditionalServerTls::None(tls::NoServerTls::NoClientHello),
+    );
+
+    let permit = call(svc.clone(), ::http::Method::POST, "/io.linkerd.Admin/Reset")
+        .await
+        .expect("request must be authorized");
+    assert_eq!(permit.labels.route.route, mk_meta("grpcroute", "admin"));
+
+    let err = call(svc.clone(), ::http::Method::POST, "/io.linkerd.Other/Reset")
+        .await
+        .expect_err("request must match a gRPC service");
+    assert!(err.is::<HttpRouteNotFound>(), "unexpected error: {}", err);
+
+    let err = call(svc, ::http::Method::GET, "/io.linkerd.Admin/Reset")
+        .await
+        .expect_err("gRPC requests must use the POST method");
+    assert!(err.is::<HttpRouteNotFound>(), "unexpected error: {}", err);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn rate_limited() {
+    let (svc, _tx) = mk_svc(
+        ServerPolicy {
+            rate_limit: Some(RateLimit {
+                requests_per_second: std::num::NonZeroU32::new(1).unwrap(),
+                burst: 2,
+                key: RateLimitKey::ClientAddr,
+            }),
+            ..routes_policy()
+        },
+        tls::ConditionalServerTls::None(tls::NoServerTls::NoClientHello),
+    );
+
+    for _ in 0..2 {
+        call(svc.clone(), ::http::Method::GET, "/healthz")
+            .await
+            .expect("requests within the burst must be permitted");
+    }
+    let err = call(svc.clone(), ::http::Method::GET, "/healthz")
+        .await
+        .expect_err("requests in excess of the burst must be limited");
+    assert!(err.is::<HttpRateLimited>(), "unexpected error: {}", err);
+
+    let err = call(svc, ::http::Method::GET, "/other")
+        .await
+        .expect_err("requests must match a route");
+    assert!(
+        err.is::<HttpRouteNotFound>(),
+        "unmatched requests must not be rate limited: {}",
+        err
+    );
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn strips_priority_header() {
+    let (mut svc, _tx) = mk_svc(
+        routes_policy(),
+        tls::ConditionalServerTls::None(tls::NoServerTls::NoClientHello),
+    );
+
+    let req = ::http::Request::builder()
+        .uri("/healthz")
+        .header(priority::L5D_PRIORITY, "critical")
+        .body(())
+        .unwrap();
+    svc::Service::call(&mut svc, req)
+        .await
+        .expect("request must be authorized");
+}
+
+#[test]
+fn header_priorities() {
+    let critical = || Some(::http::HeaderValue::from_static("critical"));
+    let authenticated = tls::ConditionalServerTls::Some(tls::ServerTls::Established {
+        client_id: Some(client_id()),
+        negotiated_protocol: None,
+    });
+    let unauthenticated = tls::ConditionalServerTls::None(tls::NoServerTls::NoClientHello);
+
+    assert_eq!(
+        header_priority(critical(), &authenticated, true),
+        Priority::Critical
+    );
+    assert_eq!(
+        header_priority(critical(), &authenticated, false),
+        Priority::Normal,
+        "the header must only be honored when trusted"
+    );
+    assert_eq!(
+        header_priority(critical(), &unauthenticated, true),
+        Priority::Normal,
+        "unauthenticated clients must not set the priority"
+    );
+    assert_eq!(
+        header_priority(None, &authenticated, true),
+        Priority::Normal
+    );
+}
+
+#[test]
+fn grpc_rpc_labels() {
+    use linkerd_app_core::metrics::FmtLabels;
+
+    struct Fmt(GrpcRpcLabels);
+    impl std::fmt::Display for Fmt {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            self.0.fmt_labels(f)
+        }
+    }
+
+    let req = ::http::Request::builder()
+        .method(::http::Method::POST)
+        .uri("/io.linkerd.Admin/Reset")
+        .body(())
+        .unwrap();
+    let rts = [grpc::Route {
+        hosts: vec![],
+        rules: vec![grpc::Rule {
+            matches: vec![MatchRoute {
+                rpc: MatchRpc {
+                    service: Some("io.linkerd.Admin".into()),
+                    method: None,
+                },
+                ..MatchRoute::default()
+            }],
+            policy: RoutePolicy {
+                meta: mk_meta("grpcroute", "admin"),
+                authorizations: Arc::new([]),
+                priority: None,
+            },
+        }],
+    }];
+    let (m, _) = grpc::find(&rts, &req).expect("route must match");
+    assert_eq!(
+        Fmt(GrpcRpcLabels::new(req.uri().path(), &m)).to_string(),
+        "grpc_service=\"io.linkerd.Admin\",grpc_method=\"\"",
+        "only explicitly matched parts of the path may be used as labels"
+    );
+}
+
+type MockSvc = HttpPolicyService<
+    (),
+    fn(
+        (HttpRoutePermit, ()),
+    ) -> svc::BoxService<::http::Request<()>, ::http::Response<BoxBody>, Error>,
+>;
+
+fn mk_svc(
+    server: ServerPolicy,
+    tls: tls::ConditionalServerTls,
+) -> (MockSvc, tokio::sync::watch::Sender<ServerPolicy>) {
+    let (policy, tx) = AllowPolicy::for_test(orig_dst_addr(), server);
+    let svc = HttpPolicyService {
+        target: (),
+        meta: ConnectionMeta {
+            dst: orig_dst_addr(),
+            client: client_addr(),
+            tls,
+        },
+        policy,
+        metrics: HttpAuthzMetrics::default(),
+        rate_limits: Default::default(),
+        priority_queue: PriorityQueue::new(None),
+        inner: |(permit, ()): (HttpRoutePermit, ())| {
+            svc::BoxService::new(svc::mk(move |req: ::http::Request<()>| {
+                assert!(
+                    req.headers().get(priority::L5D_PRIORITY).is_none(),
+                    "the priority header must not be forwarded"
+                );
+                let mut rsp = ::http::Response::new(BoxBody::default());
+                rsp.extensions_mut().insert(permit.clone());
+                future::ok(rsp)
+            }))
+        },
+        default_route_meta: Meta::new_default("default"),
+    };
+    (svc, tx)
+}
+
+async fn call(
+    mut svc: MockSvc,
+    method: ::http::Method,
+    path: &str,
+) -> Result<HttpRoutePermit, Error> {
+    let req = ::http::Request::builder()
+        .method(method)
+        .uri(path)
+        .body(())
+        .unwrap();
+    let rsp = svc::Service::call(&mut svc, req).await?;
+    Ok(rsp
+        .extensions()
+        .get::<HttpRoutePermit>()
+        .cloned()
+        .expect("response must include the route permit"))
+}
+
+fn routes_policy() -> ServerPolicy {
+    ServerPolicy {
+        protocol: Protocol::Http1,
+        authorizations: Arc::new([]),
+        http_routes: Arc::new([Route {
+            hosts: vec![],
+            rules: vec![
+                Rule {
+                    matches: vec![MatchRequest {
+                        path: Some(MatchPath::Exact("/healthz".into())),
+                        method: Some(::http::Method::GET),
+                        ..MatchRequest::default()
+                    }],
+                    policy: RoutePolicy {
+                        meta: mk_meta("httproute", "healthz"),
+                        authorizations: vec![Authorization {
+                            authentication: Authentication::Unauthenticated,
+                            networks: vec!["192.0.2.0/24".parse().unwrap()],
+                            meta: mk_meta("authorizationpolicy", "all"),
+                        }]
+                        .into(),
+                        priority: None,
+                    },
+                },
+                Rule {
+                    matches: vec![MatchRequest {
+                        path: Some(MatchPath::Prefix("/admin".into())),
+                        method: Some(::http::Method::POST),
+                        ..MatchRequest::default()
+                    }],
+                    policy: RoutePolicy {
+                        meta: mk_meta("httproute", "admin"),
+                        authorizations: vec![Authorization {
+                            authentication: Authentication::TlsAuthenticated {
+                                suffixes: vec![],
+                                identities: vec![client_id().to_string()].into_iter().collect(),
+                            },
+                            networks: vec!["192.0.2.0/24".parse().unwrap()],
+                            meta: mk_meta("authorizationpolicy", "admin"),
+                        }]
+                        .into(),
+                        priority: None,
+                    },
+                },
+            ],
+        }]),
+        grpc_routes: Arc::new([]),
+        rate_limit: None,
+        meta: mk_meta("server", "test"),
+    }
+}
+
+fn mk_meta(kind: &str, name: &str) -> Arc<Meta> {
+    Arc::new(Meta::Resource {
+        group: "policy.linkerd.io".into(),
+        kind: kind.into(),
+        name: name.into(),
+    })
+}
+
+fn client_id() -> tls::ClientId {
+    "foo.ns1.serviceaccount.identity.linkerd.cluster.local"
+        .parse()
+        .unwrap()
+}
+
+fn client_addr() -> Remote<ClientAddr> {
+    Remote(ClientAddr(([192, 0, 2, 3], 54321).into()))
+}
+
+fn orig_dst_addr() -> OrigDstAddr {
+    OrigDstAddr(([192, 0, 2, 2], 1000).into())
+}
@@ -0,0 +1,400 @@
+use super::Priority;
+use futures::ready;
+use linkerd_app_core::{
+    proxy::http::{BoxBody, HttpBody},
+    Error,
+};
+use parking_lot::Mutex;
+use pin_project::pin_project;
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tokio::{
+    sync::oneshot,
+    time::{self, Duration, Sleep},
+};
+
+/// The header with which authenticated clients may set the priority of a
+/// request on routes that do not set a priority, if the header is trusted. It
+/// is removed from all requests before they are dispatched.
+pub const L5D_PRIORITY: &str = "l5d-priority";
+
+/// Configures priority-aware load shedding of inbound HTTP requests.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PriorityShedding {
+    /// The number of requests that may be in flight across all connections.
+    pub max_in_flight: usize,
+
+    /// The number of requests that may wait for capacity. When the queue is
+    /// full, the lowest-priority request is shed.
+    pub max_queued: usize,
+
+    /// How long a request may wait for capacity before it is shed.
+    pub queue_timeout: Duration,
+
+    /// Whether clients with a TLS identity may set the priority of their
+    /// requests with the `l5d-priority` header. Otherwise, requests on routes
+    /// that do not set a priority have the normal priority.
+    pub trust_priority_header: bool,
+}
+
+/// Limits the number of in-flight requests, serving waiting requests in
+/// priority order.
+///
+/// The queue is shared by all connections in the inbound stack so that the
+/// limit applies across connections.
+#[derive(Clone, Debug)]
+pub(crate) struct PriorityQueue {
+    config: Option<PriorityShedding>,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{0} priority request shed due to overload")]
+pub struct HttpRequestShed(Priority);
+
+/// Holds capacity for a request until it is dropped.
+#[derive(Debug)]
+pub(crate) struct Permit(Arc<Mutex<State>>);
+
+/// Waits for a request to be admitted before dispatching it.
+///
+/// The request holds its capacity until its response body has ended, so that
+/// streaming responses count against the limit.
+#[pin_project]
+#[derive(Debug)]
+pub struct Admit<F> {
+    acquire: Option<Acquire>,
+    permit: Option<Permit>,
+    #[pin]
+    inner: F,
+}
+
+/// Holds a request's capacity until its response body ends.
+#[pin_project]
+#[derive(Debug)]
+struct PermitBody<B> {
+    #[pin]
+    inner: B,
+    permit: Option<Permit>,
+}
+
+#[derive(Debug)]
+enum Acquire {
+    Admitted(Option<Permit>),
+    Waiting(Waiter, Pin<Box<Sleep>>),
+    Shed(Priority),
+}
+
+#[derive(Debug)]
+struct Waiter {
+    priority: Priority,
+    rx: oneshot::Receiver<()>,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    in_flight: usize,
+
+    /// Requests waiting for capacity, indexed by priority.
+    waiting: [VecDeque<oneshot::Sender<()>>; Priority::ALL.len()],
+}
+
+// === impl PriorityQueue ===
+
+impl PriorityQueue {
+    pub(crate) fn new(config: Option<PriorityShedding>) -> Self {
+        Self {
+            config,
+            state: Default::default(),
+        }
+    }
+
+    /// Returns whether authenticated clients may set request priorities.
+    pub(crate) fn trusts_header(&self) -> bool {
+        self.config
+            .map(|c| c.trust_priority_header)
+            .unwrap_or(false)
+    }
+
+    /// Admits a request of the given priority once there is capacity for it.
+    pub(crate) fn admit<F>(&self, priority: Priority, inner: F) -> Admit<F> {
+        Admit {
+            acquire: Some(self.acquire(priority)),
+            permit: None,
+            inner,
+        }
+    }
+
+    fn acquire(&self, priority: Priority) -> Acquire {
+        let config = match self.config {
+            Some(config) => config,
+            None => return Acquire::Admitted(None),
+        };
+
+        let mut state = self.state.lock();
+        if state.in_flight < config.max_in_flight {
+            state.in_flight += 1;
+            return Acquire::Admitted(Some(Permit(self.state.clone())));
+        }
+
+        // Discard requests that are no longer waiting before checking whether
+        // the queue is full.
+        for waiting in state.waiting.iter_mut() {
+            waiting.retain(|tx| !tx.is_closed());
+        }
+        let queued = state.waiting.iter().map(VecDeque::len).sum::<usize>();
+        if queued >= config.max_queued {
+            // Shed the newest of the lowest-priority waiting requests, if it
+            // has a lower priority than this request. Otherwise, shed this
+            // request.
+            let lower = state.waiting[..priority as usize]
+                .iter_mut()
+                .find(|waiting| !waiting.is_empty());
+            match lower {
+                Some(waiting) => drop(waiting.pop_back()),
+                None => {
+                    tracing::debug!(%priority, queued, "Queue is full; shedding request");
+                    return Acquire::Shed(priority);
+                }
+            }
+        }
+
+        let (tx, rx) = oneshot::channel();
+        state.waiting[priority as usize].push_back(tx);
+        tracing::trace!(%priority, queued, "Waiting for capacity");
+        Acquire::Waiting(
+            Waiter {
+                priority,
+                rx,
+                state: self.state.clone(),
+            },
+            Box::pin(time::sleep(config.queue_timeout)),
+        )
+    }
+}
+
+// === impl Admit ===
+
+impl<F, E> Future for Admit<F>
+where
+    F: Future<Output = Result<::http::Response<BoxBody>, E>>,
+    E: Into<Error>,
+{
+    type Output = Result<::http::Response<BoxBody>, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        if let Some(acquire) = this.acquire.as_mut() {
+            let permit = ready!(acquire.poll(cx))?;
+            *this.permit = permit;
+            *this.acquire = None;
+        }
+        let rsp = ready!(this.inner.poll(cx)).map_err(Into::into)?;
+        let rsp = match this.permit.take() {
+            Some(permit) => rsp.map(|inner| {
+                BoxBody::new(PermitBody {
+                    inner,
+                    permit: Some(permit),
+                })
+            }),
+            None => rsp,
+        };
+        Poll::Ready(Ok(rsp))
+    }
+}
+
+// === impl PermitBody ===
+
+impl<B: HttpBody> HttpBody for PermitBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.project();
+        let res = ready!(this.inner.poll_data(cx));
+        if !matches!(res, Some(Ok(_))) {
+            // Release capacity as soon as the body ends or fails.
+            drop(this.permit.take());
+        }
+        Poll::Ready(res)
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<::http::HeaderMap>, Self::Error>> {
+        let this = self.project();
+        let res = ready!(this.inner.poll_trailers(cx));
+        drop(this.permit.take());
+        Poll::Ready(res)
+    }
+
+    #[inline]
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+// === impl Acquire ===
+
+impl Acquire {
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Result<Option<Permit>, HttpRequestShed>> {
+        match self {
+            Self::Admitted(permit) => Poll::Ready(Ok(permit.take())),
+            Self::Shed(priority) => Poll::Ready(Err(HttpRequestShed(*priority))),
+            Self::Waiting(waiter, sleep) => {
+                if let Poll::Ready(res) = Pin::new(&mut waiter.rx).poll(cx) {
+                    return Poll::Ready(match res {
+                        Ok(()) => Ok(Some(Permit(waiter.state.clone()))),
+                        // The request was displaced by a higher-priority
+                        // request.
+                        Err(_) => Err(HttpRequestShed(waiter.priority)),
+                    });
+                }
+                ready!(sleep.as_mut().poll(cx));
+                // Capacity may have been handed to this request just as it
+                // timed out.
+                if let Some(permit) = waiter.close() {
+                    return Poll::Ready(Ok(Some(permit)));
+                }
+                tracing::debug!(priority = %waiter.priority, "Timed out waiting for capacity");
+                Poll::Ready(Err(HttpRequestShed(waiter.priority)))
+            }
+        }
+    }
+}
+
+// === impl Waiter ===
+
+impl Waiter {
+    /// Stops waiting, returning a permit if capacity was already handed to
+    /// this request.
+    fn close(&mut self) -> Option<Permit> {
+        self.rx.close();
+        self.rx.try_recv().ok().map(|()| Permit(self.state.clone()))
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        // If the request is canceled after it was handed capacity, pass the
+        // capacity on.
+        drop(self.close());
+    }
+}
+
+// === impl Permit ===
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        // Hand capacity to the highest-priority waiting request, if any.
+        for waiting in state.waiting.iter_mut().rev() {
+            while let Some(tx) = waiting.pop_front() {
+                if tx.send(()).is_ok() {
+                    return;
+                }
+            }
+        }
+        state.in_flight = state.in_flight.saturating_sub(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn serves_by_priority() {
+        let queue = PriorityQueue::new(Some(PriorityShedding {
+            max_in_flight: 1,
+            max_queued: 2,
+            queue_timeout: Duration::from_secs(1),
+            trust_priority_header: false,
+        }));
+        let ok = || future::ok::<_, Error>(::http::Response::new(BoxBody::default()));
+
+        let permit = match queue.acquire(Priority::Normal) {
+            Acquire::Admitted(Some(permit)) => permit,
+            acquire => panic!("unexpected {:?}", acquire),
+        };
+
+        // Requests wait while the queue has room. When it is full, a
+        // higher-priority request displaces the lowest-priority one.
+        let mut low = tokio_test::task::spawn(queue.admit(Priority::Low, ok()));
+        let mut normal = tokio_test::task::spawn(queue.admit(Priority::Normal, ok()));
+        assert!(low.poll().is_pending());
+        assert!(normal.poll().is_pending());
+        let mut critical = tokio_test::task::spawn(queue.admit(Priority::Critical, ok()));
+        assert!(critical.poll().is_pending());
+        match low.poll() {
+            Poll::Ready(Err(e)) => assert!(e.is::<HttpRequestShed>(), "{}", e),
+            poll => panic!("low priority request must be shed: {:?}", poll),
+        }
+
+        // Requests that cannot displace a waiting request are shed.
+        let mut shed = tokio_test::task::spawn(queue.admit(Priority::Low, ok()));
+        assert!(matches!(shed.poll(), Poll::Ready(Err(_))));
+
+        // Released capacity is handed to the highest-priority request.
+        drop(permit);
+        assert!(critical.is_woken());
+        assert!(matches!(critical.poll(), Poll::Ready(Ok(_))));
+        drop(critical);
+        assert!(matches!(normal.poll(), Poll::Ready(Ok(_))));
+        drop(normal);
+        assert_eq!(queue.state.lock().in_flight, 0);
+
+        // Requests that wait too long are shed.
+        let _permit = queue.acquire(Priority::Normal);
+        let mut slow = tokio_test::task::spawn(queue.admit(Priority::High, ok()));
+        assert!(slow.poll().is_pending());
+        time::advance(Duration::from_secs(2)).await;
+        assert!(matches!(slow.poll(), Poll::Ready(Err(_))));
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn holds_capacity_until_response_ends() {
+        let queue = PriorityQueue::new(Some(PriorityShedding {
+            max_in_flight: 1,
+            max_queued: 1,
+            queue_timeout: Duration::from_secs(1),
+            trust_priority_header: false,
+        }));
+        let rsp = |body: BoxBody| future::ok::<_, Error>(::http::Response::new(body));
+
+        let rsp = queue
+            .admit(
+                Priority::Normal,
+                rsp(BoxBody::new(hyper::Body::from("hello"))),
+            )
+            .await
+            .expect("request must be admitted");
+        let mut waiting =
+            tokio_test::task::spawn(queue.admit(Priority::Normal, rsp(BoxBody::default())));
+        assert!(
+            waiting.poll().is_pending(),
+            "capacity must be held while the response body is streamed"
+        );
+
+        let mut body = rsp.into_body();
+        while body.data().await.is_some() {}
+        assert!(waiting.is_woken());
+        assert!(matches!(waiting.poll(), Poll::Ready(Ok(_))));
+    }
+}
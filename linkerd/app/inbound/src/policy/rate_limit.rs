@@ -0,0 +1,153 @@
+use super::{Meta, RateLimit, RateLimitKey};
+use linkerd_app_core::{
+    tls,
+    transport::{ClientAddr, Remote},
+};
+use parking_lot::Mutex;
+use std::{collections::HashMap, net::IpAddr, sync::Arc};
+use tokio::time::{Duration, Instant};
+
+/// Enforces servers' rate limits.
+///
+/// Buckets are shared by all connections in the inbound stack so that limits
+/// apply across connections. Buckets are created lazily and are discarded once
+/// they have been refilled, since a full bucket is indistinguishable from a new
+/// one.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RateLimits(Arc<Mutex<Buckets>>);
+
+#[derive(Debug, thiserror::Error)]
+#[error("too many requests on server {}/{}", .0.kind(), .0.name())]
+pub struct HttpRateLimited(Arc<Meta>);
+
+#[derive(Debug, Default)]
+struct Buckets {
+    buckets: HashMap<BucketKey, Bucket>,
+    last_swept: Option<Instant>,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct BucketKey {
+    server: Arc<Meta>,
+    client: ClientKey,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum ClientKey {
+    Server,
+    Identity(tls::ClientId),
+    Addr(IpAddr),
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    capacity: f64,
+    per_second: f64,
+    updated: Instant,
+}
+
+/// How frequently refilled buckets are discarded.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+
+// === impl RateLimits ===
+
+impl RateLimits {
+    /// Takes a token from the bucket for the given server and client, failing
+    /// if the bucket is empty.
+    pub(crate) fn check(
+        &self,
+        server: &Arc<Meta>,
+        limit: &RateLimit,
+        client: Remote<ClientAddr>,
+        tls: &tls::ConditionalServerTls,
+    ) -> Result<(), HttpRateLimited> {
+        let key = match limit.key {
+            RateLimitKey::Server => ClientKey::Server,
+            RateLimitKey::ClientIdentity => match tls {
+                tls::ConditionalServerTls::Some(tls::ServerTls::Established {
+                    client_id: Some(id),
+                    ..
+                }) => ClientKey::Identity(id.clone()),
+                _ => ClientKey::Addr(client.ip()),
+            },
+            RateLimitKey::ClientAddr => ClientKey::Addr(client.ip()),
+        };
+
+        let now = Instant::now();
+        let mut buckets = self.0.lock();
+        buckets.sweep(now);
+        let permitted = buckets
+            .buckets
+            .entry(BucketKey {
+                server: server.clone(),
+                client: key,
+            })
+            .or_insert_with(|| Bucket::new(limit, now))
+            .acquire(limit, now);
+        if !permitted {
+            tracing::debug!(
+                server.group = %server.group(),
+                server.kind = %server.kind(),
+                server.name = %server.name(),
+                client.ip = %client.ip(),
+                "Request rate limited",
+            );
+            return Err(HttpRateLimited(server.clone()));
+        }
+
+        Ok(())
+    }
+}
+
+// === impl Buckets ===
+
+impl Buckets {
+    fn sweep(&mut self, now: Instant) {
+        match self.last_swept {
+            Some(t) if now.saturating_duration_since(t) < SWEEP_INTERVAL => {}
+            _ => {
+                self.buckets.retain(|_, b| !b.is_full(now));
+                self.last_swept = Some(now);
+            }
+        }
+    }
+}
+
+// === impl Bucket ===
+
+impl Bucket {
+    fn new(limit: &RateLimit, now: Instant) -> Self {
+        let capacity = limit.capacity().into();
+        Self {
+            tokens: capacity,
+            capacity,
+            per_second: limit.requests_per_second.get().into(),
+            updated: now,
+        }
+    }
+
+    fn acquire(&mut self, limit: &RateLimit, now: Instant) -> bool {
+        // The server's limit may have been updated since the bucket was
+        // created.
+        self.capacity = limit.capacity().into();
+        self.per_second = limit.requests_per_second.get().into();
+
+        self.tokens = self.tokens_at(now);
+        self.updated = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+
+    fn tokens_at(&self, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        (self.tokens + elapsed * self.per_second).min(self.capacity)
+    }
+
+    fn is_full(&self, now: Instant) -> bool {
+        self.tokens_at(now) >= self.capacity
+    }
+}
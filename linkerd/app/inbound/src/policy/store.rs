@@ -5,21 +5,33 @@ pub use linkerd_server_policy::{
     authz::Suffix, Authentication, Authorization, Protocol, ServerPolicy,
 };
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     hash::{BuildHasherDefault, Hasher},
+    sync::Arc,
 };
-use tokio::{sync::watch, time::Duration};
-use tracing::info_span;
+use tokio::{
+    sync::{mpsc, watch},
+    time::Duration,
+};
+use tracing::{info_span, Instrument};
 
 #[derive(Clone)]
 pub struct Store<S> {
     cache: Cache<u16, Rx, BuildHasherDefault<PortHasher>>,
     default_rx: Rx,
     discover: Option<api::Watch<S>>,
+    watch: Option<PortsRx>,
+    updates: Option<UpdatesTx>,
 }
 
 type Rx = watch::Receiver<ServerPolicy>;
 
+/// Watches the policies for all configured ports.
+pub type PortsRx = watch::Receiver<Arc<HashMap<u16, ServerPolicy>>>;
+
+/// Receives each update to a watched port's policy.
+pub type UpdatesTx = mpsc::UnboundedSender<(u16, ServerPolicy)>;
+
 /// A hasher for ports.
 ///
 /// Because ports are single `u16` values, we don't have to hash them; we can just use
@@ -49,19 +61,57 @@ impl<S> Store<S> {
         Self {
             cache,
             discover: None,
+            watch: None,
+            updates: None,
             default_rx: Self::spawn_default(default),
         }
     }
 
+    /// Spawns a watch for each of the ports in the watched policies.
+    ///
+    /// Ports that are not configured use the default policy until they are
+    /// added to the watched policies. Each port's updates are sent to
+    /// `updates`, if set.
+    pub(crate) fn spawn_watch(
+        default: DefaultPolicy,
+        idle_timeout: Duration,
+        ports: PortsRx,
+        updates: Option<UpdatesTx>,
+    ) -> Self {
+        let default_rx = Self::spawn_default(default);
+        let cache = {
+            let configured = ports.borrow().keys().copied().collect::<Vec<_>>();
+            let rxs = configured.into_iter().map(|port| {
+                let rx = Self::spawn_port(ports.clone(), port, default_rx.clone(), updates.clone());
+                (port, rx)
+            });
+            Cache::with_permanent_from_iter(idle_timeout, rxs)
+        };
+
+        Self {
+            cache,
+            discover: None,
+            watch: Some(ports),
+            updates,
+            default_rx,
+        }
+    }
+
     /// Spawns a watch for each of the given ports.
     ///
     /// A discovery watch is spawned for each of the described `ports` and the
     /// result is cached for as long as the `Store` is held. The `Store` may be used to
+    ///
+    /// Ports with `initial` policies are watched as though they were
+    /// configured, and use their initial policy until a policy is discovered.
+    /// Each port's updates are sent to `updates`, if set.
     pub(super) fn spawn_discover(
         default: DefaultPolicy,
         idle_timeout: Duration,
         discover: api::Watch<S>,
-        ports: HashSet<u16>,
+        mut ports: HashSet<u16>,
+        mut initial: HashMap<u16, ServerPolicy>,
+        updates: Option<UpdatesTx>,
     ) -> Self
     where
         S: tonic::client::GrpcService<tonic::body::BoxBody, Error = Error>,
@@ -76,11 +126,16 @@ impl<S> Store<S> {
         // `idle_timeout` to prevent holding policy watches indefinitely for
         // ports that are generally unused.
         let cache = {
+            ports.extend(initial.keys().copied());
             let rxs = ports.into_iter().map(|port| {
                 let discover = discover.clone();
-                let default = default.clone();
-                let rx = info_span!("watch", port)
-                    .in_scope(|| discover.spawn_with_init(port, default.into()));
+                let init = initial
+                    .remove(&port)
+                    .unwrap_or_else(|| default.clone().into());
+                let rx = info_span!("watch", port).in_scope(|| {
+                    let rx = discover.spawn_with_init(port, init);
+                    Self::forward_updates(port, rx, updates.clone())
+                });
                 (port, rx)
             });
             Cache::with_permanent_from_iter(idle_timeout, rxs)
@@ -89,6 +144,8 @@ impl<S> Store<S> {
         Self {
             cache,
             discover: Some(discover),
+            watch: None,
+            updates,
             default_rx: Self::spawn_default(default),
         }
     }
@@ -102,6 +159,78 @@ impl<S> Store<S> {
         });
         rx
     }
+
+    /// Spawns a task that publishes a single port's policy as the watched
+    /// policies change, sending each update to `updates`, if set.
+    fn spawn_port(mut ports: PortsRx, port: u16, default_rx: Rx, updates: Option<UpdatesTx>) -> Rx {
+        let policy = move |ports: &PortsRx| {
+            ports
+                .borrow()
+                .get(&port)
+                .cloned()
+                .unwrap_or_else(|| default_rx.borrow().clone())
+        };
+        let (tx, rx) = watch::channel(policy(&ports));
+        tokio::spawn(
+            async move {
+                // The task completes when the watched policies are dropped or
+                // when the port's policy is no longer used.
+                loop {
+                    tokio::select! {
+                        res = ports.changed() => {
+                            if res.is_err() {
+                                return;
+                            }
+                        }
+                        _ = tx.closed() => return,
+                    }
+                    let policy = policy(&ports);
+                    if *tx.borrow() != policy {
+                        tracing::debug!(?policy, "Updated");
+                        if let Some(updates) = updates.as_ref() {
+                            let _ = updates.send((port, policy.clone()));
+                        }
+                        if tx.send(policy).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+            .instrument(info_span!("watch", port)),
+        );
+        rx
+    }
+
+    /// Publishes a discovered port's policy, sending each update to
+    /// `updates`, until the port's policy is no longer used.
+    fn forward_updates(port: u16, mut rx: Rx, updates: Option<UpdatesTx>) -> Rx {
+        let updates = match updates {
+            Some(updates) => updates,
+            None => return rx,
+        };
+        let (tx, forwarded) = watch::channel(rx.borrow().clone());
+        tokio::spawn(
+            async move {
+                loop {
+                    tokio::select! {
+                        res = rx.changed() => {
+                            if res.is_err() {
+                                return;
+                            }
+                        }
+                        _ = tx.closed() => return,
+                    }
+                    let policy = rx.borrow().clone();
+                    let _ = updates.send((port, policy.clone()));
+                    if tx.send(policy).is_err() {
+                        return;
+                    }
+                }
+            }
+            .in_current_span(),
+        );
+        forwarded
+    }
 }
 
 impl<S> GetPolicy for Store<S>
@@ -116,23 +245,31 @@ where
         // Lookup the polcify for the target port in the cache. If it doesn't
         // already exist, we spawn a watch on the API (if it is configured). If
         // no discovery API is configured we use the default policy.
-        let server =
-            self.cache
-                .get_or_insert_with(dst.port(), |port| match self.discover.clone() {
-                    Some(disco) => info_span!("watch", port).in_scope(|| {
-                        tracing::trace!(%port, "spawning policy discovery");
-                        disco.spawn_with_init(*port, self.default_rx.borrow().clone())
-                    }),
-
-                    // If no discovery API is configured, then we use the
-                    // default policy. Whlie it's a little wasteful to cache
-                    // these results separately, this case isn't expected to be
-                    // used outside of testing.
-                    None => {
-                        tracing::trace!(%port, "using the default policy");
-                        self.default_rx.clone()
-                    }
-                });
+        let server = self.cache.get_or_insert_with(dst.port(), |port| {
+            match (self.discover.clone(), self.watch.clone()) {
+                (Some(disco), _) => info_span!("watch", port).in_scope(|| {
+                    tracing::trace!(%port, "spawning policy discovery");
+                    let rx = disco.spawn_with_init(*port, self.default_rx.borrow().clone());
+                    Self::forward_updates(*port, rx, self.updates.clone())
+                }),
+
+                // If policies are watched, the port uses the default policy
+                // until it is configured.
+                (None, Some(ports)) => {
+                    tracing::trace!(%port, "watching policy");
+                    Self::spawn_port(ports, *port, self.default_rx.clone(), self.updates.clone())
+                }
+
+                // If no discovery API is configured, then we use the
+                // default policy. Whlie it's a little wasteful to cache
+                // these results separately, this case isn't expected to be
+                // used outside of testing.
+                (None, None) => {
+                    tracing::trace!(%port, "using the default policy");
+                    self.default_rx.clone()
+                }
+            }
+        });
 
         AllowPolicy { dst, server }
     }
@@ -21,6 +21,9 @@ async fn unauthenticated_allowed() {
             }),
         }]
         .into(),
+        http_routes: Arc::new([]),
+        grpc_routes: Arc::new([]),
+        rate_limit: None,
         meta: Arc::new(Meta::Resource {
             group: "policy.linkerd.io".into(),
             kind: "server".into(),
@@ -69,6 +72,9 @@ async fn authenticated_identity() {
             }),
         }]
         .into(),
+        http_routes: Arc::new([]),
+        grpc_routes: Arc::new([]),
+        rate_limit: None,
         meta: Arc::new(Meta::Resource {
             group: "policy.linkerd.io".into(),
             kind: "server".into(),
@@ -131,6 +137,9 @@ async fn authenticated_suffix() {
             }),
         }]
         .into(),
+        http_routes: Arc::new([]),
+        grpc_routes: Arc::new([]),
+        rate_limit: None,
         meta: Arc::new(Meta::Resource {
             group: "policy.linkerd.io".into(),
             kind: "server".into(),
@@ -189,6 +198,9 @@ async fn tls_unauthenticated() {
             }),
         }]
         .into(),
+        http_routes: Arc::new([]),
+        grpc_routes: Arc::new([]),
+        rate_limit: None,
         meta: Arc::new(Meta::Resource {
             group: "policy.linkerd.io".into(),
             kind: "server".into(),
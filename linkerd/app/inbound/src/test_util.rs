@@ -66,6 +66,9 @@ pub fn default_config() -> Config {
                     }),
                 }]
                 .into(),
+                http_routes: Arc::new([]),
+                grpc_routes: Arc::new([]),
+                rate_limit: None,
                 meta: Arc::new(Meta::Resource {
                     group: "policy.linkerd.io".into(),
                     kind: "server".into(),
@@ -77,6 +80,7 @@ pub fn default_config() -> Config {
         },
         profile_idle_timeout: Duration::from_millis(500),
         allowed_ips: Default::default(),
+        priority_shedding: None,
     }
 }
 
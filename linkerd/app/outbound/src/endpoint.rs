@@ -140,6 +140,18 @@ impl<P> svc::Param<metrics::EndpointLabels> for Endpoint<P> {
     }
 }
 
+impl<P> svc::Param<metrics::BalancerLabels> for Endpoint<P> {
+    fn param(&self) -> metrics::BalancerLabels {
+        metrics::BalancerLabels::outbound(self.logical_addr.clone())
+    }
+}
+
+impl<P> svc::Param<http::balance::EndpointZone> for Endpoint<P> {
+    fn param(&self) -> http::balance::EndpointZone {
+        http::balance::EndpointZone(self.metadata.zone().map(Into::into))
+    }
+}
+
 // === EndpointFromMetadata ===
 impl FromMetadata {
     fn client_tls(metadata: &Metadata, reason: tls::NoClientTls) -> tls::ConditionalClientTls {
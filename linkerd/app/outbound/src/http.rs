@@ -1,12 +1,18 @@
+pub(crate) mod adaptive_limit;
 pub mod detect;
 mod endpoint;
 pub mod logical;
+mod mirror;
 mod proxy_connection_close;
 mod require_id_header;
 mod retry;
 mod server;
 mod strip_proxy_error;
 
+pub use self::{
+    adaptive_limit::{AdaptiveConcurrency, ConcurrencyLimitExceeded},
+    retry::{PerTryTimeoutError, RetryableErrors},
+};
 use self::{
     proxy_connection_close::ProxyConnectionClose, require_id_header::NewRequireIdentity,
     strip_proxy_error::NewStripProxyError,
@@ -21,6 +27,7 @@ use linkerd_app_core::{
     svc::Param,
     tls, Addr, Conditional, CANONICAL_DST_HEADER,
 };
+pub use linkerd_http_retry::Spill as RetrySpill;
 use std::{net::SocketAddr, str::FromStr};
 
 pub type Accept = crate::Accept<Version>;
@@ -36,6 +43,13 @@ struct ProfileRoute {
     route: profiles::http::Route,
 }
 
+/// A logical target whose traffic split is fixed to a route's backends.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct RouteBackends {
+    logical: Logical,
+    backends: profiles::Backends,
+}
+
 #[derive(Clone, Debug)]
 pub struct CanonicalDstHeader(pub Addr);
 
@@ -196,6 +210,12 @@ impl Param<metrics::ProfileRouteLabels> for ProfileRoute {
     }
 }
 
+impl Param<RouteFilters> for ProfileRoute {
+    fn param(&self) -> RouteFilters {
+        RouteFilters(self.route.filters().clone())
+    }
+}
+
 impl Param<ResponseTimeout> for ProfileRoute {
     fn param(&self) -> ResponseTimeout {
         ResponseTimeout(self.route.timeout())
@@ -209,3 +229,23 @@ impl classify::CanClassify for ProfileRoute {
         self.route.response_classes().clone().into()
     }
 }
+
+// === impl RouteBackends ===
+
+impl Param<profiles::Receiver> for RouteBackends {
+    fn param(&self) -> profiles::Receiver {
+        self.logical.profile.clone()
+    }
+}
+
+impl Param<LogicalAddr> for RouteBackends {
+    fn param(&self) -> LogicalAddr {
+        self.logical.logical_addr.clone()
+    }
+}
+
+impl Param<Option<profiles::Backends>> for RouteBackends {
+    fn param(&self) -> Option<profiles::Backends> {
+        Some(self.backends.clone())
+    }
+}
@@ -0,0 +1,443 @@
+//! Adaptively limits the number of in-flight requests to each logical
+//! destination.
+//!
+//! The limit is adjusted with an AIMD (additive-increase,
+//! multiplicative-decrease) algorithm driven by response latency. The lowest
+//! latency observed recently serves as a baseline: when a response takes more
+//! than `tolerance` times the baseline, the destination is considered
+//! congested and the limit is multiplied by `backoff_ratio`. Otherwise, while
+//! the destination is using at least half of its limit, the limit grows by
+//! roughly one request per limit's worth of responses. Requests that fail or
+//! are canceled before they complete, e.g. by a route timeout, are considered
+//! congested as well. Requests that would exceed the limit fail immediately.
+
+use linkerd_app_core::{
+    profiles::LogicalAddr,
+    svc::{self, layer, NewService, Param},
+    Error,
+};
+use parking_lot::Mutex;
+use pin_project::pin_project;
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Weak,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// Configures adaptive concurrency limits.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AdaptiveConcurrency {
+    /// The limit of each destination before any responses are observed.
+    pub initial_limit: usize,
+
+    /// The bounds within which the limit is adjusted.
+    pub min_limit: usize,
+    pub max_limit: usize,
+
+    /// The ratio of a response's latency to the baseline latency above which
+    /// the destination is considered congested.
+    pub tolerance: f64,
+
+    /// The ratio, in `(0, 1)`, by which the limit is decreased when the
+    /// destination is congested.
+    pub backoff_ratio: f64,
+}
+
+/// Tracks the limiter of each logical destination so that it is shared by
+/// all of the destination's stacks and so that limits may be reported.
+#[derive(Clone, Debug, Default)]
+pub struct Registry(Arc<Mutex<HashMap<LogicalAddr, Weak<Limiter>>>>);
+
+#[derive(Clone, Debug)]
+pub struct NewAdaptiveLimit<N> {
+    inner: N,
+    config: Option<AdaptiveConcurrency>,
+    registry: Registry,
+}
+
+#[derive(Clone, Debug)]
+pub struct AdaptiveLimit<S> {
+    inner: S,
+    limiter: Option<Arc<Limiter>>,
+}
+
+#[pin_project(project = ResponseFutureProj)]
+pub enum ResponseFuture<F> {
+    Passthru(#[pin] F),
+    Limited(#[pin] F, Option<Permit>),
+    Rejected(Option<ConcurrencyLimitExceeded>),
+}
+
+/// Tracks a request that counts against a destination's limit.
+///
+/// A permit that is dropped before its response is observed decreases the
+/// limit.
+#[derive(Debug)]
+pub struct Permit {
+    limiter: Arc<Limiter>,
+    start: Instant,
+    completed: bool,
+}
+
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("concurrency limit of {0} requests exceeded")]
+pub struct ConcurrencyLimitExceeded(usize);
+
+#[derive(Debug)]
+struct Limiter {
+    config: AdaptiveConcurrency,
+    state: Mutex<State>,
+    rejections: AtomicU64,
+}
+
+#[derive(Debug)]
+struct State {
+    limit: f64,
+    in_flight: usize,
+
+    /// The lowest latency observed in the previous window, if it has ended.
+    prior_min: Option<Duration>,
+
+    /// The lowest latency observed in the current window.
+    window_min: Option<Duration>,
+    window_samples: usize,
+}
+
+/// The number of responses after which the baseline latency is reset, so that
+/// the baseline tracks changes in a destination's latency.
+const WINDOW_SAMPLES: usize = 500;
+
+// === impl NewAdaptiveLimit ===
+
+impl<N> NewAdaptiveLimit<N> {
+    pub fn layer(
+        config: Option<AdaptiveConcurrency>,
+        registry: Registry,
+    ) -> impl layer::Layer<N, Service = Self> + Clone {
+        layer::mk(move |inner| Self {
+            inner,
+            config,
+            registry: registry.clone(),
+        })
+    }
+}
+
+impl<T, N> NewService<T> for NewAdaptiveLimit<N>
+where
+    T: Param<LogicalAddr>,
+    N: NewService<T>,
+{
+    type Service = AdaptiveLimit<N::Service>;
+
+    fn new_service(&self, target: T) -> Self::Service {
+        let limiter = self
+            .config
+            .map(|config| self.registry.limiter(target.param(), config));
+        AdaptiveLimit {
+            inner: self.inner.new_service(target),
+            limiter,
+        }
+    }
+}
+
+// === impl AdaptiveLimit ===
+
+impl<Req, S> svc::Service<Req> for AdaptiveLimit<S>
+where
+    S: svc::Service<Req>,
+    S::Error: Into<Error>,
+{
+    type Response = S::Response;
+    type Error = Error;
+    type Future = ResponseFuture<S::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let limiter = match self.limiter.as_ref() {
+            Some(limiter) => limiter,
+            None => return ResponseFuture::Passthru(self.inner.call(req)),
+        };
+        match limiter.acquire() {
+            Ok(permit) => ResponseFuture::Limited(self.inner.call(req), Some(permit)),
+            Err(error) => {
+                tracing::debug!(%error, "Shedding request");
+                ResponseFuture::Rejected(Some(error))
+            }
+        }
+    }
+}
+
+// === impl ResponseFuture ===
+
+impl<F, T, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<T, E>>,
+    E: Into<Error>,
+{
+    type Output = Result<T, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            ResponseFutureProj::Passthru(f) => f.poll(cx).map_err(Into::into),
+            ResponseFutureProj::Limited(f, permit) => {
+                let res = futures::ready!(f.poll(cx));
+                // Responses inform the limit by their latency. Errors back off
+                // when the permit is dropped.
+                if let (Ok(_), Some(permit)) = (&res, permit.take()) {
+                    permit.complete();
+                }
+                Poll::Ready(res.map_err(Into::into))
+            }
+            ResponseFutureProj::Rejected(error) => {
+                Poll::Ready(Err(error.take().expect("polled after completion").into()))
+            }
+        }
+    }
+}
+
+// === impl Permit ===
+
+impl Permit {
+    fn complete(mut self) {
+        let latency = self.start.elapsed();
+        self.completed = true;
+        self.limiter
+            .state
+            .lock()
+            .observe(latency, &self.limiter.config);
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state.lock();
+        state.in_flight = state.in_flight.saturating_sub(1);
+        // A request that failed or was canceled, e.g. because it timed out,
+        // is treated as a sign of congestion.
+        if !self.completed {
+            state.back_off(&self.limiter.config);
+        }
+    }
+}
+
+// === impl Limiter ===
+
+impl Limiter {
+    fn new(config: AdaptiveConcurrency) -> Self {
+        Self {
+            config,
+            state: Mutex::new(State {
+                limit: config.initial_limit as f64,
+                in_flight: 0,
+                prior_min: None,
+                window_min: None,
+                window_samples: 0,
+            }),
+            rejections: AtomicU64::new(0),
+        }
+    }
+
+    fn acquire(self: &Arc<Self>) -> Result<Permit, ConcurrencyLimitExceeded> {
+        let mut state = self.state.lock();
+        let limit = state.limit();
+        if state.in_flight >= limit {
+            drop(state);
+            self.rejections.fetch_add(1, Ordering::Relaxed);
+            return Err(ConcurrencyLimitExceeded(limit));
+        }
+        state.in_flight += 1;
+        Ok(Permit {
+            limiter: self.clone(),
+            start: Instant::now(),
+            completed: false,
+        })
+    }
+
+    /// Returns the current limit.
+    fn limit(&self) -> usize {
+        self.state.lock().limit()
+    }
+
+    /// Returns the number of requests that have been rejected.
+    fn rejections(&self) -> u64 {
+        self.rejections.load(Ordering::Relaxed)
+    }
+}
+
+// === impl State ===
+
+impl State {
+    fn limit(&self) -> usize {
+        self.limit as usize
+    }
+
+    fn observe(&mut self, latency: Duration, config: &AdaptiveConcurrency) {
+        let baseline = match (self.prior_min, self.window_min) {
+            (Some(p), Some(w)) => p.min(w),
+            (p, w) => p.or(w).unwrap_or(latency),
+        };
+
+        self.window_min = Some(self.window_min.map_or(latency, |min| min.min(latency)));
+        self.window_samples += 1;
+        if self.window_samples == WINDOW_SAMPLES {
+            self.prior_min = self.window_min.take();
+            self.window_samples = 0;
+        }
+
+        if latency.as_secs_f64() > baseline.as_secs_f64() * config.tolerance {
+            self.back_off(config);
+        } else if self.in_flight * 2 >= self.limit() {
+            let limit = self.limit + 1.0 / self.limit;
+            self.limit = limit.clamp(config.min_limit as f64, config.max_limit as f64);
+        }
+    }
+
+    fn back_off(&mut self, config: &AdaptiveConcurrency) {
+        let limit = self.limit * config.backoff_ratio;
+        self.limit = limit.clamp(config.min_limit as f64, config.max_limit as f64);
+    }
+}
+
+// === impl Registry ===
+
+impl Registry {
+    /// Returns the destination's limiter, creating one if the destination has
+    /// none in use.
+    fn limiter(&self, addr: LogicalAddr, config: AdaptiveConcurrency) -> Arc<Limiter> {
+        let mut limiters = self.0.lock();
+        if let Some(limiter) = limiters.get(&addr).and_then(Weak::upgrade) {
+            return limiter;
+        }
+        let limiter = Arc::new(Limiter::new(config));
+        limiters.insert(addr, Arc::downgrade(&limiter));
+        limiter
+    }
+
+    /// Returns the limit and rejections of each destination whose limiter is
+    /// still in use, dropping those that are not.
+    pub fn limits(&self) -> Vec<(LogicalAddr, usize, u64)> {
+        let mut limiters = self.0.lock();
+        limiters.retain(|_, limiter| limiter.strong_count() > 0);
+        limiters
+            .iter()
+            .filter_map(|(addr, limiter)| {
+                let limiter = limiter.upgrade()?;
+                Some((addr.clone(), limiter.limit(), limiter.rejections()))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use svc::ServiceExt;
+
+    const CONFIG: AdaptiveConcurrency = AdaptiveConcurrency {
+        initial_limit: 4,
+        min_limit: 2,
+        max_limit: 8,
+        tolerance: 2.0,
+        backoff_ratio: 0.5,
+    };
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn adapts_limit_to_latency() {
+        let limiter = Arc::new(Limiter::new(CONFIG));
+
+        // Requests beyond the limit are rejected.
+        let permits = (0..4)
+            .map(|_| limiter.acquire().expect("must be within limit"))
+            .collect::<Vec<_>>();
+        assert!(limiter.acquire().is_err());
+        assert_eq!(limiter.rejections(), 1);
+
+        // Responses within the tolerance grow the limit while it is in use.
+        tokio::time::advance(Duration::from_millis(10)).await;
+        for permit in permits {
+            permit.complete();
+        }
+        assert_eq!(limiter.limit(), 4);
+        for _ in 0..20 {
+            let permits = (0..limiter.limit())
+                .map(|_| limiter.acquire().unwrap())
+                .collect::<Vec<_>>();
+            tokio::time::advance(Duration::from_millis(10)).await;
+            permits.into_iter().for_each(Permit::complete);
+        }
+        assert_eq!(limiter.limit(), CONFIG.max_limit);
+
+        // Slow responses shrink the limit, down to the minimum.
+        for _ in 0..3 {
+            let permit = limiter.acquire().unwrap();
+            tokio::time::advance(Duration::from_millis(100)).await;
+            permit.complete();
+        }
+        assert_eq!(limiter.limit(), CONFIG.min_limit);
+
+        // Dropped requests release their permits without shrinking the limit
+        // below the minimum.
+        drop(limiter.acquire().unwrap());
+        assert_eq!(limiter.state.lock().in_flight, 0);
+        assert_eq!(limiter.limit(), CONFIG.min_limit);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn backs_off_on_errors_and_cancellation() {
+        let limiter = Arc::new(Limiter::new(CONFIG));
+
+        // Failed requests shrink the limit.
+        let failing = AdaptiveLimit {
+            inner: svc::mk(|()| future::err::<(), Error>("unavailable".into())),
+            limiter: Some(limiter.clone()),
+        };
+        failing.oneshot(()).await.expect_err("request must fail");
+        assert_eq!(limiter.limit(), 2);
+        assert_eq!(limiter.state.lock().in_flight, 0);
+
+        // So do requests that are canceled before they complete, e.g. by a
+        // timeout.
+        limiter.state.lock().limit = 4.0;
+        let hanging = AdaptiveLimit {
+            inner: svc::mk(|()| future::pending::<Result<(), Error>>()),
+            limiter: Some(limiter.clone()),
+        };
+        let timeout = tokio::time::timeout(Duration::from_millis(10), hanging.oneshot(()));
+        timeout.await.expect_err("request must time out");
+        assert_eq!(limiter.limit(), 2);
+        assert_eq!(limiter.state.lock().in_flight, 0);
+    }
+
+    #[test]
+    fn shares_limits_by_destination() {
+        let registry = Registry::default();
+        let web = LogicalAddr("web.ns.svc.cluster.local:8080".parse().unwrap());
+        let api = LogicalAddr("api.ns.svc.cluster.local:8080".parse().unwrap());
+
+        let limiter = registry.limiter(web.clone(), CONFIG);
+        assert!(Arc::ptr_eq(
+            &limiter,
+            &registry.limiter(web.clone(), CONFIG)
+        ));
+        assert!(!Arc::ptr_eq(&limiter, &registry.limiter(api, CONFIG)));
+
+        // Once a destination's limiter is no longer used, it's replaced.
+        let weak = Arc::downgrade(&limiter);
+        drop(limiter);
+        let limiter = registry.limiter(web, CONFIG);
+        assert!(weak.upgrade().is_none());
+        assert_eq!(limiter.limit(), CONFIG.initial_limit);
+    }
+}
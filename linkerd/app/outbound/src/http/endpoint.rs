@@ -92,6 +92,9 @@ impl<C> Outbound<C> {
                 ]))
                 .push_on_service(
                     svc::layers()
+                        // Deducts the time spent in the proxy, including in
+                        // prior attempts, from the deadline sent to the peer.
+                        .push(http::RewriteDeadline::layer())
                         .push(http::BoxResponse::layer())
                         .push(svc::BoxService::layer()),
                 )
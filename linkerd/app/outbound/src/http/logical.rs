@@ -1,7 +1,10 @@
-use super::{retry, CanonicalDstHeader, Concrete, Endpoint, Logical, ProfileRoute};
+use super::{
+    adaptive_limit, mirror, retry, CanonicalDstHeader, Concrete, Endpoint, Logical, ProfileRoute,
+    RouteBackends,
+};
 use crate::{endpoint, resolve, stack_labels, Outbound};
 use linkerd_app_core::{
-    classify, config, profiles,
+    classify, config, fault, profiles,
     proxy::{
         api_resolve::{ConcreteAddr, Metadata},
         core::Resolve,
@@ -52,7 +55,7 @@ impl<E> Outbound<E> {
                 .check_service::<Concrete>()
                 .into_inner();
 
-            let concrete = endpoint
+            let balancer = endpoint
                 .clone()
                 .check_new_service::<Endpoint, http::Request<http::BoxBody>>()
                 .push_on_service(
@@ -64,10 +67,19 @@ impl<E> Outbound<E> {
                     ),
                 )
                 .check_new_service::<Endpoint, http::Request<_>>()
+                // Ejects endpoints from the balancer after consecutive
+                // failures, if configured. Responses are classified by the
+                // route's response classes, if any.
+                .push(http::balance::NewFailureAccrual::<classify::Response, _, _>::layer(
+                    config.http_failure_accrual,
+                    rt.metrics.proxy.http_balancer_ejections.clone(),
+                ))
+                .push(http::balance::NewZoned::layer(
+                    config.prefer_local_zone.clone(),
+                ))
                 // Resolve the service to its endpoints and balance requests over them.
                 //
-                // If the balancer has been empty/unavailable, eagerly fail requests.
-                // When the balancer is in failfast, spawn the service in a background
+                // When the balancer is not ready, spawn the service in a background
                 // task so it becomes ready without new requests.
                 //
                 // We *don't* ensure that the endpoint is driven to readiness here, because this
@@ -75,13 +87,27 @@ impl<E> Outbound<E> {
                 // consulting discovery to see whether the endpoint has been removed. Instead, the
                 // endpoint layer spawns each _connection_ attempt on a background task, but the
                 // decision to attempt the connection must be driven by the balancer.
-                .push(resolve::layer(resolve, watchdog))
+                .push(resolve::layer(
+                    resolve,
+                    watchdog,
+                    config.endpoint_health_check.clone(),
+                    rt.metrics.endpoint_health.registry(),
+                ))
+                // Endpoints in other zones are only used when too few
+                // endpoints in the local zone are ready.
+                .push_on_service(http::balance::ZoneAware::layer(
+                    config.prefer_local_zone.clone(),
+                ))
+                // Balances requests with the algorithm configured by the
+                // service's profile.
+                .push(http::balance::MakeBalance::layer(
+                    crate::EWMA_DEFAULT_RTT,
+                    crate::EWMA_DECAY,
+                    http::balance::PendingUntilFirstData::default(),
+                    config.slow_start,
+                ))
                 .push_on_service(
                     svc::layers()
-                        .push(http::balance::layer(
-                            crate::EWMA_DEFAULT_RTT,
-                            crate::EWMA_DECAY,
-                        ))
                         .push(
                             rt.metrics
                                 .proxy
@@ -89,7 +115,6 @@ impl<E> Outbound<E> {
                                 .layer(stack_labels("http", "balancer")),
                         )
                         .push(svc::layer::mk(svc::SpawnReady::new))
-                        .push(svc::FailFast::layer("HTTP Balancer", dispatch_timeout))
                         .push(http::BoxResponse::layer()),
                 )
                 .check_make_service::<Concrete, http::Request<_>>()
@@ -103,15 +128,39 @@ impl<E> Outbound<E> {
                 .push_map_target(Concrete::from)
                 .push(svc::ArcNewService::layer());
 
+            // If the balancer has been empty/unavailable, eagerly fail requests.
+            let concrete = balancer
+                .clone()
+                .push_on_service(svc::FailFast::layer("HTTP Balancer", dispatch_timeout))
+                .push(svc::ArcNewService::layer());
+
             // Distribute requests over a distribution of balancers via a
             // traffic split.
             //
+            // When the split's targets have more than one priority, their
+            // balancers don't fail fast, so that requests fail over from a tier
+            // whose balancers are unavailable to the next tier.
+            //
             // If the traffic split is empty/unavailable, eagerly fail requests.
             // When the split is in failfast, spawn the service in a background
             // task so it becomes ready without new requests.
             let logical = concrete
+                .clone()
+                .push_switch(
+                    |(addr, logical): (ConcreteAddr, Logical)| -> Result<_, Infallible> {
+                        if logical.profile.is_tiered() {
+                            Ok(svc::Either::B((addr, logical)))
+                        } else {
+                            Ok(svc::Either::A((addr, logical)))
+                        }
+                    },
+                    balancer.clone().into_inner(),
+                )
                 .check_new_service::<(ConcreteAddr, Logical), _>()
-                .push(profiles::split::layer())
+                .push(profiles::split::layer(
+                    config.split_failover,
+                    config.split_hash_key.clone().map(http::balance::RequestHasher::from),
+                ))
                 .push_on_service(
                     svc::layers()
                         .push(svc::layer::mk(svc::SpawnReady::new))
@@ -124,8 +173,72 @@ impl<E> Outbound<E> {
                         .push(svc::FailFast::layer("HTTP Logical", dispatch_timeout))
                         .push_spawn_buffer(buffer_capacity),
                 )
+                // Sheds requests that exceed the destination's adaptive
+                // concurrency limit, if one is configured. Requests are
+                // limited before they are buffered so that the time they spend
+                // queued informs the limit. The limit is shared with the
+                // destination's route backends.
+                .push(adaptive_limit::NewAdaptiveLimit::layer(
+                    config.adaptive_concurrency,
+                    rt.metrics.concurrency_limits.registry(),
+                ))
                 .push_cache(cache_max_idle_age);
 
+            // Routes that configure their own backends distribute requests
+            // over a distinct traffic split. These services are owned by the
+            // route, so they are not cached.
+            let route_backends = concrete
+                .clone()
+                .push_switch(
+                    |(addr, r): (ConcreteAddr, RouteBackends)| -> Result<_, Infallible> {
+                        if r.backends.is_tiered() {
+                            Ok(svc::Either::B((addr, r.logical)))
+                        } else {
+                            Ok(svc::Either::A((addr, r.logical)))
+                        }
+                    },
+                    balancer.into_inner(),
+                )
+                .check_new_service::<(ConcreteAddr, RouteBackends), _>()
+                .push(profiles::split::layer(
+                    config.split_failover,
+                    config.split_hash_key.clone().map(http::balance::RequestHasher::from),
+                ))
+                .push_on_service(
+                    svc::layers()
+                        .push(svc::layer::mk(svc::SpawnReady::new))
+                        .push(
+                            rt.metrics
+                                .proxy
+                                .stack
+                                .layer(stack_labels("http", "route.backends")),
+                        )
+                        .push(svc::FailFast::layer("HTTP Route Backends", dispatch_timeout))
+                        .push_spawn_buffer(buffer_capacity),
+                )
+                // Requests to routes' backends count against the
+                // destination's limit as well.
+                .push(adaptive_limit::NewAdaptiveLimit::layer(
+                    config.adaptive_concurrency,
+                    rt.metrics.concurrency_limits.registry(),
+                ));
+
+            // Routes may mirror requests to a balancer for another backend.
+            // These services are owned by the route, so they are not cached.
+            let mirror = concrete
+                .check_new_service::<(ConcreteAddr, Logical), _>()
+                .push_on_service(
+                    svc::layers()
+                        .push(
+                            rt.metrics
+                                .proxy
+                                .stack
+                                .layer(stack_labels("http", "route.mirror")),
+                        )
+                        .push_spawn_buffer(buffer_capacity),
+                )
+                .into_inner();
+
             // If there's no route, use the logical service directly; otherwise
             // use the per-route stack.
             logical
@@ -138,7 +251,18 @@ impl<E> Outbound<E> {
                         }
                     },
                     logical
-                        .push_map_target(|r: ProfileRoute| r.logical)
+                        .push_switch(
+                            |r: ProfileRoute| -> Result<_, Infallible> {
+                                match r.route.backends() {
+                                    None => Ok(svc::Either::A(r.logical)),
+                                    Some(backends) => Ok(svc::Either::B(RouteBackends {
+                                        backends: backends.clone(),
+                                        logical: r.logical,
+                                    })),
+                                }
+                            },
+                            route_backends.into_inner(),
+                        )
                         .push_on_service(http::BoxRequest::layer())
                         .push(
                             rt.metrics
@@ -151,10 +275,22 @@ impl<E> Outbound<E> {
                         // layer unifies any `Body` type into `BoxBody`.
                         .push_on_service(http::BoxRequest::erased())
                         .push_http_insert_target::<profiles::http::Route>()
+                        // Injects the route's faults, if any, beneath retries
+                        // and timeouts so that they may be exercised.
+                        .push(fault::NewInjectFault::layer())
+                        // Bounds each attempt of a retryable request.
+                        .push(retry::NewPerTryTimeout::layer())
                         // Sets an optional retry policy.
-                        .push(retry::layer(rt.metrics.proxy.http_profile_route_retry.clone()))
+                        .push(retry::layer(
+                            rt.metrics.proxy.http_profile_route_retry.clone(),
+                            config.retryable_errors,
+                            config.max_buffered_bytes,
+                            config.retry_spill.clone(),
+                        ))
                         // Sets an optional request timeout.
                         .push(http::NewTimeout::layer())
+                        // Applies the route's request and response filters.
+                        .push(http::NewApplyFilters::layer())
                         // Records per-route metrics.
                         .push(
                             rt.metrics
@@ -170,6 +306,13 @@ impl<E> Outbound<E> {
                                 .push(http::BoxResponse::layer())
                                 .push(svc::BoxCloneService::layer())
                         )
+                        // Mirrors a fraction of requests to the route's mirror
+                        // backend, if one is configured.
+                        .push(mirror::NewMirror::layer(
+                            mirror,
+                            config.max_buffered_bytes,
+                            rt.metrics.proxy.http_profile_route_mirror.clone(),
+                        ))
                         .into_inner(),
                 )
                 .push(profiles::http::NewServiceRouter::layer())
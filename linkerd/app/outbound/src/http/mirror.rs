@@ -0,0 +1,375 @@
+use super::{retry::clone_request, Logical, ProfileRoute};
+use crate::logical::ConcreteAddr;
+use linkerd_app_core::{
+    http_metrics::mirrors::Handle,
+    metrics::{HttpProfileRouteMirror, ProfileRouteLabels},
+    profiles::http::RequestMirror,
+    proxy::http::{self, BoxBody, HttpBody},
+    svc::{self, layer, NewService, Param, ServiceExt},
+    Error,
+};
+use linkerd_http_retry::ReplayBody;
+use pin_project::pin_project;
+use rand::{rngs::SmallRng, thread_rng, Rng, SeedableRng};
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::sync::oneshot;
+use tracing::{debug, debug_span, Instrument};
+
+/// Mirrors a fraction of each route's requests to the route's mirror backend,
+/// if one is configured.
+///
+/// Mirrored requests are dispatched in the background once the primary
+/// request's body has been sent and their responses are discarded, so the
+/// primary response is unaffected by the mirror.
+#[derive(Clone, Debug)]
+pub struct NewMirror<N, M> {
+    inner: N,
+    mirror: M,
+    max_buffered_bytes: usize,
+    metrics: HttpProfileRouteMirror,
+}
+
+#[derive(Clone, Debug)]
+pub struct Mirror<S, M> {
+    inner: S,
+    mirror: Option<MirrorBackend<M>>,
+}
+
+#[derive(Clone, Debug)]
+struct MirrorBackend<M> {
+    service: M,
+    ratio: http::route_filter::Ratio,
+    max_buffered_bytes: usize,
+    rng: SmallRng,
+    metrics: Handle,
+}
+
+/// Notifies the mirror when the primary request's body is dropped.
+#[pin_project]
+struct PrimaryBody<B> {
+    // Declared before the notification so that the body's buffered state is
+    // released before the mirror is notified.
+    #[pin]
+    inner: ReplayBody<B>,
+    _released: oneshot::Sender<()>,
+}
+
+// === impl NewMirror ===
+
+impl<N, M: Clone> NewMirror<N, M> {
+    pub fn layer(
+        mirror: M,
+        max_buffered_bytes: usize,
+        metrics: HttpProfileRouteMirror,
+    ) -> impl layer::Layer<N, Service = Self> + Clone {
+        layer::mk(move |inner| Self {
+            inner,
+            mirror: mirror.clone(),
+            max_buffered_bytes,
+            metrics: metrics.clone(),
+        })
+    }
+}
+
+impl<N, M> NewService<ProfileRoute> for NewMirror<N, M>
+where
+    N: NewService<ProfileRoute>,
+    M: NewService<(ConcreteAddr, Logical)>,
+{
+    type Service = Mirror<N::Service, M::Service>;
+
+    fn new_service(&self, route: ProfileRoute) -> Self::Service {
+        let mirror = route
+            .route
+            .mirror()
+            .map(|RequestMirror { backend, ratio }| MirrorBackend {
+                service: self
+                    .mirror
+                    .new_service((ConcreteAddr(backend.clone()), route.logical.clone())),
+                ratio: *ratio,
+                max_buffered_bytes: self.max_buffered_bytes,
+                rng: SmallRng::from_rng(&mut thread_rng()).expect("RNG must initialize"),
+                metrics: self
+                    .metrics
+                    .get_handle(Param::<ProfileRouteLabels>::param(&route)),
+            });
+        Mirror {
+            inner: self.inner.new_service(route),
+            mirror,
+        }
+    }
+}
+
+// === impl Mirror ===
+
+impl<S, M, B> svc::Service<http::Request<BoxBody>> for Mirror<S, M>
+where
+    S: svc::Service<http::Request<BoxBody>>,
+    M: svc::Service<http::Request<BoxBody>, Response = http::Response<B>> + Clone + Send + 'static,
+    M::Error: Into<Error>,
+    M::Future: Send,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<BoxBody>) -> Self::Future {
+        let backend = match self.mirror.as_mut() {
+            Some(backend) if backend.sample() => backend,
+            _ => return self.inner.call(req),
+        };
+
+        let (parts, body) = req.into_parts();
+        let body = match ReplayBody::try_new(body, backend.max_buffered_bytes) {
+            Ok(body) => body,
+            Err(body) => {
+                debug!("Request body is too large to be mirrored");
+                backend.metrics.incr_skipped();
+                return self.inner.call(http::Request::from_parts(parts, body));
+            }
+        };
+        let req = http::Request::from_parts(parts, body);
+        let mirror = clone_request(&req);
+
+        // The mirrored body may not be polled until the primary body has been
+        // released.
+        let (tx, rx) = oneshot::channel();
+        let service = backend.service.clone();
+        let metrics = backend.metrics.clone();
+        tokio::spawn(
+            async move {
+                let _ = rx.await;
+                if mirror.body().is_capped() {
+                    debug!("Request body exceeded the mirror's buffer");
+                    metrics.incr_skipped();
+                    return;
+                }
+
+                metrics.incr_mirrored();
+                let mirror = mirror.map(BoxBody::new);
+                match service.oneshot(mirror).await {
+                    Ok(rsp) if rsp.status().is_server_error() => {
+                        debug!(status = %rsp.status(), "Mirrored request failed");
+                        metrics.incr_failed();
+                    }
+                    Ok(_) => {}
+                    Err(error) => {
+                        let error: Error = error.into();
+                        debug!(%error, "Mirrored request failed");
+                        metrics.incr_failed();
+                    }
+                }
+            }
+            .instrument(debug_span!("mirror")),
+        );
+
+        self.inner.call(req.map(|inner| {
+            BoxBody::new(PrimaryBody {
+                inner,
+                _released: tx,
+            })
+        }))
+    }
+}
+
+// === impl MirrorBackend ===
+
+impl<M> MirrorBackend<M> {
+    fn sample(&mut self) -> bool {
+        self.rng
+            .gen_ratio(self.ratio.numerator(), self.ratio.denominator())
+    }
+}
+
+// === impl PrimaryBody ===
+
+impl<B> HttpBody for PrimaryBody<B>
+where
+    B: HttpBody + Unpin,
+    B::Error: Into<Error>,
+{
+    type Data = <ReplayBody<B> as HttpBody>::Data;
+    type Error = Error;
+
+    #[inline]
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        self.project().inner.poll_data(cx)
+    }
+
+    #[inline]
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<http::header::HeaderMap>, Self::Error>> {
+        self.project().inner.poll_trailers(cx)
+    }
+
+    #[inline]
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::ProxyConnectionClose;
+    use futures::future;
+    use linkerd_app_core::{
+        http_metrics::Mirrors,
+        proxy::http::{route_filter::Ratio, ClientHandle},
+        svc::Layer,
+    };
+    use tokio::sync::mpsc;
+
+    type Primary = tower::util::ServiceFn<PrimaryFn>;
+    type PrimaryFn =
+        fn(http::Request<BoxBody>) -> future::Ready<Result<http::Response<BoxBody>, Error>>;
+
+    /// A mirror backend that records its requests and responds with `status`.
+    #[derive(Clone)]
+    struct MockMirror {
+        requests: mpsc::UnboundedSender<http::Request<BoxBody>>,
+        status: http::StatusCode,
+    }
+
+    impl svc::Service<http::Request<BoxBody>> for MockMirror {
+        type Response = http::Response<BoxBody>;
+        type Error = Error;
+        type Future = future::Ready<Result<Self::Response, Error>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: http::Request<BoxBody>) -> Self::Future {
+            let _ = self.requests.send(req);
+            let mut rsp = http::Response::new(BoxBody::default());
+            *rsp.status_mut() = self.status;
+            future::ok(rsp)
+        }
+    }
+
+    fn mk_mirror(
+        ratio: Ratio,
+        status: http::StatusCode,
+    ) -> (
+        Mirror<Primary, ProxyConnectionClose<MockMirror>>,
+        Handle,
+        mpsc::UnboundedReceiver<http::Request<BoxBody>>,
+    ) {
+        let (requests, rx) = mpsc::unbounded_channel();
+        let metrics = Mirrors::<()>::default().get_handle(());
+        let mirror = Mirror {
+            // The primary request's body is dropped without being read.
+            inner: svc::mk((|_| future::ok(http::Response::new(BoxBody::default()))) as PrimaryFn),
+            mirror: Some(MirrorBackend {
+                // Like the mirror stack, requires that requests have a client
+                // handle.
+                service: ProxyConnectionClose::layer().layer(MockMirror { requests, status }),
+                ratio,
+                max_buffered_bytes: 8,
+                rng: SmallRng::seed_from_u64(0),
+                metrics: metrics.clone(),
+            }),
+        };
+        (mirror, metrics, rx)
+    }
+
+    fn mk_request(body: &'static str) -> http::Request<BoxBody> {
+        let mut req = http::Request::new(BoxBody::new(hyper::Body::from(body)));
+        let (handle, _) = ClientHandle::new(([192, 0, 2, 3], 50000).into());
+        req.extensions_mut().insert(handle);
+        req
+    }
+
+    /// Lets the mirror's background task complete.
+    async fn settle() {
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn mirrors_sampled_requests() {
+        let (mut mirror, metrics, mut requests) = mk_mirror(Ratio::ALL, http::StatusCode::OK);
+        mirror
+            .ready()
+            .await
+            .unwrap()
+            .call(mk_request("hello"))
+            .await
+            .unwrap();
+        let req = requests.recv().await.expect("request must be mirrored");
+        assert!(req.extensions().get::<ClientHandle>().is_some());
+        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+        assert_eq!(body, "hello");
+        settle().await;
+        assert_eq!(metrics.mirrored(), 1);
+        assert_eq!(metrics.failed(), 0);
+
+        // Requests that aren't sampled are not mirrored.
+        let never = Ratio::new(0, 1).unwrap();
+        let (mut mirror, metrics, mut requests) = mk_mirror(never, http::StatusCode::OK);
+        mirror
+            .ready()
+            .await
+            .unwrap()
+            .call(mk_request("hello"))
+            .await
+            .unwrap();
+        settle().await;
+        assert!(requests.try_recv().is_err());
+        assert_eq!(metrics.mirrored(), 0);
+        assert_eq!(metrics.skipped(), 0);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn skips_requests_too_large_to_buffer() {
+        let (mut mirror, metrics, mut requests) = mk_mirror(Ratio::ALL, http::StatusCode::OK);
+        mirror
+            .ready()
+            .await
+            .unwrap()
+            .call(mk_request("too large to mirror"))
+            .await
+            .unwrap();
+        settle().await;
+        assert!(requests.try_recv().is_err());
+        assert_eq!(metrics.mirrored(), 0);
+        assert_eq!(metrics.skipped(), 1);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn counts_failed_mirrors() {
+        let (mut mirror, metrics, mut requests) =
+            mk_mirror(Ratio::ALL, http::StatusCode::SERVICE_UNAVAILABLE);
+        mirror
+            .ready()
+            .await
+            .unwrap()
+            .call(mk_request("hello"))
+            .await
+            .unwrap();
+        requests.recv().await.expect("request must be mirrored");
+        settle().await;
+        assert_eq!(metrics.mirrored(), 1);
+        assert_eq!(metrics.failed(), 1);
+    }
+}
@@ -1,51 +1,118 @@
 use super::ProfileRoute;
 use futures::{future, FutureExt};
 use linkerd_app_core::{
-    classify,
+    classify, errors,
     http_metrics::retries::Handle,
     metrics, profiles,
-    proxy::http::{ClientHandle, EraseResponse, HttpBody},
-    svc::{layer, Either, Param},
+    proxy::http::{h2::Reason, ClientHandle, Deadline, EraseResponse, HttpBody},
+    svc::{self, layer, stack, Either, Param},
     Error,
 };
 use linkerd_http_classify::{Classify, ClassifyEos, ClassifyResponse};
 use linkerd_http_retry::{
     with_trailers::{self, WithTrailers},
-    ReplayBody,
+    ReplayBody, Spill,
 };
 use linkerd_retry as retry;
-use std::sync::Arc;
+use rand::thread_rng;
+use std::{io, sync::Arc, time::Duration};
 
 pub fn layer<N>(
     metrics: metrics::HttpProfileRouteRetry,
+    retryable_errors: RetryableErrors,
+    max_buffered_bytes: usize,
+    spill: Option<Spill>,
 ) -> impl layer::Layer<N, Service = retry::NewRetry<NewRetryPolicy, N, EraseResponse<()>>> + Clone {
-    retry::layer(NewRetryPolicy::new(metrics))
-        // Because we wrap the response body type on retries, we must include a
-        // `Proxy` middleware for unifying the response body types of the retry
-        // and non-retry services.
-        .with_proxy(EraseResponse::new(()))
+    retry::layer(NewRetryPolicy::new(
+        metrics,
+        retryable_errors,
+        max_buffered_bytes,
+        spill,
+    ))
+    // Because we wrap the response body type on retries, we must include a
+    // `Proxy` middleware for unifying the response body types of the retry
+    // and non-retry services.
+    .with_proxy(EraseResponse::new(()))
 }
 
 #[derive(Clone, Debug)]
 pub struct NewRetryPolicy {
     metrics: metrics::HttpProfileRouteRetry,
+    retryable_errors: RetryableErrors,
+    max_buffered_bytes: usize,
+    spill: Option<Spill>,
 }
 
 #[derive(Clone, Debug)]
 pub struct RetryPolicy {
     metrics: Handle,
-    budget: Arc<retry::Budget>,
+    retries: profiles::http::Retries,
     response_classes: profiles::http::ResponseClasses,
+    retryable_errors: RetryableErrors,
+
+    /// The number of attempts of the request that have been made.
+    attempts: u32,
+
+    /// Observed latencies of the route's requests, when requests are hedged
+    /// by a percentile of latency.
+    latencies: Option<Arc<retry::Latencies>>,
+
+    /// The maximum number of bytes of a request body that are buffered, unless
+    /// the route configures a limit.
+    max_buffered_bytes: usize,
+
+    /// Configures request bodies to be spilled to disk when they are too large
+    /// to be buffered in memory.
+    spill: Option<Spill>,
+}
+
+/// Applies a retryable route's per-try timeout beneath retries, so that each
+/// attempt of a request is bounded separately from the route's timeout.
+#[derive(Clone, Debug)]
+pub struct NewPerTryTimeout<N> {
+    inner: N,
 }
 
-/// Allow buffering requests up to 64 kb
-const MAX_BUFFERED_BYTES: usize = 64 * 1024;
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("HTTP response attempt timeout after {0:?}")]
+pub struct PerTryTimeoutError(Duration);
+
+/// Configures which errors, encountered before a response is received, may be
+/// retried on retryable routes. Retries of errors are charged against the
+/// route's retry budget, like retries of failed responses.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RetryableErrors {
+    /// Retries requests whose connection was refused or timed out. These
+    /// requests were never sent, so they are retried regardless of their
+    /// method.
+    pub connect: bool,
+
+    /// Retries requests whose stream or connection was reset before a
+    /// response was received. These requests may have been processed, so only
+    /// idempotent requests are retried, unless the server refused the stream.
+    pub reset: bool,
+
+    /// Retries requests that failed because no endpoint became available. These
+    /// requests were never dispatched, so they are retried regardless of their
+    /// method.
+    pub fail_fast: bool,
+}
 
 // === impl NewRetryPolicy ===
 
 impl NewRetryPolicy {
-    pub fn new(metrics: metrics::HttpProfileRouteRetry) -> Self {
-        Self { metrics }
+    pub fn new(
+        metrics: metrics::HttpProfileRouteRetry,
+        retryable_errors: RetryableErrors,
+        max_buffered_bytes: usize,
+        spill: Option<Spill>,
+    ) -> Self {
+        Self {
+            metrics,
+            retryable_errors,
+            max_buffered_bytes,
+            spill,
+        }
     }
 }
 
@@ -56,32 +123,48 @@ impl retry::NewPolicy<ProfileRoute> for NewRetryPolicy {
         let retries = route.route.retries().cloned()?;
 
         let metrics = self.metrics.get_handle(route.param());
+        let latencies = match retries.hedge() {
+            Some(profiles::http::Hedge::Percentile(p)) => Some(Arc::new(retry::Latencies::new(p))),
+            _ => None,
+        };
         Some(RetryPolicy {
             metrics,
-            budget: retries.budget().clone(),
+            retries,
             response_classes: route.route.response_classes().clone(),
+            retryable_errors: self.retryable_errors,
+            attempts: 1,
+            latencies,
+            max_buffered_bytes: self.max_buffered_bytes,
+            spill: self.spill.clone(),
         })
     }
 }
 
 // === impl Retry ===
 
-impl<A, B, E> retry::Policy<http::Request<ReplayBody<A>>, http::Response<WithTrailers<B>>, E>
+impl<A, B> retry::Policy<http::Request<ReplayBody<A>>, http::Response<WithTrailers<B>>, Error>
     for RetryPolicy
 where
     A: HttpBody + Unpin,
     A::Error: Into<Error>,
     B: HttpBody + Unpin,
 {
-    type Future = future::Ready<Self>;
+    type Future = future::Either<future::Ready<Self>, future::BoxFuture<'static, Self>>;
 
     fn retry(
         &self,
         req: &http::Request<ReplayBody<A>>,
-        result: Result<&http::Response<WithTrailers<B>>, &E>,
+        result: Result<&http::Response<WithTrailers<B>>, &Error>,
     ) -> Option<Self::Future> {
         let retryable = match result {
-            Err(_) => false,
+            Err(error) => {
+                let is_retryable = self.retryable_errors.is_retryable(req.method(), &**error);
+                // did the body exceed the maximum length limit?
+                let exceeded_max_len = req.body().is_capped();
+                let retryable = is_retryable && !exceeded_max_len;
+                tracing::trace!(%error, exceeded_max_len, retryable);
+                retryable
+            }
             Ok(rsp) => {
                 // is the request a failure?
                 let is_failure = classify::Request::from(self.response_classes.clone())
@@ -98,41 +181,203 @@ where
         };
 
         if !retryable {
-            self.budget.deposit();
+            self.retries.budget().deposit();
             return None;
         }
 
-        let withdrew = self.budget.withdraw().is_ok();
+        if let Some(max) = self.retries.max_attempts() {
+            if self.attempts >= max.get() {
+                tracing::debug!(attempts = self.attempts, "Retry attempts exhausted");
+                return None;
+            }
+        }
+
+        let withdrew = self.retries.budget().withdraw().is_ok();
         self.metrics.incr_retryable(withdrew);
         if !withdrew {
             return None;
         }
 
-        Some(future::ready(self.clone()))
+        let policy = Self {
+            attempts: self.attempts + 1,
+            ..self.clone()
+        };
+        match self.retries.backoff() {
+            None => Some(future::Either::Left(future::ready(policy))),
+            Some(backoff) => {
+                let delay = backoff.delay(self.attempts - 1, &mut thread_rng());
+                tracing::trace!(?delay, "Backing off");
+                Some(future::Either::Right(Box::pin(async move {
+                    tokio::time::sleep(delay).await;
+                    policy
+                })))
+            }
+        }
     }
 
     fn clone_request(
         &self,
         req: &http::Request<ReplayBody<A>>,
     ) -> Option<http::Request<ReplayBody<A>>> {
-        // Since the body is already wrapped in a ReplayBody, it must not be obviously too large to
-        // buffer/clone.
-        let mut clone = http::Request::new(req.body().clone());
-        *clone.method_mut() = req.method().clone();
-        *clone.uri_mut() = req.uri().clone();
-        *clone.headers_mut() = req.headers().clone();
-        *clone.version_mut() = req.version();
-
-        // The HTTP server sets a ClientHandle with the client's address and a means to close the
-        // server-side connection.
-        if let Some(client_handle) = req.extensions().get::<ClientHandle>().cloned() {
-            clone.extensions_mut().insert(client_handle);
+        Some(clone_request(req))
+    }
+}
+
+impl<A> retry::HedgePolicy<http::Request<ReplayBody<A>>> for RetryPolicy
+where
+    A: HttpBody + Unpin,
+    A::Error: Into<Error>,
+{
+    fn hedge_delay(&self, req: &http::Request<ReplayBody<A>>) -> Option<Duration> {
+        // Hedged requests may be processed more than once.
+        if !is_idempotent(req.method()) {
+            return None;
+        }
+        match self.retries.hedge()? {
+            profiles::http::Hedge::Delay(delay) => Some(delay),
+            profiles::http::Hedge::Percentile(_) => self.latencies.as_ref()?.estimate(),
+        }
+    }
+
+    fn clone_hedge(
+        &self,
+        req: &http::Request<ReplayBody<A>>,
+    ) -> Option<http::Request<ReplayBody<A>>> {
+        Some(clone_request(req))
+    }
+
+    fn can_hedge(&self, req: &http::Request<ReplayBody<A>>) -> bool {
+        // The hedge may only read the body once the original request has
+        // released it.
+        if !req.body().is_replayable() {
+            tracing::debug!("Request body cannot be replayed; not hedging");
+            return false;
+        }
+
+        let withdrew = self.retries.budget().withdraw().is_ok();
+        self.metrics.incr_hedge(withdrew);
+        withdrew
+    }
+
+    fn record_latency(&self, latency: Duration) {
+        if let Some(latencies) = self.latencies.as_ref() {
+            latencies.record(latency);
+        }
+    }
+}
+
+pub(super) fn clone_request<A>(req: &http::Request<ReplayBody<A>>) -> http::Request<ReplayBody<A>> {
+    // Since the body is already wrapped in a ReplayBody, it must not be obviously too large to
+    // buffer/clone.
+    let mut clone = http::Request::new(req.body().clone());
+    *clone.method_mut() = req.method().clone();
+    *clone.uri_mut() = req.uri().clone();
+    *clone.headers_mut() = req.headers().clone();
+    *clone.version_mut() = req.version();
+
+    // The HTTP server sets a ClientHandle with the client's address and a means to close the
+    // server-side connection.
+    if let Some(client_handle) = req.extensions().get::<ClientHandle>().cloned() {
+        clone.extensions_mut().insert(client_handle);
+    }
+
+    // Each attempt is bounded by the request's deadline, if any.
+    if let Some(deadline) = req.extensions().get::<Deadline>().copied() {
+        clone.extensions_mut().insert(deadline);
+    }
+
+    clone
+}
+
+// === impl NewPerTryTimeout ===
+
+impl<N> NewPerTryTimeout<N> {
+    pub fn layer() -> impl layer::Layer<N, Service = Self> + Clone {
+        layer::mk(|inner| Self { inner })
+    }
+}
+
+impl<N> svc::NewService<ProfileRoute> for NewPerTryTimeout<N>
+where
+    N: svc::NewService<ProfileRoute>,
+{
+    type Service = svc::MapErr<stack::Timeout<N::Service>, fn(Error) -> Error>;
+
+    fn new_service(&self, route: ProfileRoute) -> Self::Service {
+        let timeout = route.route.retries().and_then(|r| r.per_try_timeout());
+        let inner = self.inner.new_service(route);
+        let svc = match timeout {
+            Some(t) => stack::Timeout::new(inner, t),
+            None => stack::Timeout::passthru(inner),
+        };
+        svc::MapErr::new(svc, |error| {
+            if let Some(t) = error.downcast_ref::<stack::TimeoutError>() {
+                PerTryTimeoutError(t.duration()).into()
+            } else {
+                error
+            }
+        })
+    }
+}
+
+// === impl RetryableErrors ===
+
+impl RetryableErrors {
+    fn is_retryable(
+        &self,
+        method: &http::Method,
+        error: &(dyn std::error::Error + 'static),
+    ) -> bool {
+        // Attempts that time out were sent and may have been processed, so
+        // they are only retried when they're idempotent.
+        if errors::is_caused_by::<PerTryTimeoutError>(error) {
+            return is_idempotent(method);
+        }
+
+        if self.fail_fast && errors::is_caused_by::<errors::FailFastError>(error) {
+            return true;
+        }
+        if self.connect && errors::is_caused_by::<errors::ConnectTimeout>(error) {
+            return true;
+        }
+
+        if let Some(e) = errors::cause_ref::<io::Error>(error) {
+            match e.kind() {
+                io::ErrorKind::ConnectionRefused => return self.connect,
+                io::ErrorKind::ConnectionReset => return self.reset && is_idempotent(method),
+                _ => {}
+            }
+        }
+
+        if self.reset {
+            if let Some(e) = errors::cause_ref::<errors::H2Error>(error) {
+                return match e.reason() {
+                    // The server did not process the request.
+                    Some(Reason::REFUSED_STREAM) => true,
+                    Some(_) => is_idempotent(method),
+                    None => false,
+                };
+            }
         }
 
-        Some(clone)
+        false
     }
 }
 
+/// Idempotent methods, as defined by RFC 7231 §4.2.2, may be safely retried
+/// even when the request may have been processed.
+fn is_idempotent(method: &http::Method) -> bool {
+    matches!(
+        *method,
+        http::Method::GET
+            | http::Method::HEAD
+            | http::Method::OPTIONS
+            | http::Method::TRACE
+            | http::Method::PUT
+            | http::Method::DELETE
+    )
+}
+
 impl<A, B, E> retry::PrepareRetry<http::Request<A>, http::Response<B>, E> for RetryPolicy
 where
     A: HttpBody + Unpin,
@@ -153,7 +398,15 @@ where
         req: http::Request<A>,
     ) -> Either<Self::RetryRequest, http::Request<A>> {
         let (head, body) = req.into_parts();
-        let replay_body = match ReplayBody::try_new(body, MAX_BUFFERED_BYTES) {
+        let max_bytes = self
+            .retries
+            .max_buffered_bytes()
+            .unwrap_or(self.max_buffered_bytes);
+        let replay_body = match self.spill.clone() {
+            Some(spill) => ReplayBody::try_with_spill(body, max_bytes, spill),
+            None => ReplayBody::try_new(body, max_bytes),
+        };
+        let replay_body = match replay_body {
             Ok(body) => body,
             Err(body) => {
                 tracing::debug!(
@@ -175,3 +428,46 @@ where
         WithTrailers::map_response(rsp).map(Ok)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retryable_errors() {
+        let all = RetryableErrors {
+            connect: true,
+            reset: true,
+            fail_fast: true,
+        };
+        let refused = io::Error::from(io::ErrorKind::ConnectionRefused);
+        let reset = io::Error::from(io::ErrorKind::ConnectionReset);
+        let refused_stream = errors::H2Error::from(Reason::REFUSED_STREAM);
+        let cancel = errors::H2Error::from(Reason::CANCEL);
+        let get = http::Method::GET;
+        let post = http::Method::POST;
+
+        // Requests that were never sent may be retried regardless of method.
+        assert!(all.is_retryable(&post, &refused));
+        assert!(all.is_retryable(&post, &refused_stream));
+
+        // Requests that may have been processed are only retried when they're
+        // idempotent.
+        assert!(all.is_retryable(&get, &reset));
+        assert!(!all.is_retryable(&post, &reset));
+        assert!(all.is_retryable(&get, &cancel));
+        assert!(!all.is_retryable(&post, &cancel));
+
+        // Attempts that time out are retried when they're idempotent, even if
+        // no errors are configured to be retried.
+        let timeout = PerTryTimeoutError(Duration::from_secs(1));
+        assert!(RetryableErrors::default().is_retryable(&get, &timeout));
+        assert!(!all.is_retryable(&post, &timeout));
+
+        // Errors are only retried when their kind is configured.
+        let none = RetryableErrors::default();
+        assert!(!none.is_retryable(&get, &refused));
+        assert!(!none.is_retryable(&get, &refused_stream));
+        assert!(!none.is_retryable(&get, &io::Error::from(io::ErrorKind::Other)));
+    }
+}
@@ -54,6 +54,10 @@ impl<N> Outbound<N> {
                         .push(svc::ConcurrencyLimitLayer::new(max_in_flight_requests))
                         .push(svc::FailFast::layer("HTTP Server", dispatch_timeout))
                         .push_spawn_buffer(buffer_capacity)
+                        // Records and enforces request deadlines, so that
+                        // requests fail once their deadline elapses wherever
+                        // they are routed.
+                        .push(http::EnforceDeadline::layer())
                         .push(rt.metrics.http_errors.to_layer())
                         // Tear down server connections when a peer proxy generates an error.
                         .push(ProxyConnectionClose::layer()),
@@ -107,6 +111,15 @@ impl errors::HttpRescue<Error> for ServerRescue {
         if let Some(cause) = errors::cause_ref::<http::ResponseTimeoutError>(&*error) {
             return Ok(errors::SyntheticHttpResponse::gateway_timeout(cause));
         }
+        if let Some(cause) = errors::cause_ref::<http::DeadlineExceededError>(&*error) {
+            return Ok(errors::SyntheticHttpResponse::deadline_exceeded(cause));
+        }
+        if let Some(cause) = errors::cause_ref::<http::PerTryTimeoutError>(&*error) {
+            return Ok(errors::SyntheticHttpResponse::gateway_timeout(cause));
+        }
+        if let Some(cause) = errors::cause_ref::<http::ConcurrencyLimitExceeded>(&*error) {
+            return Ok(errors::SyntheticHttpResponse::unavailable(cause));
+        }
         if let Some(cause) = errors::cause_ref::<IdentityRequired>(&*error) {
             return Ok(errors::SyntheticHttpResponse::bad_gateway(cause));
         }
@@ -82,6 +82,7 @@ impl Outbound<svc::ArcNewHttp<http::Endpoint>> {
                 let detect_http = config.proxy.detect_http();
                 let Config {
                     allow_discovery,
+                    dns_fallback,
                     proxy:
                         ProxyConfig {
                             server: ServerConfig { h2_settings, .. },
@@ -94,13 +95,15 @@ impl Outbound<svc::ArcNewHttp<http::Endpoint>> {
                     ..
                 } = config;
                 let profile_domains = allow_discovery.names().clone();
+                let dns_fallback = *dns_fallback;
 
                 let new_http = http_logical
                     // If a profile was discovered, use it to build a logical stack.
                     // Otherwise, the override header was present but no profile
-                    // information could be discovered, so fail the request.
+                    // information could be discovered, so fail the request, unless
+                    // the name may be resolved via DNS.
                     .push_request_filter(
-                        |(profile, http): (Option<profiles::Receiver>, Http<NameAddr>)| {
+                        move |(profile, http): (Option<profiles::Receiver>, Http<NameAddr>)| {
                             if let Some(profile) = profile {
                                 if let Some(logical_addr) = profile.logical_addr() {
                                     return Ok(http::Logical {
@@ -109,6 +112,13 @@ impl Outbound<svc::ArcNewHttp<http::Endpoint>> {
                                         protocol: http.version,
                                     });
                                 }
+                            } else if dns_fallback {
+                                debug!(dst = %http.target, "Balancing over DNS endpoints");
+                                return Ok(http::Logical {
+                                    profile: dns_profile(http.target.clone()),
+                                    logical_addr: profiles::LogicalAddr(http.target),
+                                    protocol: http.version,
+                                });
                             }
 
                             Err(ProfileRequired)
@@ -235,6 +245,7 @@ impl Outbound<svc::ArcNewHttp<http::Endpoint>> {
                             // inner service is always ready (because it's a router).
                             .push(svc::ConcurrencyLimitLayer::new(*max_in_flight_requests))
                             .push(svc::FailFast::layer("Ingress server", *dispatch_timeout))
+                            .push(http::EnforceDeadline::layer())
                             .push(rt.metrics.http_errors.to_layer()),
                     )
                     .push(http::ServerRescue::layer(config.emit_headers))
@@ -269,3 +280,16 @@ impl Outbound<svc::ArcNewHttp<http::Endpoint>> {
             })
     }
 }
+
+/// Returns a profile for a name that is not served by the destination
+/// controller, so that requests are balanced over the name's DNS endpoints.
+///
+/// The profile never changes, so its sender is dropped immediately; receivers
+/// continue to observe the initial value.
+fn dns_profile(addr: NameAddr) -> profiles::Receiver {
+    let (_, rx) = tokio::sync::watch::channel(profiles::Profile {
+        addr: Some(profiles::LogicalAddr(addr)),
+        ..profiles::Profile::default()
+    });
+    rx.into()
+}
@@ -17,7 +17,7 @@ pub mod tcp;
 #[cfg(test)]
 pub(crate) mod test_util;
 
-pub use self::metrics::Metrics;
+pub use self::{metrics::Metrics, resolve::DnsFallback};
 use futures::Stream;
 use linkerd_app_core::{
     config::ProxyConfig,
@@ -27,6 +27,8 @@ use linkerd_app_core::{
     proxy::{
         api_resolve::{ConcreteAddr, Metadata},
         core::Resolve,
+        discover::health,
+        http::balance::{ConsecutiveFailures, PreferLocalZone, SlowStart},
         tap,
     },
     serve,
@@ -60,6 +62,58 @@ pub struct Config {
 
     // Whether the proxy may include informational headers on HTTP responses.
     pub emit_headers: bool,
+
+    // Configures when HTTP endpoints are ejected from balancers after
+    // consecutive failures. Endpoints are never ejected when unset.
+    pub http_failure_accrual: Option<ConsecutiveFailures>,
+
+    // Configures balancers to prefer endpoints in the proxy's zone. Endpoints
+    // are balanced without regard to their zones when unset.
+    pub prefer_local_zone: Option<PreferLocalZone>,
+
+    // Configures balancers to ramp up traffic to newly discovered endpoints.
+    // New endpoints receive their full share of traffic immediately when
+    // unset.
+    pub slow_start: Option<SlowStart>,
+
+    // Configures when traffic splits fail over from a priority tier of targets
+    // that is failing too many requests. Splits only fail over from tiers that
+    // have no ready targets when unset.
+    pub split_failover: Option<profiles::split::Failover>,
+
+    // Configures the request attribute that traffic splits hash so that
+    // requests with the same value are dispatched to the same target.
+    // Requests are split randomly when unset.
+    pub split_hash_key: Option<profiles::HashKey>,
+
+    // Configures which errors are retried on retryable routes, in addition to
+    // failed responses. Errors are not retried when empty.
+    pub retryable_errors: http::RetryableErrors,
+
+    // The maximum number of bytes of a request body that are buffered so that
+    // the request may be retried or mirrored. Retryable routes may configure
+    // their own limit.
+    pub max_buffered_bytes: usize,
+
+    // Configures retryable request bodies that exceed an in-memory limit to
+    // be buffered in temporary files. Bodies are only buffered in memory when
+    // unset.
+    pub retry_spill: Option<http::RetrySpill>,
+
+    // Configures an adaptive limit on the number of in-flight requests to
+    // each logical destination. Requests are only limited by
+    // `max_in_flight_requests` when unset.
+    pub adaptive_concurrency: Option<http::AdaptiveConcurrency>,
+
+    // Configures active health checking of discovered endpoints. Endpoints
+    // are removed from balancers while they fail their checks. Endpoints are
+    // not actively checked when unset.
+    pub endpoint_health_check: Option<health::Config>,
+
+    // Whether names outside of the discoverable domains are resolved via DNS.
+    // When set, ingress-mode proxies balance requests for these names without
+    // a service profile.
+    pub dns_fallback: bool,
 }
 
 #[derive(Clone, Debug)]
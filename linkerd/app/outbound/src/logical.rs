@@ -44,6 +44,13 @@ impl<P> svc::Param<profiles::Receiver> for Logical<P> {
     }
 }
 
+/// Used for traffic split. Logical targets use the profile's targets.
+impl<P> svc::Param<Option<profiles::Backends>> for Logical<P> {
+    fn param(&self) -> Option<profiles::Backends> {
+        None
+    }
+}
+
 /// Used for default traffic split
 impl<P> svc::Param<profiles::LookupAddr> for Logical<P> {
     fn param(&self) -> profiles::LookupAddr {
@@ -113,6 +120,13 @@ impl<P> svc::Param<ConcreteAddr> for Concrete<P> {
     }
 }
 
+/// Used to configure the balancer's algorithm.
+impl<P> svc::Param<profiles::LoadBalancer> for Concrete<P> {
+    fn param(&self) -> profiles::LoadBalancer {
+        self.logical.profile.load_balancer()
+    }
+}
+
 // === impl Outbound ===
 
 impl<C> Outbound<C> {
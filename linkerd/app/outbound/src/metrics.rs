@@ -8,7 +8,11 @@
 //! to be updated frequently or in a performance-critical area. We should probably look to use
 //! `DashMap` as we migrate other metrics registries.
 
+mod concurrency;
 pub(crate) mod error;
+mod health;
+
+pub(crate) use self::{concurrency::ConcurrencyLimits, health::EndpointHealth};
 
 pub use linkerd_app_core::metrics::*;
 
@@ -17,6 +21,8 @@ pub use linkerd_app_core::metrics::*;
 pub struct Metrics {
     pub(crate) http_errors: error::Http,
     pub(crate) tcp_errors: error::Tcp,
+    pub(crate) endpoint_health: EndpointHealth,
+    pub(crate) concurrency_limits: ConcurrencyLimits,
 
     /// Holds metrics that are common to both inbound and outbound proxies. These metrics are
     /// reported separately
@@ -28,6 +34,8 @@ impl Metrics {
         Self {
             http_errors: error::Http::default(),
             tcp_errors: error::Tcp::default(),
+            endpoint_health: EndpointHealth::default(),
+            concurrency_limits: ConcurrencyLimits::default(),
             proxy,
         }
     }
@@ -37,6 +45,8 @@ impl FmtMetrics for Metrics {
     fn fmt_metrics(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.http_errors.fmt_metrics(f)?;
         self.tcp_errors.fmt_metrics(f)?;
+        self.endpoint_health.fmt_metrics(f)?;
+        self.concurrency_limits.fmt_metrics(f)?;
 
         // XXX: Proxy metrics are reported elsewhere.
 
@@ -0,0 +1,63 @@
+use crate::http::adaptive_limit::Registry;
+use linkerd_app_core::{
+    metrics::{metrics, Counter, FmtLabels, FmtMetrics, Gauge},
+    profiles::LogicalAddr,
+};
+use std::fmt;
+
+metrics! {
+    outbound_http_concurrency_limit: Gauge {
+        "The adaptive limit of in-flight requests to an outbound destination"
+    },
+    outbound_http_concurrency_limit_rejections_total: Counter {
+        "The total number of requests rejected by an outbound destination's adaptive concurrency limit"
+    }
+}
+
+/// Reports the adaptive concurrency limit of each outbound destination.
+#[derive(Clone, Debug, Default)]
+pub struct ConcurrencyLimits(Registry);
+
+struct DstLabels<'a>(&'a LogicalAddr);
+
+// === impl ConcurrencyLimits ===
+
+impl ConcurrencyLimits {
+    pub(crate) fn registry(&self) -> Registry {
+        self.0.clone()
+    }
+}
+
+impl FmtMetrics for ConcurrencyLimits {
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limits = self.0.limits();
+        if limits.is_empty() {
+            return Ok(());
+        }
+
+        outbound_http_concurrency_limit.fmt_help(f)?;
+        for (addr, limit, _) in &limits {
+            let value = Gauge::from(*limit as u64);
+            outbound_http_concurrency_limit.fmt_metric_labeled(f, &value, &DstLabels(addr))?;
+        }
+
+        outbound_http_concurrency_limit_rejections_total.fmt_help(f)?;
+        for (addr, _, rejections) in &limits {
+            let value = Counter::<()>::from(*rejections);
+            outbound_http_concurrency_limit_rejections_total.fmt_metric_labeled(
+                f,
+                &value,
+                &DstLabels(addr),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// === impl DstLabels ===
+
+impl FmtLabels for DstLabels<'_> {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "dst=\"{}\"", self.0)
+    }
+}
@@ -2,15 +2,19 @@ mod http;
 mod tcp;
 
 pub(crate) use self::{http::Http, tcp::Tcp};
-use crate::http::IdentityRequired;
+use crate::http::{ConcurrencyLimitExceeded, IdentityRequired};
 use linkerd_app_core::{
-    errors::FailFastError, metrics::FmtLabels, proxy::http::ResponseTimeoutError,
+    errors::FailFastError,
+    metrics::FmtLabels,
+    proxy::http::{DeadlineExceededError, ResponseTimeoutError},
 };
 use std::fmt;
 
 /// Outbound proxy error types.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 enum ErrorKind {
+    ConcurrencyLimit,
+    DeadlineExceeded,
     FailFast,
     IdentityRequired,
     Io,
@@ -28,6 +32,10 @@ impl ErrorKind {
             ErrorKind::IdentityRequired
         } else if err.is::<FailFastError>() {
             ErrorKind::FailFast
+        } else if err.is::<ConcurrencyLimitExceeded>() {
+            ErrorKind::ConcurrencyLimit
+        } else if err.is::<DeadlineExceededError>() {
+            ErrorKind::DeadlineExceeded
         } else if err.is::<ResponseTimeoutError>() {
             ErrorKind::ResponseTimeout
         } else if let Some(e) = err.source() {
@@ -44,6 +52,8 @@ impl FmtLabels for ErrorKind {
             f,
             "error=\"{}\"",
             match self {
+                ErrorKind::ConcurrencyLimit => "concurrency limit",
+                ErrorKind::DeadlineExceeded => "deadline exceeded",
                 ErrorKind::FailFast => "failfast",
                 ErrorKind::IdentityRequired => "identity required",
                 ErrorKind::Io => "i/o",
@@ -0,0 +1,49 @@
+use linkerd_app_core::{
+    metrics::{metrics, FmtLabels, FmtMetrics, Gauge},
+    proxy::discover::health,
+};
+use std::{fmt, net::SocketAddr};
+
+metrics! {
+    outbound_endpoint_healthy: Gauge {
+        "Indicates whether an actively health checked outbound endpoint is healthy"
+    }
+}
+
+/// Reports the health of each endpoint that is actively health checked.
+#[derive(Clone, Debug, Default)]
+pub struct EndpointHealth(health::Registry);
+
+struct EndpointLabels(SocketAddr);
+
+// === impl EndpointHealth ===
+
+impl EndpointHealth {
+    pub(crate) fn registry(&self) -> health::Registry {
+        self.0.clone()
+    }
+}
+
+impl FmtMetrics for EndpointHealth {
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let endpoints = self.0.endpoints();
+        if endpoints.is_empty() {
+            return Ok(());
+        }
+
+        outbound_endpoint_healthy.fmt_help(f)?;
+        for (addr, healthy) in endpoints {
+            let value = Gauge::from(healthy as u64);
+            outbound_endpoint_healthy.fmt_metric_labeled(f, &value, &EndpointLabels(addr))?;
+        }
+        Ok(())
+    }
+}
+
+// === impl EndpointLabels ===
+
+impl FmtLabels for EndpointLabels {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "addr=\"{}\"", self.0)
+    }
+}
@@ -1,16 +1,43 @@
+use futures::prelude::*;
 use linkerd_app_core::{
     proxy::{
-        core::Resolve,
-        discover::{self, Buffer},
+        api_resolve::{ConcreteAddr, Metadata},
+        core::{Resolve, Update},
+        discover::{health, Buffer, FromResolve, MakeEndpoint, NewHealthCheck},
     },
     svc::{layer, NewService},
+    Addr, Error, NameMatch,
 };
-use std::time::Duration;
+use std::{
+    net::SocketAddr,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tracing::debug;
+
+/// Resolves concrete addresses via the destination controller, falling back to
+/// DNS for names outside of the discoverable domains.
+#[derive(Clone, Debug)]
+pub struct DnsFallback<R, D> {
+    resolve: R,
+    dns: D,
+    fallback: Option<NameMatch>,
+}
 
+pub type Resolution = Pin<Box<dyn Stream<Item = Result<Update<Metadata>, Error>> + Send + 'static>>;
+
+/// Discovers endpoints, removing those that fail active health checks when
+/// `health_check` is configured.
 pub fn layer<T, R, N>(
     resolve: R,
     watchdog: Duration,
-) -> impl layer::Layer<N, Service = Buffer<discover::Stack<N, R, R::Endpoint>>> + Clone
+    health_check: Option<health::Config>,
+    health: health::Registry,
+) -> impl layer::Layer<
+    N,
+    Service = Buffer<MakeEndpoint<NewHealthCheck<FromResolve<R, R::Endpoint>>, N>>,
+> + Clone
 where
     T: Clone + Send + std::fmt::Debug,
     R: Resolve<T> + Clone,
@@ -24,7 +51,133 @@ where
         Buffer::new(
             ENDPOINT_BUFFER_CAPACITY,
             watchdog,
-            discover::resolve(new_endpoint, resolve.clone()),
+            MakeEndpoint::new(
+                new_endpoint,
+                NewHealthCheck::new(
+                    health_check.clone(),
+                    health.clone(),
+                    FromResolve::new(resolve.clone()),
+                ),
+            ),
         )
     })
 }
+
+// === impl DnsFallback ===
+
+impl<R, D> DnsFallback<R, D> {
+    /// Returns a resolver that resolves names outside of `fallback` via DNS.
+    ///
+    /// When `fallback` is `None`, all names are resolved by the destination
+    /// controller.
+    pub fn new(resolve: R, dns: D, fallback: Option<NameMatch>) -> Self {
+        Self {
+            resolve,
+            dns,
+            fallback,
+        }
+    }
+}
+
+impl<R, D> tower::Service<ConcreteAddr> for DnsFallback<R, D>
+where
+    R: Resolve<ConcreteAddr, Endpoint = Metadata, Error = Error>,
+    R::Resolution: Send + 'static,
+    R::Future: Send + 'static,
+    D: Resolve<Addr, Endpoint = (), Error = Error>,
+    D::Resolution: Send + 'static,
+    D::Future: Send + 'static,
+{
+    type Response = Resolution;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Resolution, Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        futures::ready!(self.resolve.poll_ready(cx))?;
+        if self.fallback.is_some() {
+            futures::ready!(self.dns.poll_ready(cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, ConcreteAddr(addr): ConcreteAddr) -> Self::Future {
+        let discoverable = match self.fallback.as_ref() {
+            Some(domains) => domains.matches(addr.name()),
+            None => true,
+        };
+        if discoverable {
+            return Box::pin(
+                self.resolve
+                    .resolve(ConcreteAddr(addr))
+                    .map_ok(|res| Box::pin(res) as Resolution),
+            );
+        }
+
+        // The DNS resolution refreshes itself as records expire. Endpoints
+        // resolved via DNS have no metadata, so they are not meshed.
+        debug!(%addr, "Resolving via DNS");
+        Box::pin(self.dns.resolve(Addr::Name(addr)).map_ok(|res| {
+            Box::pin(res.map_ok(|update| match update {
+                Update::Reset(eps) => Update::Reset(with_default_metadata(eps)),
+                Update::Add(eps) => Update::Add(with_default_metadata(eps)),
+                Update::Remove(addrs) => Update::Remove(addrs),
+                Update::DoesNotExist => Update::DoesNotExist,
+            })) as Resolution
+        }))
+    }
+}
+
+fn with_default_metadata(eps: Vec<(SocketAddr, ())>) -> Vec<(SocketAddr, Metadata)> {
+    eps.into_iter()
+        .map(|(addr, ())| (addr, Metadata::default()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use linkerd_app_core::{linkerd_dns::Suffix, svc::ServiceExt, NameAddr};
+    use std::str::FromStr;
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn resolves_undiscoverable_names_via_dns() {
+        let _trace = linkerd_tracing::test::trace_init();
+
+        let svc = NameAddr::from_str("foo.ns.svc.cluster.local:8080").unwrap();
+        let svc_ep = SocketAddr::new([10, 0, 0, 1].into(), 8080);
+        let dst = support::resolver().endpoint_exists(svc.clone(), svc_ep, Metadata::default());
+
+        let dns_ep = SocketAddr::new([192, 0, 2, 1].into(), 8080);
+        let dns = tower::service_fn(move |addr: Addr| {
+            assert_eq!(addr.to_string(), "example.com:8080");
+            let updates = stream::iter(Some(Ok::<_, Error>(Update::Reset(vec![(dns_ep, ())]))));
+            future::ok::<_, Error>(updates)
+        });
+
+        let domains = Some(Suffix::from_str("cluster.local").unwrap())
+            .into_iter()
+            .collect();
+        let resolve = DnsFallback::new(dst, dns, Some(domains));
+
+        let mut res = resolve
+            .clone()
+            .oneshot(ConcreteAddr(svc))
+            .await
+            .expect("must resolve");
+        match res.next().await {
+            Some(Ok(Update::Add(eps))) => assert_eq!(eps[0].0, svc_ep),
+            up => panic!("unexpected update: {:?}", up),
+        }
+
+        let external = NameAddr::from_str("example.com:8080").unwrap();
+        let mut res = resolve
+            .oneshot(ConcreteAddr(external))
+            .await
+            .expect("must resolve");
+        match res.next().await {
+            Some(Ok(Update::Reset(eps))) => assert_eq!(eps[0].0, dns_ep),
+            up => panic!("unexpected update: {:?}", up),
+        }
+    }
+}
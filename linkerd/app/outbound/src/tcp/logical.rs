@@ -5,6 +5,7 @@ use linkerd_app_core::{
     proxy::{
         api_resolve::{ConcreteAddr, Metadata},
         core::Resolve,
+        discover::health,
         resolve::map_endpoint,
         tcp,
     },
@@ -70,13 +71,35 @@ impl<C> Outbound<C> {
                         server.id = t.tls.value().map(|tls| tracing::field::display(&tls.server_id)),
                     )
                 })
-                .push(resolve::layer(resolve, config.proxy.cache_max_idle_age * 2))
+                .push(tcp::balance::NewZoned::layer(
+                    config.prefer_local_zone.clone(),
+                ))
+                // Opaque streams' endpoints may not serve the HTTP or gRPC
+                // checks that they request, so they're only checked for
+                // accepting connections.
+                .push(resolve::layer(
+                    resolve,
+                    config.proxy.cache_max_idle_age * 2,
+                    config
+                        .endpoint_health_check
+                        .clone()
+                        .map(|config| health::Config {
+                            request_probes: false,
+                            ..config
+                        }),
+                    rt.metrics.endpoint_health.registry(),
+                ))
+                .push_on_service(tcp::balance::ZoneAware::layer(
+                    config.prefer_local_zone.clone(),
+                ))
+                .push(tcp::balance::MakeBalance::layer(
+                    crate::EWMA_DEFAULT_RTT,
+                    crate::EWMA_DECAY,
+                    tcp::balance::CompleteOnResponse::default(),
+                    config.slow_start,
+                ))
                 .push_on_service(
                     svc::layers()
-                        .push(tcp::balance::layer(
-                            crate::EWMA_DEFAULT_RTT,
-                            crate::EWMA_DECAY,
-                        ))
                         .push(
                             rt.metrics
                                 .proxy
@@ -90,7 +113,11 @@ impl<C> Outbound<C> {
                 .push_map_target(Concrete::from)
                 .push(svc::ArcNewService::layer())
                 .check_new_service::<(ConcreteAddr, Logical), I>()
-                .push(profiles::split::layer())
+                // Connections carry no hash key, so they are split randomly.
+                .push(profiles::split::layer(
+                    config.split_failover,
+                    None::<tcp::balance::NoHash>,
+                ))
                 .push_on_service(
                     svc::layers()
                         .push(
@@ -45,6 +45,17 @@ pub(crate) fn default_config() -> Config {
             detect_protocol_timeout: Duration::from_secs(3),
         },
         inbound_ips: Default::default(),
+        http_failure_accrual: None,
+        prefer_local_zone: None,
+        slow_start: None,
+        split_failover: None,
+        split_hash_key: None,
+        retryable_errors: Default::default(),
+        max_buffered_bytes: 64 * 1024,
+        retry_spill: None,
+        adaptive_concurrency: None,
+        endpoint_health_check: None,
+        dns_fallback: false,
     }
 }
 
@@ -1,31 +1,85 @@
+use crate::{file, snapshot, xds};
+use futures::prelude::*;
 use linkerd_app_core::{
     control, dns,
     exp_backoff::{ExponentialBackoff, ExponentialBackoffStream},
     identity, metrics,
     profiles::{self, DiscoveryRejected},
-    proxy::{api_resolve as api, http, resolve::recover},
+    proxy::{
+        api_resolve::{self as api, ConcreteAddr, Metadata},
+        core::resolve,
+        dns_resolve::DnsResolve,
+        http,
+        resolve::recover,
+    },
     svc::{self, NewService},
     Error, Recover,
 };
+use linkerd_app_inbound::policy::PortsRx;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
 
 #[derive(Clone, Debug)]
-pub struct Config {
-    pub control: control::Config,
-    pub context: String,
+pub enum Config {
+    /// Discovers destinations from the destination service.
+    Control {
+        control: control::Config,
+        context: String,
+
+        /// Persists discovered state so that it can be served at startup
+        /// while the control plane is unavailable.
+        snapshot: Option<snapshot::Config>,
+    },
+
+    /// Discovers destinations, profiles, and inbound policies from a local
+    /// file.
+    File(file::Config),
+
+    /// Discovers destinations and profiles from an xDS management server.
+    Xds(xds::Config),
 }
 
 /// Handles to destination service clients.
 pub struct Dst<S> {
-    /// The address of the destination service, used for logging.
-    pub addr: control::ControlAddr,
+    /// The address of the destination service or xDS management server, used
+    /// for logging. Unset when destinations are discovered from a file.
+    pub addr: Option<control::ControlAddr>,
 
     /// Resolves profiles.
-    pub profiles: profiles::Client<BackoffUnlessInvalidArgument, S>,
+    pub profiles: Profiles<S>,
 
     /// Resolves endpoints.
-    pub resolve: recover::Resolve<BackoffUnlessInvalidArgument, api::Resolve<S>>,
+    pub resolve: Resolve<S>,
+
+    /// Resolves endpoints via DNS, for names that are not served by the
+    /// destination service.
+    pub dns_resolve: recover::Resolve<BackoffUnlessInvalidArgument, DnsResolve>,
+
+    /// Watches inbound policies, when they are discovered from a file.
+    pub policies: Option<PortsRx>,
+
+    /// Records discovered state, when it is persisted.
+    pub snapshots: Option<snapshot::Snapshots>,
 }
 
+pub type Profiles<S> = svc::Either<
+    snapshot::Profiles<profiles::Client<BackoffUnlessInvalidArgument, S>>,
+    file::Profiles,
+>;
+
+/// Resolves endpoints from the destination service, or from a snapshot read
+/// from a file or discovered from an xDS management server.
+#[derive(Clone)]
+pub enum Resolve<S> {
+    Control(ControlResolve<S>),
+    File(file::Resolve),
+}
+
+type ControlResolve<S> =
+    snapshot::Resolve<recover::Resolve<BackoffUnlessInvalidArgument, api::Resolve<S>>>;
+
 #[derive(Copy, Clone, Debug, Default)]
 pub struct BackoffUnlessInvalidArgument(ExponentialBackoff);
 
@@ -48,18 +102,88 @@ impl Config {
         >,
         Error,
     > {
-        let addr = self.control.addr.clone();
-        let backoff = BackoffUnlessInvalidArgument(self.control.connect.backoff);
-        let svc = self.control.build(dns, metrics, identity).new_service(());
+        let (control, context, snapshot) = match self {
+            Self::Control {
+                control,
+                context,
+                snapshot,
+            } => (control, context, snapshot),
+            Self::File(config) => {
+                let backoff = BackoffUnlessInvalidArgument::default();
+                let file = config.build()?;
+                return Ok(Dst {
+                    addr: None,
+                    profiles: svc::Either::B(file.profiles),
+                    resolve: Resolve::File(file.resolve),
+                    dns_resolve: recover::Resolve::new(backoff, DnsResolve::new(dns)),
+                    policies: Some(file.policies),
+                    snapshots: None,
+                });
+            }
+            Self::Xds(config) => {
+                let addr = config.control.addr.clone();
+                let backoff = BackoffUnlessInvalidArgument(config.control.connect.backoff);
+                let xds = config.build(dns.clone(), metrics, identity);
+                return Ok(Dst {
+                    addr: Some(addr),
+                    profiles: svc::Either::B(xds.profiles),
+                    resolve: Resolve::File(xds.resolve),
+                    dns_resolve: recover::Resolve::new(backoff, DnsResolve::new(dns)),
+                    policies: None,
+                    snapshots: None,
+                });
+            }
+        };
 
+        let addr = control.addr.clone();
+        let backoff = BackoffUnlessInvalidArgument(control.connect.backoff);
+        let dns_resolve = recover::Resolve::new(backoff, DnsResolve::new(dns.clone()));
+        let svc = control.build(dns, metrics, identity).new_service(());
+        let snapshots = snapshot.map(snapshot::Config::build);
+
+        let profiles = profiles::Client::new(backoff, svc.clone(), context.clone());
+        let resolve = recover::Resolve::new(backoff, api::Resolve::new(svc, context));
         Ok(Dst {
-            addr,
-            profiles: profiles::Client::new(backoff, svc.clone(), self.context.clone()),
-            resolve: recover::Resolve::new(backoff, api::Resolve::new(svc, self.context)),
+            addr: Some(addr),
+            profiles: svc::Either::A(snapshot::Profiles::new(profiles, snapshots.clone())),
+            resolve: Resolve::Control(snapshot::Resolve::new(resolve, snapshots.clone())),
+            dns_resolve,
+            policies: None,
+            snapshots,
         })
     }
 }
 
+// === impl Resolve ===
+
+impl<S> tower::Service<ConcreteAddr> for Resolve<S>
+where
+    ControlResolve<S>: resolve::Resolve<ConcreteAddr, Endpoint = Metadata, Error = Error>,
+    <ControlResolve<S> as resolve::Resolve<ConcreteAddr>>::Resolution: Send + 'static,
+    <ControlResolve<S> as resolve::Resolve<ConcreteAddr>>::Future: Send + 'static,
+{
+    type Response = file::Resolution;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<file::Resolution, Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        match self {
+            Self::Control(res) => resolve::Resolve::poll_ready(res, cx),
+            Self::File(res) => tower::Service::<ConcreteAddr>::poll_ready(res, cx),
+        }
+    }
+
+    fn call(&mut self, target: ConcreteAddr) -> Self::Future {
+        match self {
+            Self::Control(res) => Box::pin(
+                resolve::Resolve::resolve(res, target)
+                    .map_ok(|res| Box::pin(res) as file::Resolution),
+            ),
+            Self::File(res) => Box::pin(tower::Service::call(res, target)),
+        }
+    }
+}
+
 // === impl BackoffUnlessInvalidArgument ===
 
 impl Recover<Error> for BackoffUnlessInvalidArgument {
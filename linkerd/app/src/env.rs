@@ -2,7 +2,14 @@ use crate::core::{
     addr,
     config::*,
     control::{Config as ControlConfig, ControlAddr},
-    proxy::http::{h1, h2},
+    profiles::split::Failover,
+    proxy::{
+        discover::health,
+        http::{
+            balance::{ConsecutiveFailures, HashKey, PreferLocalZone, SlowStart},
+            h1, h2,
+        },
+    },
     tls,
     transport::{Keepalive, ListenAddr},
     Addr, AddrMatch, Conditional, IpNet,
@@ -13,6 +20,7 @@ use std::{
     collections::{HashMap, HashSet},
     fs,
     net::{IpAddr, SocketAddr},
+    num::{NonZeroU32, NonZeroUsize},
     path::PathBuf,
     str::FromStr,
     time::Duration,
@@ -84,6 +92,10 @@ pub enum ParseError {
     InvalidTrustAnchors,
     #[error("not a valid port policy: {0}")]
     InvalidPortPolicy(String),
+    #[error("not a valid hash key: {0}")]
+    InvalidHashKey(String),
+    #[error("not a retryable error kind: {0}")]
+    InvalidRetryableError(String),
 }
 
 // Environment variables to look at when loading the configuration
@@ -123,6 +135,142 @@ const ENV_OUTBOUND_MAX_IDLE_CONNS_PER_ENDPOINT: &str =
 pub const ENV_INBOUND_MAX_IN_FLIGHT: &str = "LINKERD2_PROXY_INBOUND_MAX_IN_FLIGHT";
 pub const ENV_OUTBOUND_MAX_IN_FLIGHT: &str = "LINKERD2_PROXY_OUTBOUND_MAX_IN_FLIGHT";
 
+/// The number of consecutive failures after which an endpoint is ejected from
+/// its outbound balancer. Endpoints are not ejected when unset.
+const ENV_OUTBOUND_FAILURE_ACCRUAL_CONSECUTIVE_FAILURES: &str =
+    "LINKERD2_PROXY_OUTBOUND_FAILURE_ACCRUAL_CONSECUTIVE_FAILURES";
+
+/// The maximum fraction, in (0, 1], of an outbound balancer's endpoints that
+/// may be ejected at once.
+const ENV_OUTBOUND_FAILURE_ACCRUAL_MAX_EJECTED_RATIO: &str =
+    "LINKERD2_PROXY_OUTBOUND_FAILURE_ACCRUAL_MAX_EJECTED_RATIO";
+
+/// The zone in which the proxy runs. When set, outbound balancers prefer
+/// endpoints in the same zone.
+const ENV_OUTBOUND_LOCAL_ZONE: &str = "LINKERD2_PROXY_OUTBOUND_LOCAL_ZONE";
+
+/// The fraction of an outbound balancer's local-zone endpoints that must be
+/// ready for requests to stay in the local zone.
+const ENV_OUTBOUND_LOCAL_ZONE_MIN_READY: &str = "LINKERD2_PROXY_OUTBOUND_LOCAL_ZONE_MIN_READY";
+
+/// The time over which outbound balancers ramp up traffic to newly discovered
+/// endpoints. New endpoints are not slowly started when unset or zero.
+const ENV_OUTBOUND_SLOW_START_WINDOW: &str = "LINKERD2_PROXY_OUTBOUND_SLOW_START_WINDOW";
+
+/// The fraction of its full weight that a newly discovered endpoint receives.
+const ENV_OUTBOUND_SLOW_START_MIN_WEIGHT: &str = "LINKERD2_PROXY_OUTBOUND_SLOW_START_MIN_WEIGHT";
+
+/// Shapes the slow-start ramp: `1.0` ramps linearly, while larger values ramp
+/// up more quickly at first.
+const ENV_OUTBOUND_SLOW_START_AGGRESSION: &str = "LINKERD2_PROXY_OUTBOUND_SLOW_START_AGGRESSION";
+
+/// The fraction of requests to a traffic split's priority tier that may fail
+/// before requests fail over to the next tier. Tiers only fail over when they
+/// have no ready targets when unset.
+const ENV_OUTBOUND_SPLIT_FAILOVER_MAX_FAILURE_RATE: &str =
+    "LINKERD2_PROXY_OUTBOUND_SPLIT_FAILOVER_MAX_FAILURE_RATE";
+const ENV_OUTBOUND_SPLIT_FAILOVER_MIN_REQUESTS: &str =
+    "LINKERD2_PROXY_OUTBOUND_SPLIT_FAILOVER_MIN_REQUESTS";
+const ENV_OUTBOUND_SPLIT_FAILOVER_WINDOW: &str = "LINKERD2_PROXY_OUTBOUND_SPLIT_FAILOVER_WINDOW";
+
+/// How long requests fail over from a tier before they are returned to it.
+const ENV_OUTBOUND_SPLIT_FAILOVER_COOLDOWN: &str =
+    "LINKERD2_PROXY_OUTBOUND_SPLIT_FAILOVER_COOLDOWN";
+
+/// The request attribute that traffic splits hash to consistently dispatch
+/// requests to the same target: `header:<name>`, `cookie:<name>`, or
+/// `client-addr`. Requests are split randomly when unset.
+///
+/// `client-addr` is only accepted in ingress mode: a sidecar's outbound
+/// clients are always its local application, so every request would hash to
+/// the same target.
+const ENV_OUTBOUND_SPLIT_HASH_KEY: &str = "LINKERD2_PROXY_OUTBOUND_SPLIT_HASH_KEY";
+
+/// A comma-separated list of the kinds of errors that are retried on
+/// retryable routes: `connect`, `reset`, and `fail-fast`. Errors are not
+/// retried when unset.
+const ENV_OUTBOUND_RETRYABLE_ERRORS: &str = "LINKERD2_PROXY_OUTBOUND_RETRYABLE_ERRORS";
+
+/// The maximum number of bytes of a request body that are buffered so that the
+/// request may be retried or mirrored. Retryable routes may configure their own
+/// limit. Requests with larger bodies are not retried or mirrored.
+const ENV_OUTBOUND_MAX_BUFFERED_BYTES: &str = "LINKERD2_PROXY_OUTBOUND_MAX_BUFFERED_BYTES";
+
+/// A directory in which retryable request bodies are buffered once they exceed
+/// the spill memory limit. Bodies are only buffered in memory when unset.
+const ENV_OUTBOUND_RETRY_SPILL_DIR: &str = "LINKERD2_PROXY_OUTBOUND_RETRY_SPILL_DIR";
+
+/// The number of bytes of a retryable request body that are buffered in memory
+/// before the body is spilled to the spill directory. Must be less than the
+/// maximum number of buffered bytes.
+const ENV_OUTBOUND_RETRY_SPILL_MEMORY_LIMIT: &str =
+    "LINKERD2_PROXY_OUTBOUND_RETRY_SPILL_MEMORY_LIMIT";
+
+/// The initial limit of in-flight requests to each outbound destination, which
+/// is adapted to the destination's latency. Destinations are not adaptively
+/// limited when unset.
+const ENV_OUTBOUND_ADAPTIVE_CONCURRENCY_INITIAL_LIMIT: &str =
+    "LINKERD2_PROXY_OUTBOUND_ADAPTIVE_CONCURRENCY_INITIAL_LIMIT";
+
+/// The bounds of each outbound destination's adaptive concurrency limit.
+const ENV_OUTBOUND_ADAPTIVE_CONCURRENCY_MIN_LIMIT: &str =
+    "LINKERD2_PROXY_OUTBOUND_ADAPTIVE_CONCURRENCY_MIN_LIMIT";
+const ENV_OUTBOUND_ADAPTIVE_CONCURRENCY_MAX_LIMIT: &str =
+    "LINKERD2_PROXY_OUTBOUND_ADAPTIVE_CONCURRENCY_MAX_LIMIT";
+
+/// The ratio of a response's latency to the destination's lowest recent
+/// latency above which its adaptive concurrency limit is decreased.
+const ENV_OUTBOUND_ADAPTIVE_CONCURRENCY_TOLERANCE: &str =
+    "LINKERD2_PROXY_OUTBOUND_ADAPTIVE_CONCURRENCY_TOLERANCE";
+
+/// The ratio, in (0, 1), by which an adaptive concurrency limit is decreased
+/// when a destination's latency exceeds the tolerance.
+const ENV_OUTBOUND_ADAPTIVE_CONCURRENCY_BACKOFF_RATIO: &str =
+    "LINKERD2_PROXY_OUTBOUND_ADAPTIVE_CONCURRENCY_BACKOFF_RATIO";
+
+/// Whether outbound endpoints are actively health checked. Endpoints are
+/// checked for accepting connections unless they request an HTTP or gRPC check
+/// with a `health_check` label (`http:<path>`, `grpc`, or `grpc:<service>`).
+///
+/// Requested checks are only sent by HTTP balancers. They are sent in
+/// plaintext, so meshed endpoints are only checked for accepting connections.
+const ENV_OUTBOUND_HEALTH_CHECK: &str = "LINKERD2_PROXY_OUTBOUND_HEALTH_CHECK";
+const ENV_OUTBOUND_HEALTH_CHECK_INTERVAL: &str = "LINKERD2_PROXY_OUTBOUND_HEALTH_CHECK_INTERVAL";
+const ENV_OUTBOUND_HEALTH_CHECK_TIMEOUT: &str = "LINKERD2_PROXY_OUTBOUND_HEALTH_CHECK_TIMEOUT";
+
+/// The number of consecutive failed checks after which an endpoint is removed
+/// from outbound balancers.
+const ENV_OUTBOUND_HEALTH_CHECK_UNHEALTHY_THRESHOLD: &str =
+    "LINKERD2_PROXY_OUTBOUND_HEALTH_CHECK_UNHEALTHY_THRESHOLD";
+
+/// The number of consecutive successful checks after which a removed endpoint
+/// is restored.
+const ENV_OUTBOUND_HEALTH_CHECK_HEALTHY_THRESHOLD: &str =
+    "LINKERD2_PROXY_OUTBOUND_HEALTH_CHECK_HEALTHY_THRESHOLD";
+
+/// The maximum fraction, in (0, 1], of an outbound balancer's endpoints that
+/// may be removed at once for failing their checks.
+const ENV_OUTBOUND_HEALTH_CHECK_MAX_UNHEALTHY_RATIO: &str =
+    "LINKERD2_PROXY_OUTBOUND_HEALTH_CHECK_MAX_UNHEALTHY_RATIO";
+
+/// The number of inbound HTTP requests that may be in flight across all
+/// connections before requests are queued and shed by priority. Requests are
+/// not shed by priority when unset.
+const ENV_INBOUND_PRIORITY_MAX_IN_FLIGHT: &str = "LINKERD2_PROXY_INBOUND_PRIORITY_MAX_IN_FLIGHT";
+
+/// The number of inbound HTTP requests that may wait for capacity before the
+/// lowest-priority requests are shed.
+const ENV_INBOUND_PRIORITY_MAX_QUEUED: &str = "LINKERD2_PROXY_INBOUND_PRIORITY_MAX_QUEUED";
+
+/// Whether clients with a TLS identity may set the priority of inbound
+/// requests with the `l5d-priority` header. The header is ignored by default
+/// and is never forwarded to the application.
+const ENV_INBOUND_PRIORITY_TRUST_HEADER: &str = "LINKERD2_PROXY_INBOUND_PRIORITY_TRUST_HEADER";
+
+/// When set, outbound names outside of the destination profile suffixes are
+/// resolved via DNS (SRV records, falling back to A records).
+const ENV_OUTBOUND_DNS_FALLBACK: &str = "LINKERD2_PROXY_OUTBOUND_DNS_FALLBACK";
+
 const ENV_OUTBOUND_DISABLE_INFORMATIONAL_HEADERS: &str =
     "LINKERD2_PROXY_OUTBOUND_DISABLE_INFORMATIONAL_HEADERS";
 
@@ -191,6 +339,41 @@ pub const ENV_IDENTITY_SVC_BASE: &str = "LINKERD2_PROXY_IDENTITY_SVC";
 
 pub const ENV_DESTINATION_SVC_BASE: &str = "LINKERD2_PROXY_DESTINATION_SVC";
 
+/// A JSON or YAML file describing destinations, service profiles, and inbound
+/// policies. When set, the file is used instead of the destination and policy
+/// services. Files with a `.yaml` or `.yml` extension are read as YAML.
+pub const ENV_DISCOVERY_FILE: &str = "LINKERD2_PROXY_DISCOVERY_FILE";
+
+/// How often the discovery file is checked for changes.
+pub const ENV_DISCOVERY_FILE_POLL_INTERVAL: &str = "LINKERD2_PROXY_DISCOVERY_FILE_POLL_INTERVAL";
+
+/// A file in which discovered endpoints, profiles, and inbound policies are
+/// periodically saved, so that they can be served at startup until the control
+/// plane responds.
+pub const ENV_DISCOVERY_SNAPSHOT: &str = "LINKERD2_PROXY_DISCOVERY_SNAPSHOT";
+
+/// How often the discovery snapshot is written.
+pub const ENV_DISCOVERY_SNAPSHOT_INTERVAL: &str = "LINKERD2_PROXY_DISCOVERY_SNAPSHOT_INTERVAL";
+
+/// How long ago the discovery snapshot may have been written for it to be
+/// served at startup. Older snapshots are ignored.
+pub const ENV_DISCOVERY_SNAPSHOT_MAX_AGE: &str = "LINKERD2_PROXY_DISCOVERY_SNAPSHOT_MAX_AGE";
+
+/// An xDS management server. When set, destinations and profiles are
+/// discovered from the server instead of the destination service.
+pub const ENV_XDS_SVC_BASE: &str = "LINKERD2_PROXY_XDS_SVC";
+
+/// The node ID presented to the xDS management server. Defaults to the
+/// hostname.
+pub const ENV_XDS_NODE_ID: &str = "LINKERD2_PROXY_XDS_NODE_ID";
+
+/// The service cluster presented to the xDS management server.
+pub const ENV_XDS_NODE_CLUSTER: &str = "LINKERD2_PROXY_XDS_NODE_CLUSTER";
+
+/// A comma-separated list of the xDS route configurations from which profiles
+/// are discovered.
+pub const ENV_XDS_ROUTE_CONFIGS: &str = "LINKERD2_PROXY_XDS_ROUTE_CONFIGS";
+
 pub const ENV_HOSTNAME: &str = "HOSTNAME";
 
 pub const ENV_TRACE_COLLECTOR_SVC_BASE: &str = "LINKERD2_PROXY_TRACE_COLLECTOR_SVC";
@@ -234,6 +417,27 @@ const DEFAULT_OUTBOUND_DETECT_TIMEOUT: Duration = Duration::from_secs(10);
 const DEFAULT_OUTBOUND_CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
 const DEFAULT_OUTBOUND_CONNECT_BACKOFF: ExponentialBackoff =
     ExponentialBackoff::new_unchecked(Duration::from_millis(100), Duration::from_millis(500), 0.1);
+const DEFAULT_OUTBOUND_FAILURE_ACCRUAL_BACKOFF: ExponentialBackoff =
+    ExponentialBackoff::new_unchecked(Duration::from_secs(1), Duration::from_secs(60), 0.5);
+const DEFAULT_OUTBOUND_FAILURE_ACCRUAL_MAX_EJECTED_RATIO: f64 = 0.5;
+const DEFAULT_OUTBOUND_LOCAL_ZONE_MIN_READY: f64 = 0.7;
+const DEFAULT_OUTBOUND_SLOW_START_MIN_WEIGHT: f64 = 0.1;
+const DEFAULT_OUTBOUND_SLOW_START_AGGRESSION: f64 = 1.0;
+const DEFAULT_OUTBOUND_SPLIT_FAILOVER_MIN_REQUESTS: u32 = 10;
+const DEFAULT_OUTBOUND_SPLIT_FAILOVER_WINDOW: Duration = Duration::from_secs(10);
+const DEFAULT_OUTBOUND_SPLIT_FAILOVER_COOLDOWN: Duration = Duration::from_secs(30);
+const DEFAULT_OUTBOUND_MAX_BUFFERED_BYTES: usize = 64 * 1024;
+const DEFAULT_OUTBOUND_RETRY_SPILL_MEMORY_LIMIT: usize = 16 * 1024;
+const DEFAULT_OUTBOUND_ADAPTIVE_CONCURRENCY_MIN_LIMIT: usize = 1;
+const DEFAULT_OUTBOUND_ADAPTIVE_CONCURRENCY_MAX_LIMIT: usize = 1_000;
+const DEFAULT_OUTBOUND_ADAPTIVE_CONCURRENCY_TOLERANCE: f64 = 2.0;
+const DEFAULT_OUTBOUND_ADAPTIVE_CONCURRENCY_BACKOFF_RATIO: f64 = 0.9;
+const DEFAULT_OUTBOUND_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+const DEFAULT_OUTBOUND_HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(1);
+const DEFAULT_OUTBOUND_HEALTH_CHECK_UNHEALTHY_THRESHOLD: u32 = 3;
+const DEFAULT_OUTBOUND_HEALTH_CHECK_HEALTHY_THRESHOLD: u32 = 2;
+const DEFAULT_OUTBOUND_HEALTH_CHECK_MAX_UNHEALTHY_RATIO: f64 = 0.5;
+const DEFAULT_INBOUND_PRIORITY_MAX_QUEUED: usize = 1_000;
 const DEFAULT_RESOLV_CONF: &str = "/etc/resolv.conf";
 
 const DEFAULT_INITIAL_STREAM_WINDOW_SIZE: u32 = 65_535; // Protocol default
@@ -278,12 +482,16 @@ const DEFAULT_BUFFER_CAPACITY: usize = 10_000;
 
 const DEFAULT_DESTINATION_PROFILE_SUFFIXES: &str = "svc.cluster.local.";
 const DEFAULT_DESTINATION_PROFILE_IDLE_TIMEOUT: Duration = Duration::from_millis(500);
+const DEFAULT_DISCOVERY_FILE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const DEFAULT_DISCOVERY_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(30);
+const DEFAULT_DISCOVERY_SNAPSHOT_MAX_AGE: Duration = Duration::from_secs(60 * 60);
 
 const DEFAULT_IDENTITY_MIN_REFRESH: Duration = Duration::from_secs(10);
 const DEFAULT_IDENTITY_MAX_REFRESH: Duration = Duration::from_secs(60 * 60 * 24);
 
 const INBOUND_CONNECT_BASE: &str = "INBOUND_CONNECT";
 const OUTBOUND_CONNECT_BASE: &str = "OUTBOUND_CONNECT";
+const OUTBOUND_FAILURE_ACCRUAL_BASE: &str = "OUTBOUND_FAILURE_ACCRUAL";
 
 /// Load a `App` by reading ENV variables.
 pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError> {
@@ -332,8 +540,129 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
     );
 
     let inbound_max_in_flight = parse(strings, ENV_INBOUND_MAX_IN_FLIGHT, parse_number);
+    let inbound_priority_max_in_flight = parse(
+        strings,
+        ENV_INBOUND_PRIORITY_MAX_IN_FLIGHT,
+        parse_number::<usize>,
+    );
+    let inbound_priority_max_queued = parse(
+        strings,
+        ENV_INBOUND_PRIORITY_MAX_QUEUED,
+        parse_number::<usize>,
+    );
+    let inbound_priority_trust_header =
+        parse(strings, ENV_INBOUND_PRIORITY_TRUST_HEADER, parse_bool);
     let outbound_max_in_flight = parse(strings, ENV_OUTBOUND_MAX_IN_FLIGHT, parse_number);
 
+    let outbound_failure_accrual_consecutive_failures = parse(
+        strings,
+        ENV_OUTBOUND_FAILURE_ACCRUAL_CONSECUTIVE_FAILURES,
+        parse_number::<usize>,
+    );
+    let outbound_failure_accrual_max_ejected_ratio = parse(
+        strings,
+        ENV_OUTBOUND_FAILURE_ACCRUAL_MAX_EJECTED_RATIO,
+        parse_number::<f64>,
+    );
+    let outbound_local_zone = strings.get(ENV_OUTBOUND_LOCAL_ZONE);
+    let outbound_local_zone_min_ready = parse(
+        strings,
+        ENV_OUTBOUND_LOCAL_ZONE_MIN_READY,
+        parse_number::<f64>,
+    );
+    let outbound_slow_start_window = parse(strings, ENV_OUTBOUND_SLOW_START_WINDOW, parse_duration);
+    let outbound_slow_start_min_weight = parse(
+        strings,
+        ENV_OUTBOUND_SLOW_START_MIN_WEIGHT,
+        parse_number::<f64>,
+    );
+    let outbound_slow_start_aggression = parse(
+        strings,
+        ENV_OUTBOUND_SLOW_START_AGGRESSION,
+        parse_number::<f64>,
+    );
+    let outbound_split_failover_max_failure_rate = parse(
+        strings,
+        ENV_OUTBOUND_SPLIT_FAILOVER_MAX_FAILURE_RATE,
+        parse_number::<f64>,
+    );
+    let outbound_split_failover_min_requests = parse(
+        strings,
+        ENV_OUTBOUND_SPLIT_FAILOVER_MIN_REQUESTS,
+        parse_number::<u32>,
+    );
+    let outbound_split_failover_window =
+        parse(strings, ENV_OUTBOUND_SPLIT_FAILOVER_WINDOW, parse_duration);
+    let outbound_split_failover_cooldown = parse(
+        strings,
+        ENV_OUTBOUND_SPLIT_FAILOVER_COOLDOWN,
+        parse_duration,
+    );
+    let outbound_split_hash_key = parse(strings, ENV_OUTBOUND_SPLIT_HASH_KEY, parse_hash_key);
+    let outbound_retryable_errors = parse(
+        strings,
+        ENV_OUTBOUND_RETRYABLE_ERRORS,
+        parse_retryable_errors,
+    );
+    let outbound_max_buffered_bytes = parse(
+        strings,
+        ENV_OUTBOUND_MAX_BUFFERED_BYTES,
+        parse_number::<usize>,
+    );
+    let outbound_retry_spill_dir = parse(strings, ENV_OUTBOUND_RETRY_SPILL_DIR, |s| {
+        Ok(PathBuf::from(s))
+    });
+    let outbound_retry_spill_memory_limit = parse(
+        strings,
+        ENV_OUTBOUND_RETRY_SPILL_MEMORY_LIMIT,
+        parse_number::<usize>,
+    );
+    let outbound_adaptive_concurrency_initial_limit = parse(
+        strings,
+        ENV_OUTBOUND_ADAPTIVE_CONCURRENCY_INITIAL_LIMIT,
+        parse_number::<usize>,
+    );
+    let outbound_adaptive_concurrency_min_limit = parse(
+        strings,
+        ENV_OUTBOUND_ADAPTIVE_CONCURRENCY_MIN_LIMIT,
+        parse_number::<usize>,
+    );
+    let outbound_adaptive_concurrency_max_limit = parse(
+        strings,
+        ENV_OUTBOUND_ADAPTIVE_CONCURRENCY_MAX_LIMIT,
+        parse_number::<usize>,
+    );
+    let outbound_adaptive_concurrency_tolerance = parse(
+        strings,
+        ENV_OUTBOUND_ADAPTIVE_CONCURRENCY_TOLERANCE,
+        parse_number::<f64>,
+    );
+    let outbound_adaptive_concurrency_backoff_ratio = parse(
+        strings,
+        ENV_OUTBOUND_ADAPTIVE_CONCURRENCY_BACKOFF_RATIO,
+        parse_number::<f64>,
+    );
+    let outbound_health_check = parse(strings, ENV_OUTBOUND_HEALTH_CHECK, parse_bool);
+    let outbound_health_check_interval =
+        parse(strings, ENV_OUTBOUND_HEALTH_CHECK_INTERVAL, parse_duration);
+    let outbound_health_check_timeout =
+        parse(strings, ENV_OUTBOUND_HEALTH_CHECK_TIMEOUT, parse_duration);
+    let outbound_health_check_unhealthy_threshold = parse(
+        strings,
+        ENV_OUTBOUND_HEALTH_CHECK_UNHEALTHY_THRESHOLD,
+        parse_number::<NonZeroU32>,
+    );
+    let outbound_health_check_healthy_threshold = parse(
+        strings,
+        ENV_OUTBOUND_HEALTH_CHECK_HEALTHY_THRESHOLD,
+        parse_number::<NonZeroU32>,
+    );
+    let outbound_health_check_max_unhealthy_ratio = parse(
+        strings,
+        ENV_OUTBOUND_HEALTH_CHECK_MAX_UNHEALTHY_RATIO,
+        parse_number::<f64>,
+    );
+
     let metrics_retain_idle = parse(strings, ENV_METRICS_RETAIN_IDLE, parse_duration);
 
     // DNS
@@ -355,6 +684,23 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
 
     let dst_addr = parse_control_addr(strings, ENV_DESTINATION_SVC_BASE);
     let dst_token = strings.get(ENV_DESTINATION_CONTEXT);
+    let discovery_file = parse(strings, ENV_DISCOVERY_FILE, |s| Ok(PathBuf::from(s)));
+    let discovery_file_poll_interval =
+        parse(strings, ENV_DISCOVERY_FILE_POLL_INTERVAL, parse_duration);
+    let discovery_snapshot = parse(strings, ENV_DISCOVERY_SNAPSHOT, |s| Ok(PathBuf::from(s)));
+    let discovery_snapshot_interval =
+        parse(strings, ENV_DISCOVERY_SNAPSHOT_INTERVAL, parse_duration);
+    let discovery_snapshot_max_age = parse(strings, ENV_DISCOVERY_SNAPSHOT_MAX_AGE, parse_duration);
+    let xds_addr = parse_control_addr(strings, ENV_XDS_SVC_BASE);
+    let xds_node_id = strings.get(ENV_XDS_NODE_ID);
+    let xds_node_cluster = strings.get(ENV_XDS_NODE_CLUSTER);
+    let xds_route_configs = parse(strings, ENV_XDS_ROUTE_CONFIGS, |s| {
+        Ok(s.split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect::<Vec<_>>())
+    });
     let dst_profile_idle_timeout = parse(
         strings,
         ENV_DESTINATION_PROFILE_INITIAL_TIMEOUT,
@@ -414,6 +760,17 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
         )?
         .unwrap_or(ingress_mode);
 
+        let dns_fallback = parse(strings, ENV_OUTBOUND_DNS_FALLBACK, parse_bool)?.unwrap_or(false);
+
+        let split_hash_key = outbound_split_hash_key?;
+        if split_hash_key == Some(HashKey::ClientAddr) && !ingress_mode {
+            error!(
+                "{}=client-addr requires {}",
+                ENV_OUTBOUND_SPLIT_HASH_KEY, ENV_INGRESS_MODE
+            );
+            return Err(EnvError::InvalidEnvVar);
+        }
+
         let addr = ListenAddr(
             outbound_listener_addr?
                 .unwrap_or_else(|| parse_socket_addr(DEFAULT_OUTBOUND_LISTEN_ADDR).unwrap()),
@@ -449,6 +806,197 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
         let dispatch_timeout =
             outbound_dispatch_timeout?.unwrap_or(DEFAULT_OUTBOUND_DISPATCH_TIMEOUT);
 
+        // Endpoints are only ejected when a number of consecutive failures is
+        // configured.
+        let http_failure_accrual = {
+            let backoff = parse_backoff(
+                strings,
+                OUTBOUND_FAILURE_ACCRUAL_BASE,
+                DEFAULT_OUTBOUND_FAILURE_ACCRUAL_BACKOFF,
+            )?;
+            let max_ejected_ratio = outbound_failure_accrual_max_ejected_ratio?
+                .unwrap_or(DEFAULT_OUTBOUND_FAILURE_ACCRUAL_MAX_EJECTED_RATIO);
+            if !(max_ejected_ratio > 0.0 && max_ejected_ratio <= 1.0) {
+                error!(
+                    "{} must be in (0, 1]",
+                    ENV_OUTBOUND_FAILURE_ACCRUAL_MAX_EJECTED_RATIO
+                );
+                return Err(EnvError::InvalidEnvVar);
+            }
+            outbound_failure_accrual_consecutive_failures?
+                .and_then(NonZeroUsize::new)
+                .map(|max_failures| ConsecutiveFailures {
+                    max_failures,
+                    backoff,
+                    max_ejected_ratio,
+                })
+        };
+
+        // Balancers only prefer local endpoints when the proxy's zone is
+        // known.
+        let prefer_local_zone = {
+            let min_ready =
+                outbound_local_zone_min_ready?.unwrap_or(DEFAULT_OUTBOUND_LOCAL_ZONE_MIN_READY);
+            if !(0.0..=1.0).contains(&min_ready) {
+                error!("{} must be in [0, 1]", ENV_OUTBOUND_LOCAL_ZONE_MIN_READY);
+                return Err(EnvError::InvalidEnvVar);
+            }
+            outbound_local_zone?
+                .filter(|zone| !zone.is_empty())
+                .map(|zone| PreferLocalZone {
+                    zone: zone.into(),
+                    min_ready,
+                })
+        };
+
+        // Endpoints are only slowly started when a window is configured.
+        let slow_start = {
+            let min_weight =
+                outbound_slow_start_min_weight?.unwrap_or(DEFAULT_OUTBOUND_SLOW_START_MIN_WEIGHT);
+            if !(min_weight > 0.0 && min_weight <= 1.0) {
+                error!("{} must be in (0, 1]", ENV_OUTBOUND_SLOW_START_MIN_WEIGHT);
+                return Err(EnvError::InvalidEnvVar);
+            }
+            let aggression =
+                outbound_slow_start_aggression?.unwrap_or(DEFAULT_OUTBOUND_SLOW_START_AGGRESSION);
+            if !aggression.is_finite() || aggression <= 0.0 {
+                error!("{} must be positive", ENV_OUTBOUND_SLOW_START_AGGRESSION);
+                return Err(EnvError::InvalidEnvVar);
+            }
+            outbound_slow_start_window?
+                .filter(|w| *w > Duration::ZERO)
+                .map(|window| SlowStart {
+                    window,
+                    min_weight,
+                    aggression,
+                })
+        };
+
+        // Splits only fail over from tiers that fail too many requests when a
+        // maximum failure rate is configured.
+        let split_failover = {
+            let min_requests = outbound_split_failover_min_requests?
+                .unwrap_or(DEFAULT_OUTBOUND_SPLIT_FAILOVER_MIN_REQUESTS);
+            let window =
+                outbound_split_failover_window?.unwrap_or(DEFAULT_OUTBOUND_SPLIT_FAILOVER_WINDOW);
+            let cooldown = outbound_split_failover_cooldown?
+                .unwrap_or(DEFAULT_OUTBOUND_SPLIT_FAILOVER_COOLDOWN);
+            match outbound_split_failover_max_failure_rate? {
+                Some(rate) if !(0.0..=1.0).contains(&rate) => {
+                    error!(
+                        "{} must be in [0, 1]",
+                        ENV_OUTBOUND_SPLIT_FAILOVER_MAX_FAILURE_RATE
+                    );
+                    return Err(EnvError::InvalidEnvVar);
+                }
+                rate => rate.map(|max_failure_rate| Failover {
+                    max_failure_rate,
+                    min_requests,
+                    window,
+                    cooldown,
+                }),
+            }
+        };
+
+        let max_buffered_bytes =
+            outbound_max_buffered_bytes?.unwrap_or(DEFAULT_OUTBOUND_MAX_BUFFERED_BYTES);
+
+        // Request bodies are only spilled to disk when a directory is configured.
+        let retry_spill = {
+            let memory_limit = outbound_retry_spill_memory_limit?
+                .unwrap_or(DEFAULT_OUTBOUND_RETRY_SPILL_MEMORY_LIMIT);
+            match outbound_retry_spill_dir? {
+                // Bodies would never be spilled if they were capped before
+                // they exceeded the memory limit.
+                Some(_) if memory_limit >= max_buffered_bytes => {
+                    error!(
+                        "{} must be less than {}",
+                        ENV_OUTBOUND_RETRY_SPILL_MEMORY_LIMIT, ENV_OUTBOUND_MAX_BUFFERED_BYTES
+                    );
+                    return Err(EnvError::InvalidEnvVar);
+                }
+                dir => dir.map(|dir| outbound::http::RetrySpill::new(dir, memory_limit)),
+            }
+        };
+
+        // Destinations are only adaptively limited when an initial limit is
+        // configured.
+        let adaptive_concurrency = {
+            let min_limit = outbound_adaptive_concurrency_min_limit?
+                .unwrap_or(DEFAULT_OUTBOUND_ADAPTIVE_CONCURRENCY_MIN_LIMIT);
+            let max_limit = outbound_adaptive_concurrency_max_limit?
+                .unwrap_or(DEFAULT_OUTBOUND_ADAPTIVE_CONCURRENCY_MAX_LIMIT);
+            if min_limit == 0 || min_limit > max_limit {
+                error!(
+                    "{} must be positive and no greater than {}",
+                    ENV_OUTBOUND_ADAPTIVE_CONCURRENCY_MIN_LIMIT,
+                    ENV_OUTBOUND_ADAPTIVE_CONCURRENCY_MAX_LIMIT
+                );
+                return Err(EnvError::InvalidEnvVar);
+            }
+            let tolerance = outbound_adaptive_concurrency_tolerance?
+                .unwrap_or(DEFAULT_OUTBOUND_ADAPTIVE_CONCURRENCY_TOLERANCE);
+            if !tolerance.is_finite() || tolerance <= 1.0 {
+                error!(
+                    "{} must be greater than 1",
+                    ENV_OUTBOUND_ADAPTIVE_CONCURRENCY_TOLERANCE
+                );
+                return Err(EnvError::InvalidEnvVar);
+            }
+            let backoff_ratio = outbound_adaptive_concurrency_backoff_ratio?
+                .unwrap_or(DEFAULT_OUTBOUND_ADAPTIVE_CONCURRENCY_BACKOFF_RATIO);
+            if !(backoff_ratio > 0.0 && backoff_ratio < 1.0) {
+                error!(
+                    "{} must be in (0, 1)",
+                    ENV_OUTBOUND_ADAPTIVE_CONCURRENCY_BACKOFF_RATIO
+                );
+                return Err(EnvError::InvalidEnvVar);
+            }
+            outbound_adaptive_concurrency_initial_limit?.map(|initial_limit| {
+                outbound::http::AdaptiveConcurrency {
+                    initial_limit: initial_limit.clamp(min_limit, max_limit),
+                    min_limit,
+                    max_limit,
+                    tolerance,
+                    backoff_ratio,
+                }
+            })
+        };
+
+        // Endpoints are only actively checked when enabled.
+        let endpoint_health_check = {
+            let interval =
+                outbound_health_check_interval?.unwrap_or(DEFAULT_OUTBOUND_HEALTH_CHECK_INTERVAL);
+            let timeout =
+                outbound_health_check_timeout?.unwrap_or(DEFAULT_OUTBOUND_HEALTH_CHECK_TIMEOUT);
+            let unhealthy_threshold =
+                outbound_health_check_unhealthy_threshold?.unwrap_or_else(|| {
+                    NonZeroU32::new(DEFAULT_OUTBOUND_HEALTH_CHECK_UNHEALTHY_THRESHOLD).unwrap()
+                });
+            let healthy_threshold = outbound_health_check_healthy_threshold?.unwrap_or_else(|| {
+                NonZeroU32::new(DEFAULT_OUTBOUND_HEALTH_CHECK_HEALTHY_THRESHOLD).unwrap()
+            });
+            let max_unhealthy_ratio = outbound_health_check_max_unhealthy_ratio?
+                .unwrap_or(DEFAULT_OUTBOUND_HEALTH_CHECK_MAX_UNHEALTHY_RATIO);
+            if !(max_unhealthy_ratio > 0.0 && max_unhealthy_ratio <= 1.0) {
+                error!(
+                    "{} must be in (0, 1]",
+                    ENV_OUTBOUND_HEALTH_CHECK_MAX_UNHEALTHY_RATIO
+                );
+                return Err(EnvError::InvalidEnvVar);
+            }
+            outbound_health_check?
+                .unwrap_or(false)
+                .then(|| health::Config {
+                    request_probes: true,
+                    interval,
+                    timeout,
+                    unhealthy_threshold,
+                    healthy_threshold,
+                    max_unhealthy_ratio,
+                })
+        };
+
         outbound::Config {
             ingress_mode,
             emit_headers: !disable_headers,
@@ -464,6 +1012,17 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
                 detect_protocol_timeout,
             },
             inbound_ips: inbound_ips.clone(),
+            http_failure_accrual,
+            prefer_local_zone,
+            slow_start,
+            split_failover,
+            split_hash_key,
+            retryable_errors: outbound_retryable_errors?.unwrap_or_default(),
+            max_buffered_bytes,
+            retry_spill,
+            adaptive_concurrency,
+            endpoint_health_check,
+            dns_fallback,
         }
     };
 
@@ -510,6 +1069,23 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
         let dispatch_timeout =
             inbound_dispatch_timeout?.unwrap_or(DEFAULT_INBOUND_DISPATCH_TIMEOUT);
 
+        // Requests are only shed by priority when a limit is configured. Queued
+        // requests wait no longer than the dispatch timeout.
+        let priority_shedding = match inbound_priority_max_in_flight? {
+            Some(0) => {
+                error!("{} must be positive", ENV_INBOUND_PRIORITY_MAX_IN_FLIGHT);
+                return Err(EnvError::InvalidEnvVar);
+            }
+            Some(max_in_flight) => Some(inbound::policy::PriorityShedding {
+                max_in_flight,
+                max_queued: inbound_priority_max_queued?
+                    .unwrap_or(DEFAULT_INBOUND_PRIORITY_MAX_QUEUED),
+                queue_timeout: dispatch_timeout,
+                trust_priority_header: inbound_priority_trust_header?.unwrap_or(false),
+            }),
+            None => None,
+        };
+
         // Ensure that connections that directly target the inbound port are secured (unless
         // identity is disabled).
         let policy = {
@@ -588,6 +1164,8 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
                         workload,
                         control,
                         cache_max_idle_age,
+                        initial: HashMap::new(),
+                        updates: None,
                     }
                 }
 
@@ -692,23 +1270,58 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
             profile_idle_timeout: dst_profile_idle_timeout?
                 .unwrap_or(DEFAULT_DESTINATION_PROFILE_IDLE_TIMEOUT),
             allowed_ips: inbound_ips.into(),
+            priority_shedding,
         }
     };
 
-    let dst = {
+    let dst = if let Some(path) = discovery_file? {
+        super::dst::Config::File(super::file::Config {
+            path,
+            poll_interval: discovery_file_poll_interval?
+                .unwrap_or(DEFAULT_DISCOVERY_FILE_POLL_INTERVAL),
+        })
+    } else if let Some(addr) = xds_addr? {
+        let connect = if addr.addr.is_loopback() {
+            inbound.proxy.connect.clone()
+        } else {
+            outbound.proxy.connect.clone()
+        };
+        super::dst::Config::Xds(super::xds::Config {
+            control: ControlConfig {
+                addr,
+                connect,
+                buffer_capacity,
+            },
+            node_id: match xds_node_id? {
+                Some(id) => id,
+                None => strings.get(ENV_HOSTNAME)?.unwrap_or_default(),
+            },
+            node_cluster: xds_node_cluster?.unwrap_or_default(),
+            route_configs: xds_route_configs?.unwrap_or_default(),
+        })
+    } else {
         let addr = dst_addr?.ok_or(EnvError::NoDestinationAddress)?;
         let connect = if addr.addr.is_loopback() {
             inbound.proxy.connect.clone()
         } else {
             outbound.proxy.connect.clone()
         };
-        super::dst::Config {
+        let snapshot_interval =
+            discovery_snapshot_interval?.unwrap_or(DEFAULT_DISCOVERY_SNAPSHOT_INTERVAL);
+        let snapshot_max_age =
+            discovery_snapshot_max_age?.unwrap_or(DEFAULT_DISCOVERY_SNAPSHOT_MAX_AGE);
+        super::dst::Config::Control {
             context: dst_token?.unwrap_or_default(),
             control: ControlConfig {
                 addr,
                 connect,
                 buffer_capacity,
             },
+            snapshot: discovery_snapshot?.map(|path| super::snapshot::Config {
+                path,
+                interval: snapshot_interval,
+                max_age: snapshot_max_age,
+            }),
         }
     };
 
@@ -906,6 +1519,31 @@ fn parse_duration(s: &str) -> Result<Duration, ParseError> {
     }
 }
 
+fn parse_hash_key(s: &str) -> Result<HashKey, ParseError> {
+    match s.split_once(':') {
+        None if s == "client-addr" => Ok(HashKey::ClientAddr),
+        Some(("header", name)) => name
+            .parse()
+            .map(HashKey::Header)
+            .map_err(|_| ParseError::InvalidHashKey(s.to_string())),
+        Some(("cookie", name)) if !name.is_empty() => Ok(HashKey::Cookie(name.to_string())),
+        _ => Err(ParseError::InvalidHashKey(s.to_string())),
+    }
+}
+
+fn parse_retryable_errors(s: &str) -> Result<outbound::http::RetryableErrors, ParseError> {
+    let mut errors = outbound::http::RetryableErrors::default();
+    for kind in s.split(',').map(str::trim).filter(|k| !k.is_empty()) {
+        match kind {
+            "connect" => errors.connect = true,
+            "reset" => errors.reset = true,
+            "fail-fast" => errors.fail_fast = true,
+            _ => return Err(ParseError::InvalidRetryableError(kind.to_string())),
+        }
+    }
+    Ok(errors)
+}
+
 fn parse_socket_addr(s: &str) -> Result<SocketAddr, ParseError> {
     match parse_addr(s)? {
         Addr::Socket(a) => Ok(a),
@@ -1225,6 +1863,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_hash_keys() {
+        assert_eq!(
+            parse_hash_key("header:x-user-id").unwrap(),
+            HashKey::Header(http::header::HeaderName::from_static("x-user-id"))
+        );
+        assert_eq!(
+            parse_hash_key("cookie:session").unwrap(),
+            HashKey::Cookie("session".to_string())
+        );
+        assert_eq!(parse_hash_key("client-addr").unwrap(), HashKey::ClientAddr);
+        assert!(parse_hash_key("header:").is_err());
+        assert!(parse_hash_key("cookie:").is_err());
+        assert!(parse_hash_key("path").is_err());
+    }
+
+    #[test]
+    fn parse_retryable_error_kinds() {
+        assert_eq!(
+            parse_retryable_errors("connect, fail-fast").unwrap(),
+            outbound::http::RetryableErrors {
+                connect: true,
+                reset: false,
+                fail_fast: true,
+            }
+        );
+        assert_eq!(
+            parse_retryable_errors("").unwrap(),
+            outbound::http::RetryableErrors::default()
+        );
+        assert!(parse_retryable_errors("connect,timeout").is_err());
+    }
+
     #[test]
     fn parse_duration_unit_ms() {
         test_unit("ms", Duration::from_millis);
@@ -0,0 +1,512 @@
+//! Discovery from a local file, for proxies that run without a control plane.
+//!
+//! The file describes destinations, service profiles, and inbound server
+//! policies as JSON or YAML (see [`convert`] for its structure). It is polled
+//! for changes; when a change is valid, the watches of the profiles, endpoints,
+//! and policies that changed are updated. Invalid changes are logged and
+//! ignored so that the last valid configuration continues to be served.
+
+mod convert;
+
+pub use self::convert::InvalidFile;
+use futures::prelude::*;
+use linkerd_app_core::{
+    profiles::{self, LogicalAddr, LookupAddr},
+    proxy::{
+        api_resolve::{ConcreteAddr, Metadata},
+        core::Update,
+    },
+    svc::Param,
+    Addr, Error, NameAddr,
+};
+use linkerd_app_inbound::policy::{PortsRx, ServerPolicy};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::sync::watch;
+use tokio_stream::wrappers::WatchStream;
+use tracing::{debug, info_span, warn, Instrument};
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub path: PathBuf,
+    pub poll_interval: Duration,
+}
+
+/// The format of a discovery file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Yaml,
+}
+
+/// Handles to the file's contents.
+pub struct File {
+    pub path: PathBuf,
+
+    /// Resolves profiles for the file's destinations and endpoints.
+    pub profiles: Profiles,
+
+    /// Resolves the file's destinations.
+    pub resolve: Resolve,
+
+    /// Watches the file's inbound server policies.
+    pub policies: PortsRx,
+}
+
+/// Resolves endpoints from the file.
+#[derive(Clone, Debug)]
+pub struct Resolve(pub(crate) watch::Receiver<Arc<Snapshot>>);
+
+/// Resolves profiles from the file.
+#[derive(Clone, Debug)]
+pub struct Profiles(pub(crate) watch::Receiver<Arc<Snapshot>>);
+
+pub type Resolution = Pin<Box<dyn Stream<Item = Result<Update<Metadata>, Error>> + Send + 'static>>;
+
+/// The contents of a discovery file.
+#[derive(Clone, Debug, Default)]
+pub struct Snapshot {
+    pub(crate) destinations: HashMap<NameAddr, Vec<(SocketAddr, Metadata)>>,
+    pub(crate) profiles: HashMap<Addr, profiles::Profile>,
+    pub(crate) policies: Arc<HashMap<u16, ServerPolicy>>,
+
+    /// The file's description of each profile, so that profiles are only
+    /// updated when their descriptions change. Converted profiles can't be
+    /// compared, since their routes are compared by identity.
+    pub(crate) profile_sources: HashMap<Addr, serde_json::Value>,
+}
+
+// === impl Config ===
+
+impl Config {
+    /// Reads the file and spawns a task that reloads it as it changes.
+    ///
+    /// Fails if the file cannot be read or is invalid.
+    pub fn build(self) -> Result<File, Error> {
+        let format = Format::from_path(&self.path);
+        let contents = std::fs::read(&self.path)?;
+        let snapshot = Snapshot::decode(&contents, format)?;
+        debug!(
+            destinations = snapshot.destinations.len(),
+            profiles = snapshot.profiles.len(),
+            policies = snapshot.policies.len(),
+            "Loaded"
+        );
+
+        let (policies_tx, policies) = watch::channel(snapshot.policies.clone());
+        let (snapshot_tx, snapshot) = watch::channel(Arc::new(snapshot));
+        tokio::spawn(
+            reload(
+                self.path.clone(),
+                format,
+                self.poll_interval,
+                contents,
+                snapshot_tx,
+                policies_tx,
+            )
+            .instrument(info_span!("file", path = %self.path.display())),
+        );
+
+        Ok(File {
+            path: self.path,
+            profiles: Profiles(snapshot.clone()),
+            resolve: Resolve(snapshot),
+            policies,
+        })
+    }
+}
+
+async fn reload(
+    path: PathBuf,
+    format: Format,
+    poll_interval: Duration,
+    mut contents: Vec<u8>,
+    snapshots: watch::Sender<Arc<Snapshot>>,
+    policies: watch::Sender<Arc<HashMap<u16, ServerPolicy>>>,
+) {
+    let mut interval = tokio::time::interval(poll_interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+
+        let updated = match tokio::fs::read(&path).await {
+            Ok(updated) => updated,
+            Err(error) => {
+                warn!(%error, "Failed to read file");
+                continue;
+            }
+        };
+        if updated == contents {
+            continue;
+        }
+        contents = updated;
+
+        let snapshot = match Snapshot::decode(&contents, format) {
+            Ok(snapshot) => snapshot,
+            Err(error) => {
+                warn!(%error, "Ignoring invalid file");
+                continue;
+            }
+        };
+        debug!(
+            destinations = snapshot.destinations.len(),
+            profiles = snapshot.profiles.len(),
+            policies = snapshot.policies.len(),
+            "Reloaded"
+        );
+
+        if *policies.borrow() != snapshot.policies {
+            let _ = policies.send(snapshot.policies.clone());
+        }
+        if snapshots.send(Arc::new(snapshot)).is_err() && policies.is_closed() {
+            return;
+        }
+    }
+}
+
+// === impl Format ===
+
+impl Format {
+    /// Files with a `.yaml` or `.yml` extension are YAML; all others are JSON.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => Self::Yaml,
+            _ => Self::Json,
+        }
+    }
+}
+
+// === impl Snapshot ===
+
+impl Snapshot {
+    pub(crate) fn decode(bytes: &[u8], format: Format) -> Result<Self, InvalidFile> {
+        convert::snapshot(bytes, format)
+    }
+
+    /// Encodes the snapshot as a discovery file.
+    ///
+    /// Not all discovery state can be described by the file, so the encoding
+    /// is lossy (see [`convert::encode`]).
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        convert::encode(self).to_string().into_bytes()
+    }
+
+    pub(crate) fn endpoints(&self, addr: &NameAddr) -> Option<&Vec<(SocketAddr, Metadata)>> {
+        self.destinations.get(addr)
+    }
+
+    /// Returns whether both snapshots describe the same profile for a lookup.
+    ///
+    /// Profiles without a description (e.g. those that were not read from a
+    /// file) are never known to be the same.
+    fn same_profile(&self, other: &Self, addr: &Addr) -> bool {
+        match (
+            self.profiles.contains_key(addr),
+            other.profiles.contains_key(addr),
+        ) {
+            (true, true) => matches!(
+                (self.profile_sources.get(addr), other.profile_sources.get(addr)),
+                (Some(a), Some(b)) if a == b
+            ),
+            (false, false) => match addr {
+                Addr::Name(name) => {
+                    self.destinations.contains_key(name) == other.destinations.contains_key(name)
+                }
+                Addr::Socket(sa) => self.endpoint(sa) == other.endpoint(sa),
+            },
+            _ => false,
+        }
+    }
+
+    fn endpoint(&self, addr: &SocketAddr) -> Option<&(SocketAddr, Metadata)> {
+        self.destinations
+            .values()
+            .flatten()
+            .find(|(ep, _)| ep == addr)
+    }
+
+    /// Returns the profile for a lookup, if the file describes the address.
+    ///
+    /// Addresses are described by an explicit profile. Otherwise, names are
+    /// described by a destination, and socket addresses are described by any
+    /// destination endpoint with the same address, so that the endpoint's
+    /// metadata is used.
+    pub(crate) fn profile(&self, addr: &Addr) -> Option<profiles::Profile> {
+        if let Some(profile) = self.profiles.get(addr) {
+            return Some(profile.clone());
+        }
+        match addr {
+            Addr::Name(name) => self
+                .destinations
+                .contains_key(name)
+                .then(|| profiles::Profile {
+                    addr: Some(LogicalAddr(name.clone())),
+                    ..Default::default()
+                }),
+            Addr::Socket(sa) => self.endpoint(sa).map(|ep| profiles::Profile {
+                endpoint: Some(ep.clone()),
+                ..Default::default()
+            }),
+        }
+    }
+}
+
+// === impl Resolve ===
+
+impl<T: Param<ConcreteAddr>> tower::Service<T> for Resolve {
+    type Response = Resolution;
+    type Error = Error;
+    type Future = future::Ready<Result<Resolution, Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, target: T) -> Self::Future {
+        let ConcreteAddr(addr) = target.param();
+        let mut last = None;
+        let updates = WatchStream::new(self.0.clone()).filter_map(move |snapshot| {
+            // Only publish updates when the destination's endpoints change.
+            let eps = snapshot.endpoints(&addr).cloned();
+            let update = if last.as_ref() != Some(&eps) {
+                last = Some(eps.clone());
+                Some(Ok(match eps {
+                    Some(eps) => Update::Reset(eps),
+                    None => Update::DoesNotExist,
+                }))
+            } else {
+                None
+            };
+            future::ready(update)
+        });
+        future::ok(Box::pin(updates) as Resolution)
+    }
+}
+
+// === impl Profiles ===
+
+impl tower::Service<LookupAddr> for Profiles {
+    type Response = Option<profiles::Receiver>;
+    type Error = Error;
+    type Future = future::Ready<Result<Option<profiles::Receiver>, Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, LookupAddr(addr): LookupAddr) -> Self::Future {
+        let mut last = self.0.borrow().clone();
+        let profile = match last.profile(&addr) {
+            Some(profile) => profile,
+            None => return future::ok(None),
+        };
+
+        let (tx, rx) = watch::channel(profile);
+        let mut snapshots = self.0.clone();
+        tokio::spawn(
+            async move {
+                // The task completes when the file is no longer watched or
+                // when the profile is no longer used.
+                loop {
+                    tokio::select! {
+                        res = snapshots.changed() => {
+                            if res.is_err() {
+                                return;
+                            }
+                        }
+                        _ = tx.closed() => return,
+                    }
+                    let snapshot = snapshots.borrow().clone();
+                    // Rebuilding an unchanged profile would rebuild its
+                    // routes' stacks and reset their retry budgets.
+                    if snapshot.same_profile(&last, &addr) {
+                        continue;
+                    }
+                    let profile = snapshot.profile(&addr).unwrap_or_default();
+                    last = snapshot;
+                    if tx.send(profile).is_err() {
+                        return;
+                    }
+                }
+            }
+            .in_current_span(),
+        );
+        future::ok(Some(rx.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linkerd_app_core::svc::ServiceExt;
+    use std::str::FromStr;
+
+    const FILE: &str = r#"{
+        "destinations": {
+            "web.ns.svc.cluster.local:8080": [
+                { "addr": "10.1.1.1:8080", "protocol_hint": "h2", "labels": { "zone": "east" } }
+            ]
+        },
+        "profiles": {
+            "web.ns.svc.cluster.local:8080": {
+                "retry_budget": { "retry_ratio": 0.2 },
+                "routes": [{ "name": "get", "method": "GET", "retryable": true, "timeout_ms": 500 }],
+                "targets": [{ "addr": "web-v2.ns.svc.cluster.local:8080", "weight": 10 }]
+            }
+        },
+        "policies": {
+            "8080": { "protocol": "http2", "authorizations": [{ "networks": ["0.0.0.0/0"] }] }
+        }
+    }"#;
+
+    #[test]
+    fn converts_file() {
+        let snapshot =
+            convert::snapshot(FILE.as_bytes(), Format::Json).expect("file must be valid");
+
+        let web = NameAddr::from_str("web.ns.svc.cluster.local:8080").unwrap();
+        let eps = snapshot.endpoints(&web).expect("destination must exist");
+        assert_eq!(eps[0].0, SocketAddr::from(([10, 1, 1, 1], 8080)));
+        assert_eq!(eps[0].1.zone(), Some("east"));
+
+        let profile = snapshot
+            .profile(&Addr::Name(web.clone()))
+            .expect("profile must exist");
+        assert_eq!(profile.addr, Some(LogicalAddr(web)));
+        assert_eq!(profile.http_routes.len(), 1);
+        let (_, route) = &profile.http_routes[0];
+        assert!(route.retries().is_some());
+        assert_eq!(route.timeout(), Some(Duration::from_millis(500)));
+        assert_eq!(profile.targets[0].weight, 10);
+
+        let profile = snapshot
+            .profile(&Addr::Socket(([10, 1, 1, 1], 8080).into()))
+            .expect("endpoint profile must exist");
+        assert!(profile.endpoint.is_some());
+
+        let policy = snapshot.policies.get(&8080).expect("policy must exist");
+        assert_eq!(
+            policy.protocol,
+            linkerd_app_inbound::policy::Protocol::Http2
+        );
+        assert_eq!(policy.authorizations.len(), 1);
+    }
+
+    #[test]
+    fn converts_yaml_file() {
+        const YAML: &str = r#"
+destinations:
+  web.ns.svc.cluster.local:8080:
+    - addr: 10.1.1.1:8080
+      protocol_hint: h2
+      labels: { zone: east }
+profiles:
+  web.ns.svc.cluster.local:8080:
+    routes:
+      - { name: get, method: GET, timeout_ms: 500 }
+policies:
+  "8080":
+    protocol: http2
+    authorizations:
+      - networks: [0.0.0.0/0]
+"#;
+        let snapshot = Snapshot::decode(YAML.as_bytes(), Format::Yaml).expect("file must be valid");
+
+        let web = NameAddr::from_str("web.ns.svc.cluster.local:8080").unwrap();
+        let eps = snapshot.endpoints(&web).expect("destination must exist");
+        assert_eq!(eps[0].0, SocketAddr::from(([10, 1, 1, 1], 8080)));
+        assert_eq!(eps[0].1.zone(), Some("east"));
+
+        let profile = snapshot
+            .profile(&Addr::Name(web))
+            .expect("profile must exist");
+        let (_, route) = &profile.http_routes[0];
+        assert_eq!(route.timeout(), Some(Duration::from_millis(500)));
+
+        assert!(snapshot.policies.contains_key(&8080));
+        assert_eq!(
+            Format::from_path("/etc/linkerd/discovery.yml".as_ref()),
+            Format::Yaml
+        );
+        assert_eq!(
+            Format::from_path("/etc/linkerd/discovery.json".as_ref()),
+            Format::Json
+        );
+    }
+
+    #[tokio::test]
+    async fn only_updates_changed_profiles() {
+        let decode =
+            |file: &str| Arc::new(Snapshot::decode(file.as_bytes(), Format::Json).unwrap());
+        let (tx, rx) = watch::channel(decode(FILE));
+        let web = Addr::from_str("web.ns.svc.cluster.local:8080").unwrap();
+        let mut profile: watch::Receiver<profiles::Profile> = Profiles(rx)
+            .oneshot(LookupAddr(web))
+            .await
+            .unwrap()
+            .expect("profile must exist")
+            .into();
+
+        // Reloading the file without changing the profile doesn't update it.
+        tx.send(decode(&FILE.replace("0.0.0.0/0", "10.0.0.0/8")))
+            .unwrap();
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), profile.changed())
+                .await
+                .is_err(),
+            "profile must not be updated"
+        );
+
+        tx.send(decode(&FILE.replace("500", "1000"))).unwrap();
+        profile.changed().await.expect("profile must be updated");
+        let profile = profile.borrow();
+        let (_, route) = &profile.http_routes[0];
+        assert_eq!(route.timeout(), Some(Duration::from_millis(1000)));
+    }
+
+    #[test]
+    fn encodes_file() {
+        let snapshot = Snapshot::decode(FILE.as_bytes(), Format::Json).expect("file must be valid");
+        let decoded =
+            Snapshot::decode(&snapshot.encode(), Format::Json).expect("encoded file must be valid");
+
+        let web = NameAddr::from_str("web.ns.svc.cluster.local:8080").unwrap();
+        assert_eq!(decoded.endpoints(&web), snapshot.endpoints(&web));
+
+        let profile = decoded
+            .profile(&Addr::Name(web.clone()))
+            .expect("profile must exist");
+        assert_eq!(profile.addr, Some(LogicalAddr(web)));
+        assert_eq!(profile.http_routes.len(), 1);
+        let (_, route) = &profile.http_routes[0];
+        assert!(route.retries().is_some());
+        assert_eq!(route.timeout(), Some(Duration::from_millis(500)));
+        assert_eq!(profile.targets[0].weight, 10);
+
+        assert_eq!(decoded.policies, snapshot.policies);
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        for file in [
+            r#"[]"#,
+            r#"{ "destinations": { "web:8080": [{ "addr": "nope" }] } }"#,
+            r#"{ "profiles": { "web:8080": { "routes": [{ "retryable": true }] } } }"#,
+            r#"{ "policies": { "http": {} } }"#,
+            r#"{ "policies": { "8080": { "protocol": "h3" } } }"#,
+        ] {
+            assert!(
+                convert::snapshot(file.as_bytes(), Format::Json).is_err(),
+                "{}",
+                file
+            );
+        }
+    }
+}
@@ -0,0 +1,840 @@
+//! Converts a JSON or YAML discovery file into the proxy's discovery types.
+//!
+//! The file's structure mirrors the control plane's APIs. As JSON:
+//!
+//! ```json
+//! {
+//!   "destinations": {
+//!     "web.default.svc.cluster.local:8080": [{
+//!       "addr": "10.1.1.1:8080",
+//!       "labels": { "zone": "east" },
+//!       "identity": "web.default.serviceaccount.identity.linkerd.cluster.local",
+//!       "protocol_hint": "h2",
+//!       "opaque_transport_port": 4143
+//!     }]
+//!   },
+//!   "profiles": {
+//!     "web.default.svc.cluster.local:8080": {
+//!       "retry_budget": { "retry_ratio": 0.2, "min_retries_per_second": 10, "ttl_secs": 10 },
+//!       "routes": [{
+//!         "name": "GET /api",
+//!         "method": "GET",
+//!         "path_regex": "/api/.*",
+//!         "timeout_ms": 1000,
+//!         "retryable": true,
+//!         "retry_timeout_ms": 250,
+//!         "retry_max_attempts": 3,
+//!         "retry_max_buffered_bytes": 131072,
+//!         "retry_backoff": { "min_ms": 25, "max_ms": 250, "jitter": 0.5 },
+//!         "hedge": { "percentile": 95 },
+//!         "failure_statuses": [{ "min": 500, "max": 599 }]
+//!       }],
+//!       "targets": [{ "addr": "web-v2.default.svc.cluster.local:8080", "weight": 100 }],
+//!       "opaque_protocol": false
+//!     },
+//!     "10.1.1.1:8080": {
+//!       "logical_addr": "web.default.svc.cluster.local:8080",
+//!       "endpoint": { "addr": "10.1.1.1:8080", "protocol_hint": "h2" }
+//!     }
+//!   },
+//!   "policies": {
+//!     "8080": {
+//!       "name": "web-http",
+//!       "protocol": "http1",
+//!       "authorizations": [{
+//!         "name": "all-authenticated",
+//!         "networks": ["0.0.0.0/0", "::/0"],
+//!         "authentication": { "identities": [], "suffixes": ["cluster.local"] }
+//!       }]
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! YAML files have the same structure.
+//!
+//! Profiles may be keyed by name or by socket address. A named profile's
+//! logical address defaults to its name. Targets may set a `priority` tier
+//! (0 by default); requests fail over to targets in higher tiers only when
+//! lower tiers are unavailable.
+
+use super::{Format, Snapshot};
+use linkerd_app_core::{
+    exp_backoff::ExponentialBackoff,
+    profiles::{self, LogicalAddr, Target},
+    proxy::api_resolve::{Metadata, ProtocolHint},
+    tls::client::ServerId,
+    Addr, IpNet, NameAddr,
+};
+use linkerd_app_inbound::policy::{
+    Authentication, Authorization, Meta, Protocol, ServerPolicy, Suffix,
+};
+use regex::Regex;
+use serde_json::{json, Map, Value};
+use std::{
+    collections::{BTreeSet, HashMap},
+    net::SocketAddr,
+    num::NonZeroU32,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+use thiserror::Error;
+use tower::retry::budget::Budget;
+
+#[derive(Debug, Error)]
+#[error("invalid {path}: {reason}")]
+pub struct InvalidFile {
+    path: String,
+    reason: String,
+}
+
+type Result<T> = std::result::Result<T, InvalidFile>;
+
+pub(super) fn snapshot(bytes: &[u8], format: Format) -> Result<Snapshot> {
+    let root = match format {
+        Format::Json => serde_json::from_slice::<Value>(bytes).map_err(|e| invalid("file", e))?,
+        Format::Yaml => serde_yaml::from_slice::<Value>(bytes).map_err(|e| invalid("file", e))?,
+    };
+    let root = object(&root, "file")?;
+
+    let mut destinations = HashMap::new();
+    for (name, eps) in optional_object(root, "destinations", "file")? {
+        let path = format!("destinations.{}", name);
+        let dst = name_addr(name, &path)?;
+        let eps = array(eps, &path)?
+            .iter()
+            .enumerate()
+            .map(|(i, ep)| endpoint(ep, &format!("{}[{}]", path, i)))
+            .collect::<Result<Vec<_>>>()?;
+        destinations.insert(dst, eps);
+    }
+
+    let mut profiles = HashMap::new();
+    let mut profile_sources = HashMap::new();
+    for (name, source) in optional_object(root, "profiles", "file")? {
+        let path = format!("profiles.{}", name);
+        let dst = Addr::from_str(name).map_err(|e| invalid(&path, e))?;
+        let profile = self::profile(&dst, source, &path)?;
+        profiles.insert(dst.clone(), profile);
+        profile_sources.insert(dst, source.clone());
+    }
+
+    let mut policies = HashMap::new();
+    for (port, policy) in optional_object(root, "policies", "file")? {
+        let path = format!("policies.{}", port);
+        let port = port
+            .parse::<u16>()
+            .map_err(|e| invalid(&path, format!("invalid port: {}", e)))?;
+        policies.insert(port, server_policy(policy, &path)?);
+    }
+
+    Ok(Snapshot {
+        destinations,
+        profiles,
+        policies: Arc::new(policies),
+        profile_sources,
+    })
+}
+
+/// Encodes a snapshot in the file's format.
+///
+/// Only what the format can describe is encoded. Profile routes are encoded
+/// when they match on a method and/or a path, without their response
+/// classes, and retryable routes share a default retry budget. Policy routes
+/// and rate limits are not encoded; and authorizations with network
+/// exceptions are omitted rather than broadened.
+pub(super) fn encode(snapshot: &Snapshot) -> Value {
+    let destinations = snapshot
+        .destinations
+        .iter()
+        .map(|(dst, eps)| {
+            let eps = eps.iter().map(|(addr, meta)| encode_endpoint(*addr, meta));
+            (dst.to_string(), eps.collect::<Value>())
+        })
+        .collect::<Map<_, _>>();
+
+    let profiles = snapshot
+        .profiles
+        .iter()
+        .map(|(dst, profile)| (dst.to_string(), encode_profile(dst, profile)))
+        .collect::<Map<_, _>>();
+
+    let policies = snapshot
+        .policies
+        .iter()
+        .map(|(port, policy)| (port.to_string(), encode_server_policy(policy)))
+        .collect::<Map<_, _>>();
+
+    json!({
+        "destinations": destinations,
+        "profiles": profiles,
+        "policies": policies,
+    })
+}
+
+// === destinations ===
+
+fn endpoint(ep: &Value, path: &str) -> Result<(SocketAddr, Metadata)> {
+    let ep = object(ep, path)?;
+    let addr = required_str(ep, "addr", path)?
+        .parse::<SocketAddr>()
+        .map_err(|e| invalid(&format!("{}.addr", path), e))?;
+
+    let labels = optional_object(ep, "labels", path)?
+        .map(|(k, v)| {
+            let v = string(v, &format!("{}.labels.{}", path, k))?;
+            Ok((k.clone(), v.to_string()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let protocol_hint = match optional_str(ep, "protocol_hint", path)? {
+        None | Some("unknown") => ProtocolHint::Unknown,
+        Some("h2") => ProtocolHint::Http2,
+        Some(hint) => {
+            return Err(invalid(
+                &format!("{}.protocol_hint", path),
+                format!("unknown protocol hint: {}", hint),
+            ))
+        }
+    };
+
+    let opaque_transport_port = optional_u64(ep, "opaque_transport_port", path)?
+        .map(|p| port(p, &format!("{}.opaque_transport_port", path)))
+        .transpose()?;
+
+    let identity = optional_str(ep, "identity", path)?
+        .map(|id| ServerId::from_str(id).map_err(|e| invalid(&format!("{}.identity", path), e)))
+        .transpose()?;
+
+    let authority_override = optional_str(ep, "authority_override", path)?
+        .map(|a| {
+            a.parse::<http::uri::Authority>()
+                .map_err(|e| invalid(&format!("{}.authority_override", path), e))
+        })
+        .transpose()?;
+
+    let meta = Metadata::new(
+        labels,
+        protocol_hint,
+        opaque_transport_port,
+        identity,
+        authority_override,
+    );
+    Ok((addr, meta))
+}
+
+fn encode_endpoint(addr: SocketAddr, meta: &Metadata) -> Value {
+    let mut ep = Map::new();
+    ep.insert("addr".to_string(), addr.to_string().into());
+
+    let labels = meta.labels();
+    if !labels.is_empty() {
+        let labels = labels
+            .iter()
+            .map(|(k, v)| (k.clone(), Value::from(v.clone())))
+            .collect::<Map<_, _>>();
+        ep.insert("labels".to_string(), labels.into());
+    }
+    if let Some(id) = meta.identity() {
+        ep.insert("identity".to_string(), id.to_string().into());
+    }
+    if meta.protocol_hint() == ProtocolHint::Http2 {
+        ep.insert("protocol_hint".to_string(), "h2".into());
+    }
+    if let Some(port) = meta.opaque_transport_port() {
+        ep.insert("opaque_transport_port".to_string(), port.into());
+    }
+    if let Some(authority) = meta.authority_override() {
+        ep.insert(
+            "authority_override".to_string(),
+            authority.to_string().into(),
+        );
+    }
+
+    ep.into()
+}
+
+// === profiles ===
+
+fn profile(dst: &Addr, profile: &Value, path: &str) -> Result<profiles::Profile> {
+    let profile = object(profile, path)?;
+
+    let addr = match optional_str(profile, "logical_addr", path)? {
+        Some(addr) => Some(name_addr(addr, &format!("{}.logical_addr", path))?),
+        None => dst.name_addr().cloned(),
+    };
+
+    let endpoint = profile
+        .get("endpoint")
+        .map(|ep| endpoint(ep, &format!("{}.endpoint", path)))
+        .transpose()?;
+
+    let retry_budget = match profile.get("retry_budget") {
+        Some(budget) => Some(retry_budget(budget, &format!("{}.retry_budget", path))?),
+        None => None,
+    };
+
+    let http_routes = optional_array(profile, "routes", path)?
+        .iter()
+        .enumerate()
+        .map(|(i, r)| route(r, retry_budget.as_ref(), &format!("{}.routes[{}]", path, i)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let targets = optional_array(profile, "targets", path)?
+        .iter()
+        .enumerate()
+        .map(|(i, t)| {
+            let path = format!("{}.targets[{}]", path, i);
+            let t = object(t, &path)?;
+            let addr = name_addr(required_str(t, "addr", &path)?, &format!("{}.addr", path))?;
+            let weight = optional_u64(t, "weight", &path)?.unwrap_or(1);
+            let weight = u32::try_from(weight)
+                .map_err(|_| invalid(&format!("{}.weight", path), "weight is too large"))?;
+            let priority = optional_u64(t, "priority", &path)?.unwrap_or(0);
+            let priority = u32::try_from(priority)
+                .map_err(|_| invalid(&format!("{}.priority", path), "priority is too large"))?;
+            Ok(Target {
+                addr,
+                weight,
+                priority,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(profiles::Profile {
+        addr: addr.map(LogicalAddr),
+        http_routes,
+        targets,
+        opaque_protocol: optional_bool(profile, "opaque_protocol", path)?.unwrap_or(false),
+        endpoint,
+        ..profiles::Profile::default()
+    })
+}
+
+fn route(
+    route: &Value,
+    retry_budget: Option<&Arc<Budget>>,
+    path: &str,
+) -> Result<(profiles::http::RequestMatch, profiles::http::Route)> {
+    let route = object(route, path)?;
+
+    let mut matches = Vec::new();
+    if let Some(method) = optional_str(route, "method", path)? {
+        let method =
+            http::Method::from_str(method).map_err(|e| invalid(&format!("{}.method", path), e))?;
+        matches.push(profiles::http::RequestMatch::Method(method));
+    }
+    if let Some(re) = optional_str(route, "path_regex", path)? {
+        // Anchor the expression so that it matches the entire path, as the
+        // destination API does.
+        let re = Regex::new(&format!("^{}$", re))
+            .map_err(|e| invalid(&format!("{}.path_regex", path), e))?;
+        matches.push(profiles::http::RequestMatch::Path(Box::new(re)));
+    }
+
+    let failures = optional_array(route, "failure_statuses", path)?
+        .iter()
+        .enumerate()
+        .map(|(i, range)| {
+            let path = format!("{}.failure_statuses[{}]", path, i);
+            let range = object(range, &path)?;
+            let status = |key: &str| {
+                let code = optional_u64(range, key, &path)?
+                    .ok_or_else(|| invalid(&path, format!("missing {}", key)))?;
+                u16::try_from(code)
+                    .ok()
+                    .and_then(|c| http::StatusCode::from_u16(c).ok())
+                    .ok_or_else(|| invalid(&format!("{}.{}", path, key), "invalid status"))
+            };
+            let match_ = profiles::http::ResponseMatch::Status {
+                min: status("min")?,
+                max: status("max")?,
+            };
+            Ok(profiles::http::ResponseClass::new(true, match_))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let labels =
+        optional_str(route, "name", path)?.map(|name| ("route".to_string(), name.to_string()));
+    let mut r = profiles::http::Route::new(labels.into_iter(), failures);
+
+    if optional_bool(route, "retryable", path)?.unwrap_or(false) {
+        let budget = retry_budget.ok_or_else(|| {
+            invalid(
+                &format!("{}.retryable", path),
+                "retries require a profile retry_budget",
+            )
+        })?;
+        let retries = r.set_retries(budget.clone());
+        if let Some(ms) = optional_u64(route, "retry_timeout_ms", path)? {
+            retries.set_per_try_timeout(Duration::from_millis(ms));
+        }
+        if let Some(n) = optional_u64(route, "retry_max_attempts", path)? {
+            let n = u32::try_from(n)
+                .ok()
+                .and_then(NonZeroU32::new)
+                .ok_or_else(|| {
+                    invalid(&format!("{}.retry_max_attempts", path), "must be positive")
+                })?;
+            retries.set_max_attempts(n);
+        }
+        if let Some(n) = optional_u64(route, "retry_max_buffered_bytes", path)? {
+            let n = usize::try_from(n).map_err(|_| {
+                invalid(
+                    &format!("{}.retry_max_buffered_bytes", path),
+                    "is too large",
+                )
+            })?;
+            retries.set_max_buffered_bytes(n);
+        }
+        if let Some(backoff) = route.get("retry_backoff") {
+            retries.set_backoff(retry_backoff(backoff, &format!("{}.retry_backoff", path))?);
+        }
+        if let Some(h) = route.get("hedge") {
+            retries.set_hedge(hedge(h, &format!("{}.hedge", path))?);
+        }
+    } else if [
+        "retry_timeout_ms",
+        "retry_max_attempts",
+        "retry_max_buffered_bytes",
+        "retry_backoff",
+        "hedge",
+    ]
+    .iter()
+    .any(|k| route.contains_key(*k))
+    {
+        return Err(invalid(path, "retry settings require a retryable route"));
+    }
+    if let Some(ms) = optional_u64(route, "timeout_ms", path)? {
+        r.set_timeout(Duration::from_millis(ms));
+    }
+
+    Ok((profiles::http::RequestMatch::All(matches), r))
+}
+
+fn encode_profile(dst: &Addr, profile: &profiles::Profile) -> Value {
+    let mut p = Map::new();
+
+    if let Some(LogicalAddr(addr)) = profile.addr.as_ref() {
+        if dst.name_addr() != Some(addr) {
+            p.insert("logical_addr".to_string(), addr.to_string().into());
+        }
+    }
+    if let Some((addr, meta)) = profile.endpoint.as_ref() {
+        p.insert("endpoint".to_string(), encode_endpoint(*addr, meta));
+    }
+
+    let routes = profile
+        .http_routes
+        .iter()
+        .filter_map(|(m, r)| encode_route(m, r))
+        .collect::<Vec<_>>();
+    if routes.iter().any(|r| r.get("retryable").is_some()) {
+        // Budgets cannot be inspected, so the default budget is used.
+        p.insert("retry_budget".to_string(), json!({}));
+    }
+    if !routes.is_empty() {
+        p.insert("routes".to_string(), routes.into());
+    }
+
+    if !profile.targets.is_empty() {
+        let targets = profile
+            .targets
+            .iter()
+            .map(|t| {
+                let mut target = json!({ "addr": t.addr.to_string(), "weight": t.weight });
+                if t.priority != 0 {
+                    target["priority"] = t.priority.into();
+                }
+                target
+            })
+            .collect::<Value>();
+        p.insert("targets".to_string(), targets);
+    }
+    if profile.opaque_protocol {
+        p.insert("opaque_protocol".to_string(), true.into());
+    }
+
+    p.into()
+}
+
+fn encode_route(m: &profiles::http::RequestMatch, r: &profiles::http::Route) -> Option<Value> {
+    let (method, path) = encode_request_match(m)?;
+
+    let mut route = Map::new();
+    if let Some(name) = r.labels().get("route") {
+        route.insert("name".to_string(), name.clone().into());
+    }
+    if let Some(method) = method {
+        route.insert("method".to_string(), method.as_str().into());
+    }
+    if let Some(re) = path {
+        // Expressions are anchored when they are read.
+        let re = re.as_str();
+        let re = re.strip_prefix('^').unwrap_or(re);
+        let re = re.strip_suffix('$').unwrap_or(re);
+        route.insert("path_regex".to_string(), re.into());
+    }
+    if let Some(timeout) = r.timeout() {
+        route.insert("timeout_ms".to_string(), millis(timeout).into());
+    }
+    if let Some(retries) = r.retries() {
+        route.insert("retryable".to_string(), true.into());
+        if let Some(timeout) = retries.per_try_timeout() {
+            route.insert("retry_timeout_ms".to_string(), millis(timeout).into());
+        }
+        if let Some(n) = retries.max_attempts() {
+            route.insert("retry_max_attempts".to_string(), n.get().into());
+        }
+        if let Some(n) = retries.max_buffered_bytes() {
+            route.insert("retry_max_buffered_bytes".to_string(), n.into());
+        }
+        if let Some(backoff) = retries.backoff() {
+            route.insert(
+                "retry_backoff".to_string(),
+                json!({
+                    "min_ms": millis(backoff.min()),
+                    "max_ms": millis(backoff.max()),
+                    "jitter": backoff.jitter_ratio(),
+                }),
+            );
+        }
+        match retries.hedge() {
+            Some(profiles::http::Hedge::Delay(delay)) => {
+                route.insert("hedge".to_string(), json!({ "delay_ms": millis(delay) }));
+            }
+            Some(profiles::http::Hedge::Percentile(p)) => {
+                route.insert("hedge".to_string(), json!({ "percentile": p }));
+            }
+            None => {}
+        }
+    }
+
+    Some(route.into())
+}
+
+/// Returns the method and path that a request match requires, if the match
+/// can be described by the file.
+fn encode_request_match(
+    m: &profiles::http::RequestMatch,
+) -> Option<(Option<&http::Method>, Option<&Regex>)> {
+    match m {
+        profiles::http::RequestMatch::Method(method) => Some((Some(method), None)),
+        profiles::http::RequestMatch::Path(re) => Some((None, Some(&**re))),
+        profiles::http::RequestMatch::All(ms) => {
+            ms.iter().try_fold((None, None), |(method, path), m| {
+                let (m, p) = encode_request_match(m)?;
+                if (m.is_some() && method.is_some()) || (p.is_some() && path.is_some()) {
+                    return None;
+                }
+                Some((method.or(m), path.or(p)))
+            })
+        }
+        profiles::http::RequestMatch::Any(_) | profiles::http::RequestMatch::Not(_) => None,
+    }
+}
+
+fn retry_backoff(backoff: &Value, path: &str) -> Result<ExponentialBackoff> {
+    let backoff = object(backoff, path)?;
+    let duration = |key: &str| {
+        optional_u64(backoff, key, path)?
+            .map(Duration::from_millis)
+            .ok_or_else(|| invalid(path, format!("missing {}", key)))
+    };
+    let min = duration("min_ms")?;
+    let max = duration("max_ms")?;
+    let jitter = backoff
+        .get("jitter")
+        .map(|v| {
+            v.as_f64()
+                .ok_or_else(|| invalid(&format!("{}.jitter", path), "must be a number"))
+        })
+        .transpose()?
+        .unwrap_or(0.0);
+    ExponentialBackoff::try_new(min, max, jitter).map_err(|e| invalid(path, e))
+}
+
+/// Idempotent requests may be hedged after either a fixed `delay_ms` or a
+/// `percentile` of the route's observed latency.
+fn hedge(hedge: &Value, path: &str) -> Result<profiles::http::Hedge> {
+    let hedge = object(hedge, path)?;
+    if let Some(ms) = optional_u64(hedge, "delay_ms", path)? {
+        return Ok(profiles::http::Hedge::Delay(Duration::from_millis(ms)));
+    }
+    match hedge.get("percentile").map(Value::as_f64) {
+        Some(Some(p)) if p > 0.0 && p <= 100.0 => Ok(profiles::http::Hedge::Percentile(p)),
+        Some(_) => Err(invalid(
+            &format!("{}.percentile", path),
+            "must be a number in (0, 100]",
+        )),
+        None => Err(invalid(path, "missing delay_ms or percentile")),
+    }
+}
+
+fn retry_budget(budget: &Value, path: &str) -> Result<Arc<Budget>> {
+    let budget = object(budget, path)?;
+    let retry_ratio = budget
+        .get("retry_ratio")
+        .map(|v| {
+            v.as_f64()
+                .ok_or_else(|| invalid(&format!("{}.retry_ratio", path), "must be a number"))
+        })
+        .transpose()?
+        .unwrap_or(0.2) as f32;
+    if !(0.0..=1000.0).contains(&retry_ratio) {
+        return Err(invalid(
+            &format!("{}.retry_ratio", path),
+            "must be between 0 and 1000",
+        ));
+    }
+    let min_retries = optional_u64(budget, "min_retries_per_second", path)?.unwrap_or(10);
+    let min_retries = u32::try_from(min_retries).map_err(|_| {
+        invalid(
+            &format!("{}.min_retries_per_second", path),
+            "value is too large",
+        )
+    })?;
+    let ttl = Duration::from_secs(optional_u64(budget, "ttl_secs", path)?.unwrap_or(10));
+    if ttl < Duration::from_secs(1) || ttl > Duration::from_secs(60) {
+        return Err(invalid(
+            &format!("{}.ttl_secs", path),
+            "must be between 1 and 60",
+        ));
+    }
+    Ok(Arc::new(Budget::new(ttl, min_retries, retry_ratio)))
+}
+
+// === policies ===
+
+fn server_policy(policy: &Value, path: &str) -> Result<ServerPolicy> {
+    let policy = object(policy, path)?;
+    let name = optional_str(policy, "name", path)?.unwrap_or("file");
+
+    let protocol = match optional_str(policy, "protocol", path)?.unwrap_or("detect") {
+        "detect" => {
+            let ms = optional_u64(policy, "detect_timeout_ms", path)?.unwrap_or(10_000);
+            Protocol::Detect {
+                timeout: Duration::from_millis(ms),
+            }
+        }
+        "http1" => Protocol::Http1,
+        "http2" => Protocol::Http2,
+        "grpc" => Protocol::Grpc,
+        "opaque" => Protocol::Opaque,
+        "tls" => Protocol::Tls,
+        p => {
+            return Err(invalid(
+                &format!("{}.protocol", path),
+                format!("unknown protocol: {}", p),
+            ))
+        }
+    };
+
+    let authorizations = optional_array(policy, "authorizations", path)?
+        .iter()
+        .enumerate()
+        .map(|(i, authz)| authorization(authz, &format!("{}.authorizations[{}]", path, i)))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(ServerPolicy {
+        protocol,
+        authorizations: authorizations.into(),
+        http_routes: Arc::new([]),
+        grpc_routes: Arc::new([]),
+        rate_limit: None,
+        meta: Meta::new_default(name.to_string()),
+    })
+}
+
+fn encode_server_policy(policy: &ServerPolicy) -> Value {
+    let mut p = Map::new();
+    p.insert("name".to_string(), policy.meta.name().into());
+
+    let protocol = match policy.protocol {
+        Protocol::Detect { timeout } => {
+            p.insert("detect_timeout_ms".to_string(), millis(timeout).into());
+            "detect"
+        }
+        Protocol::Http1 => "http1",
+        Protocol::Http2 => "http2",
+        Protocol::Grpc => "grpc",
+        Protocol::Opaque => "opaque",
+        Protocol::Tls => "tls",
+    };
+    p.insert("protocol".to_string(), protocol.into());
+
+    let authorizations = policy
+        .authorizations
+        .iter()
+        .filter(|authz| authz.networks.iter().all(|n| n.except.is_empty()))
+        .map(encode_authorization)
+        .collect::<Value>();
+    p.insert("authorizations".to_string(), authorizations);
+
+    p.into()
+}
+
+fn encode_authorization(authz: &Authorization) -> Value {
+    let networks = authz
+        .networks
+        .iter()
+        .map(|n| n.net.to_string())
+        .collect::<Vec<_>>();
+    let authentication = match &authz.authentication {
+        Authentication::Unauthenticated => json!("unauthenticated"),
+        Authentication::TlsUnauthenticated => json!("tls"),
+        Authentication::TlsAuthenticated {
+            identities,
+            suffixes,
+        } => json!({
+            "identities": identities,
+            "suffixes": suffixes.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        }),
+    };
+    json!({
+        "name": authz.meta.name(),
+        "networks": networks,
+        "authentication": authentication,
+    })
+}
+
+fn authorization(authz: &Value, path: &str) -> Result<Authorization> {
+    let authz = object(authz, path)?;
+    let name = optional_str(authz, "name", path)?.unwrap_or("file");
+
+    let networks = optional_array(authz, "networks", path)?
+        .iter()
+        .enumerate()
+        .map(|(i, net)| {
+            let path = format!("{}.networks[{}]", path, i);
+            let net = string(net, &path)?
+                .parse::<IpNet>()
+                .map_err(|e| invalid(&path, e))?;
+            Ok(net.into())
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let authn_path = format!("{}.authentication", path);
+    let authentication = match authz.get("authentication") {
+        None => Authentication::Unauthenticated,
+        Some(Value::String(s)) if s == "unauthenticated" => Authentication::Unauthenticated,
+        Some(Value::String(s)) if s == "tls" => Authentication::TlsUnauthenticated,
+        Some(Value::Object(authn)) => {
+            let identities = optional_array(authn, "identities", &authn_path)?
+                .iter()
+                .map(|id| string(id, &authn_path).map(String::from))
+                .collect::<Result<BTreeSet<_>>>()?;
+            let suffixes = optional_array(authn, "suffixes", &authn_path)?
+                .iter()
+                .map(|sfx| {
+                    let sfx = string(sfx, &authn_path)?;
+                    let parts = sfx
+                        .split('.')
+                        .filter(|p| !p.is_empty())
+                        .map(String::from)
+                        .collect::<Vec<_>>();
+                    Ok(Suffix::from(parts))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Authentication::TlsAuthenticated {
+                identities,
+                suffixes,
+            }
+        }
+        Some(_) => {
+            return Err(invalid(
+                &authn_path,
+                "must be \"unauthenticated\", \"tls\", or an object",
+            ))
+        }
+    };
+
+    Ok(Authorization {
+        networks,
+        authentication,
+        meta: Meta::new_default(name.to_string()),
+    })
+}
+
+// === helpers ===
+
+fn invalid(path: &str, reason: impl std::fmt::Display) -> InvalidFile {
+    InvalidFile {
+        path: path.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+fn name_addr(s: &str, path: &str) -> Result<NameAddr> {
+    NameAddr::from_str(s).map_err(|e| invalid(path, e))
+}
+
+fn millis(d: Duration) -> u64 {
+    u64::try_from(d.as_millis()).unwrap_or(u64::MAX)
+}
+
+fn port(p: u64, path: &str) -> Result<u16> {
+    u16::try_from(p).map_err(|_| invalid(path, "invalid port"))
+}
+
+fn object<'v>(v: &'v Value, path: &str) -> Result<&'v Map<String, Value>> {
+    v.as_object()
+        .ok_or_else(|| invalid(path, "must be an object"))
+}
+
+fn array<'v>(v: &'v Value, path: &str) -> Result<&'v Vec<Value>> {
+    v.as_array()
+        .ok_or_else(|| invalid(path, "must be an array"))
+}
+
+fn string<'v>(v: &'v Value, path: &str) -> Result<&'v str> {
+    v.as_str().ok_or_else(|| invalid(path, "must be a string"))
+}
+
+fn optional_object<'v>(
+    obj: &'v Map<String, Value>,
+    key: &str,
+    path: &str,
+) -> Result<impl Iterator<Item = (&'v String, &'v Value)>> {
+    let obj = obj
+        .get(key)
+        .map(|v| object(v, &format!("{}.{}", path, key)))
+        .transpose()?;
+    Ok(obj.into_iter().flatten())
+}
+
+fn optional_array<'v>(obj: &'v Map<String, Value>, key: &str, path: &str) -> Result<&'v [Value]> {
+    match obj.get(key) {
+        Some(v) => array(v, &format!("{}.{}", path, key)).map(Vec::as_slice),
+        None => Ok(&[]),
+    }
+}
+
+fn required_str<'v>(obj: &'v Map<String, Value>, key: &str, path: &str) -> Result<&'v str> {
+    optional_str(obj, key, path)?.ok_or_else(|| invalid(path, format!("missing {}", key)))
+}
+
+fn optional_str<'v>(obj: &'v Map<String, Value>, key: &str, path: &str) -> Result<Option<&'v str>> {
+    obj.get(key)
+        .map(|v| string(v, &format!("{}.{}", path, key)))
+        .transpose()
+}
+
+fn optional_u64(obj: &Map<String, Value>, key: &str, path: &str) -> Result<Option<u64>> {
+    obj.get(key)
+        .map(|v| {
+            v.as_u64()
+                .ok_or_else(|| invalid(&format!("{}.{}", path, key), "must be an unsigned integer"))
+        })
+        .transpose()
+}
+
+fn optional_bool(obj: &Map<String, Value>, key: &str, path: &str) -> Result<Option<bool>> {
+    obj.get(key)
+        .map(|v| {
+            v.as_bool()
+                .ok_or_else(|| invalid(&format!("{}.{}", path, key), "must be a boolean"))
+        })
+        .transpose()
+}
@@ -5,9 +5,12 @@
 
 pub mod dst;
 pub mod env;
+pub mod file;
 pub mod identity;
 pub mod oc_collector;
+pub mod snapshot;
 pub mod tap;
+pub mod xds;
 
 pub use self::metrics::Metrics;
 use futures::{future, Future, FutureExt};
@@ -62,7 +65,7 @@ pub struct Config {
 pub struct App {
     admin: admin::Task,
     drain: drain::Signal,
-    dst: ControlAddr,
+    dst: Option<ControlAddr>,
     identity: identity::Identity,
     inbound_addr: Local<ServerAddr>,
     oc_collector: oc_collector::OcCollector,
@@ -151,13 +154,30 @@ impl Config {
             span_sink: oc_collector.span_sink(),
             drain: drain_rx.clone(),
         };
-        let inbound = Inbound::new(inbound, runtime.clone());
+        let inbound = {
+            let mut inbound = inbound;
+            // Policies discovered from a file supersede those configured by
+            // the environment.
+            if let Some(policies) = dst.policies.clone() {
+                inbound.policy = inbound.policy.into_watch(policies);
+            }
+            // Policies from a snapshot are used until policies are discovered,
+            // and discovered policies are recorded in the snapshot.
+            if let Some(snapshots) = dst.snapshots.as_ref() {
+                inbound.policy = inbound
+                    .policy
+                    .with_initial(snapshots.policies())
+                    .with_updates(snapshots.policy_updates());
+            }
+            Inbound::new(inbound, runtime.clone())
+        };
         let outbound = Outbound::new(outbound, runtime);
 
         let inbound_policies = {
             let dns = dns.resolver;
             let metrics = metrics.control;
-            info_span!("policy").in_scope(|| inbound.build_policies(dns, metrics))
+            let policies = info_span!("policy").in_scope(|| inbound.build_policies(dns, metrics));
+            snapshot::Policies::new(policies, dst.snapshots.clone())
         };
 
         let admin = {
@@ -167,6 +187,7 @@ impl Config {
             let report = inbound
                 .metrics()
                 .and_report(outbound.metrics())
+                .and_report(dst.snapshots.clone())
                 .and_report(report);
             info_span!("admin").in_scope(move || {
                 admin.build(
@@ -206,7 +227,15 @@ impl Config {
             let identity_ready = identity.ready();
             let inbound_addr = inbound_addr;
             let profiles = dst.profiles;
-            let resolve = dst.resolve;
+            let resolve = {
+                let config = outbound.config();
+                let fallback = if config.dns_fallback {
+                    Some(config.allow_discovery.names().clone())
+                } else {
+                    None
+                };
+                outbound::DnsFallback::new(dst.resolve, dst.dns_resolve, fallback)
+            };
 
             Box::pin(async move {
                 Self::await_identity(identity_ready).await;
@@ -280,8 +309,10 @@ impl App {
         }
     }
 
-    pub fn dst_addr(&self) -> &ControlAddr {
-        &self.dst
+    /// Returns the address of the destination service or xDS management
+    /// server, unless destinations are discovered from a file.
+    pub fn dst_addr(&self) -> Option<&ControlAddr> {
+        self.dst.as_ref()
     }
 
     pub fn local_identity(&self) -> identity::Name {
@@ -0,0 +1,797 @@
+//! Persists discovered state so that it can be served while the control plane
+//! is unavailable at startup.
+//!
+//! When configured, the endpoints, profiles, and inbound policies discovered
+//! from the control plane are periodically written to a file in the discovery
+//! file format (see [`crate::file`]). When the proxy starts, the file is read
+//! and its state is served until the control plane first responds. Lookups
+//! that are served from the snapshot continue to watch the control plane and
+//! are updated as soon as it responds. Snapshots that were last written longer
+//! ago than a configured maximum age are ignored, so that long-outdated
+//! endpoints are never served.
+
+use crate::file;
+use futures::prelude::*;
+use linkerd_app_core::{
+    metrics::{metrics, FmtLabels, FmtMetrics, Gauge},
+    profiles::{self, GetProfile, LookupAddr},
+    proxy::{
+        api_resolve::{ConcreteAddr, Metadata},
+        core::{resolve, Update},
+    },
+    transport::OrigDstAddr,
+    Addr, Error, NameAddr,
+};
+use linkerd_app_inbound::policy::{AllowPolicy, GetPolicy, ServerPolicy, UpdatesTx};
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, SystemTime},
+};
+use tokio::sync::{mpsc, watch};
+use tracing::{debug, info_span, warn, Instrument};
+
+metrics! {
+    discovery_snapshot_stale: Gauge {
+        "Indicates that discovery for an address is being served from a snapshot"
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub path: PathBuf,
+    pub interval: Duration,
+
+    /// How long ago a snapshot may have been written for it to be loaded.
+    pub max_age: Duration,
+}
+
+/// Serves the snapshot that was loaded at startup and records discovered
+/// state to be written to the next snapshot.
+#[derive(Clone)]
+pub struct Snapshots(Arc<Inner>);
+
+/// Resolves endpoints, serving endpoints from the snapshot until the control
+/// plane responds.
+#[derive(Clone)]
+pub struct Resolve<R> {
+    inner: R,
+    snapshots: Option<Snapshots>,
+}
+
+/// Resolves profiles, serving profiles from the snapshot until the control
+/// plane responds.
+#[derive(Clone)]
+pub struct Profiles<P> {
+    inner: P,
+    snapshots: Option<Snapshots>,
+}
+
+/// Records the inbound policies that are discovered from the control plane.
+#[derive(Clone)]
+pub struct Policies<P> {
+    inner: P,
+    snapshots: Option<Snapshots>,
+}
+
+pub struct Resolution {
+    addr: NameAddr,
+    snapshots: Option<Snapshots>,
+
+    /// Endpoints from the snapshot that have not yet been published.
+    init: Option<Vec<(SocketAddr, Metadata)>>,
+
+    /// Set while the resolution is served from the snapshot.
+    stale: Option<Stale>,
+
+    state: State,
+
+    /// The endpoints discovered from the control plane.
+    endpoints: HashMap<SocketAddr, Metadata>,
+}
+
+type Connecting = Pin<Box<dyn Future<Output = Result<file::Resolution, Error>> + Send + 'static>>;
+
+enum State {
+    Connecting(Connecting),
+    Live(file::Resolution),
+}
+
+struct Inner {
+    path: PathBuf,
+
+    /// The snapshot that was loaded at startup.
+    loaded: file::Snapshot,
+
+    /// Set when the control plane first responds. Thereafter, the loaded
+    /// snapshot is no longer served to new lookups.
+    connected: AtomicBool,
+
+    recorded: Mutex<Recorded>,
+
+    /// Counts the lookups that are being served from the snapshot.
+    stale: Mutex<HashMap<(Kind, Addr), usize>>,
+}
+
+struct Recorded {
+    snapshot: file::Snapshot,
+    changed: bool,
+}
+
+/// Marks an address as being served from the snapshot until it is dropped.
+struct Stale {
+    snapshots: Snapshots,
+    key: (Kind, Addr),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum Kind {
+    Endpoints,
+    Profile,
+}
+
+struct StaleLabels<'l>(&'l (Kind, Addr));
+
+// === impl Config ===
+
+impl Config {
+    /// Loads the snapshot, if one exists, and spawns a task that writes the
+    /// discovered state to it periodically.
+    pub fn build(self) -> Snapshots {
+        let loaded = load(&self.path, self.max_age);
+        let snapshots = Snapshots::new(self.path, loaded);
+        tokio::spawn(
+            snapshots
+                .clone()
+                .write(self.interval)
+                .instrument(info_span!("snapshot")),
+        );
+
+        snapshots
+    }
+}
+
+// === impl Snapshots ===
+
+impl Snapshots {
+    fn new(path: PathBuf, loaded: file::Snapshot) -> Self {
+        // State from the loaded snapshot is retained until it is replaced by
+        // discovered state, so that the snapshot isn't lost if the control
+        // plane is unavailable for longer than a write interval.
+        Self(Arc::new(Inner {
+            path,
+            recorded: Mutex::new(Recorded {
+                snapshot: loaded.clone(),
+                changed: false,
+            }),
+            loaded,
+            connected: AtomicBool::new(false),
+            stale: Default::default(),
+        }))
+    }
+
+    /// Returns the policies from the loaded snapshot.
+    pub fn policies(&self) -> HashMap<u16, ServerPolicy> {
+        (*self.0.loaded.policies).clone()
+    }
+
+    async fn write(self, interval: Duration) {
+        let mut interval = tokio::time::interval(interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+
+            let bytes = {
+                let mut recorded = self.0.recorded.lock();
+                if !recorded.changed {
+                    continue;
+                }
+                recorded.changed = false;
+                recorded.snapshot.encode()
+            };
+
+            match write_file(&self.0.path, &bytes).await {
+                Ok(()) => debug!(bytes = bytes.len(), "Wrote snapshot"),
+                Err(error) => {
+                    warn!(path = %self.0.path.display(), %error, "Failed to write snapshot");
+                    self.0.recorded.lock().changed = true;
+                }
+            }
+        }
+    }
+
+    fn set_connected(&self) {
+        self.0.connected.store(true, Ordering::Release);
+    }
+
+    fn is_connected(&self) -> bool {
+        self.0.connected.load(Ordering::Acquire)
+    }
+
+    fn stale_endpoints(&self, addr: &NameAddr) -> Option<Vec<(SocketAddr, Metadata)>> {
+        if self.is_connected() {
+            return None;
+        }
+        self.0.loaded.endpoints(addr).cloned()
+    }
+
+    fn stale_profile(&self, addr: &Addr) -> Option<profiles::Profile> {
+        if self.is_connected() {
+            return None;
+        }
+        self.0.loaded.profile(addr)
+    }
+
+    fn stale(&self, kind: Kind, addr: Addr) -> Stale {
+        let key = (kind, addr);
+        *self.0.stale.lock().entry(key.clone()).or_default() += 1;
+        Stale {
+            snapshots: self.clone(),
+            key,
+        }
+    }
+
+    fn record_endpoints(&self, addr: &NameAddr, endpoints: &HashMap<SocketAddr, Metadata>) {
+        let mut recorded = self.0.recorded.lock();
+        if endpoints.is_empty() {
+            recorded.snapshot.destinations.remove(addr);
+        } else {
+            let eps = endpoints
+                .iter()
+                .map(|(addr, meta)| (*addr, meta.clone()))
+                .collect();
+            recorded.snapshot.destinations.insert(addr.clone(), eps);
+        }
+        recorded.changed = true;
+    }
+
+    fn record_profile(&self, addr: &Addr, profile: Option<profiles::Profile>) {
+        // Profiles that don't describe a logical service or an endpoint are
+        // equivalent to not having a profile at all.
+        let profile = profile.filter(|p| p.addr.is_some() || p.endpoint.is_some());
+        let mut recorded = self.0.recorded.lock();
+        match profile {
+            Some(profile) => {
+                recorded.snapshot.profiles.insert(addr.clone(), profile);
+            }
+            None => {
+                if recorded.snapshot.profiles.remove(addr).is_none() {
+                    return;
+                }
+            }
+        }
+        recorded.changed = true;
+    }
+
+    fn record_policy(&self, port: u16, policy: &ServerPolicy) {
+        // Default policies are used when no policy has been discovered, so
+        // they are not recorded.
+        if policy.meta.kind() == "default" {
+            return;
+        }
+        let mut recorded = self.0.recorded.lock();
+        if recorded.snapshot.policies.get(&port) != Some(policy) {
+            Arc::make_mut(&mut recorded.snapshot.policies).insert(port, policy.clone());
+            recorded.changed = true;
+        }
+    }
+
+    /// Returns a sender on which the policy store sends policy updates, and
+    /// spawns a task that records them until the store is dropped.
+    ///
+    /// Updates are recorded while their ports are watched, even if the ports
+    /// have no connections.
+    pub fn policy_updates(&self) -> UpdatesTx {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let snapshots = self.clone();
+        tokio::spawn(
+            async move {
+                while let Some((port, policy)) = rx.recv().await {
+                    snapshots.record_policy(port, &policy);
+                }
+            }
+            .instrument(info_span!("policies")),
+        );
+        tx
+    }
+
+    /// Publishes updates from a discovered profile until it is no longer
+    /// used, recording each update.
+    ///
+    /// The current profile is expected to have been published already.
+    async fn forward_profile(
+        self,
+        addr: Addr,
+        mut live: watch::Receiver<profiles::Profile>,
+        tx: watch::Sender<profiles::Profile>,
+    ) {
+        loop {
+            tokio::select! {
+                res = live.changed() => if res.is_err() {
+                    return;
+                },
+                _ = tx.closed() => return,
+            }
+
+            let profile = live.borrow().clone();
+            self.record_profile(&addr, Some(profile.clone()));
+            if tx.send(profile).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+impl FmtMetrics for Snapshots {
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stale = self.0.stale.lock();
+        if stale.is_empty() {
+            return Ok(());
+        }
+
+        discovery_snapshot_stale.fmt_help(f)?;
+        let value = Gauge::from(1);
+        for key in stale.keys() {
+            discovery_snapshot_stale.fmt_metric_labeled(f, &value, &StaleLabels(key))?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the snapshot at `path`, unless it was written more than `max_age` ago.
+///
+/// A snapshot that cannot be read is ignored so that it never prevents the
+/// proxy from starting.
+fn load(path: &Path, max_age: Duration) -> file::Snapshot {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            debug!(path = %path.display(), "No snapshot");
+            return file::Snapshot::default();
+        }
+        Err(error) => {
+            warn!(path = %path.display(), %error, "Failed to read snapshot");
+            return file::Snapshot::default();
+        }
+    };
+
+    // A snapshot that was modified in the future (e.g. because the clock was
+    // adjusted) is considered current.
+    let age = std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .map(|modified| {
+            SystemTime::now()
+                .duration_since(modified)
+                .unwrap_or_default()
+        });
+    let age = match age {
+        Ok(age) if age <= max_age => age,
+        Ok(age) => {
+            warn!(path = %path.display(), ?age, ?max_age, "Ignoring expired snapshot");
+            return file::Snapshot::default();
+        }
+        Err(error) => {
+            warn!(path = %path.display(), %error, "Ignoring snapshot of unknown age");
+            return file::Snapshot::default();
+        }
+    };
+
+    match file::Snapshot::decode(&bytes, file::Format::Json) {
+        Ok(snapshot) => {
+            debug!(
+                path = %path.display(),
+                ?age,
+                destinations = snapshot.destinations.len(),
+                profiles = snapshot.profiles.len(),
+                policies = snapshot.policies.len(),
+                "Loaded snapshot"
+            );
+            snapshot
+        }
+        Err(error) => {
+            warn!(path = %path.display(), %error, "Ignoring invalid snapshot");
+            file::Snapshot::default()
+        }
+    }
+}
+
+/// Writes the file atomically, so that a partially-written snapshot is never
+/// read.
+async fn write_file(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    tokio::fs::write(&tmp, bytes).await?;
+    tokio::fs::rename(&tmp, path).await
+}
+
+// === impl Resolve ===
+
+impl<R> Resolve<R> {
+    pub fn new(inner: R, snapshots: Option<Snapshots>) -> Self {
+        Self { inner, snapshots }
+    }
+}
+
+impl<R> tower::Service<ConcreteAddr> for Resolve<R>
+where
+    R: resolve::Resolve<ConcreteAddr, Endpoint = Metadata, Error = Error>,
+    R::Resolution: Send + 'static,
+    R::Future: Send + 'static,
+{
+    type Response = Resolution;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Resolution, Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        resolve::Resolve::poll_ready(&mut self.inner, cx)
+    }
+
+    fn call(&mut self, target: ConcreteAddr) -> Self::Future {
+        let addr = target.0.clone();
+        let live: Connecting = Box::pin(
+            resolve::Resolve::resolve(&mut self.inner, target)
+                .map_ok(|res| Box::pin(res) as file::Resolution),
+        );
+
+        let snapshots = self.snapshots.clone();
+        if let Some(s) = snapshots.as_ref() {
+            if let Some(init) = s.stale_endpoints(&addr) {
+                // The live resolution isn't available until the control plane
+                // responds, so it's polled by the resolution.
+                debug!(%addr, endpoints = init.len(), "Serving endpoints from snapshot");
+                let stale = s.stale(Kind::Endpoints, addr.clone().into());
+                return Box::pin(future::ok(Resolution {
+                    addr,
+                    snapshots,
+                    init: Some(init),
+                    stale: Some(stale),
+                    state: State::Connecting(live),
+                    endpoints: HashMap::new(),
+                }));
+            }
+        }
+
+        Box::pin(live.map_ok(move |live| Resolution {
+            addr,
+            snapshots,
+            init: None,
+            stale: None,
+            state: State::Live(live),
+            endpoints: HashMap::new(),
+        }))
+    }
+}
+
+// === impl Resolution ===
+
+impl Stream for Resolution {
+    type Item = Result<Update<Metadata>, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        if let Some(eps) = this.init.take() {
+            return Poll::Ready(Some(Ok(Update::Reset(eps))));
+        }
+
+        let live = loop {
+            match &mut this.state {
+                State::Live(live) => break live,
+                State::Connecting(connecting) => match futures::ready!(connecting.poll_unpin(cx)) {
+                    Ok(live) => this.state = State::Live(live),
+                    Err(error) => return Poll::Ready(Some(Err(error))),
+                },
+            }
+        };
+
+        let update = match futures::ready!(live.poll_next_unpin(cx)) {
+            Some(Ok(update)) => update,
+            res => return Poll::Ready(res),
+        };
+
+        let snapshots = match this.snapshots.as_ref() {
+            Some(snapshots) => snapshots,
+            None => return Poll::Ready(Some(Ok(update))),
+        };
+        snapshots.set_connected();
+
+        match &update {
+            Update::Reset(eps) => {
+                this.endpoints.clear();
+                this.endpoints.extend(eps.iter().cloned());
+            }
+            Update::Add(eps) => this.endpoints.extend(eps.iter().cloned()),
+            Update::Remove(addrs) => {
+                for addr in addrs {
+                    this.endpoints.remove(addr);
+                }
+            }
+            Update::DoesNotExist => this.endpoints.clear(),
+        }
+        snapshots.record_endpoints(&this.addr, &this.endpoints);
+
+        // The first discovered update replaces the snapshot's endpoints.
+        if this.stale.take().is_some() {
+            debug!(addr = %this.addr, "Serving discovered endpoints");
+            if let Update::Add(_) | Update::Remove(_) = update {
+                let eps = this
+                    .endpoints
+                    .iter()
+                    .map(|(addr, meta)| (*addr, meta.clone()))
+                    .collect();
+                return Poll::Ready(Some(Ok(Update::Reset(eps))));
+            }
+        }
+
+        Poll::Ready(Some(Ok(update)))
+    }
+}
+
+// === impl Profiles ===
+
+impl<P> Profiles<P> {
+    pub fn new(inner: P, snapshots: Option<Snapshots>) -> Self {
+        Self { inner, snapshots }
+    }
+}
+
+impl<P> tower::Service<LookupAddr> for Profiles<P>
+where
+    P: GetProfile<LookupAddr>,
+    P::Future: Send + 'static,
+{
+    type Response = Option<profiles::Receiver>;
+    type Error = Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Option<profiles::Receiver>, Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, LookupAddr(addr): LookupAddr) -> Self::Future {
+        let live = self
+            .inner
+            .get_profile(LookupAddr(addr.clone()))
+            .map_err(Into::<Error>::into);
+        let snapshots = match self.snapshots.clone() {
+            Some(snapshots) => snapshots,
+            None => return Box::pin(live),
+        };
+
+        if let Some(profile) = snapshots.stale_profile(&addr) {
+            debug!(%addr, "Serving profile from snapshot");
+            let stale = snapshots.stale(Kind::Profile, addr.clone());
+            let (tx, rx) = watch::channel(profile);
+            tokio::spawn(
+                async move {
+                    let live = tokio::select! {
+                        res = live => res,
+                        _ = tx.closed() => return,
+                    };
+                    snapshots.set_connected();
+                    drop(stale);
+
+                    let live = match live {
+                        Ok(Some(live)) => watch::Receiver::from(live),
+                        Ok(None) => {
+                            // The profile is no longer described by the
+                            // control plane.
+                            snapshots.record_profile(&addr, None);
+                            let _ = tx.send(profiles::Profile::default());
+                            return;
+                        }
+                        Err(error) => {
+                            debug!(%error, "Failed to discover profile");
+                            return;
+                        }
+                    };
+                    debug!("Serving discovered profile");
+                    let profile = live.borrow().clone();
+                    snapshots.record_profile(&addr, Some(profile.clone()));
+                    if tx.send(profile).is_ok() {
+                        snapshots.forward_profile(addr, live, tx).await;
+                    }
+                }
+                .in_current_span(),
+            );
+            return Box::pin(future::ok(Some(rx.into())));
+        }
+
+        Box::pin(async move {
+            let live = match live.await? {
+                Some(live) => watch::Receiver::from(live),
+                None => {
+                    snapshots.record_profile(&addr, None);
+                    return Ok(None);
+                }
+            };
+            snapshots.set_connected();
+
+            let profile = live.borrow().clone();
+            snapshots.record_profile(&addr, Some(profile.clone()));
+            let (tx, rx) = watch::channel(profile);
+            tokio::spawn(snapshots.forward_profile(addr, live, tx).in_current_span());
+            Ok(Some(rx.into()))
+        })
+    }
+}
+
+// === impl Policies ===
+
+impl<P> Policies<P> {
+    pub fn new(inner: P, snapshots: Option<Snapshots>) -> Self {
+        Self { inner, snapshots }
+    }
+}
+
+impl<P: GetPolicy> GetPolicy for Policies<P> {
+    fn get_policy(&self, dst: OrigDstAddr) -> AllowPolicy {
+        let policy = self.inner.get_policy(dst);
+        if let Some(snapshots) = self.snapshots.as_ref() {
+            snapshots.record_policy(dst.port(), &*policy.borrow());
+        }
+        policy
+    }
+}
+
+// === impl Stale ===
+
+impl Drop for Stale {
+    fn drop(&mut self) {
+        let mut stale = self.snapshots.0.stale.lock();
+        if let Some(count) = stale.get_mut(&self.key) {
+            *count -= 1;
+            if *count == 0 {
+                stale.remove(&self.key);
+            }
+        }
+    }
+}
+
+// === impl Kind ===
+
+impl Kind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Endpoints => "endpoints",
+            Self::Profile => "profile",
+        }
+    }
+}
+
+impl FmtLabels for StaleLabels<'_> {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (kind, addr) = self.0;
+        write!(f, "kind=\"{}\",addr=\"{}\"", kind.as_str(), addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linkerd_app_core::svc;
+    use std::str::FromStr;
+    use tokio::sync::oneshot;
+    use tokio_stream::wrappers::UnboundedReceiverStream;
+
+    #[tokio::test]
+    async fn serves_snapshot_until_discovered() {
+        let web = NameAddr::from_str("web.ns.svc.cluster.local:8080").unwrap();
+        let stale = (SocketAddr::from(([10, 1, 1, 1], 8080)), Metadata::default());
+        let live = (SocketAddr::from(([10, 1, 1, 2], 8080)), Metadata::default());
+
+        let mut loaded = file::Snapshot::default();
+        loaded.destinations.insert(web.clone(), vec![stale.clone()]);
+        let snapshots = Snapshots::new(PathBuf::new(), loaded);
+
+        // The resolution does not complete until the control plane is
+        // available.
+        let (connect_tx, connect_rx) = oneshot::channel::<()>();
+        let (updates_tx, updates_rx) = mpsc::unbounded_channel::<Result<_, Error>>();
+        let mut connect = Some((connect_rx, updates_rx));
+        let inner = svc::mk(move |_: ConcreteAddr| {
+            let (connected, updates) = connect.take().expect("must only resolve once");
+            async move {
+                let _ = connected.await;
+                Ok::<_, Error>(UnboundedReceiverStream::new(updates))
+            }
+        });
+        let mut resolve = Resolve::new(inner, Some(snapshots.clone()));
+
+        let mut resolution = tower::Service::call(&mut resolve, ConcreteAddr(web.clone()))
+            .await
+            .expect("resolution must be served from the snapshot");
+        match resolution.next().await {
+            Some(Ok(Update::Reset(eps))) => assert_eq!(eps, vec![stale]),
+            _ => panic!("expected the snapshot's endpoints"),
+        }
+        assert_eq!(snapshots.0.stale.lock().len(), 1);
+
+        connect_tx.send(()).unwrap();
+        updates_tx
+            .send(Ok(Update::Add(vec![live.clone()])))
+            .unwrap();
+        match resolution.next().await {
+            Some(Ok(Update::Reset(eps))) => assert_eq!(eps, vec![live.clone()]),
+            _ => panic!("expected the discovered endpoints to replace the snapshot's"),
+        }
+        assert!(snapshots.0.stale.lock().is_empty());
+        assert_eq!(
+            snapshots.0.recorded.lock().snapshot.endpoints(&web),
+            Some(&vec![live])
+        );
+    }
+
+    #[tokio::test]
+    async fn records_policy_updates() {
+        use linkerd_app_inbound::policy::{DefaultPolicy, Meta, Protocol};
+
+        let snapshots = Snapshots::new(PathBuf::new(), file::Snapshot::default());
+        let updates = snapshots.policy_updates();
+
+        let policy = ServerPolicy {
+            protocol: Protocol::Http1,
+            authorizations: Arc::new([]),
+            http_routes: Arc::new([]),
+            grpc_routes: Arc::new([]),
+            rate_limit: None,
+            meta: Arc::new(Meta::Resource {
+                group: "policy.linkerd.io".into(),
+                kind: "server".into(),
+                name: "web".into(),
+            }),
+        };
+        updates.send((8080, policy.clone())).unwrap();
+        updates
+            .send((8081, ServerPolicy::from(DefaultPolicy::Deny)))
+            .unwrap();
+
+        // The recording task completes once the store drops its sender.
+        drop(updates);
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while Arc::strong_count(&snapshots.0) > 1 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("recording must complete");
+
+        // Default policies are not recorded.
+        let recorded = snapshots.0.recorded.lock();
+        assert_eq!(recorded.snapshot.policies.get(&8080), Some(&policy));
+        assert!(recorded.snapshot.policies.get(&8081).is_none());
+    }
+
+    #[test]
+    fn ignores_expired_snapshots() {
+        let web = NameAddr::from_str("web.ns.svc.cluster.local:8080").unwrap();
+        let ep = (SocketAddr::from(([10, 1, 1, 1], 8080)), Metadata::default());
+        let mut snapshot = file::Snapshot::default();
+        snapshot.destinations.insert(web.clone(), vec![ep.clone()]);
+
+        let path = std::env::temp_dir().join(format!(
+            "linkerd-discovery-snapshot-{}.json",
+            std::process::id()
+        ));
+        std::fs::write(&path, snapshot.encode()).unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+
+        let loaded = load(&path, Duration::from_secs(60));
+        let expired = load(&path, Duration::from_millis(1));
+        std::fs::remove_file(&path).unwrap();
+
+        let addrs = loaded.destinations[&web].iter().map(|(a, _)| *a);
+        assert_eq!(addrs.collect::<Vec<_>>(), vec![ep.0]);
+        assert!(expired.destinations.is_empty());
+        assert!(load(&path, Duration::from_secs(60)).destinations.is_empty());
+    }
+}
@@ -0,0 +1,621 @@
+//! Discovery from an Envoy xDS management server.
+//!
+//! The proxy subscribes to clusters (CDS), their endpoints (EDS), and a
+//! configured set of route configurations (RDS) over a single aggregated
+//! discovery (ADS) stream, using xDS's state-of-the-world protocol. Resources
+//! are translated into the same snapshot that is read from a discovery file
+//! (see [`convert`]), so endpoints and profiles are served by
+//! [`file::Resolve`] and [`file::Profiles`].
+//!
+//! When the stream fails, it is re-established with a backoff. The last
+//! accepted resources continue to be served in the meantime.
+
+mod api;
+mod convert;
+
+use crate::file::{self, Snapshot};
+use futures::prelude::*;
+use linkerd_app_core::{
+    control, dns, exp_backoff::ExponentialBackoff, identity, metrics, proxy::http, svc::NewService,
+    Error,
+};
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::{mpsc, watch};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tracing::{debug, info_span, warn, Instrument};
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub control: control::Config,
+
+    /// Identifies the proxy to the management server.
+    pub node_id: String,
+
+    /// The service cluster to which the proxy belongs, if any.
+    pub node_cluster: String,
+
+    /// The names of the route configurations from which profiles are
+    /// discovered.
+    pub route_configs: Vec<String>,
+}
+
+/// Handles to the resources discovered from the management server.
+pub struct Xds {
+    /// Resolves profiles from route configurations and clusters.
+    pub profiles: file::Profiles,
+
+    /// Resolves cluster endpoints.
+    pub resolve: file::Resolve,
+}
+
+/// The resources most recently accepted from the management server.
+#[derive(Debug, Default)]
+struct Resources {
+    clusters: HashMap<String, api::Cluster>,
+
+    /// Cluster load assignments, by EDS service name.
+    endpoints: HashMap<String, api::ClusterLoadAssignment>,
+
+    routes: HashMap<String, api::RouteConfiguration>,
+}
+
+struct Client<S> {
+    ads: api::AdsClient<S>,
+    node: api::Node,
+    resources: Resources,
+    clusters: Subscription,
+    endpoints: Subscription,
+    routes: Subscription,
+}
+
+/// The state of a subscription to a resource type.
+#[derive(Debug)]
+struct Subscription {
+    type_url: &'static str,
+
+    /// The names of the subscribed resources. Empty for a wildcard
+    /// subscription.
+    names: Vec<String>,
+
+    /// The last accepted version.
+    version: String,
+
+    /// The nonce of the last response on the current stream.
+    nonce: String,
+}
+
+// === impl Config ===
+
+impl Config {
+    /// Spawns a task that streams resources from the management server.
+    pub fn build(
+        self,
+        dns: dns::Resolver,
+        metrics: metrics::ControlHttp,
+        identity: identity::NewClient,
+    ) -> Xds {
+        let addr = self.control.addr.clone();
+        let backoff = self.control.connect.backoff;
+        let svc = self.control.build(dns, metrics, identity).new_service(());
+        let client = Client::new(svc, self.node_id, self.node_cluster, self.route_configs);
+
+        let (tx, rx) = watch::channel(Arc::new(Snapshot::default()));
+        tokio::spawn(
+            client
+                .run(tx, backoff)
+                .instrument(info_span!("xds", addr = %addr.addr)),
+        );
+
+        Xds {
+            profiles: file::Profiles(rx.clone()),
+            resolve: file::Resolve(rx),
+        }
+    }
+}
+
+// === impl Client ===
+
+impl<S> Client<S>
+where
+    S: tonic::client::GrpcService<tonic::body::BoxBody, Error = Error>,
+    S::ResponseBody: http::HttpBody + Send + 'static,
+    <S::ResponseBody as http::HttpBody>::Error: Into<Error> + Send,
+{
+    fn new(svc: S, node_id: String, node_cluster: String, route_configs: Vec<String>) -> Self {
+        Self {
+            ads: api::AdsClient::new(svc),
+            node: api::Node {
+                id: node_id,
+                cluster: node_cluster,
+                user_agent_name: "linkerd2-proxy".to_string(),
+            },
+            resources: Resources::default(),
+            clusters: Subscription::new(api::CLUSTER, Vec::new()),
+            endpoints: Subscription::new(api::CLUSTER_LOAD_ASSIGNMENT, Vec::new()),
+            routes: Subscription::new(api::ROUTE_CONFIGURATION, route_configs),
+        }
+    }
+
+    async fn run(mut self, snapshots: watch::Sender<Arc<Snapshot>>, backoff: ExponentialBackoff) {
+        let mut backoffs = backoff.stream();
+        loop {
+            let mut received = false;
+            match self.stream(&snapshots, &mut received).await {
+                Ok(()) => {
+                    debug!("Resources are no longer watched");
+                    return;
+                }
+                Err(status) => {
+                    warn!(%status, "Stream failed");
+                    // Only back off repeatedly while the stream keeps failing
+                    // before the server responds.
+                    if received {
+                        backoffs = backoff.stream();
+                    }
+                }
+            }
+            backoffs.next().await;
+        }
+    }
+
+    /// Streams resources until the stream fails or all snapshot receivers are
+    /// dropped.
+    async fn stream(
+        &mut self,
+        snapshots: &watch::Sender<Arc<Snapshot>>,
+        received: &mut bool,
+    ) -> Result<(), tonic::Status> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        for sub in [&mut self.clusters, &mut self.endpoints, &mut self.routes] {
+            // Nonces are scoped to a stream.
+            sub.nonce.clear();
+            // The cluster subscription is a wildcard and is always requested.
+            if sub.type_url == api::CLUSTER || !sub.names.is_empty() {
+                let _ = tx.send(sub.request(&self.node, None));
+            }
+        }
+
+        let mut rsps = self
+            .ads
+            .stream_aggregated_resources(UnboundedReceiverStream::new(rx))
+            .await?
+            .into_inner();
+        debug!("Connected");
+        loop {
+            let rsp = tokio::select! {
+                rsp = rsps.message() => match rsp? {
+                    Some(rsp) => rsp,
+                    None => return Err(tonic::Status::unavailable("stream closed by the server")),
+                },
+                _ = snapshots.closed() => return Ok(()),
+            };
+            *received = true;
+
+            let (reqs, updated) = self.update(rsp);
+            for req in reqs {
+                let _ = tx.send(req);
+            }
+            if updated {
+                let snapshot = convert::snapshot(&self.resources);
+                debug!(
+                    destinations = snapshot.destinations.len(),
+                    profiles = snapshot.profiles.len(),
+                    "Updated"
+                );
+                if snapshots.send(Arc::new(snapshot)).is_err() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Applies a response, returning the requests that acknowledge it and
+    /// whether the resources were updated.
+    fn update(&mut self, rsp: api::DiscoveryResponse) -> (Vec<api::DiscoveryRequest>, bool) {
+        let mut resubscribe_endpoints = false;
+        let (sub, result) = match rsp.type_url.as_str() {
+            api::CLUSTER => {
+                let result = decode::<api::Cluster>(&rsp).map(|clusters| {
+                    // Cluster responses always include every cluster.
+                    self.resources.clusters =
+                        clusters.into_iter().map(|c| (c.name.clone(), c)).collect();
+                    let names = convert::eds_resource_names(self.resources.clusters.values());
+                    if names != self.endpoints.names {
+                        self.resources.endpoints.retain(|n, _| names.contains(n));
+                        self.endpoints.names = names;
+                        resubscribe_endpoints = true;
+                    }
+                });
+                (&mut self.clusters, result)
+            }
+            api::CLUSTER_LOAD_ASSIGNMENT => {
+                let names = &self.endpoints.names;
+                let result = decode::<api::ClusterLoadAssignment>(&rsp).map(|clas| {
+                    for cla in clas.into_iter() {
+                        if names.contains(&cla.cluster_name) {
+                            self.resources
+                                .endpoints
+                                .insert(cla.cluster_name.clone(), cla);
+                        }
+                    }
+                });
+                (&mut self.endpoints, result)
+            }
+            api::ROUTE_CONFIGURATION => {
+                let names = &self.routes.names;
+                let result = decode::<api::RouteConfiguration>(&rsp).map(|rcs| {
+                    for rc in rcs.into_iter() {
+                        if names.contains(&rc.name) {
+                            self.resources.routes.insert(rc.name.clone(), rc);
+                        }
+                    }
+                });
+                (&mut self.routes, result)
+            }
+            type_url => {
+                debug!(%type_url, "Ignoring response for an unsubscribed type");
+                return (Vec::new(), false);
+            }
+        };
+
+        sub.nonce = rsp.nonce;
+        match result {
+            Ok(()) => {
+                debug!(type_url = %sub.type_url, version = %rsp.version_info, "Accepted");
+                sub.version = rsp.version_info;
+                let mut reqs = vec![sub.request(&self.node, None)];
+                if resubscribe_endpoints {
+                    reqs.push(self.endpoints.request(&self.node, None));
+                }
+                (reqs, true)
+            }
+            Err(error) => {
+                warn!(type_url = %sub.type_url, version = %rsp.version_info, %error, "Rejected");
+                let status = api::Status {
+                    code: tonic::Code::InvalidArgument as i32,
+                    message: error.to_string(),
+                };
+                (vec![sub.request(&self.node, Some(status))], false)
+            }
+        }
+    }
+}
+
+fn decode<M: prost::Message + Default>(rsp: &api::DiscoveryResponse) -> Result<Vec<M>, Error> {
+    rsp.resources
+        .iter()
+        .map(|any| {
+            if any.type_url != rsp.type_url {
+                return Err(format!("unexpected resource type: {}", any.type_url).into());
+            }
+            M::decode(any.value.as_slice()).map_err(Into::into)
+        })
+        .collect()
+}
+
+// === impl Subscription ===
+
+impl Subscription {
+    fn new(type_url: &'static str, names: Vec<String>) -> Self {
+        Self {
+            type_url,
+            names,
+            version: String::new(),
+            nonce: String::new(),
+        }
+    }
+
+    /// Builds a request that acknowledges the last response, or rejects it
+    /// when an error is provided.
+    fn request(&self, node: &api::Node, error: Option<api::Status>) -> api::DiscoveryRequest {
+        api::DiscoveryRequest {
+            version_info: self.version.clone(),
+            node: Some(node.clone()),
+            resource_names: self.names.clone(),
+            type_url: self.type_url.to_string(),
+            response_nonce: self.nonce.clone(),
+            error_detail: error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linkerd_app_core::{
+        profiles::{LoadBalancer, LookupAddr},
+        proxy::{api_resolve::ConcreteAddr, core::Update},
+        svc::ServiceExt,
+        NameAddr,
+    };
+    use parking_lot::Mutex;
+    use prost::Message;
+    use std::{
+        net::SocketAddr,
+        task::{Context, Poll},
+    };
+
+    /// A management server that records requests and serves the responses
+    /// sent by the test.
+    #[derive(Clone)]
+    struct StandIn {
+        requests: mpsc::UnboundedSender<api::DiscoveryRequest>,
+        responses: Arc<Mutex<Option<mpsc::UnboundedReceiver<api::DiscoveryResponse>>>>,
+    }
+
+    #[tokio::test]
+    async fn discovers_clusters_endpoints_and_routes() {
+        let (req_tx, mut reqs) = mpsc::unbounded_channel();
+        let (rsps, rsp_rx) = mpsc::unbounded_channel();
+        let server = StandIn {
+            requests: req_tx,
+            responses: Arc::new(Mutex::new(Some(rsp_rx))),
+        };
+        let client = Client::new(
+            server,
+            "web-1".to_string(),
+            "web".to_string(),
+            vec!["web-routes".to_string()],
+        );
+        let (tx, snapshots) = watch::channel(Arc::new(Snapshot::default()));
+        tokio::spawn(client.run(tx, ExponentialBackoff::default()));
+
+        let req = reqs.recv().await.expect("must request clusters");
+        assert_eq!(req.type_url, api::CLUSTER);
+        assert!(req.resource_names.is_empty());
+        assert_eq!(req.node.expect("must identify the node").id, "web-1");
+        let req = reqs.recv().await.expect("must request routes");
+        assert_eq!(req.type_url, api::ROUTE_CONFIGURATION);
+        assert_eq!(req.resource_names, vec!["web-routes".to_string()]);
+
+        rsps.send(response(
+            api::CLUSTER,
+            "1",
+            vec![
+                api::Cluster {
+                    name: "outbound|8080||web.ns.svc.cluster.local".to_string(),
+                    r#type: api::discovery_type::EDS,
+                    lb_policy: api::lb_policy::LEAST_REQUEST,
+                    ..Default::default()
+                },
+                api::Cluster {
+                    name: "web-v2.ns.svc.cluster.local:8080".to_string(),
+                    r#type: api::discovery_type::EDS,
+                    ..Default::default()
+                },
+            ],
+        ))
+        .unwrap();
+        let ack = reqs.recv().await.expect("must acknowledge clusters");
+        assert_eq!(ack.type_url, api::CLUSTER);
+        assert_eq!(ack.version_info, "1");
+        assert_eq!(ack.response_nonce, "nonce-1");
+        assert!(ack.error_detail.is_none());
+        let req = reqs.recv().await.expect("must request endpoints");
+        assert_eq!(req.type_url, api::CLUSTER_LOAD_ASSIGNMENT);
+        assert_eq!(
+            req.resource_names,
+            vec![
+                "outbound|8080||web.ns.svc.cluster.local".to_string(),
+                "web-v2.ns.svc.cluster.local:8080".to_string(),
+            ]
+        );
+
+        let ep = |ip: &str, health_status: i32| api::LbEndpoint {
+            endpoint: Some(api::Endpoint {
+                address: Some(api::Address {
+                    socket_address: Some(api::SocketAddress {
+                        address: ip.to_string(),
+                        port_value: Some(8080),
+                    }),
+                }),
+            }),
+            health_status,
+        };
+        rsps.send(response(
+            api::CLUSTER_LOAD_ASSIGNMENT,
+            "1",
+            vec![api::ClusterLoadAssignment {
+                cluster_name: "outbound|8080||web.ns.svc.cluster.local".to_string(),
+                endpoints: vec![
+                    api::LocalityLbEndpoints {
+                        locality: Some(api::Locality {
+                            zone: "east".to_string(),
+                            ..Default::default()
+                        }),
+                        lb_endpoints: vec![
+                            ep("10.0.0.1", 1),
+                            ep("10.0.0.2", api::health_status::UNHEALTHY),
+                        ],
+                        priority: 0,
+                    },
+                    api::LocalityLbEndpoints {
+                        lb_endpoints: vec![ep("10.0.0.3", 1)],
+                        priority: 1,
+                        ..Default::default()
+                    },
+                ],
+            }],
+        ))
+        .unwrap();
+        let ack = reqs.recv().await.expect("must acknowledge endpoints");
+        assert_eq!(ack.type_url, api::CLUSTER_LOAD_ASSIGNMENT);
+        assert_eq!(ack.version_info, "1");
+
+        let web = NameAddr::from_str_and_port("web.ns.svc.cluster.local", 8080).unwrap();
+        let resolve = file::Resolve(snapshots.clone());
+        let mut resolution = resolve
+            .oneshot(ConcreteAddr(web.clone()))
+            .await
+            .expect("resolution must succeed");
+        match resolution.next().await.expect("resolution must update") {
+            Ok(Update::Reset(eps)) => {
+                let addrs = eps.iter().map(|(a, _)| *a).collect::<Vec<_>>();
+                assert_eq!(addrs, vec![SocketAddr::from(([10, 0, 0, 1], 8080))]);
+                assert_eq!(
+                    eps[0].1.labels().get("zone").map(String::as_str),
+                    Some("east")
+                );
+            }
+            up => panic!("unexpected update: {:?}", up.map(|_| ())),
+        }
+
+        // An invalid route configuration is rejected without discarding the
+        // previously accepted resources.
+        rsps.send(api::DiscoveryResponse {
+            version_info: "1".to_string(),
+            resources: vec![prost_types::Any {
+                type_url: api::ROUTE_CONFIGURATION.to_string(),
+                value: vec![0xff],
+            }],
+            type_url: api::ROUTE_CONFIGURATION.to_string(),
+            nonce: "nonce-1".to_string(),
+        })
+        .unwrap();
+        let nack = reqs.recv().await.expect("must reject routes");
+        assert_eq!(nack.type_url, api::ROUTE_CONFIGURATION);
+        assert_eq!(nack.version_info, "");
+        assert_eq!(nack.response_nonce, "nonce-1");
+        assert!(nack.error_detail.is_some());
+
+        let route = |prefix: &str, clusters: Vec<(&str, u32)>| api::Route {
+            r#match: Some(api::RouteMatch {
+                prefix: Some(prefix.to_string()),
+                ..Default::default()
+            }),
+            route: Some(api::RouteAction {
+                weighted_clusters: Some(api::WeightedCluster {
+                    clusters: clusters
+                        .into_iter()
+                        .map(|(name, weight)| api::ClusterWeight {
+                            name: name.to_string(),
+                            weight: Some(weight),
+                        })
+                        .collect(),
+                }),
+                ..Default::default()
+            }),
+            name: prefix.to_string(),
+        };
+        rsps.send(response(
+            api::ROUTE_CONFIGURATION,
+            "2",
+            vec![api::RouteConfiguration {
+                name: "web-routes".to_string(),
+                virtual_hosts: vec![api::VirtualHost {
+                    name: "web".to_string(),
+                    domains: vec![
+                        "web.ns.svc.cluster.local".to_string(),
+                        "web.ns.svc.cluster.local:8080".to_string(),
+                    ],
+                    routes: vec![
+                        route("/api", vec![("web-v2.ns.svc.cluster.local:8080", 1)]),
+                        route(
+                            "/",
+                            vec![
+                                ("outbound|8080||web.ns.svc.cluster.local", 90),
+                                ("web-v2.ns.svc.cluster.local:8080", 10),
+                            ],
+                        ),
+                    ],
+                }],
+            }],
+        ))
+        .unwrap();
+        let ack = reqs.recv().await.expect("must acknowledge routes");
+        assert_eq!(ack.version_info, "2");
+        assert!(ack.error_detail.is_none());
+
+        let profiles = file::Profiles(snapshots);
+        let profile = profiles
+            .oneshot(LookupAddr(web.clone().into()))
+            .await
+            .unwrap()
+            .expect("profile must be discovered");
+        let profile = watch::Receiver::from(profile).borrow().clone();
+        assert_eq!(profile.addr.map(|a| a.0), Some(web));
+        assert_eq!(profile.load_balancer, LoadBalancer::LeastRequest);
+        assert_eq!(profile.http_routes.len(), 2);
+        assert_eq!(
+            profile
+                .targets
+                .iter()
+                .map(|t| (t.addr.to_string(), t.weight))
+                .collect::<Vec<_>>(),
+            vec![
+                ("web.ns.svc.cluster.local:8080".to_string(), 90),
+                ("web-v2.ns.svc.cluster.local:8080".to_string(), 10),
+            ]
+        );
+    }
+
+    fn response<M: Message>(
+        type_url: &str,
+        version: &str,
+        resources: Vec<M>,
+    ) -> api::DiscoveryResponse {
+        api::DiscoveryResponse {
+            version_info: version.to_string(),
+            resources: resources
+                .into_iter()
+                .map(|r| prost_types::Any {
+                    type_url: type_url.to_string(),
+                    value: r.encode_to_vec(),
+                })
+                .collect(),
+            type_url: type_url.to_string(),
+            nonce: format!("nonce-{}", version),
+        }
+    }
+
+    // === impl StandIn ===
+
+    impl tower::Service<http::Request<tonic::body::BoxBody>> for StandIn {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = Error;
+        type Future = future::BoxFuture<'static, Result<Self::Response, Error>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: http::Request<tonic::body::BoxBody>) -> Self::Future {
+            let svc = self.clone();
+            Box::pin(async move {
+                let mut grpc = tonic::server::Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc.streaming(svc, req).await)
+            })
+        }
+    }
+
+    impl tonic::server::StreamingService<api::DiscoveryRequest> for StandIn {
+        type Response = api::DiscoveryResponse;
+        type ResponseStream =
+            stream::BoxStream<'static, Result<api::DiscoveryResponse, tonic::Status>>;
+        type Future = future::Ready<Result<tonic::Response<Self::ResponseStream>, tonic::Status>>;
+
+        fn call(
+            &mut self,
+            req: tonic::Request<tonic::Streaming<api::DiscoveryRequest>>,
+        ) -> Self::Future {
+            let requests = self.requests.clone();
+            let mut stream = req.into_inner();
+            tokio::spawn(async move {
+                while let Ok(Some(req)) = stream.message().await {
+                    if requests.send(req).is_err() {
+                        return;
+                    }
+                }
+            });
+
+            let rsps = self
+                .responses
+                .lock()
+                .take()
+                .expect("only one stream may be opened");
+            future::ok(tonic::Response::new(
+                UnboundedReceiverStream::new(rsps).map(Ok).boxed(),
+            ))
+        }
+    }
+}
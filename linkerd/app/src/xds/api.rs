@@ -0,0 +1,350 @@
+//! The subset of Envoy's v3 xDS protobuf API that the proxy consumes.
+//!
+//! Messages are declared by hand rather than generated so that the proxy does
+//! not need to vendor Envoy's (large) proto tree. Only the fields that the
+//! proxy interprets are declared; all others are skipped when decoding. Fields
+//! that are part of a `oneof` are declared as optional fields, which is
+//! equivalent on the wire.
+//!
+//! Some fields are declared as [`Unsupported`] only so that their presence can
+//! be detected: a route that matches on query parameters, for instance, must
+//! be ignored rather than treated as matching every request.
+
+use linkerd_app_core::{proxy::http, Error};
+
+pub const CLUSTER: &str = "type.googleapis.com/envoy.config.cluster.v3.Cluster";
+pub const CLUSTER_LOAD_ASSIGNMENT: &str =
+    "type.googleapis.com/envoy.config.endpoint.v3.ClusterLoadAssignment";
+pub const ROUTE_CONFIGURATION: &str =
+    "type.googleapis.com/envoy.config.route.v3.RouteConfiguration";
+
+const STREAM_AGGREGATED_RESOURCES: &str =
+    "/envoy.service.discovery.v3.AggregatedDiscoveryService/StreamAggregatedResources";
+
+/// A client for the aggregated discovery service.
+#[derive(Clone, Debug)]
+pub struct AdsClient<S>(tonic::client::Grpc<S>);
+
+// === envoy.service.discovery.v3 ===
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DiscoveryRequest {
+    #[prost(string, tag = "1")]
+    pub version_info: String,
+    #[prost(message, optional, tag = "2")]
+    pub node: Option<Node>,
+    #[prost(string, repeated, tag = "3")]
+    pub resource_names: Vec<String>,
+    #[prost(string, tag = "4")]
+    pub type_url: String,
+    #[prost(string, tag = "5")]
+    pub response_nonce: String,
+    #[prost(message, optional, tag = "6")]
+    pub error_detail: Option<Status>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DiscoveryResponse {
+    #[prost(string, tag = "1")]
+    pub version_info: String,
+    #[prost(message, repeated, tag = "2")]
+    pub resources: Vec<prost_types::Any>,
+    #[prost(string, tag = "4")]
+    pub type_url: String,
+    #[prost(string, tag = "5")]
+    pub nonce: String,
+}
+
+/// `google.rpc.Status`, used to describe why a response was rejected.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Status {
+    #[prost(int32, tag = "1")]
+    pub code: i32,
+    #[prost(string, tag = "2")]
+    pub message: String,
+}
+
+// === envoy.config.core.v3 ===
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Node {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(string, tag = "2")]
+    pub cluster: String,
+    #[prost(string, tag = "6")]
+    pub user_agent_name: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Locality {
+    #[prost(string, tag = "1")]
+    pub region: String,
+    #[prost(string, tag = "2")]
+    pub zone: String,
+    #[prost(string, tag = "3")]
+    pub sub_zone: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Address {
+    #[prost(message, optional, tag = "1")]
+    pub socket_address: Option<SocketAddress>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SocketAddress {
+    #[prost(string, tag = "2")]
+    pub address: String,
+    #[prost(uint32, optional, tag = "3")]
+    pub port_value: Option<u32>,
+}
+
+/// A message whose contents are not interpreted.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Unsupported {}
+
+// === envoy.config.cluster.v3 ===
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Cluster {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    /// A `DiscoveryType`.
+    #[prost(int32, tag = "2")]
+    pub r#type: i32,
+    #[prost(message, optional, tag = "3")]
+    pub eds_cluster_config: Option<EdsClusterConfig>,
+    /// An `LbPolicy`.
+    #[prost(int32, tag = "6")]
+    pub lb_policy: i32,
+    #[prost(message, optional, tag = "33")]
+    pub load_assignment: Option<ClusterLoadAssignment>,
+    #[prost(message, optional, tag = "38")]
+    pub cluster_type: Option<Unsupported>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct EdsClusterConfig {
+    #[prost(string, tag = "2")]
+    pub service_name: String,
+}
+
+pub mod discovery_type {
+    pub const STATIC: i32 = 0;
+    pub const EDS: i32 = 3;
+}
+
+pub mod lb_policy {
+    pub const ROUND_ROBIN: i32 = 0;
+    pub const LEAST_REQUEST: i32 = 1;
+}
+
+// === envoy.config.endpoint.v3 ===
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ClusterLoadAssignment {
+    #[prost(string, tag = "1")]
+    pub cluster_name: String,
+    #[prost(message, repeated, tag = "2")]
+    pub endpoints: Vec<LocalityLbEndpoints>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct LocalityLbEndpoints {
+    #[prost(message, optional, tag = "1")]
+    pub locality: Option<Locality>,
+    #[prost(message, repeated, tag = "2")]
+    pub lb_endpoints: Vec<LbEndpoint>,
+    #[prost(uint32, tag = "5")]
+    pub priority: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct LbEndpoint {
+    #[prost(message, optional, tag = "1")]
+    pub endpoint: Option<Endpoint>,
+    /// A `HealthStatus`.
+    #[prost(int32, tag = "2")]
+    pub health_status: i32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Endpoint {
+    #[prost(message, optional, tag = "1")]
+    pub address: Option<Address>,
+}
+
+pub mod health_status {
+    pub const UNHEALTHY: i32 = 2;
+    pub const DRAINING: i32 = 3;
+    pub const TIMEOUT: i32 = 4;
+}
+
+// === envoy.config.route.v3 ===
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RouteConfiguration {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(message, repeated, tag = "2")]
+    pub virtual_hosts: Vec<VirtualHost>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct VirtualHost {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, repeated, tag = "2")]
+    pub domains: Vec<String>,
+    #[prost(message, repeated, tag = "3")]
+    pub routes: Vec<Route>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Route {
+    #[prost(message, optional, tag = "1")]
+    pub r#match: Option<RouteMatch>,
+    #[prost(message, optional, tag = "2")]
+    pub route: Option<RouteAction>,
+    #[prost(string, tag = "14")]
+    pub name: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RouteMatch {
+    #[prost(string, optional, tag = "1")]
+    pub prefix: Option<String>,
+    #[prost(string, optional, tag = "2")]
+    pub path: Option<String>,
+    #[prost(message, optional, tag = "4")]
+    pub case_sensitive: Option<bool>,
+    #[prost(message, repeated, tag = "6")]
+    pub headers: Vec<HeaderMatcher>,
+    #[prost(message, repeated, tag = "7")]
+    pub query_parameters: Vec<Unsupported>,
+    #[prost(message, optional, tag = "8")]
+    pub grpc: Option<Unsupported>,
+    #[prost(message, optional, tag = "9")]
+    pub runtime_fraction: Option<Unsupported>,
+    #[prost(message, optional, tag = "10")]
+    pub safe_regex: Option<RegexMatcher>,
+    #[prost(message, optional, tag = "12")]
+    pub connect_matcher: Option<Unsupported>,
+    #[prost(string, optional, tag = "14")]
+    pub path_separated_prefix: Option<String>,
+    #[prost(message, optional, tag = "15")]
+    pub path_match_policy: Option<Unsupported>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HeaderMatcher {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, optional, tag = "4")]
+    pub exact_match: Option<String>,
+    #[prost(bool, tag = "8")]
+    pub invert_match: bool,
+    #[prost(message, optional, tag = "13")]
+    pub string_match: Option<StringMatcher>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StringMatcher {
+    #[prost(string, optional, tag = "1")]
+    pub exact: Option<String>,
+    #[prost(bool, tag = "6")]
+    pub ignore_case: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RegexMatcher {
+    #[prost(string, tag = "2")]
+    pub regex: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RouteAction {
+    #[prost(string, optional, tag = "1")]
+    pub cluster: Option<String>,
+    #[prost(message, optional, tag = "2")]
+    pub cluster_header: Option<Unsupported>,
+    #[prost(message, optional, tag = "3")]
+    pub weighted_clusters: Option<WeightedCluster>,
+    #[prost(message, optional, tag = "8")]
+    pub timeout: Option<prost_types::Duration>,
+    #[prost(message, optional, tag = "9")]
+    pub retry_policy: Option<RetryPolicy>,
+    #[prost(string, optional, tag = "37")]
+    pub cluster_specifier_plugin: Option<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WeightedCluster {
+    #[prost(message, repeated, tag = "1")]
+    pub clusters: Vec<ClusterWeight>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ClusterWeight {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(message, optional, tag = "2")]
+    pub weight: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RetryPolicy {
+    #[prost(string, tag = "1")]
+    pub retry_on: String,
+    #[prost(message, optional, tag = "2")]
+    pub num_retries: Option<u32>,
+    #[prost(message, optional, tag = "3")]
+    pub per_try_timeout: Option<prost_types::Duration>,
+    #[prost(uint32, repeated, tag = "7")]
+    pub retriable_status_codes: Vec<u32>,
+    #[prost(message, optional, tag = "8")]
+    pub retry_back_off: Option<RetryBackOff>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RetryBackOff {
+    #[prost(message, optional, tag = "1")]
+    pub base_interval: Option<prost_types::Duration>,
+    #[prost(message, optional, tag = "2")]
+    pub max_interval: Option<prost_types::Duration>,
+}
+
+// === impl AdsClient ===
+
+impl<S> AdsClient<S>
+where
+    S: tonic::client::GrpcService<tonic::body::BoxBody, Error = Error>,
+    S::ResponseBody: http::HttpBody + Send + 'static,
+    <S::ResponseBody as http::HttpBody>::Error: Into<Error> + Send,
+{
+    pub fn new(inner: S) -> Self {
+        Self(tonic::client::Grpc::new(inner))
+    }
+
+    /// Opens an aggregated discovery stream, sending each request produced by
+    /// `requests`.
+    pub async fn stream_aggregated_resources(
+        &mut self,
+        requests: impl futures::Stream<Item = DiscoveryRequest> + Send + 'static,
+    ) -> Result<tonic::Response<tonic::Streaming<DiscoveryResponse>>, tonic::Status> {
+        self.0.ready().await.map_err(|e| {
+            tonic::Status::new(
+                tonic::Code::Unknown,
+                format!("Service was not ready: {}", e),
+            )
+        })?;
+        self.0
+            .streaming(
+                tonic::Request::new(requests),
+                http::uri::PathAndQuery::from_static(STREAM_AGGREGATED_RESOURCES),
+                tonic::codec::ProstCodec::default(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,431 @@
+//! Translates xDS resources into the proxy's discovery types.
+//!
+//! Clusters are addressed by their names, which must either be an authority
+//! (e.g. `web.ns.svc.cluster.local:8080`) or follow Istio's outbound naming
+//! convention (e.g. `outbound|8080||web.ns.svc.cluster.local`). Clusters with
+//! other names, including Istio's subset clusters, cannot be addressed by the
+//! proxy and are ignored.
+//!
+//! Each virtual host domain that includes a port describes the profile for
+//! that authority. Features that the proxy cannot honor (e.g. query parameter
+//! matches, redirects, or DNS clusters) are skipped rather than approximated,
+//! so that traffic is never routed by a rule that would match more broadly
+//! than the management server intended.
+
+use super::{api, Resources};
+use crate::file::Snapshot;
+use linkerd_app_core::{
+    exp_backoff::ExponentialBackoff,
+    profiles::{self, LoadBalancer, LogicalAddr, Target},
+    proxy::{
+        api_resolve::{Metadata, ProtocolHint},
+        http,
+    },
+    Addr, NameAddr,
+};
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    num::NonZeroU32,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+use tower::retry::budget::Budget;
+use tracing::debug;
+
+/// Envoy applies this timeout to routes that do not configure one.
+const DEFAULT_ROUTE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Envoy backs off between retries by this base interval when a retry policy
+/// does not configure one.
+const DEFAULT_RETRY_BASE_INTERVAL: Duration = Duration::from_millis(25);
+
+pub(super) fn snapshot(resources: &Resources) -> Snapshot {
+    let clusters = resources
+        .clusters
+        .values()
+        .filter_map(|c| Some((cluster_addr(&c.name)?, c)))
+        .collect::<HashMap<_, _>>();
+
+    let mut destinations = HashMap::new();
+    let mut profiles = HashMap::new();
+    for (addr, cluster) in clusters.iter() {
+        let load_assignment = match cluster.r#type {
+            api::discovery_type::EDS => resources.endpoints.get(eds_service_name(cluster)),
+            api::discovery_type::STATIC if cluster.cluster_type.is_none() => {
+                cluster.load_assignment.as_ref()
+            }
+            _ => {
+                debug!(cluster = %cluster.name, "Ignoring cluster with unsupported discovery type");
+                continue;
+            }
+        };
+        if let Some(cla) = load_assignment {
+            destinations.insert(addr.clone(), endpoints(cla));
+        }
+
+        profiles.insert(
+            Addr::from(addr.clone()),
+            profiles::Profile {
+                addr: Some(LogicalAddr(addr.clone())),
+                load_balancer: load_balancer(cluster),
+                ..profiles::Profile::default()
+            },
+        );
+    }
+
+    let vhosts = resources
+        .routes
+        .values()
+        .flat_map(|rc| rc.virtual_hosts.iter());
+    for vhost in vhosts {
+        for domain in vhost.domains.iter() {
+            if let Ok(addr) = NameAddr::from_str(domain) {
+                let mut profile = profile(vhost, &clusters);
+                profile.load_balancer = clusters
+                    .get(&addr)
+                    .map(|c| load_balancer(c))
+                    .unwrap_or_default();
+                profile.addr = Some(LogicalAddr(addr.clone()));
+                profiles.insert(Addr::from(addr), profile);
+            }
+        }
+    }
+
+    Snapshot {
+        destinations,
+        profiles,
+        policies: Default::default(),
+        profile_sources: Default::default(),
+    }
+}
+
+/// Returns the names of the EDS resources needed by `clusters`.
+pub(super) fn eds_resource_names<'c>(
+    clusters: impl Iterator<Item = &'c api::Cluster>,
+) -> Vec<String> {
+    let mut names = clusters
+        .filter(|c| c.r#type == api::discovery_type::EDS && cluster_addr(&c.name).is_some())
+        .map(|c| eds_service_name(c).to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn eds_service_name(cluster: &api::Cluster) -> &str {
+    match cluster.eds_cluster_config.as_ref() {
+        Some(eds) if !eds.service_name.is_empty() => &eds.service_name,
+        _ => &cluster.name,
+    }
+}
+
+fn cluster_addr(name: &str) -> Option<NameAddr> {
+    let mut parts = name.split('|');
+    match (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) {
+        (Some("outbound"), Some(port), Some(""), Some(host), None) => {
+            NameAddr::from_str(&format!("{}:{}", host, port)).ok()
+        }
+        (Some(_), None, ..) => NameAddr::from_str(name).ok(),
+        _ => None,
+    }
+}
+
+fn load_balancer(cluster: &api::Cluster) -> LoadBalancer {
+    match cluster.lb_policy {
+        api::lb_policy::ROUND_ROBIN => LoadBalancer::RoundRobin,
+        api::lb_policy::LEAST_REQUEST => LoadBalancer::LeastRequest,
+        _ => LoadBalancer::default(),
+    }
+}
+
+// === endpoints ===
+
+/// Returns the usable endpoints in the cluster's highest-priority localities.
+///
+/// Lower-priority localities are only used when no higher-priority locality
+/// has a usable endpoint.
+fn endpoints(cla: &api::ClusterLoadAssignment) -> Vec<(SocketAddr, Metadata)> {
+    let priority = cla
+        .endpoints
+        .iter()
+        .filter(|l| usable(l).next().is_some())
+        .map(|l| l.priority)
+        .min();
+    cla.endpoints
+        .iter()
+        .filter(|l| Some(l.priority) == priority)
+        .flat_map(usable)
+        .collect()
+}
+
+/// Returns the endpoints in a locality that are not known to be unhealthy.
+fn usable(
+    locality: &api::LocalityLbEndpoints,
+) -> impl Iterator<Item = (SocketAddr, Metadata)> + '_ {
+    let labels = locality.locality.as_ref().map(labels).unwrap_or_default();
+    locality
+        .lb_endpoints
+        .iter()
+        .filter(|ep| {
+            !matches!(
+                ep.health_status,
+                api::health_status::UNHEALTHY
+                    | api::health_status::DRAINING
+                    | api::health_status::TIMEOUT
+            )
+        })
+        .filter_map(move |ep| {
+            let addr = ep.endpoint.as_ref()?.address.as_ref()?;
+            let addr = socket_addr(addr.socket_address.as_ref()?)?;
+            let meta = Metadata::new(labels.clone(), ProtocolHint::Unknown, None, None, None);
+            Some((addr, meta))
+        })
+}
+
+fn labels(locality: &api::Locality) -> Vec<(String, String)> {
+    [
+        ("region", &locality.region),
+        ("zone", &locality.zone),
+        ("sub_zone", &locality.sub_zone),
+    ]
+    .into_iter()
+    .filter(|(_, v)| !v.is_empty())
+    .map(|(k, v)| (k.to_string(), v.clone()))
+    .collect()
+}
+
+/// Endpoints must be addressed by IP; names are not resolved.
+fn socket_addr(addr: &api::SocketAddress) -> Option<SocketAddr> {
+    let ip = IpAddr::from_str(&addr.address).ok()?;
+    let port = u16::try_from(addr.port_value?).ok()?;
+    Some(SocketAddr::new(ip, port))
+}
+
+// === routes ===
+
+fn profile(
+    vhost: &api::VirtualHost,
+    clusters: &HashMap<NameAddr, &api::Cluster>,
+) -> profiles::Profile {
+    let mut http_routes = Vec::new();
+    let mut targets = None;
+    for route in vhost.routes.iter() {
+        let (m, r) = match self::route(route, clusters) {
+            Some(route) => route,
+            None => {
+                debug!(vhost = %vhost.name, route = %route.name, "Ignoring unsupported route");
+                continue;
+            }
+        };
+
+        // Requests that match no route are sent to the backends of the first
+        // route that matches every request, as they would be by Envoy.
+        if targets.is_none() && is_catch_all(route) {
+            targets = r.backends().map(|b| b.to_vec());
+        }
+        http_routes.push((m, r));
+    }
+
+    profiles::Profile {
+        http_routes,
+        targets: targets.unwrap_or_default(),
+        ..profiles::Profile::default()
+    }
+}
+
+fn is_catch_all(route: &api::Route) -> bool {
+    match route.r#match.as_ref() {
+        Some(m) => {
+            m.prefix
+                .as_deref()
+                .map_or(false, |p| p.is_empty() || p == "/")
+                && m.headers.is_empty()
+        }
+        None => false,
+    }
+}
+
+fn route(
+    route: &api::Route,
+    clusters: &HashMap<NameAddr, &api::Cluster>,
+) -> Option<(profiles::http::RequestMatch, profiles::http::Route)> {
+    let action = route.route.as_ref()?;
+    let m = request_match(route.r#match.as_ref()?)?;
+
+    let backends = match (action.cluster.as_ref(), action.weighted_clusters.as_ref()) {
+        (Some(cluster), None) => vec![Target {
+            addr: cluster_addr(cluster)?,
+            weight: 1,
+            priority: 0,
+        }],
+        (None, Some(weighted)) => weighted
+            .clusters
+            .iter()
+            .map(|c| {
+                Some(Target {
+                    addr: cluster_addr(&c.name)?,
+                    weight: c.weight.unwrap_or(1),
+                    priority: 0,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?,
+        _ => return None,
+    };
+    if backends.iter().any(|t| !clusters.contains_key(&t.addr)) {
+        return None;
+    }
+
+    let labels = Some(route.name.clone())
+        .filter(|n| !n.is_empty())
+        .map(|name| ("route".to_string(), name));
+    let mut r = profiles::http::Route::new(
+        labels.into_iter(),
+        action
+            .retry_policy
+            .as_ref()
+            .map(response_classes)
+            .unwrap_or_default(),
+    );
+    r.set_backends(backends);
+
+    match action.timeout.as_ref() {
+        None => r.set_timeout(DEFAULT_ROUTE_TIMEOUT),
+        // A zero timeout disables the route's timeout.
+        Some(t) if t.seconds == 0 && t.nanos == 0 => {}
+        Some(t) => r.set_timeout(duration(t)?),
+    }
+
+    if let Some(retry) = action.retry_policy.as_ref() {
+        if retry.num_retries != Some(0) {
+            // xDS limits the number of retries per request rather than the
+            // ratio of retries, so the destination API's default budget is
+            // used in addition to the policy's limit.
+            let retries = r.set_retries(Arc::new(Budget::new(Duration::from_secs(10), 10, 0.2)));
+            if let Some(n) = retry.num_retries {
+                retries.set_max_attempts(NonZeroU32::new(n.saturating_add(1))?);
+            }
+            // A zero timeout disables the per-try timeout.
+            if let Some(t) = retry.per_try_timeout.as_ref() {
+                let t = duration(t)?;
+                if t > Duration::ZERO {
+                    retries.set_per_try_timeout(t);
+                }
+            }
+            retries.set_backoff(retry_backoff(retry.retry_back_off.as_ref())?);
+        }
+    }
+
+    Some((m, r))
+}
+
+/// Envoy fully jitters backoffs that grow from the base interval up to the
+/// maximum interval, which defaults to ten times the base interval.
+fn retry_backoff(backoff: Option<&api::RetryBackOff>) -> Option<ExponentialBackoff> {
+    let base = match backoff.and_then(|b| b.base_interval.as_ref()) {
+        Some(base) => duration(base)?,
+        None => DEFAULT_RETRY_BASE_INTERVAL,
+    };
+    let max = match backoff.and_then(|b| b.max_interval.as_ref()) {
+        Some(max) => duration(max)?,
+        None => base * 10,
+    };
+    ExponentialBackoff::try_new(base, max, 1.0).ok()
+}
+
+fn duration(d: &prost_types::Duration) -> Option<Duration> {
+    Some(
+        Duration::from_secs(u64::try_from(d.seconds).ok()?)
+            + Duration::from_nanos(u64::try_from(d.nanos).ok()?),
+    )
+}
+
+fn request_match(m: &api::RouteMatch) -> Option<profiles::http::RequestMatch> {
+    if !m.query_parameters.is_empty()
+        || m.grpc.is_some()
+        || m.runtime_fraction.is_some()
+        || m.connect_matcher.is_some()
+        || m.path_separated_prefix.is_some()
+        || m.path_match_policy.is_some()
+    {
+        return None;
+    }
+
+    let flags = if m.case_sensitive.unwrap_or(true) {
+        ""
+    } else {
+        "(?i)"
+    };
+    let re = match (m.prefix.as_ref(), m.path.as_ref(), m.safe_regex.as_ref()) {
+        (Some(prefix), None, None) => format!("{}^{}", flags, regex::escape(prefix)),
+        (None, Some(path), None) => format!("{}^{}$", flags, regex::escape(path)),
+        (None, None, Some(re)) => format!("^(?:{})$", re.regex),
+        _ => return None,
+    };
+    let mut matches = vec![profiles::http::RequestMatch::Path(Box::new(
+        Regex::new(&re).ok()?,
+    ))];
+
+    for header in m.headers.iter() {
+        // Only method matches are supported.
+        if header.name != ":method" || header.invert_match {
+            return None;
+        }
+        let method = match (header.exact_match.as_ref(), header.string_match.as_ref()) {
+            (Some(method), None) => method,
+            (
+                None,
+                Some(api::StringMatcher {
+                    exact: Some(method),
+                    ignore_case: false,
+                }),
+            ) => method,
+            _ => return None,
+        };
+        let method = http::Method::from_str(method).ok()?;
+        matches.push(profiles::http::RequestMatch::Method(method));
+    }
+
+    Some(profiles::http::RequestMatch::All(matches))
+}
+
+/// Classifies the statuses that a retry policy retries as failures.
+fn response_classes(retry: &api::RetryPolicy) -> Vec<profiles::http::ResponseClass> {
+    let mut ranges = retry
+        .retriable_status_codes
+        .iter()
+        .filter_map(|&code| {
+            let status = http::StatusCode::from_u16(u16::try_from(code).ok()?).ok()?;
+            Some((status, status))
+        })
+        .collect::<Vec<_>>();
+    for condition in retry.retry_on.split(',').map(str::trim) {
+        let range = match condition {
+            "5xx" => (500, 599),
+            "gateway-error" => (502, 504),
+            _ => continue,
+        };
+        ranges.push((
+            http::StatusCode::from_u16(range.0).expect("valid status"),
+            http::StatusCode::from_u16(range.1).expect("valid status"),
+        ));
+    }
+
+    ranges
+        .into_iter()
+        .map(|(min, max)| {
+            profiles::http::ResponseClass::new(
+                true,
+                profiles::http::ResponseMatch::Status { min, max },
+            )
+        })
+        .collect()
+}
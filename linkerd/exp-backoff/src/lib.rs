@@ -11,7 +11,7 @@ use thiserror::Error;
 use tokio::time;
 
 /// A jittered exponential backoff strategy.
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct ExponentialBackoff {
     /// The minimum amount of time to wait before resuming an operation.
     min: time::Duration,
@@ -68,6 +68,21 @@ impl ExponentialBackoff {
         Ok(ExponentialBackoff { min, max, jitter })
     }
 
+    /// The minimum amount of time to wait before resuming an operation.
+    pub fn min(&self) -> time::Duration {
+        self.min
+    }
+
+    /// The maximum amount of time to wait before resuming an operation.
+    pub fn max(&self) -> time::Duration {
+        self.max
+    }
+
+    /// The ratio of the base timeout that may be randomly added to a backoff.
+    pub fn jitter_ratio(&self) -> f64 {
+        self.jitter
+    }
+
     pub fn stream(&self) -> ExponentialBackoffStream {
         ExponentialBackoffStream {
             backoff: *self,
@@ -78,6 +93,13 @@ impl ExponentialBackoff {
         }
     }
 
+    /// Returns the jittered duration to wait after `iterations` prior
+    /// backoffs.
+    pub fn delay<R: rand::Rng>(&self, iterations: u32, rng: &mut R) -> time::Duration {
+        let base = self.base(iterations);
+        base + self.jitter(base, rng)
+    }
+
     fn base(&self, iterations: u32) -> time::Duration {
         debug_assert!(
             self.min <= self.max,
@@ -132,10 +154,7 @@ impl Stream for ExponentialBackoffStream {
                 return Poll::Ready(None);
             }
 
-            let backoff = {
-                let base = this.backoff.base(*this.iterations);
-                base + this.backoff.jitter(base, &mut this.rng)
-            };
+            let backoff = this.backoff.delay(*this.iterations, &mut this.rng);
             this.sleep.as_mut().reset(time::Instant::now() + backoff);
             *this.sleeping = true;
         }
@@ -170,6 +189,17 @@ mod tests {
             TestResult::from_bool(min <= delay && delay <= max)
         }
 
+        fn backoff_delay(min_ms: u64, max_ms: u64, jitter: f64, iterations: u32) -> TestResult {
+            let min = time::Duration::from_millis(min_ms);
+            let max = time::Duration::from_millis(max_ms);
+            let backoff = match ExponentialBackoff::try_new(min, max, jitter) {
+                Err(_) => return TestResult::discard(),
+                Ok(backoff) => backoff,
+            };
+            let delay = backoff.delay(iterations, &mut rand::thread_rng());
+            TestResult::from_bool(min <= delay && delay <= max)
+        }
+
         fn backoff_jitter(base_ms: u64, max_ms: u64, jitter: f64) -> TestResult {
             let base = time::Duration::from_millis(base_ms);
             let max = time::Duration::from_millis(max_ms);
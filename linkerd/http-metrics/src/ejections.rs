@@ -0,0 +1,163 @@
+use super::{Prefixed, Registry, Report};
+use linkerd_metrics::{FmtLabels, FmtMetric, FmtMetrics, Gauge, LastUpdate, Metric};
+use parking_lot::Mutex;
+use std::{fmt, hash::Hash, sync::Arc};
+use tokio::time::{Duration, Instant};
+use tracing::trace;
+
+#[derive(Debug)]
+pub struct Ejections<T>(Registry<T, Metrics>)
+where
+    T: Hash + Eq;
+
+#[derive(Clone, Debug)]
+pub struct Handle(Arc<Mutex<Metrics>>);
+
+/// Holds an endpoint's ejection, decrementing the ejection gauge when dropped.
+#[derive(Debug)]
+pub struct Ejected(Handle);
+
+/// Counts an endpoint towards its target's endpoints until dropped.
+#[derive(Debug)]
+pub struct Registered(Handle);
+
+#[derive(Debug)]
+pub struct Metrics {
+    last_update: Instant,
+    ejected: Gauge,
+    endpoints: usize,
+}
+
+// === impl Ejections ===
+
+impl<T: Hash + Eq> Default for Ejections<T> {
+    fn default() -> Self {
+        Ejections(Registry::default())
+    }
+}
+
+impl<T: Hash + Eq> Ejections<T> {
+    pub fn into_report(self, retain_idle: Duration) -> Report<T, Metrics> {
+        Report::new(retain_idle, self.0)
+    }
+
+    pub fn get_handle(&self, target: T) -> Handle {
+        let mut reg = self.0.lock();
+        Handle(reg.entry(target).or_default().clone())
+    }
+}
+
+impl<T: Hash + Eq> Clone for Ejections<T> {
+    fn clone(&self) -> Self {
+        Ejections(self.0.clone())
+    }
+}
+
+// === impl Handle ===
+
+impl Handle {
+    /// Records that an endpoint exists until the returned guard is dropped.
+    pub fn register(&self) -> Registered {
+        self.0.lock().endpoints += 1;
+        Registered(self.clone())
+    }
+
+    /// Records that an endpoint has been ejected until the returned guard is
+    /// dropped, unless doing so would eject more than `max_ratio` of the
+    /// registered endpoints.
+    pub fn try_eject(&self, max_ratio: f64) -> Option<Ejected> {
+        let mut m = self.0.lock();
+        let ejected = m.ejected.value() + 1;
+        if ejected as f64 > max_ratio * m.endpoints as f64 {
+            return None;
+        }
+        m.last_update = Instant::now();
+        m.ejected.incr();
+        Some(Ejected(self.clone()))
+    }
+
+    /// Returns the number of endpoints that are currently ejected.
+    pub fn ejected(&self) -> u64 {
+        self.0.lock().ejected.value()
+    }
+}
+
+// === impl Ejected ===
+
+impl Drop for Ejected {
+    fn drop(&mut self) {
+        let mut m = (self.0).0.lock();
+        m.last_update = Instant::now();
+        m.ejected.decr();
+    }
+}
+
+// === impl Registered ===
+
+impl Drop for Registered {
+    fn drop(&mut self) {
+        let mut m = (self.0).0.lock();
+        m.endpoints = m.endpoints.saturating_sub(1);
+    }
+}
+
+// === impl Metrics ===
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            last_update: Instant::now(),
+            ejected: Gauge::default(),
+            endpoints: 0,
+        }
+    }
+}
+
+impl LastUpdate for Metrics {
+    fn last_update(&self) -> Instant {
+        self.last_update
+    }
+}
+
+// === impl Report ===
+
+impl<T> Report<T, Metrics>
+where
+    T: FmtLabels + Hash + Eq,
+{
+    fn ejected_endpoints(&self) -> Metric<'_, Prefixed<'_, &'static str>, Gauge> {
+        Metric::new(
+            self.prefix_key("ejected_endpoints"),
+            "The number of endpoints currently ejected from the balancer after consecutive failures.",
+        )
+    }
+}
+
+impl<T> FmtMetrics for Report<T, Metrics>
+where
+    T: FmtLabels + Hash + Eq,
+{
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut registry = self.registry.lock();
+        trace!(
+            prefix = %self.prefix,
+            targets = %registry.len(),
+            "Formatting HTTP ejection metrics",
+        );
+
+        if registry.is_empty() {
+            return Ok(());
+        }
+
+        let metric = self.ejected_endpoints();
+        metric.fmt_help(f)?;
+        for (tgt, tm) in registry.iter() {
+            let m = tm.lock();
+            m.ejected.fmt_metric_labeled(f, &metric.name, tgt)?;
+        }
+
+        registry.retain_since(Instant::now() - self.retain_idle);
+
+        Ok(())
+    }
+}
@@ -1,11 +1,13 @@
 #![deny(rust_2018_idioms, clippy::disallowed_methods, clippy::disallowed_types)]
 #![forbid(unsafe_code)]
 
-pub use self::{requests::Requests, retries::Retries};
+pub use self::{ejections::Ejections, mirrors::Mirrors, requests::Requests, retries::Retries};
 use linkerd_metrics::SharedStore;
 use parking_lot::Mutex;
 use std::{fmt, hash::Hash, time::Duration};
 
+pub mod ejections;
+pub mod mirrors;
 pub mod requests;
 pub mod retries;
 
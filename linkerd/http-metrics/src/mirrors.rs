@@ -0,0 +1,173 @@
+use super::{Prefixed, Registry, Report};
+use linkerd_metrics::{Counter, FmtLabels, FmtMetric, FmtMetrics, LastUpdate, Metric};
+use parking_lot::Mutex;
+use std::{fmt, hash::Hash, sync::Arc};
+use tokio::time::{Duration, Instant};
+use tracing::trace;
+
+#[derive(Debug)]
+pub struct Mirrors<T>(Registry<T, Metrics>)
+where
+    T: Hash + Eq;
+
+#[derive(Clone, Debug)]
+pub struct Handle(Arc<Mutex<Metrics>>);
+
+#[derive(Debug)]
+pub struct Metrics {
+    last_update: Instant,
+    mirrored: Counter,
+    skipped: Counter,
+    failed: Counter,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum ResultLabel {
+    Mirrored,
+    Skipped,
+    Failed,
+}
+
+// === impl Mirrors ===
+
+impl<T: Hash + Eq> Default for Mirrors<T> {
+    fn default() -> Self {
+        Mirrors(Registry::default())
+    }
+}
+
+impl<T: Hash + Eq> Mirrors<T> {
+    pub fn into_report(self, retain_idle: Duration) -> Report<T, Metrics> {
+        Report::new(retain_idle, self.0)
+    }
+
+    pub fn get_handle(&self, target: T) -> Handle {
+        let mut reg = self.0.lock();
+        Handle(reg.entry(target).or_default().clone())
+    }
+}
+
+impl<T: Hash + Eq> Clone for Mirrors<T> {
+    fn clone(&self) -> Self {
+        Mirrors(self.0.clone())
+    }
+}
+
+// === impl Handle ===
+
+impl Handle {
+    /// Records that a request was dispatched to a mirror.
+    pub fn incr_mirrored(&self) {
+        self.incr(|m| &m.mirrored);
+    }
+
+    /// Records that a request was selected for mirroring but could not be
+    /// mirrored, i.e. because its body could not be buffered.
+    pub fn incr_skipped(&self) {
+        self.incr(|m| &m.skipped);
+    }
+
+    /// Records that a mirrored request failed.
+    pub fn incr_failed(&self) {
+        self.incr(|m| &m.failed);
+    }
+
+    /// Returns the number of requests that were dispatched to a mirror.
+    pub fn mirrored(&self) -> u64 {
+        u64::from(&self.0.lock().mirrored)
+    }
+
+    /// Returns the number of requests that could not be mirrored.
+    pub fn skipped(&self) -> u64 {
+        u64::from(&self.0.lock().skipped)
+    }
+
+    /// Returns the number of mirrored requests that failed.
+    pub fn failed(&self) -> u64 {
+        u64::from(&self.0.lock().failed)
+    }
+
+    fn incr(&self, counter: impl FnOnce(&Metrics) -> &Counter) {
+        let mut m = self.0.lock();
+        m.last_update = Instant::now();
+        counter(&m).incr();
+    }
+}
+
+// === impl Metrics ===
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            last_update: Instant::now(),
+            mirrored: Counter::default(),
+            skipped: Counter::default(),
+            failed: Counter::default(),
+        }
+    }
+}
+
+impl LastUpdate for Metrics {
+    fn last_update(&self) -> Instant {
+        self.last_update
+    }
+}
+
+// === impl Report ===
+
+impl<T> Report<T, Metrics>
+where
+    T: FmtLabels + Hash + Eq,
+{
+    fn mirror_total(&self) -> Metric<'_, Prefixed<'_, &'static str>, Counter> {
+        Metric::new(
+            self.prefix_key("mirror_total"),
+            "Total count of HTTP requests selected for mirroring.",
+        )
+    }
+}
+
+impl<T> FmtMetrics for Report<T, Metrics>
+where
+    T: FmtLabels + Hash + Eq,
+{
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut registry = self.registry.lock();
+        trace!(
+            prefix = %self.prefix,
+            targets = %registry.len(),
+            "Formatting HTTP mirror metrics",
+        );
+
+        if registry.is_empty() {
+            return Ok(());
+        }
+
+        let metric = self.mirror_total();
+        metric.fmt_help(f)?;
+        for (tgt, tm) in registry.iter() {
+            let m = tm.lock();
+            m.mirrored
+                .fmt_metric_labeled(f, &metric.name, (tgt, ResultLabel::Mirrored))?;
+            m.skipped
+                .fmt_metric_labeled(f, &metric.name, (tgt, ResultLabel::Skipped))?;
+            m.failed
+                .fmt_metric_labeled(f, &metric.name, (tgt, ResultLabel::Failed))?;
+        }
+
+        registry.retain_since(Instant::now() - self.retain_idle);
+
+        Ok(())
+    }
+}
+
+impl FmtLabels for ResultLabel {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let result = match self {
+            Self::Mirrored => "mirrored",
+            Self::Skipped => "skipped",
+            Self::Failed => "failed",
+        };
+        write!(f, "result=\"{}\"", result)
+    }
+}
@@ -18,6 +18,8 @@ pub struct Metrics {
     last_update: Instant,
     retryable: Counter,
     no_budget: Counter,
+    hedges: Counter,
+    hedges_no_budget: Counter,
 }
 
 struct NoBudgetLabel;
@@ -58,6 +60,15 @@ impl Handle {
             m.no_budget.incr();
         }
     }
+
+    pub fn incr_hedge(&self, has_budget: bool) {
+        let mut m = self.0.lock();
+        m.last_update = Instant::now();
+        m.hedges.incr();
+        if !has_budget {
+            m.hedges_no_budget.incr();
+        }
+    }
 }
 
 // === impl Metrics ===
@@ -68,6 +79,8 @@ impl Default for Metrics {
             last_update: Instant::now(),
             retryable: Counter::default(),
             no_budget: Counter::default(),
+            hedges: Counter::default(),
+            hedges_no_budget: Counter::default(),
         }
     }
 }
@@ -90,6 +103,13 @@ where
             "Total count of retryable HTTP responses.",
         )
     }
+
+    fn hedges_total(&self) -> Metric<'_, Prefixed<'_, &'static str>, Counter> {
+        Metric::new(
+            self.prefix_key("hedges_total"),
+            "Total count of HTTP requests that were eligible to be hedged.",
+        )
+    }
 }
 
 impl<T> FmtMetrics for Report<T, Metrics>
@@ -117,6 +137,15 @@ where
                 .fmt_metric_labeled(f, &metric.name, (tgt, NoBudgetLabel))?;
         }
 
+        let metric = self.hedges_total();
+        metric.fmt_help(f)?;
+        for (tgt, tm) in registry.iter() {
+            let m = tm.lock();
+            m.hedges.fmt_metric_labeled(f, &metric.name, tgt)?;
+            m.hedges_no_budget
+                .fmt_metric_labeled(f, &metric.name, (tgt, NoBudgetLabel))?;
+        }
+
         registry.retain_since(Instant::now() - self.retain_idle);
 
         Ok(())
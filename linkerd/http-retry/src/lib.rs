@@ -4,4 +4,7 @@
 pub mod replay;
 pub mod with_trailers;
 
-pub use self::{replay::ReplayBody, with_trailers::WithTrailers};
+pub use self::{
+    replay::{ReplayBody, Spill},
+    with_trailers::WithTrailers,
+};
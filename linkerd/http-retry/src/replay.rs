@@ -3,8 +3,19 @@ use http::HeaderMap;
 use http_body::{Body, SizeHint};
 use linkerd_error::Error;
 use parking_lot::Mutex;
-use std::{collections::VecDeque, io::IoSlice, pin::Pin, sync::Arc, task::Context, task::Poll};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    future::Future,
+    io::{self, IoSlice, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    task::Context,
+    task::Poll,
+};
 use thiserror::Error;
+use tokio::task::{self, JoinHandle};
 
 /// Wraps an HTTP body type and lazily buffers data as it is read from the inner
 /// body.
@@ -35,6 +46,21 @@ pub struct ReplayBody<B> {
 
     /// Should this clone replay trailers from the shared state?
     replay_trailers: bool,
+
+    /// The offset of the next spilled data to be replayed by this clone, if
+    /// it has not yet replayed all spilled data.
+    replay_spill: Option<u64>,
+}
+
+/// Configures buffered bodies to spill data to a temporary file once the data
+/// buffered in memory reaches a limit.
+///
+/// Spilled data is written and read on blocking tasks, so that file I/O does
+/// not block the runtime. The directory should be on a local filesystem.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Spill {
+    dir: Arc<Path>,
+    memory_limit: usize,
 }
 
 #[derive(Debug, Error)]
@@ -68,6 +94,8 @@ struct SharedState<B> {
     was_empty: bool,
 
     orig_size_hint: SizeHint,
+
+    spill: Option<Spill>,
 }
 
 #[derive(Debug)]
@@ -77,10 +105,41 @@ struct BodyState<B> {
     rest: Option<B>,
     is_completed: bool,
 
+    /// Data buffered beyond the spill memory limit.
+    spilled: Option<Spilled>,
+
     /// Maximum number of bytes to buffer.
     max_bytes: usize,
 }
 
+/// Buffered data written to an anonymous temporary file.
+///
+/// The file is only accessed on blocking tasks. A body waits for the task
+/// using the file, if any, before it writes or reads more data, so that data is
+/// written and replayed in order.
+#[derive(Debug)]
+struct Spilled {
+    dir: Arc<Path>,
+
+    /// The file, once it has been created, unless a task is using it.
+    file: Option<File>,
+
+    /// The task writing to or reading from the file, if any.
+    task: Option<JoinHandle<SpillResult>>,
+
+    /// Data read by the last completed task, with its offset.
+    read: Option<(u64, Bytes)>,
+
+    /// The number of bytes written, or being written, to the file.
+    len: u64,
+}
+
+/// The file, if it has not been lost, and the result of a spill task.
+type SpillResult = (Option<File>, io::Result<Option<(u64, Bytes)>>);
+
+/// The maximum size of each chunk of spilled data that is replayed.
+const SPILL_CHUNK_SIZE: usize = 64 * 1024;
+
 // === impl ReplayBody ===
 
 impl<B: Body> ReplayBody<B> {
@@ -95,6 +154,17 @@ impl<B: Body> ReplayBody<B> {
     /// If the body has a size hint with a lower bound greater than `max_bytes`, the original body
     /// is returned in the error variant.
     pub fn try_new(body: B, max_bytes: usize) -> Result<Self, B> {
+        Self::try_new_inner(body, max_bytes, None)
+    }
+
+    /// Wraps an initial `Body` in a `ReplayBody` that buffers at most
+    /// `max_bytes`, spilling buffered data to a temporary file once the
+    /// spill's memory limit is reached.
+    pub fn try_with_spill(body: B, max_bytes: usize, spill: Spill) -> Result<Self, B> {
+        Self::try_new_inner(body, max_bytes, Some(spill))
+    }
+
+    fn try_new_inner(body: B, max_bytes: usize, spill: Option<Spill>) -> Result<Self, B> {
         let orig_size_hint = body.size_hint();
         tracing::trace!(body.size_hint = %orig_size_hint.lower(), %max_bytes);
         if orig_size_hint.lower() > max_bytes as u64 {
@@ -106,17 +176,20 @@ impl<B: Body> ReplayBody<B> {
                 body: Mutex::new(None),
                 orig_size_hint,
                 was_empty: body.is_end_stream(),
+                spill,
             }),
             state: Some(BodyState {
                 buf: Default::default(),
                 trailers: None,
                 rest: Some(body),
                 is_completed: false,
+                spilled: None,
                 max_bytes: max_bytes + 1,
             }),
             // The initial `ReplayBody` has nothing to replay
             replay_body: false,
             replay_trailers: false,
+            replay_spill: None,
         })
     }
 
@@ -153,6 +226,22 @@ impl<B: Body> ReplayBody<B> {
                     .is_capped()
             })
     }
+
+    /// Returns `true` if this clone may be polled while other clones exist,
+    /// because the body has been read to completion (or was empty) and
+    /// released by the clone that read it.
+    ///
+    /// Unlike a retry, a hedged request is sent while the initial request may
+    /// still be in flight, so its body must not be polled unless this is
+    /// `true`.
+    pub fn is_replayable(&self) -> bool {
+        if let Some(state) = self.state.as_ref() {
+            return state.is_completed && !state.is_capped();
+        }
+        self.shared.body.lock().as_ref().map_or(false, |state| {
+            (state.is_completed || self.shared.was_empty) && !state.is_capped()
+        })
+    }
 }
 
 impl<B> Body for ReplayBody<B>
@@ -169,6 +258,18 @@ where
     ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
         let this = self.get_mut();
         let state = Self::acquire_state(&mut this.state, &this.shared.body);
+
+        // Wait for prior data to be spilled before buffering or replaying more
+        // data. If it can't be spilled, the body can't be replayed.
+        if let Some(spilled) = state.spilled.as_mut() {
+            if let Err(error) = futures::ready!(spilled.poll_idle(cx)) {
+                tracing::warn!(%error, "Failed to spill body to disk, discarding buffer");
+                state.buf = Default::default();
+                state.spilled = None;
+                state.max_bytes = 0;
+            }
+        }
+
         // Move these out to avoid mutable borrow issues in the `map` closure
         // when polling the inner body.
         tracing::trace!(
@@ -195,6 +296,25 @@ where
             }
         }
 
+        // Replay any data that was spilled to disk after the data buffered in
+        // memory.
+        if let Some(offset) = this.replay_spill {
+            match state.spilled.as_mut() {
+                Some(spilled) if offset < spilled.len => {
+                    let bytes = match futures::ready!(spilled.poll_read_at(cx, offset)) {
+                        Ok(bytes) => bytes,
+                        Err(error) => return Poll::Ready(Some(Err(error.into()))),
+                    };
+                    tracing::trace!(offset, len = bytes.len(), "Replaying spilled body");
+                    this.replay_spill = Some(offset + bytes.len() as u64);
+                    let mut buf = BufList::default();
+                    buf.bufs.push_back(bytes);
+                    return Poll::Ready(Some(Ok(Data::Replay(buf))));
+                }
+                _ => this.replay_spill = None,
+            }
+        }
+
         // If the inner body has previously ended, don't poll it again.
         //
         // NOTE(eliza): we would expect the inner body to just happily return
@@ -235,14 +355,27 @@ where
         let chunk = if state.is_capped() {
             // If there's data in the buffer, discard it now, since we won't
             // allow any clones to have a complete body.
-            if state.buf.has_remaining() {
+            if state.buf.has_remaining() || state.spilled.is_some() {
                 tracing::debug!(
                     buf.size = state.buf.remaining(),
                     "Buffered maximum capacity, discarding buffer"
                 );
                 state.buf = Default::default();
+                state.spilled = None;
             }
             data.copy_to_bytes(length)
+        } else if let Some(spill) = this.shared.spill.as_ref().filter(|spill| {
+            state.spilled.is_some() || state.buf.remaining() + length > spill.memory_limit
+        }) {
+            // Once data has been spilled, all subsequent data must be spilled
+            // so that it is replayed in order. The data is returned while it
+            // is written.
+            let bytes = data.copy_to_bytes(length);
+            state
+                .spilled
+                .get_or_insert_with(|| Spilled::new(spill))
+                .write(bytes.clone());
+            bytes
         } else {
             // Buffer and return the bytes.
             state.buf.push_chunk(data)
@@ -299,9 +432,16 @@ where
             .and_then(|state| state.rest.as_ref().map(Body::is_end_stream))
             .unwrap_or(false);
 
+        // if this clone has not replayed all spilled data, it is not EOS
+        let has_spilled = match (self.replay_spill, self.state.as_ref()) {
+            (Some(offset), Some(state)) => state.spilled.as_ref().map_or(false, |s| s.len > offset),
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+
         // if this body has data or trailers remaining to play back, it
         // is not EOS
-        !self.replay_body && !self.replay_trailers
+        !self.replay_body && !self.replay_trailers && !has_spilled
             // if we have replayed everything, the initial body may
             // still have data remaining, so ask it
             && is_inner_eos
@@ -317,7 +457,7 @@ where
 
         // Otherwise, if we're holding the state but have dropped the inner
         // body, the entire body is buffered so we know the exact size hint.
-        let buffered = state.buf.remaining() as u64;
+        let buffered = state.buf.remaining() as u64 + state.spilled.as_ref().map_or(0, |s| s.len);
         let rest_hint = match state.rest.as_ref() {
             Some(rest) => rest.size_hint(),
             None => return SizeHint::with_exact(buffered),
@@ -344,6 +484,20 @@ impl<B> Clone for ReplayBody<B> {
             // reading any additional data from the initial body.
             replay_body: true,
             replay_trailers: true,
+            replay_spill: self.shared.spill.as_ref().map(|_| 0),
+        }
+    }
+}
+
+// === impl Spill ===
+
+impl Spill {
+    /// Spills buffered data to temporary files in `dir` once more than
+    /// `memory_limit` bytes of a body are buffered in memory.
+    pub fn new(dir: impl Into<PathBuf>, memory_limit: usize) -> Self {
+        Self {
+            dir: dir.into().into(),
+            memory_limit,
         }
     }
 }
@@ -500,6 +654,82 @@ impl<B> BodyState<B> {
     }
 }
 
+// === impl Spilled ===
+
+impl Spilled {
+    fn new(spill: &Spill) -> Self {
+        Self {
+            dir: spill.dir.clone(),
+            file: None,
+            task: None,
+            read: None,
+            len: 0,
+        }
+    }
+
+    /// Appends `bytes` to the file, creating it if necessary.
+    ///
+    /// The file must not be in use.
+    fn write(&mut self, bytes: Bytes) {
+        debug_assert!(self.task.is_none(), "spilled file must not be in use");
+        let file = self.file.take();
+        let dir = self.dir.clone();
+        self.len += bytes.len() as u64;
+        self.task = Some(task::spawn_blocking(move || {
+            let mut file = match file.map_or_else(|| tempfile::tempfile_in(&*dir), Ok) {
+                Ok(file) => file,
+                Err(error) => return (None, Err(error)),
+            };
+            let res = file
+                .seek(SeekFrom::End(0))
+                .and_then(|_| file.write_all(&bytes))
+                .map(|()| None);
+            (Some(file), res)
+        }));
+    }
+
+    /// Reads a chunk of spilled data starting at `offset`.
+    fn poll_read_at(&mut self, cx: &mut Context<'_>, offset: u64) -> Poll<io::Result<Bytes>> {
+        loop {
+            futures::ready!(self.poll_idle(cx))?;
+            if let Some((_, bytes)) = self.read.take().filter(|(o, _)| *o == offset) {
+                return Poll::Ready(Ok(bytes));
+            }
+
+            let file = self.file.take();
+            let len = self.len.saturating_sub(offset).min(SPILL_CHUNK_SIZE as u64) as usize;
+            self.task = Some(task::spawn_blocking(move || {
+                let mut file = match file {
+                    Some(file) => file,
+                    None => {
+                        let error = io::Error::new(io::ErrorKind::NotFound, "missing spill file");
+                        return (None, Err(error));
+                    }
+                };
+                let mut buf = vec![0; len];
+                let res = file
+                    .seek(SeekFrom::Start(offset))
+                    .and_then(|_| file.read_exact(&mut buf))
+                    .map(|()| Some((offset, Bytes::from(buf))));
+                (Some(file), res)
+            }));
+        }
+    }
+
+    /// Waits for the task using the file, if any, to complete.
+    fn poll_idle(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let Some(task) = self.task.as_mut() {
+            let res = futures::ready!(Pin::new(task).poll(cx));
+            self.task = None;
+            let (file, res) = res
+                .unwrap_or_else(|error| (None, Err(io::Error::new(io::ErrorKind::Other, error))));
+            self.file = file;
+            self.read = res?;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -683,6 +913,28 @@ mod tests {
         assert_eq!(replay2_tlrs.as_ref(), Some(&tlrs));
     }
 
+    #[tokio::test(flavor = "current_thread")]
+    async fn replayable_once_released() {
+        let Test {
+            mut tx,
+            mut initial,
+            replay,
+            _trace,
+        } = Test::new();
+
+        tx.send_data("hello world").await;
+        drop(tx);
+        assert!(!replay.is_replayable());
+
+        assert_eq!(body_to_string(&mut initial).await, "hello world");
+        // The initial body still owns the buffered data.
+        assert!(!replay.is_replayable());
+
+        drop(initial);
+        assert!(replay.is_replayable());
+        assert_eq!(body_to_string(replay).await, "hello world");
+    }
+
     #[tokio::test(flavor = "current_thread")]
     async fn multiple_incomplete_replays() {
         let Test {
@@ -856,6 +1108,62 @@ mod tests {
         assert!(err.is::<Capped>())
     }
 
+    #[tokio::test(flavor = "current_thread")]
+    async fn replays_spilled_body() {
+        // Test that data buffered beyond the spill's memory limit is written
+        // to disk and replayed after the data buffered in memory.
+        let _trace = linkerd_tracing::test::with_default_filter("linkerd_http_retry=trace");
+
+        let (mut tx, body) = hyper::Body::channel();
+        let spill = Spill::new(std::env::temp_dir(), 8);
+        let mut initial = ReplayBody::try_with_spill(body, 64, spill)
+            .expect("channel body must not be too large");
+        let mut replay = initial.clone();
+
+        tx.send_data(Bytes::from("aaaaaa")).await.unwrap();
+        assert_eq!(chunk(&mut initial).await, Some("aaaaaa".to_string()));
+        tx.send_data(Bytes::from("bbbbbb")).await.unwrap();
+        assert_eq!(chunk(&mut initial).await, Some("bbbbbb".to_string()));
+        tx.send_data(Bytes::from("cccccc")).await.unwrap();
+        assert_eq!(chunk(&mut initial).await, Some("cccccc".to_string()));
+        drop(tx);
+        assert_eq!(chunk(&mut initial).await, None);
+        drop(initial);
+
+        assert_eq!(chunk(&mut replay).await, Some("aaaaaa".to_string()));
+        assert_eq!(replay.size_hint().exact(), Some(18));
+        assert_eq!(chunk(&mut replay).await, Some("bbbbbbcccccc".to_string()));
+        assert_eq!(chunk(&mut replay).await, None);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn discards_body_when_spill_fails() {
+        // Test that, when data can't be spilled, the initial body continues
+        // but the replay fails.
+        let _trace = linkerd_tracing::test::with_default_filter("linkerd_http_retry=trace");
+
+        let (mut tx, body) = hyper::Body::channel();
+        let spill = Spill::new(std::env::temp_dir().join("linkerd-http-retry-missing"), 4);
+        let mut initial = ReplayBody::try_with_spill(body, 64, spill)
+            .expect("channel body must not be too large");
+        let mut replay = initial.clone();
+
+        tx.send_data(Bytes::from("aaaaaa")).await.unwrap();
+        assert_eq!(chunk(&mut initial).await, Some("aaaaaa".to_string()));
+        tx.send_data(Bytes::from("bbbbbb")).await.unwrap();
+        assert_eq!(chunk(&mut initial).await, Some("bbbbbb".to_string()));
+        drop(tx);
+        assert_eq!(chunk(&mut initial).await, None);
+        drop(initial);
+
+        let err = replay
+            .data()
+            .await
+            .expect("replay must yield Some(Err(..)) when capped")
+            .expect_err("replay must error when cappped");
+        assert!(err.is::<Capped>())
+    }
+
     #[tokio::test(flavor = "current_thread")]
     async fn caps_across_replays() {
         // Test that, when the initial body is longer than the preconfigured
@@ -1,6 +1,6 @@
 pub mod r#match;
 
-pub use self::r#match::MatchRoute;
+pub use self::r#match::{MatchRoute, MatchRpc};
 #[cfg(test)]
 mod tests;
 
@@ -185,3 +185,29 @@ fn multiple() {
         .unwrap();
     assert_eq!(m.match_request(&req), None);
 }
+
+#[test]
+fn summarizes_rpc_match() {
+    let req = http::Request::builder()
+        .method(http::Method::POST)
+        .uri("http://example.com/foo/bar")
+        .body(())
+        .unwrap();
+
+    let m = MatchRoute {
+        rpc: MatchRpc {
+            service: Some("foo".to_string()),
+            method: None,
+        },
+        ..MatchRoute::default()
+    };
+    let summary = m.match_request(&req).expect("must match");
+    assert!(summary.is_service_match());
+    assert!(!summary.is_method_match());
+
+    let summary = MatchRoute::default()
+        .match_request(&req)
+        .expect("must match");
+    assert!(!summary.is_service_match());
+    assert!(!summary.is_method_match());
+}
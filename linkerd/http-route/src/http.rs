@@ -1,8 +1,12 @@
+pub mod filter;
 pub mod r#match;
 #[cfg(test)]
 mod tests;
 
-pub use self::r#match::{HostMatch, MatchHeader, MatchHost, MatchRequest};
+pub use self::{
+    filter::Filter,
+    r#match::{HostMatch, MatchHeader, MatchHost, MatchRequest},
+};
 
 pub type RouteMatch = crate::RouteMatch<r#match::RequestMatch>;
 
@@ -0,0 +1,34 @@
+pub mod inject_fault;
+pub mod modify_header;
+pub mod modify_path;
+pub mod redirect;
+#[cfg(test)]
+mod tests;
+
+pub use self::{
+    inject_fault::{FaultAbort, FaultDelay, InjectFault, InvalidRatio, Ratio},
+    modify_header::ModifyHeader,
+    modify_path::ModifyPath,
+    redirect::{InvalidRedirect, RedirectRequest, Redirection},
+};
+
+/// Modifies a request (or its response) once its route has been matched.
+///
+/// Filters are applied in the order in which they are configured on a route.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub enum Filter {
+    /// Modifies the headers of the request before it is dispatched.
+    RequestHeaders(ModifyHeader),
+
+    /// Modifies the headers of the response before it is returned.
+    ResponseHeaders(ModifyHeader),
+
+    /// Rewrites the request's path before it is dispatched.
+    RewritePath(ModifyPath),
+
+    /// Responds to the request with a redirect instead of dispatching it.
+    Redirect(RedirectRequest),
+
+    /// Fails or delays a fraction of requests.
+    InjectFault(InjectFault),
+}
@@ -0,0 +1,83 @@
+use http::StatusCode;
+use std::time::Duration;
+
+/// Injects failures and delays into a fraction of requests.
+///
+/// Requests are first considered for an abort and, if they are not aborted,
+/// then for a delay.
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct InjectFault {
+    pub abort: Option<FaultAbort>,
+    pub delay: Option<FaultDelay>,
+}
+
+/// Fails requests with a synthesized error response.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct FaultAbort {
+    pub http_status: StatusCode,
+
+    /// The `grpc-status` code used when aborting gRPC requests. When unset,
+    /// the code is derived from `http_status`.
+    pub grpc_status: Option<u16>,
+
+    pub ratio: Ratio,
+}
+
+/// Delays requests before they are dispatched.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct FaultDelay {
+    pub duration: Duration,
+    pub ratio: Ratio,
+}
+
+/// The fraction of requests to which a fault applies.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Ratio {
+    numerator: u32,
+    denominator: u32,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid ratio: {numerator}/{denominator}")]
+pub struct InvalidRatio {
+    numerator: u32,
+    denominator: u32,
+}
+
+// === impl Ratio ===
+
+impl Ratio {
+    /// A ratio that applies to all requests.
+    pub const ALL: Self = Self {
+        numerator: 1,
+        denominator: 1,
+    };
+
+    pub fn new(numerator: u32, denominator: u32) -> Result<Self, InvalidRatio> {
+        if denominator == 0 || numerator > denominator {
+            return Err(InvalidRatio {
+                numerator,
+                denominator,
+            });
+        }
+        Ok(Self {
+            numerator,
+            denominator,
+        })
+    }
+
+    pub fn numerator(&self) -> u32 {
+        self.numerator
+    }
+
+    /// Always non-zero and no less than the numerator.
+    pub fn denominator(&self) -> u32 {
+        self.denominator
+    }
+}
+
+impl Default for Ratio {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
@@ -0,0 +1,32 @@
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+
+/// Modifies a set of HTTP headers.
+///
+/// Headers are first added, then set, and then removed.
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct ModifyHeader {
+    /// Headers to be appended to any existing values.
+    pub add: Vec<(HeaderName, HeaderValue)>,
+
+    /// Headers to be set, replacing any existing values.
+    pub set: Vec<(HeaderName, HeaderValue)>,
+
+    /// Headers to be removed.
+    pub remove: Vec<HeaderName>,
+}
+
+// === impl ModifyHeader ===
+
+impl ModifyHeader {
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        for (name, value) in &self.add {
+            headers.append(name, value.clone());
+        }
+        for (name, value) in &self.set {
+            headers.insert(name, value.clone());
+        }
+        for name in &self.remove {
+            headers.remove(name);
+        }
+    }
+}
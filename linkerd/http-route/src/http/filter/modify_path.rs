@@ -0,0 +1,42 @@
+/// Rewrites a request path.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub enum ModifyPath {
+    /// Replaces the entire path.
+    ReplaceFullPath(String),
+
+    /// Replaces the leading `prefix` of the path with `replacement`.
+    ///
+    /// As with `MatchPath::Prefix`, the prefix must match whole path segments.
+    /// Paths that do not start with the prefix are not modified.
+    ReplacePrefixMatch { prefix: String, replacement: String },
+}
+
+// === impl ModifyPath ===
+
+impl ModifyPath {
+    /// Returns the rewritten path, or `None` if the path is not modified.
+    pub fn apply(&self, path: &str) -> Option<String> {
+        match self {
+            Self::ReplaceFullPath(p) => Some(p.clone()),
+
+            Self::ReplacePrefixMatch {
+                prefix,
+                replacement,
+            } => {
+                let prefix = prefix.trim_end_matches('/');
+                let rest = path.strip_prefix(prefix)?;
+                if !rest.is_empty() && !rest.starts_with('/') {
+                    return None;
+                }
+
+                let replacement = replacement.trim_end_matches('/');
+                let path = match (replacement.is_empty(), rest.is_empty()) {
+                    (true, true) => "/".to_string(),
+                    (true, false) => rest.to_string(),
+                    (false, _) => format!("{}{}", replacement, rest),
+                };
+                Some(path)
+            }
+        }
+    }
+}
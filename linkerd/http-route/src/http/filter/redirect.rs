@@ -0,0 +1,108 @@
+use super::ModifyPath;
+use http::{
+    uri::{Authority, InvalidUri, PathAndQuery, Scheme, Uri},
+    StatusCode,
+};
+
+/// Configures a redirect response for a request.
+///
+/// Unset fields are taken from the original request.
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct RedirectRequest {
+    pub scheme: Option<Scheme>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub path: Option<ModifyPath>,
+
+    /// The status of the redirect response. Defaults to `302 Found`.
+    pub status: Option<StatusCode>,
+}
+
+/// A redirect response to be returned for a request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Redirection {
+    pub status: StatusCode,
+    pub location: Uri,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidRedirect {
+    #[error("redirect status must be 3xx: {0}")]
+    Status(StatusCode),
+
+    #[error("request does not have a host to redirect to")]
+    MissingHost,
+
+    #[error("invalid redirect location: {0}")]
+    Location(#[from] InvalidUri),
+
+    #[error("invalid redirect location: {0}")]
+    Uri(#[from] http::Error),
+}
+
+// === impl RedirectRequest ===
+
+impl RedirectRequest {
+    /// Builds a redirect for a request.
+    ///
+    /// The request's authority is determined from its URI or, if the URI is in
+    /// origin-form, its `host` header.
+    pub fn redirect<B>(&self, req: &http::Request<B>) -> Result<Redirection, InvalidRedirect> {
+        let status = self.status.unwrap_or(StatusCode::FOUND);
+        if !status.is_redirection() {
+            return Err(InvalidRedirect::Status(status));
+        }
+
+        let orig_authority = req.uri().authority().cloned().or_else(|| {
+            req.headers()
+                .get(http::header::HOST)
+                .and_then(|h| h.to_str().ok())
+                .and_then(|h| h.parse::<Authority>().ok())
+        });
+
+        let host = match self.host.as_deref() {
+            Some(host) => host,
+            None => orig_authority
+                .as_ref()
+                .map(|a| a.host())
+                .ok_or(InvalidRedirect::MissingHost)?,
+        };
+
+        // When the scheme is changed, the original port is not preserved so
+        // that the default port for the new scheme is used.
+        let port = self.port.or_else(|| {
+            if self.scheme.is_some() {
+                return None;
+            }
+            orig_authority.as_ref().and_then(|a| a.port_u16())
+        });
+        let authority = match port {
+            Some(port) => format!("{}:{}", host, port).parse::<Authority>()?,
+            None => host.parse::<Authority>()?,
+        };
+
+        let scheme = self
+            .scheme
+            .clone()
+            .or_else(|| req.uri().scheme().cloned())
+            .unwrap_or(Scheme::HTTP);
+
+        let path = self.path.as_ref().and_then(|p| p.apply(req.uri().path()));
+        let path_and_query = match (path, req.uri().query()) {
+            (None, _) => req
+                .uri()
+                .path_and_query()
+                .cloned()
+                .unwrap_or_else(|| PathAndQuery::from_static("/")),
+            (Some(path), None) => path.parse()?,
+            (Some(path), Some(query)) => format!("{}?{}", path, query).parse()?,
+        };
+
+        let location = Uri::builder()
+            .scheme(scheme)
+            .authority(authority)
+            .path_and_query(path_and_query)
+            .build()?;
+        Ok(Redirection { status, location })
+    }
+}
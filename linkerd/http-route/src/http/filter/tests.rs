@@ -0,0 +1,134 @@
+use super::*;
+use http::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    uri::Scheme,
+    StatusCode,
+};
+
+#[test]
+fn modify_header() {
+    let mut headers = HeaderMap::new();
+    headers.insert("x-add", HeaderValue::from_static("a"));
+    headers.insert("x-set", HeaderValue::from_static("a"));
+    headers.insert("x-remove", HeaderValue::from_static("a"));
+
+    ModifyHeader {
+        add: vec![(
+            HeaderName::from_static("x-add"),
+            HeaderValue::from_static("b"),
+        )],
+        set: vec![(
+            HeaderName::from_static("x-set"),
+            HeaderValue::from_static("b"),
+        )],
+        remove: vec![HeaderName::from_static("x-remove")],
+    }
+    .apply(&mut headers);
+
+    let added = headers.get_all("x-add").iter().collect::<Vec<_>>();
+    assert_eq!(added, vec!["a", "b"]);
+    let set = headers.get_all("x-set").iter().collect::<Vec<_>>();
+    assert_eq!(set, vec!["b"]);
+    assert!(!headers.contains_key("x-remove"));
+}
+
+#[test]
+fn replace_prefix_match() {
+    let rewrite = |prefix: &str, replacement: &str, path: &str| {
+        ModifyPath::ReplacePrefixMatch {
+            prefix: prefix.to_string(),
+            replacement: replacement.to_string(),
+        }
+        .apply(path)
+    };
+
+    assert_eq!(rewrite("/foo", "/bar", "/foo"), Some("/bar".to_string()));
+    assert_eq!(
+        rewrite("/foo/", "/bar", "/foo/baz"),
+        Some("/bar/baz".to_string())
+    );
+    assert_eq!(rewrite("/foo", "/", "/foo/baz"), Some("/baz".to_string()));
+    assert_eq!(rewrite("/foo", "/", "/foo"), Some("/".to_string()));
+    assert_eq!(rewrite("/foo", "/bar", "/foobar"), None);
+    assert_eq!(rewrite("/foo", "/bar", "/baz"), None);
+}
+
+#[test]
+fn replace_full_path() {
+    assert_eq!(
+        ModifyPath::ReplaceFullPath("/bar".to_string()).apply("/foo/baz"),
+        Some("/bar".to_string())
+    );
+}
+
+#[test]
+fn redirect_defaults_to_request() {
+    let req = http::Request::builder()
+        .uri("/foo?bar=baz")
+        .header("host", "example.com:8080")
+        .body(())
+        .unwrap();
+
+    let Redirection { status, location } = RedirectRequest {
+        path: Some(ModifyPath::ReplacePrefixMatch {
+            prefix: "/foo".to_string(),
+            replacement: "/qux".to_string(),
+        }),
+        ..RedirectRequest::default()
+    }
+    .redirect(&req)
+    .expect("redirect must be valid");
+    assert_eq!(status, StatusCode::FOUND);
+    assert_eq!(location, "http://example.com:8080/qux?bar=baz");
+}
+
+#[test]
+fn redirect_scheme_resets_port() {
+    let req = http::Request::builder()
+        .uri("http://example.com:8080/foo")
+        .body(())
+        .unwrap();
+
+    let Redirection { status, location } = RedirectRequest {
+        scheme: Some(Scheme::HTTPS),
+        host: Some("example.org".to_string()),
+        status: Some(StatusCode::MOVED_PERMANENTLY),
+        ..RedirectRequest::default()
+    }
+    .redirect(&req)
+    .expect("redirect must be valid");
+    assert_eq!(status, StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(location, "https://example.org/foo");
+}
+
+#[test]
+fn redirect_invalid() {
+    let req = http::Request::builder().uri("/foo").body(()).unwrap();
+    assert!(matches!(
+        RedirectRequest::default().redirect(&req),
+        Err(InvalidRedirect::MissingHost)
+    ));
+
+    let req = http::Request::builder()
+        .uri("http://example.com/foo")
+        .body(())
+        .unwrap();
+    assert!(matches!(
+        RedirectRequest {
+            status: Some(StatusCode::OK),
+            ..RedirectRequest::default()
+        }
+        .redirect(&req),
+        Err(InvalidRedirect::Status(_))
+    ));
+}
+
+#[test]
+fn ratio() {
+    assert_eq!(Ratio::default(), Ratio::ALL);
+    let ratio = Ratio::new(1, 4).expect("ratio must be valid");
+    assert_eq!((ratio.numerator(), ratio.denominator()), (1, 4));
+    assert!(Ratio::new(0, 1).is_ok());
+    assert!(Ratio::new(1, 0).is_err());
+    assert!(Ratio::new(2, 1).is_err());
+}
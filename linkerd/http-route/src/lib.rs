@@ -99,6 +99,16 @@ pub fn find<'r, M: Match + 'r, P, B>(
     }))
 }
 
+// === impl RouteMatch ===
+
+impl<T> RouteMatch<T> {
+    /// Returns the summary of the rule match.
+    #[inline]
+    pub fn route(&self) -> &T {
+        &self.route
+    }
+}
+
 #[inline]
 fn best<M: Ord, P>(matches: impl Iterator<Item = (M, P)>) -> Option<(M, P)> {
     // This is roughly equivalent to `max_by(...)` but we want to ensure
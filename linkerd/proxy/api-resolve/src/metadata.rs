@@ -1,4 +1,6 @@
 use http::uri::Authority;
+use linkerd_proxy_discover::health::Probe;
+use linkerd_stack::Param;
 use linkerd_tls::client::ServerId;
 use std::collections::BTreeMap;
 
@@ -69,6 +71,11 @@ impl Metadata {
         self.labels.clone()
     }
 
+    /// Returns the endpoint's zone, if the destination service labels it.
+    pub fn zone(&self) -> Option<&str> {
+        self.labels.get("zone").map(String::as_str)
+    }
+
     pub fn protocol_hint(&self) -> ProtocolHint {
         self.protocol_hint
     }
@@ -91,3 +98,17 @@ impl Metadata {
         self.authority_override = None;
     }
 }
+
+impl Param<Option<ServerId>> for Metadata {
+    fn param(&self) -> Option<ServerId> {
+        self.identity.clone()
+    }
+}
+
+/// Endpoints request an HTTP or gRPC health check with a `health_check` label,
+/// e.g. `http:/ready` or `grpc`. Invalid labels are ignored.
+impl Param<Option<Probe>> for Metadata {
+    fn param(&self) -> Option<Probe> {
+        self.labels.get("health_check")?.parse().ok()
+    }
+}
@@ -0,0 +1,123 @@
+use linkerd_error::Error;
+use std::{
+    hash::Hash,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tower::{
+    discover::{Change, Discover},
+    ready_cache::{error::Failed, ReadyCache},
+};
+use tracing::{debug, trace};
+
+/// A discovered set of endpoints, tracked in the order they were discovered.
+pub(crate) struct Endpoints<D, Req>
+where
+    D: Discover,
+    D::Key: Hash,
+{
+    discover: D,
+    services: ReadyCache<D::Key, D::Service, Req>,
+    keys: Vec<D::Key>,
+}
+
+// === impl Endpoints ===
+
+impl<D, Req> Endpoints<D, Req>
+where
+    D: Discover + Unpin,
+    D::Key: Hash + Clone,
+    D::Error: Into<Error>,
+    D::Service: tower::Service<Req>,
+    <D::Service as tower::Service<Req>>::Error: Into<Error>,
+{
+    pub(crate) fn new(discover: D) -> Self {
+        Self {
+            discover,
+            services: ReadyCache::default(),
+            keys: Vec::new(),
+        }
+    }
+
+    /// Returns the keys of all discovered endpoints, whether or not they are
+    /// ready.
+    pub(crate) fn keys(&self) -> &[D::Key] {
+        &self.keys
+    }
+
+    pub(crate) fn ready_len(&self) -> usize {
+        self.services.ready_len()
+    }
+
+    /// Returns the index of the endpoint in the ready set, if it is ready.
+    pub(crate) fn ready_index(&self, key: &D::Key) -> Option<usize> {
+        self.services.get_ready(key).map(|(index, _, _)| index)
+    }
+
+    /// Processes discovery updates and drives pending endpoints to readiness.
+    ///
+    /// Returns true if the set of discovered endpoints changed.
+    pub(crate) fn poll_update(&mut self, cx: &mut Context<'_>) -> Result<bool, Error> {
+        let mut changed = false;
+        while let Poll::Ready(change) = Pin::new(&mut self.discover).poll_discover(cx) {
+            match change
+                .ok_or("discovery stream closed")?
+                .map_err(Into::<Error>::into)?
+            {
+                Change::Remove(key) => {
+                    trace!("remove");
+                    self.services.evict(&key);
+                    self.keys.retain(|k| *k != key);
+                }
+                Change::Insert(key, svc) => {
+                    trace!("insert");
+                    if !self.keys.contains(&key) {
+                        self.keys.push(key.clone());
+                    }
+                    // If this service already existed in the set, it will be
+                    // replaced as the new one becomes ready.
+                    self.services.push(key, svc);
+                }
+            }
+            changed = true;
+        }
+
+        loop {
+            match self.services.poll_pending(cx) {
+                Poll::Ready(Ok(())) | Poll::Pending => break,
+                Poll::Ready(Err(Failed(_, error))) => {
+                    // An individual service was lost; continue processing
+                    // pending services.
+                    debug!(%error, "dropping failed endpoint");
+                }
+            }
+        }
+        trace!(
+            ready = %self.services.ready_len(),
+            pending = %self.services.pending_len(),
+            "poll_unready"
+        );
+
+        Ok(changed)
+    }
+
+    /// Checks that the endpoint at the given index of the ready set is still
+    /// ready, moving it to the pending set otherwise.
+    pub(crate) fn check_ready_index(&mut self, cx: &mut Context<'_>, index: usize) -> bool {
+        match self.services.check_ready_index(cx, index) {
+            Ok(ready) => ready,
+            Err(Failed(_, error)) => {
+                debug!(%error, "endpoint failed");
+                false
+            }
+        }
+    }
+
+    pub(crate) fn call_ready_index(
+        &mut self,
+        index: usize,
+        req: Req,
+    ) -> <D::Service as tower::Service<Req>>::Future {
+        self.services.call_ready_index(index, req)
+    }
+}
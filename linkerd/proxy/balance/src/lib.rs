@@ -0,0 +1,415 @@
+//! Load balancers that distribute requests over a discovered set of endpoints.
+//!
+//! Each balancer is built with a [`LoadBalancer`] algorithm, so that the
+//! algorithm may be configured per-destination.
+
+#![deny(rust_2018_idioms, clippy::disallowed_methods, clippy::disallowed_types)]
+#![forbid(unsafe_code)]
+
+mod endpoints;
+mod ring_hash;
+mod round_robin;
+pub mod slow_start;
+pub mod zone;
+
+pub use self::{
+    ring_hash::RingHash,
+    round_robin::RoundRobin,
+    slow_start::{RampDiscover, SlowStart},
+    zone::{EndpointZone, NewZoned, PreferLocalZone, ZoneAware, Zoned},
+};
+use futures::{future, ready, TryFuture};
+use linkerd_error::Error;
+use linkerd_stack::{layer, Param};
+use pin_project::pin_project;
+use std::{
+    any::Any,
+    fmt,
+    future::Future,
+    hash::Hash,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tower::{
+    balance::p2c,
+    discover::Discover,
+    load::{
+        completion::TrackCompletionFuture, peak_ewma, pending_requests, PeakEwmaDiscover,
+        PendingRequestsDiscover, TrackCompletion,
+    },
+};
+
+/// Determines how a balancer selects an endpoint for each request.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LoadBalancer {
+    /// Chooses the less loaded of two random endpoints, where load is
+    /// estimated from each endpoint's peak-EWMA latency and pending requests.
+    PeakEwma,
+
+    /// Chooses the endpoint with fewer pending requests of two random
+    /// endpoints.
+    LeastRequest,
+
+    /// Cycles through endpoints in order.
+    RoundRobin,
+
+    /// Places endpoints on a hash ring so that requests with the same hash key
+    /// are dispatched to the same endpoint while it remains available.
+    RingHash(HashKey),
+}
+
+/// Identifies the part of a request that is hashed by a ring-hash balancer.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum HashKey {
+    /// The value of the named request header.
+    Header(http::header::HeaderName),
+
+    /// The value of the named request cookie.
+    Cookie(String),
+
+    /// The IP address of the client connected to the proxy.
+    ///
+    /// Outbound sidecar traffic always originates from the local application,
+    /// so this is only meaningful for proxies whose clients are remote, e.g.
+    /// in ingress mode.
+    ClientAddr,
+}
+
+/// Hashes requests for ring-hash balancers.
+///
+/// Requests without a hash are dispatched to a random endpoint.
+pub trait HashRequest<Req> {
+    fn hash_request(&self, req: &Req) -> Option<u64>;
+}
+
+/// A request hasher for requests that carry no hash key, so that ring-hash
+/// balancers distribute requests randomly.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NoHash(());
+
+/// A type-erased load handle.
+///
+/// Algorithms track load with distinct handle types. The handles are erased so
+/// that every algorithm produces the same response type. The inner handle is
+/// held only so that it is dropped when the response completes.
+pub struct LoadHandle(#[allow(dead_code)] Box<dyn Any + Send + Sync>);
+
+/// Erases the load handles tracked by a `C`-typed completion.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct EraseHandle<C>(C);
+
+/// Builds a balancer over each discovered set of endpoints, using the
+/// algorithm configured for the target.
+#[derive(Debug)]
+pub struct MakeBalance<M, C, H, Req> {
+    inner: M,
+    default_rtt: Duration,
+    decay: Duration,
+    completion: C,
+    slow_start: Option<SlowStart>,
+    _marker: PhantomData<fn(Req) -> H>,
+}
+
+#[pin_project]
+#[derive(Debug)]
+pub struct MakeFuture<F, C, H, Req> {
+    #[pin]
+    inner: F,
+    balancer: Option<(LoadBalancer, Duration, Duration, C, Option<SlowStart>)>,
+    _marker: PhantomData<fn(Req) -> H>,
+}
+
+/// A load balancer using one of several algorithms.
+pub struct Balance<D, C, H, Req>(Inner<D, C, H, Req>)
+where
+    D: Discover,
+    D::Key: Hash,
+    C: Clone;
+
+enum Inner<D, C, H, Req>
+where
+    D: Discover,
+    D::Key: Hash,
+    C: Clone,
+{
+    PeakEwma(p2c::Balance<RampDiscover<PeakEwmaDiscover<D, EraseHandle<C>>, D::Key>, Req>),
+    LeastRequest(
+        p2c::Balance<RampDiscover<PendingRequestsDiscover<D, EraseHandle<C>>, D::Key>, Req>,
+    ),
+    RoundRobin(RoundRobin<PendingRequestsDiscover<D, EraseHandle<C>>, Req>),
+    RingHash(RingHash<PendingRequestsDiscover<D, EraseHandle<C>>, H, Req>),
+}
+
+type LoadFuture<F, C, H, E> =
+    future::MapErr<TrackCompletionFuture<F, EraseHandle<C>, H>, fn(E) -> Error>;
+
+pub type ResponseFuture<F, C, E> = future::Either<
+    LoadFuture<F, C, peak_ewma::Handle, E>,
+    LoadFuture<F, C, pending_requests::Handle, E>,
+>;
+
+// === impl LoadBalancer ===
+
+impl Default for LoadBalancer {
+    fn default() -> Self {
+        Self::PeakEwma
+    }
+}
+
+// === impl NoHash ===
+
+impl From<HashKey> for NoHash {
+    fn from(key: HashKey) -> Self {
+        // e.g. TCP connections carry no headers, cookies, or per-request
+        // client, so they cannot be hashed.
+        tracing::warn!(
+            ?key,
+            "Ring-hash balancing is not supported for this protocol; balancing randomly"
+        );
+        Self(())
+    }
+}
+
+impl<Req> HashRequest<Req> for NoHash {
+    fn hash_request(&self, _: &Req) -> Option<u64> {
+        None
+    }
+}
+
+// === impl LoadHandle ===
+
+impl fmt::Debug for LoadHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("LoadHandle").finish()
+    }
+}
+
+// === impl EraseHandle ===
+
+impl<C> EraseHandle<C> {
+    pub fn new(completion: C) -> Self {
+        Self(completion)
+    }
+}
+
+impl<C, H, V> TrackCompletion<H, V> for EraseHandle<C>
+where
+    C: TrackCompletion<LoadHandle, V>,
+    H: Send + Sync + 'static,
+{
+    type Output = C::Output;
+
+    fn track_completion(&self, handle: H, value: V) -> Self::Output {
+        self.0.track_completion(LoadHandle(Box::new(handle)), value)
+    }
+}
+
+// === impl MakeBalance ===
+
+impl<M, C: Clone, H, Req> MakeBalance<M, C, H, Req> {
+    /// Returns a layer that builds balancers over the endpoints discovered by
+    /// an inner `MakeService`.
+    ///
+    /// The `default_rtt` and `decay` parameters configure peak-EWMA load
+    /// estimates. When `slow_start` is set, power-of-two-choices balancers
+    /// ramp up traffic to newly discovered endpoints.
+    pub fn layer(
+        default_rtt: Duration,
+        decay: Duration,
+        completion: C,
+        slow_start: Option<SlowStart>,
+    ) -> impl layer::Layer<M, Service = Self> + Clone {
+        layer::mk(move |inner| Self {
+            inner,
+            default_rtt,
+            decay,
+            completion: completion.clone(),
+            slow_start,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<M: Clone, C: Clone, H, Req> Clone for MakeBalance<M, C, H, Req> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            default_rtt: self.default_rtt,
+            decay: self.decay,
+            completion: self.completion.clone(),
+            slow_start: self.slow_start,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, M, C, H, Req> tower::Service<T> for MakeBalance<M, C, H, Req>
+where
+    T: Param<LoadBalancer>,
+    M: tower::Service<T>,
+    M::Response: Discover + Unpin,
+    <M::Response as Discover>::Key: Hash + Clone,
+    <M::Response as Discover>::Error: Into<Error>,
+    <M::Response as Discover>::Service: tower::Service<Req>,
+    <<M::Response as Discover>::Service as tower::Service<Req>>::Error: Into<Error>,
+    C: TrackCompletion<
+            LoadHandle,
+            <<M::Response as Discover>::Service as tower::Service<Req>>::Response,
+        > + Clone,
+    H: From<HashKey>,
+{
+    type Response = Balance<M::Response, C, H, Req>;
+    type Error = M::Error;
+    type Future = MakeFuture<M::Future, C, H, Req>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, target: T) -> Self::Future {
+        let balancer = (
+            target.param(),
+            self.default_rtt,
+            self.decay,
+            self.completion.clone(),
+            self.slow_start,
+        );
+        MakeFuture {
+            inner: self.inner.call(target),
+            balancer: Some(balancer),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F, C, H, Req> Future for MakeFuture<F, C, H, Req>
+where
+    F: TryFuture,
+    F::Ok: Discover + Unpin,
+    <F::Ok as Discover>::Key: Hash + Clone,
+    <F::Ok as Discover>::Error: Into<Error>,
+    <F::Ok as Discover>::Service: tower::Service<Req>,
+    <<F::Ok as Discover>::Service as tower::Service<Req>>::Error: Into<Error>,
+    C: TrackCompletion<LoadHandle, <<F::Ok as Discover>::Service as tower::Service<Req>>::Response>
+        + Clone,
+    H: From<HashKey>,
+{
+    type Output = Result<Balance<F::Ok, C, H, Req>, F::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let discover = ready!(this.inner.try_poll(cx))?;
+        let (balancer, default_rtt, decay, completion, slow_start) =
+            this.balancer.take().expect("polled after ready");
+        Poll::Ready(Ok(Balance::new(
+            balancer,
+            discover,
+            default_rtt,
+            decay,
+            completion,
+            slow_start,
+        )))
+    }
+}
+
+// === impl Balance ===
+
+impl<D, C, H, Req> Balance<D, C, H, Req>
+where
+    D: Discover + Unpin,
+    D::Key: Hash + Clone,
+    D::Error: Into<Error>,
+    D::Service: tower::Service<Req>,
+    <D::Service as tower::Service<Req>>::Error: Into<Error>,
+    C: TrackCompletion<LoadHandle, <D::Service as tower::Service<Req>>::Response> + Clone,
+    H: From<HashKey>,
+{
+    pub fn new(
+        balancer: LoadBalancer,
+        discover: D,
+        default_rtt: Duration,
+        decay: Duration,
+        completion: C,
+        slow_start: Option<SlowStart>,
+    ) -> Self {
+        let completion = EraseHandle(completion);
+        // Only power-of-two-choices balancers compare endpoint loads, so only
+        // they are slowly started.
+        let inner = match balancer {
+            LoadBalancer::PeakEwma => {
+                let loaded = PeakEwmaDiscover::new::<Req>(discover, default_rtt, decay, completion);
+                Inner::PeakEwma(p2c::Balance::new(RampDiscover::new(loaded, slow_start)))
+            }
+            LoadBalancer::LeastRequest => {
+                let loaded = PendingRequestsDiscover::new::<Req>(discover, completion);
+                Inner::LeastRequest(p2c::Balance::new(RampDiscover::new(loaded, slow_start)))
+            }
+            LoadBalancer::RoundRobin => {
+                let loaded = PendingRequestsDiscover::new::<Req>(discover, completion);
+                Inner::RoundRobin(RoundRobin::new(loaded))
+            }
+            LoadBalancer::RingHash(key) => {
+                let loaded = PendingRequestsDiscover::new::<Req>(discover, completion);
+                Inner::RingHash(RingHash::new(loaded, H::from(key)))
+            }
+        };
+        Self(inner)
+    }
+}
+
+impl<D, C, H, Req> tower::Service<Req> for Balance<D, C, H, Req>
+where
+    D: Discover + Unpin,
+    D::Key: Hash + Clone,
+    D::Error: Into<Error>,
+    D::Service: tower::Service<Req>,
+    <D::Service as tower::Service<Req>>::Error: Into<Error>,
+    C: TrackCompletion<LoadHandle, <D::Service as tower::Service<Req>>::Response> + Clone,
+    H: HashRequest<Req>,
+{
+    type Response = C::Output;
+    type Error = Error;
+    type Future = ResponseFuture<
+        <D::Service as tower::Service<Req>>::Future,
+        C,
+        <D::Service as tower::Service<Req>>::Error,
+    >;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match &mut self.0 {
+            Inner::PeakEwma(b) => b.poll_ready(cx),
+            Inner::LeastRequest(b) => b.poll_ready(cx),
+            Inner::RoundRobin(b) => b.poll_ready(cx),
+            Inner::RingHash(b) => b.poll_ready(cx),
+        }
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        match &mut self.0 {
+            Inner::PeakEwma(b) => future::Either::Left(b.call(req)),
+            Inner::LeastRequest(b) => future::Either::Right(b.call(req)),
+            Inner::RoundRobin(b) => future::Either::Right(b.call(req)),
+            Inner::RingHash(b) => future::Either::Right(b.call(req)),
+        }
+    }
+}
+
+impl<D, C, H, Req> fmt::Debug for Balance<D, C, H, Req>
+where
+    D: Discover,
+    D::Key: Hash,
+    C: Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let algorithm = match self.0 {
+            Inner::PeakEwma(_) => "PeakEwma",
+            Inner::LeastRequest(_) => "LeastRequest",
+            Inner::RoundRobin(_) => "RoundRobin",
+            Inner::RingHash(_) => "RingHash",
+        };
+        f.debug_tuple("Balance").field(&algorithm).finish()
+    }
+}
@@ -0,0 +1,214 @@
+use crate::{endpoints::Endpoints, HashRequest};
+use futures::{future, TryFutureExt};
+use linkerd_error::Error;
+use rand::{rngs::SmallRng, thread_rng, Rng, SeedableRng};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    task::{Context, Poll},
+};
+use tower::discover::Discover;
+
+/// The number of points each endpoint occupies on the ring.
+///
+/// More points distribute load more evenly at the cost of a larger ring.
+const POINTS_PER_ENDPOINT: u64 = 100;
+
+/// Dispatches requests to endpoints on a consistent hash ring.
+///
+/// Requests with the same hash are dispatched to the same endpoint while it
+/// remains ready. When an endpoint is unavailable, requests are dispatched to
+/// the next ready endpoint on the ring, so that only the unavailable
+/// endpoint's requests are redistributed.
+pub struct RingHash<D, H, Req>
+where
+    D: Discover,
+    D::Key: Hash,
+{
+    endpoints: Endpoints<D, Req>,
+    hasher: H,
+    ring: Vec<(u64, D::Key)>,
+    rng: SmallRng,
+}
+
+// === impl RingHash ===
+
+impl<D, H, Req> RingHash<D, H, Req>
+where
+    D: Discover + Unpin,
+    D::Key: Hash + Clone,
+    D::Error: Into<Error>,
+    D::Service: tower::Service<Req>,
+    <D::Service as tower::Service<Req>>::Error: Into<Error>,
+{
+    pub fn new(discover: D, hasher: H) -> Self {
+        Self {
+            endpoints: Endpoints::new(discover),
+            hasher,
+            ring: Vec::new(),
+            rng: SmallRng::from_rng(&mut thread_rng()).expect("RNG must be valid"),
+        }
+    }
+
+    fn update(&mut self, cx: &mut Context<'_>) -> Result<(), Error> {
+        if self.endpoints.poll_update(cx)? {
+            self.ring = self
+                .endpoints
+                .keys()
+                .iter()
+                .flat_map(|key| {
+                    (0..POINTS_PER_ENDPOINT).map(move |point| {
+                        let mut hasher = DefaultHasher::new();
+                        key.hash(&mut hasher);
+                        point.hash(&mut hasher);
+                        (hasher.finish(), key.clone())
+                    })
+                })
+                .collect();
+            self.ring.sort_unstable_by_key(|(hash, _)| *hash);
+            tracing::trace!(points = self.ring.len(), "rebuilt ring");
+        }
+        Ok(())
+    }
+
+    /// Returns the ready-set index of the first ready endpoint at or after
+    /// `hash` on the ring.
+    fn ready_index_for(&self, hash: u64) -> Option<usize> {
+        let start = self.ring.partition_point(|(h, _)| *h < hash);
+        let (after, before) = self.ring.split_at(start);
+        before
+            .iter()
+            .chain(after)
+            .find_map(|(_, key)| self.endpoints.ready_index(key))
+    }
+}
+
+impl<D, H, Req> tower::Service<Req> for RingHash<D, H, Req>
+where
+    D: Discover + Unpin,
+    D::Key: Hash + Clone,
+    D::Error: Into<Error>,
+    D::Service: tower::Service<Req>,
+    <D::Service as tower::Service<Req>>::Error: Into<Error>,
+    H: HashRequest<Req>,
+{
+    type Response = <D::Service as tower::Service<Req>>::Response;
+    type Error = Error;
+    type Future = future::MapErr<
+        <D::Service as tower::Service<Req>>::Future,
+        fn(<D::Service as tower::Service<Req>>::Error) -> Error,
+    >;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.update(cx)?;
+
+        loop {
+            // The endpoint is not selected until the request's hash is known,
+            // so every ready endpoint must remain ready. Endpoints are checked
+            // in reverse so that evicting an endpoint from the ready set does
+            // not change the indices of those not yet checked.
+            let ready = self.endpoints.ready_len();
+            let mut became_unready = false;
+            for index in (0..ready).rev() {
+                if !self.endpoints.check_ready_index(cx, index) {
+                    became_unready = true;
+                }
+            }
+
+            if self.endpoints.ready_len() > 0 {
+                return Poll::Ready(Ok(()));
+            }
+            if !became_unready {
+                // We have previously registered interest in updates from
+                // discovery and pending endpoints.
+                return Poll::Pending;
+            }
+
+            // Ready endpoints became unready, so poll them again to register
+            // interest in their readiness.
+            self.update(cx)?;
+        }
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let hash = self
+            .hasher
+            .hash_request(&req)
+            .unwrap_or_else(|| self.rng.gen());
+        let index = self.ready_index_for(hash).expect("called before ready");
+        self.endpoints
+            .call_ready_index(index, req)
+            .map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{stream, StreamExt};
+    use std::convert::Infallible;
+    use tower::{discover::Change, Service};
+    use tower_test::mock;
+
+    /// Hashes requests by their value.
+    struct Identity;
+
+    impl HashRequest<u64> for Identity {
+        fn hash_request(&self, req: &u64) -> Option<u64> {
+            let mut hasher = DefaultHasher::new();
+            req.hash(&mut hasher);
+            Some(hasher.finish())
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn requests_with_the_same_hash_use_the_same_endpoint() {
+        let mut handles = Vec::new();
+        let mut changes = Vec::new();
+        for key in 0..3usize {
+            let (svc, mut handle) = mock::pair::<u64, usize>();
+            handle.allow(100);
+            handles.push(handle);
+            changes.push(Ok::<_, Infallible>(Change::Insert(key, svc)));
+        }
+        let discover = stream::iter(changes).chain(stream::pending());
+        let mut balance = RingHash::new(Box::pin(discover), Identity);
+
+        let mut endpoints = Vec::new();
+        for req in [7u64, 8, 7, 8, 7] {
+            future::poll_fn(|cx| balance.poll_ready(cx)).await.unwrap();
+            let rsp = balance.call(req);
+            let mut dispatched = None;
+            for (key, handle) in handles.iter_mut().enumerate() {
+                if let Poll::Ready(Some((r, send))) = handle.poll_request() {
+                    assert_eq!(r, req);
+                    send.send_response(key);
+                    dispatched = Some(key);
+                }
+            }
+            let key = dispatched.expect("request must be dispatched");
+            assert_eq!(rsp.await.unwrap(), key);
+            endpoints.push(key);
+        }
+        assert_eq!(endpoints[0], endpoints[2]);
+        assert_eq!(endpoints[0], endpoints[4]);
+        assert_eq!(endpoints[1], endpoints[3]);
+
+        // When the selected endpoint becomes unready, its requests move to
+        // another endpoint.
+        let unready = endpoints[0];
+        handles[unready].allow(0);
+        future::poll_fn(|cx| balance.poll_ready(cx)).await.unwrap();
+        let rsp = balance.call(7);
+        let mut dispatched = None;
+        for (key, handle) in handles.iter_mut().enumerate() {
+            if let Poll::Ready(Some((_, send))) = handle.poll_request() {
+                send.send_response(key);
+                dispatched = Some(key);
+            }
+        }
+        let key = dispatched.expect("request must be dispatched");
+        assert_ne!(key, unready);
+        assert_eq!(rsp.await.unwrap(), key);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::endpoints::Endpoints;
+use futures::{future, TryFutureExt};
+use linkerd_error::Error;
+use std::{
+    hash::Hash,
+    task::{Context, Poll},
+};
+use tower::discover::Discover;
+
+/// Dispatches requests to each ready endpoint in turn.
+pub struct RoundRobin<D, Req>
+where
+    D: Discover,
+    D::Key: Hash,
+{
+    endpoints: Endpoints<D, Req>,
+    next: usize,
+    selected: Option<D::Key>,
+}
+
+// === impl RoundRobin ===
+
+impl<D, Req> RoundRobin<D, Req>
+where
+    D: Discover + Unpin,
+    D::Key: Hash + Clone,
+    D::Error: Into<Error>,
+    D::Service: tower::Service<Req>,
+    <D::Service as tower::Service<Req>>::Error: Into<Error>,
+{
+    pub fn new(discover: D) -> Self {
+        Self {
+            endpoints: Endpoints::new(discover),
+            next: 0,
+            selected: None,
+        }
+    }
+
+    /// Selects the next ready endpoint, starting after the last selected
+    /// endpoint.
+    fn select(&mut self, cx: &mut Context<'_>) -> Option<D::Key> {
+        let len = self.endpoints.keys().len();
+        for _ in 0..len {
+            let key = self.endpoints.keys()[self.next % len].clone();
+            self.next = (self.next + 1) % len;
+            if let Some(index) = self.endpoints.ready_index(&key) {
+                if self.endpoints.check_ready_index(cx, index) {
+                    return Some(key);
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<D, Req> tower::Service<Req> for RoundRobin<D, Req>
+where
+    D: Discover + Unpin,
+    D::Key: Hash + Clone,
+    D::Error: Into<Error>,
+    D::Service: tower::Service<Req>,
+    <D::Service as tower::Service<Req>>::Error: Into<Error>,
+{
+    type Response = <D::Service as tower::Service<Req>>::Response;
+    type Error = Error;
+    type Future = future::MapErr<
+        <D::Service as tower::Service<Req>>::Future,
+        fn(<D::Service as tower::Service<Req>>::Error) -> Error,
+    >;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.endpoints.poll_update(cx)?;
+
+        loop {
+            // If an endpoint has already been selected, ensure that it is
+            // still ready.
+            if let Some(key) = self.selected.take() {
+                if let Some(index) = self.endpoints.ready_index(&key) {
+                    if self.endpoints.check_ready_index(cx, index) {
+                        self.selected = Some(key);
+                        return Poll::Ready(Ok(()));
+                    }
+                }
+            }
+
+            let ready = self.endpoints.ready_len();
+            self.selected = self.select(cx);
+            if self.selected.is_some() {
+                return Poll::Ready(Ok(()));
+            }
+            if ready == 0 {
+                // We have previously registered interest in updates from
+                // discovery and pending endpoints.
+                return Poll::Pending;
+            }
+
+            // Ready endpoints became unready, so poll them again to register
+            // interest in their readiness.
+            self.endpoints.poll_update(cx)?;
+        }
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let key = self.selected.take().expect("called before ready");
+        let index = self
+            .endpoints
+            .ready_index(&key)
+            .expect("selected endpoint must be ready");
+        self.endpoints
+            .call_ready_index(index, req)
+            .map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{stream, StreamExt};
+    use std::convert::Infallible;
+    use tower::{discover::Change, Service};
+    use tower_test::mock;
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn cycles_through_ready_endpoints() {
+        let (svc0, mut handle0) = mock::pair::<(), usize>();
+        let (svc1, mut handle1) = mock::pair::<(), usize>();
+        let (svc2, mut handle2) = mock::pair::<(), usize>();
+        let discover = stream::iter(
+            [(0, svc0), (1, svc1), (2, svc2)]
+                .into_iter()
+                .map(|(k, s)| Ok::<_, Infallible>(Change::Insert(k, s))),
+        )
+        .chain(stream::pending());
+        let mut balance = RoundRobin::<_, ()>::new(Box::pin(discover));
+
+        // Endpoints that are not ready are skipped.
+        handle0.allow(10);
+        handle1.allow(0);
+        handle2.allow(10);
+        for expected in [0, 2, 0, 2] {
+            future::poll_fn(|cx| balance.poll_ready(cx)).await.unwrap();
+            let rsp = balance.call(());
+            let handle = if expected == 0 {
+                &mut handle0
+            } else {
+                &mut handle2
+            };
+            let ((), send) = handle
+                .next_request()
+                .await
+                .expect("request must be dispatched");
+            send.send_response(expected);
+            assert_eq!(rsp.await.unwrap(), expected);
+        }
+
+        handle1.allow(10);
+        for expected in [0, 1, 2, 0, 1, 2] {
+            future::poll_fn(|cx| balance.poll_ready(cx)).await.unwrap();
+            let rsp = balance.call(());
+            let handle = match expected {
+                0 => &mut handle0,
+                1 => &mut handle1,
+                _ => &mut handle2,
+            };
+            let ((), send) = handle
+                .next_request()
+                .await
+                .expect("request must be dispatched");
+            send.send_response(expected);
+            assert_eq!(rsp.await.unwrap(), expected);
+        }
+    }
+}
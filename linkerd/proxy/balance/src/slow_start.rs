@@ -0,0 +1,213 @@
+//! Ramps traffic to newly discovered endpoints.
+//!
+//! Power-of-two-choices balancers pick the less loaded of two endpoints, so a
+//! new endpoint (which has no load) immediately receives its full share of
+//! requests. During its slow-start window, an endpoint's load is instead
+//! reported as deferred with a probability of `1 - weight`, so that it loses
+//! the comparison against any endpoint that is not deferred. The endpoint's
+//! weight ramps from `min_weight` to 1 over the window.
+
+use futures::{ready, TryStream};
+use pin_project::pin_project;
+use rand::{thread_rng, Rng};
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time::Instant;
+use tower::{discover::Change, load::Load};
+
+/// Configures how traffic to newly discovered endpoints is ramped up.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SlowStart {
+    /// The time over which an endpoint's weight ramps to its full value.
+    pub window: Duration,
+
+    /// The weight of an endpoint when it is first discovered, in `(0, 1]`.
+    pub min_weight: f64,
+
+    /// Shapes the ramp: the weight grows with `(elapsed / window) ^ (1 /
+    /// aggression)`, so `1.0` is linear and larger values ramp up more
+    /// quickly at first.
+    pub aggression: f64,
+}
+
+/// Wraps the services discovered by a `D`-typed discovery stream so that new
+/// endpoints are slowly started.
+#[pin_project]
+#[derive(Debug)]
+pub struct RampDiscover<D, K> {
+    #[pin]
+    discover: D,
+    config: Option<SlowStart>,
+
+    /// The time at which each endpoint was first discovered. Updates to an
+    /// endpoint do not restart its ramp.
+    started: HashMap<K, Instant>,
+}
+
+/// An endpoint service whose load is deferred while it is slowly started.
+#[derive(Debug)]
+pub struct Ramp<S> {
+    inner: S,
+    ramp: Option<(SlowStart, Instant)>,
+}
+
+/// A load metric that orders deferred endpoints after all others.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub struct RampLoad<M> {
+    deferred: bool,
+    load: M,
+}
+
+// === impl SlowStart ===
+
+impl SlowStart {
+    /// Returns the weight of an endpoint that was discovered at `started`.
+    fn weight(&self, started: Instant, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(started);
+        if elapsed >= self.window {
+            return 1.0;
+        }
+        let progress = elapsed.as_secs_f64() / self.window.as_secs_f64();
+        progress
+            .powf(1.0 / self.aggression)
+            .max(self.min_weight)
+            .clamp(0.0, 1.0)
+    }
+}
+
+// === impl RampDiscover ===
+
+impl<D, K> RampDiscover<D, K> {
+    pub fn new(discover: D, config: Option<SlowStart>) -> Self {
+        Self {
+            discover,
+            config,
+            started: HashMap::new(),
+        }
+    }
+}
+
+impl<D, K, S> futures::Stream for RampDiscover<D, K>
+where
+    D: TryStream<Ok = Change<K, S>>,
+    K: Hash + Eq + Clone,
+{
+    type Item = Result<Change<K, Ramp<S>>, D::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let change = match ready!(this.discover.try_poll_next(cx)) {
+            Some(Ok(Change::Insert(key, inner))) => {
+                let ramp = this.config.map(|config| {
+                    let started = *this.started.entry(key.clone()).or_insert_with(Instant::now);
+                    (config, started)
+                });
+                Ok(Change::Insert(key, Ramp { inner, ramp }))
+            }
+            Some(Ok(Change::Remove(key))) => {
+                this.started.remove(&key);
+                Ok(Change::Remove(key))
+            }
+            Some(Err(e)) => Err(e),
+            None => return Poll::Ready(None),
+        };
+        Poll::Ready(Some(change))
+    }
+}
+
+// === impl Ramp ===
+
+impl<S: Load> Load for Ramp<S> {
+    type Metric = RampLoad<S::Metric>;
+
+    fn load(&self) -> Self::Metric {
+        let deferred = match self.ramp {
+            Some((config, started)) => {
+                let weight = config.weight(started, Instant::now());
+                weight < 1.0 && !thread_rng().gen_bool(weight)
+            }
+            None => false,
+        };
+        RampLoad {
+            deferred,
+            load: self.inner.load(),
+        }
+    }
+}
+
+impl<Req, S> tower::Service<Req> for Ramp<S>
+where
+    S: tower::Service<Req>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // Stop tracking the ramp once it has completed.
+        if let Some((config, started)) = self.ramp {
+            if config.weight(started, Instant::now()) >= 1.0 {
+                self.ramp = None;
+            }
+        }
+        self.inner.poll_ready(cx)
+    }
+
+    #[inline]
+    fn call(&mut self, req: Req) -> Self::Future {
+        self.inner.call(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weight_ramps_over_window() {
+        let linear = SlowStart {
+            window: Duration::from_secs(10),
+            min_weight: 0.1,
+            aggression: 1.0,
+        };
+        let t0 = Instant::now();
+        assert_eq!(linear.weight(t0, t0), 0.1);
+        assert_eq!(linear.weight(t0, t0 + Duration::from_secs(5)), 0.5);
+        assert_eq!(linear.weight(t0, t0 + Duration::from_secs(10)), 1.0);
+        assert_eq!(linear.weight(t0, t0 + Duration::from_secs(60)), 1.0);
+
+        let aggressive = SlowStart {
+            aggression: 2.0,
+            ..linear
+        };
+        let w = aggressive.weight(t0, t0 + Duration::from_millis(2_500));
+        assert!((w - 0.5).abs() < 1e-9, "{}", w);
+    }
+
+    #[test]
+    fn deferred_load_is_ordered_last() {
+        let deferred = RampLoad {
+            deferred: true,
+            load: 0,
+        };
+        let loaded = RampLoad {
+            deferred: false,
+            load: 100,
+        };
+        assert!(loaded < deferred);
+        assert!(
+            RampLoad {
+                deferred: true,
+                load: 1
+            } < RampLoad {
+                deferred: true,
+                load: 2
+            }
+        );
+    }
+}
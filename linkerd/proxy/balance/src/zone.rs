@@ -0,0 +1,341 @@
+//! Prefers endpoints in the proxy's own zone.
+//!
+//! Endpoints in other zones are held unready, so that balancers only dispatch
+//! requests to them when too few local endpoints are ready. Balancers skip
+//! unready endpoints regardless of the algorithm, so zone preference composes
+//! with any [`LoadBalancer`](crate::LoadBalancer).
+
+use futures::{ready, TryStream};
+use linkerd_stack::{layer, NewService, Param};
+use parking_lot::Mutex;
+use pin_project::pin_project;
+use std::{
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+};
+use tower::discover::Change;
+use tracing::debug;
+
+/// Configures balancers to prefer endpoints in the local zone.
+#[derive(Clone, Debug)]
+pub struct PreferLocalZone {
+    /// The zone in which this proxy runs.
+    pub zone: Arc<str>,
+
+    /// The fraction of local endpoints that must be ready for requests to be
+    /// kept in the local zone. When fewer local endpoints are ready, requests
+    /// spill over to endpoints in other zones.
+    pub min_ready: f64,
+}
+
+/// The zone in which an endpoint runs, if known.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EndpointZone(pub Option<Arc<str>>);
+
+/// Builds [`Zoned`] endpoint services.
+#[derive(Clone, Debug)]
+pub struct NewZoned<N> {
+    inner: N,
+    local: Option<Arc<str>>,
+}
+
+/// An endpoint service that is held unready while enough endpoints in the
+/// local zone are ready.
+#[derive(Debug)]
+pub struct Zoned<S> {
+    inner: S,
+    is_local: bool,
+    state: Option<Arc<Zone>>,
+    is_ready: bool,
+}
+
+/// Attaches discovered [`Zoned`] endpoints to a balancer's zone state.
+#[pin_project]
+#[derive(Debug)]
+pub struct ZoneAware<D> {
+    #[pin]
+    discover: D,
+    state: Option<Arc<Zone>>,
+}
+
+/// Tracks the readiness of a balancer's local endpoints.
+#[derive(Debug)]
+struct Zone {
+    min_ready: f64,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    local: usize,
+    local_ready: usize,
+    remote_wakers: Vec<Waker>,
+}
+
+// === impl NewZoned ===
+
+impl<N> NewZoned<N> {
+    pub fn layer(config: Option<PreferLocalZone>) -> impl layer::Layer<N, Service = Self> + Clone {
+        let local = config.map(|c| c.zone);
+        layer::mk(move |inner| Self {
+            inner,
+            local: local.clone(),
+        })
+    }
+}
+
+impl<T, N> NewService<T> for NewZoned<N>
+where
+    T: Param<EndpointZone>,
+    N: NewService<T>,
+{
+    type Service = Zoned<N::Service>;
+
+    fn new_service(&self, target: T) -> Self::Service {
+        // Endpoints without a zone are never preferred, but they are also
+        // never held unready unless zone preference is enabled.
+        let EndpointZone(zone) = target.param();
+        let is_local = match (self.local.as_ref(), zone) {
+            (Some(local), Some(zone)) => *local == zone,
+            _ => false,
+        };
+        Zoned {
+            inner: self.inner.new_service(target),
+            is_local,
+            state: None,
+            is_ready: false,
+        }
+    }
+}
+
+// === impl Zoned ===
+
+impl<S> Zoned<S> {
+    fn attach(&mut self, state: Arc<Zone>) {
+        if self.is_local {
+            state.update(|s| s.local += 1);
+        }
+        self.state = Some(state);
+    }
+}
+
+impl<Req, S> tower::Service<Req> for Zoned<S>
+where
+    S: tower::Service<Req>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let state = match self.state.as_ref() {
+            Some(state) => state,
+            None => return self.inner.poll_ready(cx),
+        };
+
+        if !self.is_local {
+            if !state.spills_over(cx) {
+                return Poll::Pending;
+            }
+            return self.inner.poll_ready(cx);
+        }
+
+        let poll = self.inner.poll_ready(cx);
+        let is_ready = matches!(poll, Poll::Ready(Ok(())));
+        if is_ready != self.is_ready {
+            self.is_ready = is_ready;
+            state.update(|s| {
+                if is_ready {
+                    s.local_ready += 1;
+                } else {
+                    s.local_ready -= 1;
+                }
+            });
+        }
+        poll
+    }
+
+    #[inline]
+    fn call(&mut self, req: Req) -> Self::Future {
+        self.inner.call(req)
+    }
+}
+
+impl<S> Drop for Zoned<S> {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.take() {
+            if self.is_local {
+                let was_ready = self.is_ready;
+                state.update(|s| {
+                    s.local -= 1;
+                    if was_ready {
+                        s.local_ready -= 1;
+                    }
+                });
+            }
+        }
+    }
+}
+
+// === impl ZoneAware ===
+
+impl<D> ZoneAware<D> {
+    pub fn new(discover: D, config: Option<PreferLocalZone>) -> Self {
+        let state = config.map(|c| {
+            Arc::new(Zone {
+                min_ready: c.min_ready,
+                state: Mutex::default(),
+            })
+        });
+        Self { discover, state }
+    }
+
+    pub fn layer(config: Option<PreferLocalZone>) -> impl layer::Layer<D, Service = Self> + Clone {
+        layer::mk(move |discover| Self::new(discover, config.clone()))
+    }
+}
+
+impl<D, K, S> futures::Stream for ZoneAware<D>
+where
+    D: TryStream<Ok = Change<K, Zoned<S>>>,
+{
+    type Item = Result<Change<K, Zoned<S>>, D::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let change = match ready!(this.discover.try_poll_next(cx)) {
+            Some(Ok(Change::Insert(key, mut svc))) => {
+                if let Some(state) = this.state.as_ref() {
+                    svc.attach(state.clone());
+                }
+                Ok(Change::Insert(key, svc))
+            }
+            Some(Ok(Change::Remove(key))) => Ok(Change::Remove(key)),
+            Some(Err(e)) => Err(e),
+            None => return Poll::Ready(None),
+        };
+        Poll::Ready(Some(change))
+    }
+}
+
+// === impl Zone ===
+
+impl Zone {
+    /// Returns true if requests may be dispatched to endpoints in other zones.
+    /// Otherwise, the task is notified when requests begin to spill over.
+    fn spills_over(&self, cx: &mut Context<'_>) -> bool {
+        let mut state = self.state.lock();
+        if state.spills_over(self.min_ready) {
+            return true;
+        }
+        if !state.remote_wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            state.remote_wakers.push(cx.waker().clone());
+        }
+        false
+    }
+
+    fn update(&self, f: impl FnOnce(&mut State)) {
+        let mut state = self.state.lock();
+        let spilled = state.spills_over(self.min_ready);
+        f(&mut state);
+        if !spilled && state.spills_over(self.min_ready) {
+            debug!(
+                local = state.local,
+                local_ready = state.local_ready,
+                "Spilling over to other zones"
+            );
+            for waker in state.remote_wakers.drain(..) {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl State {
+    fn spills_over(&self, min_ready: f64) -> bool {
+        self.local_ready == 0 || (self.local_ready as f64) < (self.local as f64) * min_ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::Stream;
+    use std::convert::Infallible;
+    use tower::{Layer, Service};
+    use tower_test::mock;
+
+    #[derive(Clone)]
+    struct Target(&'static str);
+
+    impl Param<EndpointZone> for Target {
+        fn param(&self) -> EndpointZone {
+            EndpointZone(Some(self.0.into()))
+        }
+    }
+
+    fn is_ready<S: Service<()>>(svc: &mut S) -> bool {
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        matches!(svc.poll_ready(&mut cx), Poll::Ready(Ok(())))
+    }
+
+    fn discovered<D, S>(discover: &mut ZoneAware<D>) -> Zoned<S>
+    where
+        D: TryStream<Ok = Change<usize, Zoned<S>>> + Unpin,
+    {
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        match Pin::new(discover).poll_next(&mut cx) {
+            Poll::Ready(Some(Ok(Change::Insert(_, svc)))) => svc,
+            _ => panic!("expected an endpoint to be discovered"),
+        }
+    }
+
+    #[test]
+    fn spills_over_when_local_endpoints_are_unready() {
+        let config = PreferLocalZone {
+            zone: "a".into(),
+            min_ready: 1.0,
+        };
+        let (local, mut local_handle) = mock::pair::<(), ()>();
+        let (remote, mut remote_handle) = mock::pair::<(), ()>();
+        let local = NewZoned::layer(Some(config.clone()))
+            .layer(move |_: Target| local.clone())
+            .new_service(Target("a"));
+        let remote = NewZoned::layer(Some(config.clone()))
+            .layer(move |_: Target| remote.clone())
+            .new_service(Target("b"));
+
+        let mut discover = ZoneAware::new(
+            futures::stream::iter([
+                Ok::<_, Infallible>(Change::Insert(0, local)),
+                Ok(Change::Insert(1, remote)),
+            ]),
+            Some(config),
+        );
+        let mut local = discovered(&mut discover);
+        let mut remote = discovered(&mut discover);
+
+        // Until a local endpoint is known to be ready, requests spill over.
+        local_handle.allow(0);
+        remote_handle.allow(1);
+        assert!(!is_ready(&mut local));
+        assert!(is_ready(&mut remote));
+
+        local_handle.allow(1);
+        assert!(is_ready(&mut local));
+        assert!(!is_ready(&mut remote), "remote endpoint must be held");
+
+        // When the local endpoint becomes unready, requests spill over again.
+        drop(local.call(()));
+        assert!(!is_ready(&mut local));
+        assert!(is_ready(&mut remote), "remote endpoint must be released");
+
+        // Removing the local endpoint also releases the remote endpoint.
+        local_handle.allow(1);
+        assert!(is_ready(&mut local));
+        assert!(!is_ready(&mut remote));
+        drop(local);
+        assert!(is_ready(&mut remote));
+    }
+}
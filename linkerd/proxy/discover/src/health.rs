@@ -0,0 +1,692 @@
+//! Active health checking of discovered endpoints.
+//!
+//! When health checking is configured, each discovered endpoint is probed
+//! periodically on a background task. An endpoint that fails
+//! `unhealthy_threshold` consecutive probes is removed from the discovery
+//! stream; it is restored once it passes `healthy_threshold` consecutive
+//! probes. Newly discovered endpoints are considered healthy until they fail,
+//! so that probing never delays discovery.
+//!
+//! Endpoints are probed for accepting connections unless their discovery
+//! metadata requests an HTTP or gRPC probe, so that requests are only sent to
+//! endpoints that expect them. Requested probes are only used by streams that
+//! allow request probes.
+//!
+//! So that a widespread failure does not leave a balancer without endpoints,
+//! unhealthy endpoints are not removed once a configured fraction of the
+//! stream's endpoints has been removed.
+
+mod probe;
+
+pub use self::probe::{InvalidProbe, Probe, ProbeError};
+use futures::{prelude::*, ready};
+use linkerd_stack::Param;
+use linkerd_tls::ServerId;
+use parking_lot::Mutex;
+use pin_project::pin_project;
+use std::{
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+    num::NonZeroU32,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Weak,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::{sync::mpsc, task::JoinHandle, time};
+use tower::discover::Change;
+use tracing::{debug, info, Instrument};
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Whether endpoints are probed with the HTTP or gRPC probes that they
+    /// request. Otherwise, e.g. for endpoints of opaque TCP services, they are
+    /// only probed for accepting connections.
+    pub request_probes: bool,
+
+    /// How often each endpoint is probed.
+    pub interval: Duration,
+
+    /// How long a probe may take before it fails.
+    pub timeout: Duration,
+
+    /// The number of consecutive failed probes after which a healthy endpoint
+    /// is removed.
+    pub unhealthy_threshold: NonZeroU32,
+
+    /// The number of consecutive successful probes after which an unhealthy
+    /// endpoint is restored.
+    pub healthy_threshold: NonZeroU32,
+
+    /// The maximum fraction, in `(0, 1]`, of a stream's endpoints that may be
+    /// removed at once. Endpoints that become unhealthy while this many are
+    /// removed remain in the stream.
+    pub max_unhealthy_ratio: f64,
+}
+
+/// Tracks the health of each endpoint that is being checked.
+#[derive(Clone, Debug, Default)]
+pub struct Registry(Arc<Mutex<HashMap<SocketAddr, Weak<AtomicBool>>>>);
+
+/// Wraps an `M`-typed `MakeDiscover` so that the endpoints it discovers are
+/// health checked.
+#[derive(Clone, Debug)]
+pub struct NewHealthCheck<M> {
+    inner: M,
+    config: Option<Arc<Config>>,
+    registry: Registry,
+}
+
+#[pin_project]
+#[derive(Debug)]
+pub struct HealthCheckFuture<F> {
+    #[pin]
+    inner: F,
+    config: Option<Arc<Config>>,
+    registry: Registry,
+}
+
+/// Filters unhealthy endpoints from a `D`-typed discovery stream.
+///
+/// Endpoints that have a TLS identity are meshed and are only probed for
+/// accepting connections, so that plaintext requests are never sent to them.
+/// Other endpoints are probed as their `Option<Probe>` param requests.
+#[pin_project]
+pub struct HealthCheck<D, E> {
+    #[pin]
+    inner: D,
+    config: Option<Arc<Config>>,
+    registry: Registry,
+
+    /// Endpoints that are being checked.
+    endpoints: HashMap<SocketAddr, Checked<E>>,
+
+    /// Changes that have been received but not yet emitted.
+    pending: VecDeque<Change<SocketAddr, E>>,
+
+    /// Health transitions reported by each endpoint's task, identified by
+    /// the check's ID so that reports from replaced checks are ignored.
+    transitions_tx: mpsc::UnboundedSender<(SocketAddr, u64, bool)>,
+    transitions_rx: mpsc::UnboundedReceiver<(SocketAddr, u64, bool)>,
+    next_id: u64,
+}
+
+struct Checked<E> {
+    endpoint: E,
+    id: u64,
+    healthy: bool,
+
+    /// Whether the endpoint has been emitted. Unhealthy endpoints remain
+    /// published while too many endpoints have been removed.
+    published: bool,
+    task: JoinHandle<()>,
+}
+
+// === impl Registry ===
+
+impl Registry {
+    /// Returns whether each endpoint that is being checked is healthy.
+    pub fn endpoints(&self) -> Vec<(SocketAddr, bool)> {
+        let mut endpoints = self.0.lock();
+        let mut healthy = Vec::with_capacity(endpoints.len());
+        endpoints.retain(|addr, health| match health.upgrade() {
+            Some(h) => {
+                healthy.push((*addr, h.load(Ordering::Acquire)));
+                true
+            }
+            None => false,
+        });
+        healthy.sort_by_key(|(addr, _)| *addr);
+        healthy
+    }
+
+    /// Returns the health of an endpoint, which is shared by all checks of the
+    /// endpoint.
+    fn health(&self, addr: SocketAddr) -> Arc<AtomicBool> {
+        let mut endpoints = self.0.lock();
+        if let Some(health) = endpoints.get(&addr).and_then(Weak::upgrade) {
+            return health;
+        }
+        let health = Arc::new(AtomicBool::new(true));
+        endpoints.insert(addr, Arc::downgrade(&health));
+        health
+    }
+}
+
+// === impl NewHealthCheck ===
+
+impl<M> NewHealthCheck<M> {
+    /// Checks discovered endpoints when `config` is set. Otherwise, endpoints
+    /// are discovered without being checked.
+    pub fn new(config: Option<Config>, registry: Registry, inner: M) -> Self {
+        Self {
+            inner,
+            config: config.map(Arc::new),
+            registry,
+        }
+    }
+}
+
+impl<T, M, E> tower::Service<T> for NewHealthCheck<M>
+where
+    M: tower::Service<T>,
+    M::Response: TryStream<Ok = Change<SocketAddr, E>>,
+{
+    type Response = HealthCheck<M::Response, E>;
+    type Error = M::Error;
+    type Future = HealthCheckFuture<M::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    #[inline]
+    fn call(&mut self, target: T) -> Self::Future {
+        HealthCheckFuture {
+            inner: self.inner.call(target),
+            config: self.config.clone(),
+            registry: self.registry.clone(),
+        }
+    }
+}
+
+// === impl HealthCheckFuture ===
+
+impl<F, D, E> Future for HealthCheckFuture<F>
+where
+    F: TryFuture<Ok = D>,
+    D: TryStream<Ok = Change<SocketAddr, E>>,
+{
+    type Output = Result<HealthCheck<D, E>, F::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let inner = ready!(this.inner.try_poll(cx))?;
+        Poll::Ready(Ok(HealthCheck::new(
+            inner,
+            this.config.clone(),
+            this.registry.clone(),
+        )))
+    }
+}
+
+// === impl HealthCheck ===
+
+impl<D, E> HealthCheck<D, E> {
+    fn new(inner: D, config: Option<Arc<Config>>, registry: Registry) -> Self {
+        let (transitions_tx, transitions_rx) = mpsc::unbounded_channel();
+        Self {
+            inner,
+            config,
+            registry,
+            endpoints: HashMap::default(),
+            pending: VecDeque::new(),
+            transitions_tx,
+            transitions_rx,
+            next_id: 0,
+        }
+    }
+}
+
+impl<D, E> Stream for HealthCheck<D, E>
+where
+    D: TryStream<Ok = Change<SocketAddr, E>>,
+    E: Clone + Param<Option<ServerId>> + Param<Option<Probe>>,
+{
+    type Item = Result<Change<SocketAddr, E>, D::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let this = self.as_mut().project();
+            let config = match this.config.as_ref() {
+                Some(config) => config,
+                None => return this.inner.try_poll_next(cx),
+            };
+
+            if let Some(change) = this.pending.pop_front() {
+                return Poll::Ready(Some(Ok(change)));
+            }
+
+            // Apply health transitions before processing new discovery
+            // updates.
+            if let Poll::Ready(Some((addr, id, healthy))) = this.transitions_rx.poll_recv(cx) {
+                if let Some(ep) = this.endpoints.get_mut(&addr) {
+                    if ep.id == id && ep.healthy != healthy {
+                        ep.healthy = healthy;
+                        reconcile(this.endpoints, this.pending, config.max_unhealthy_ratio);
+                    }
+                }
+                continue;
+            }
+
+            match ready!(this.inner.try_poll_next(cx)) {
+                None => return Poll::Ready(None),
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+
+                Some(Ok(Change::Insert(addr, endpoint))) => {
+                    // An updated endpoint continues to be checked by its
+                    // existing task and is only emitted while it's published.
+                    if let Some(ep) = this.endpoints.get_mut(&addr) {
+                        ep.endpoint = endpoint.clone();
+                        if ep.published {
+                            this.pending.push_back(Change::Insert(addr, endpoint));
+                        }
+                        continue;
+                    }
+
+                    let id = *this.next_id;
+                    *this.next_id += 1;
+                    let meshed = Param::<Option<ServerId>>::param(&endpoint).is_some();
+                    let probe = match Param::<Option<Probe>>::param(&endpoint) {
+                        Some(probe) if config.request_probes && !meshed => probe,
+                        _ => Probe::Tcp,
+                    };
+                    let task = tokio::spawn(
+                        check(
+                            addr,
+                            probe,
+                            id,
+                            config.clone(),
+                            this.registry.health(addr),
+                            this.transitions_tx.clone(),
+                        )
+                        .in_current_span(),
+                    );
+                    this.endpoints.insert(
+                        addr,
+                        Checked {
+                            endpoint: endpoint.clone(),
+                            id,
+                            healthy: true,
+                            published: true,
+                            task,
+                        },
+                    );
+                    this.pending.push_back(Change::Insert(addr, endpoint));
+                    // The new endpoint may allow an unhealthy endpoint to be
+                    // removed.
+                    reconcile(this.endpoints, this.pending, config.max_unhealthy_ratio);
+                }
+
+                Some(Ok(Change::Remove(addr))) => {
+                    // Unpublished endpoints have already been removed.
+                    if let Some(ep) = this.endpoints.remove(&addr) {
+                        if ep.published {
+                            this.pending.push_back(Change::Remove(addr));
+                        }
+                        reconcile(this.endpoints, this.pending, config.max_unhealthy_ratio);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Publishes healthy endpoints and removes unhealthy endpoints, so long as no
+/// more than `max_unhealthy_ratio` of the endpoints are removed. Unhealthy
+/// endpoints are republished when the limit is exceeded, e.g. because other
+/// endpoints were removed from discovery.
+fn reconcile<E: Clone>(
+    endpoints: &mut HashMap<SocketAddr, Checked<E>>,
+    pending: &mut VecDeque<Change<SocketAddr, E>>,
+    max_unhealthy_ratio: f64,
+) {
+    for (addr, ep) in endpoints.iter_mut() {
+        if ep.healthy && !ep.published {
+            ep.published = true;
+            pending.push_back(Change::Insert(*addr, ep.endpoint.clone()));
+        }
+    }
+
+    let max_removed = max_unhealthy_ratio * endpoints.len() as f64;
+    let mut removed = endpoints.values().filter(|ep| !ep.published).count();
+    for (addr, ep) in endpoints.iter_mut().filter(|(_, ep)| !ep.healthy) {
+        if ep.published && (removed + 1) as f64 <= max_removed {
+            ep.published = false;
+            removed += 1;
+            pending.push_back(Change::Remove(*addr));
+        } else if !ep.published && removed as f64 > max_removed {
+            ep.published = true;
+            removed -= 1;
+            pending.push_back(Change::Insert(*addr, ep.endpoint.clone()));
+        }
+    }
+}
+
+// === impl Checked ===
+
+impl<E> Drop for Checked<E> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Probes an endpoint until its check is dropped, reporting each change in
+/// its health.
+async fn check(
+    addr: SocketAddr,
+    probe: Probe,
+    id: u64,
+    config: Arc<Config>,
+    health: Arc<AtomicBool>,
+    transitions: mpsc::UnboundedSender<(SocketAddr, u64, bool)>,
+) {
+    let mut interval = time::interval(config.interval);
+    interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+    let mut healthy = true;
+    let (mut successes, mut failures) = (0, 0);
+    loop {
+        interval.tick().await;
+
+        match time::timeout(config.timeout, probe.probe(addr)).await {
+            Ok(Ok(())) => {
+                successes += 1;
+                failures = 0;
+            }
+            Ok(Err(error)) => {
+                debug!(%addr, %error, "Probe failed");
+                successes = 0;
+                failures += 1;
+            }
+            Err(_) => {
+                debug!(%addr, timeout = ?config.timeout, "Probe timed out");
+                successes = 0;
+                failures += 1;
+            }
+        }
+
+        let next = if healthy {
+            failures < config.unhealthy_threshold.get()
+        } else {
+            successes >= config.healthy_threshold.get()
+        };
+        if next != healthy {
+            healthy = next;
+            if healthy {
+                info!(%addr, "Endpoint is healthy");
+            } else {
+                info!(%addr, failures, "Endpoint is unhealthy");
+            }
+            health.store(healthy, Ordering::Release);
+            if transitions.send((addr, id, healthy)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linkerd_error::Infallible;
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::UnboundedReceiverStream;
+
+    #[derive(Clone, Debug)]
+    struct Ep(&'static str, Option<ServerId>, Option<Probe>);
+
+    impl Ep {
+        fn plain(name: &'static str) -> Self {
+            Self(name, None, None)
+        }
+
+        fn meshed(name: &'static str) -> Self {
+            let id = "foo.ns.serviceaccount.identity.linkerd.cluster.local"
+                .parse()
+                .unwrap();
+            Self(name, Some(id), None)
+        }
+
+        fn probed(self) -> Self {
+            let probe = Probe::Http {
+                path: http::uri::PathAndQuery::from_static("/ready"),
+            };
+            Self(self.0, self.1, Some(probe))
+        }
+    }
+
+    impl Param<Option<ServerId>> for Ep {
+        fn param(&self) -> Option<ServerId> {
+            self.1.clone()
+        }
+    }
+
+    impl Param<Option<Probe>> for Ep {
+        fn param(&self) -> Option<Probe> {
+            self.2.clone()
+        }
+    }
+
+    fn config(request_probes: bool, max_unhealthy_ratio: f64) -> Arc<Config> {
+        Arc::new(Config {
+            request_probes,
+            interval: Duration::from_millis(10),
+            timeout: Duration::from_millis(100),
+            unhealthy_threshold: NonZeroU32::new(1).unwrap(),
+            healthy_threshold: NonZeroU32::new(1).unwrap(),
+            max_unhealthy_ratio,
+        })
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn removes_endpoints_until_they_recover() {
+        let healthy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let healthy_addr = healthy.local_addr().unwrap();
+        let unhealthy_addr = {
+            let l = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            l.local_addr().unwrap()
+        };
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let registry = Registry::default();
+        let config = Config {
+            request_probes: true,
+            interval: Duration::from_millis(10),
+            timeout: Duration::from_secs(1),
+            unhealthy_threshold: NonZeroU32::new(2).unwrap(),
+            healthy_threshold: NonZeroU32::new(1).unwrap(),
+            max_unhealthy_ratio: 1.0,
+        };
+        let mut checked = HealthCheck::new(
+            UnboundedReceiverStream::new(rx),
+            Some(Arc::new(config)),
+            registry.clone(),
+        );
+
+        // Endpoints are published as soon as they are discovered.
+        tx.send(Ok::<_, Infallible>(Change::Insert(
+            healthy_addr,
+            Ep::plain("a"),
+        )))
+        .unwrap();
+        tx.send(Ok(Change::Insert(unhealthy_addr, Ep::plain("b"))))
+            .unwrap();
+        assert!(matches!(
+            checked.try_next().await,
+            Ok(Some(Change::Insert(sa, Ep("a", ..)))) if sa == healthy_addr
+        ));
+        assert!(matches!(
+            checked.try_next().await,
+            Ok(Some(Change::Insert(sa, Ep("b", ..)))) if sa == unhealthy_addr
+        ));
+
+        // The endpoint that refuses connections is removed.
+        assert!(matches!(
+            checked.try_next().await,
+            Ok(Some(Change::Remove(sa))) if sa == unhealthy_addr
+        ));
+        assert_eq!(
+            registry.endpoints().into_iter().collect::<HashMap<_, _>>(),
+            vec![(healthy_addr, true), (unhealthy_addr, false)]
+                .into_iter()
+                .collect()
+        );
+
+        // Once it accepts connections, it's restored.
+        let _listener = TcpListener::bind(unhealthy_addr).await.unwrap();
+        assert!(matches!(
+            checked.try_next().await,
+            Ok(Some(Change::Insert(sa, Ep("b", ..)))) if sa == unhealthy_addr
+        ));
+
+        // Removed endpoints are no longer checked.
+        tx.send(Ok(Change::Remove(unhealthy_addr))).unwrap();
+        assert!(matches!(
+            checked.try_next().await,
+            Ok(Some(Change::Remove(sa))) if sa == unhealthy_addr
+        ));
+        drop(tx);
+        assert!(checked.try_next().await.unwrap().is_none());
+        drop(checked);
+        time::timeout(Duration::from_secs(1), async {
+            while !registry.endpoints().is_empty() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("checks must stop");
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn only_sends_requested_probes_to_unmeshed_endpoints() {
+        // The listeners accept connections but never respond to requests.
+        let probed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let probed_addr = probed.local_addr().unwrap();
+        let meshed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let meshed_addr = meshed.local_addr().unwrap();
+        let plain = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let plain_addr = plain.local_addr().unwrap();
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let registry = Registry::default();
+        let mut checked = HealthCheck::new(
+            UnboundedReceiverStream::new(rx),
+            Some(config(true, 1.0)),
+            registry.clone(),
+        );
+
+        for (addr, ep) in [
+            (probed_addr, Ep::plain("a").probed()),
+            (meshed_addr, Ep::meshed("b").probed()),
+            (plain_addr, Ep::plain("c")),
+        ] {
+            tx.send(Ok::<_, Infallible>(Change::Insert(addr, ep)))
+                .unwrap();
+            assert!(matches!(
+                checked.try_next().await,
+                Ok(Some(Change::Insert(sa, _))) if sa == addr
+            ));
+        }
+
+        // Only the HTTP probes of the unmeshed endpoint that requested them
+        // time out. The other endpoints are healthy because they accept
+        // connections.
+        assert!(matches!(
+            checked.try_next().await,
+            Ok(Some(Change::Remove(sa))) if sa == probed_addr
+        ));
+        assert_eq!(
+            registry.endpoints().into_iter().collect::<HashMap<_, _>>(),
+            vec![
+                (probed_addr, false),
+                (meshed_addr, true),
+                (plain_addr, true)
+            ]
+            .into_iter()
+            .collect()
+        );
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn ignores_requested_probes_unless_allowed() {
+        // The listener accepts connections but never responds to requests.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let registry = Registry::default();
+        let mut checked = HealthCheck::new(
+            UnboundedReceiverStream::new(rx),
+            Some(config(false, 1.0)),
+            registry.clone(),
+        );
+
+        tx.send(Ok::<_, Infallible>(Change::Insert(
+            addr,
+            Ep::plain("a").probed(),
+        )))
+        .unwrap();
+        assert!(matches!(
+            checked.try_next().await,
+            Ok(Some(Change::Insert(sa, _))) if sa == addr
+        ));
+
+        // The endpoint is only probed for accepting connections, so it
+        // remains healthy.
+        assert!(
+            time::timeout(Duration::from_millis(100), checked.try_next())
+                .await
+                .is_err()
+        );
+        assert_eq!(registry.endpoints(), vec![(addr, true)]);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn limits_removed_endpoints() {
+        let healthy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let healthy_addr = healthy.local_addr().unwrap();
+        let other = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let other_addr = other.local_addr().unwrap();
+        // These addresses refuse connections once their listeners are dropped.
+        let unhealthy_addrs = {
+            let a = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let b = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            [a.local_addr().unwrap(), b.local_addr().unwrap()]
+        };
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut checked = HealthCheck::new(
+            UnboundedReceiverStream::new(rx),
+            Some(config(true, 0.5)),
+            Registry::default(),
+        );
+
+        for (addr, name) in unhealthy_addrs.iter().zip(["a", "b"]) {
+            tx.send(Ok::<_, Infallible>(Change::Insert(*addr, Ep::plain(name))))
+                .unwrap();
+            assert!(matches!(
+                checked.try_next().await,
+                Ok(Some(Change::Insert(sa, _))) if sa == *addr
+            ));
+        }
+
+        // Only half of the endpoints are removed when all of them fail.
+        let removed = match checked.try_next().await {
+            Ok(Some(Change::Remove(sa))) => sa,
+            change => panic!("unexpected change: {:?}", change),
+        };
+        assert!(
+            time::timeout(Duration::from_millis(100), checked.try_next())
+                .await
+                .is_err()
+        );
+
+        // Once there are enough endpoints, the other unhealthy endpoint is
+        // removed as well.
+        for (addr, name) in [(healthy_addr, "c"), (other_addr, "d")] {
+            tx.send(Ok(Change::Insert(addr, Ep::plain(name)))).unwrap();
+            assert!(matches!(
+                checked.try_next().await,
+                Ok(Some(Change::Insert(sa, _))) if sa == addr
+            ));
+        }
+        assert!(matches!(
+            checked.try_next().await,
+            Ok(Some(Change::Remove(sa))) if sa != removed && unhealthy_addrs.contains(&sa)
+        ));
+    }
+}
@@ -0,0 +1,263 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use hyper::{body::HttpBody, client::conn, Body};
+use prost::Message;
+use std::{net::SocketAddr, str::FromStr};
+use tokio::net::TcpStream;
+use tracing::debug;
+
+/// Determines how an endpoint's health is probed.
+///
+/// Probes connect directly to the endpoint and HTTP and gRPC probes are sent
+/// in plaintext, so they are not protected by mTLS. Meshed endpoints are
+/// only probed with [`Probe::Tcp`], which sends no data.
+///
+/// Probes are parsed from `tcp`, `http:<path>`, `grpc`, or `grpc:<service>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Probe {
+    /// The endpoint is healthy if it accepts a TCP connection.
+    Tcp,
+
+    /// The endpoint is healthy if it responds to an HTTP/1.1 `GET` request for
+    /// `path` with a 2XX status.
+    Http { path: http::uri::PathAndQuery },
+
+    /// The endpoint is healthy if it responds to a gRPC health `Check` request
+    /// for `service` with `SERVING`. An empty service checks the health of the
+    /// server as a whole.
+    Grpc { service: String },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProbeError {
+    #[error("connection failed: {0}")]
+    Connect(#[source] std::io::Error),
+
+    #[error("request failed: {0}")]
+    Http(#[source] hyper::Error),
+
+    #[error("unexpected HTTP status: {0}")]
+    Status(http::StatusCode),
+
+    #[error("unexpected gRPC status: {0}")]
+    GrpcStatus(String),
+
+    #[error("service is not serving")]
+    NotServing,
+}
+
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("invalid health check: {0}")]
+pub struct InvalidProbe(String);
+
+/// `grpc.health.v1.HealthCheckRequest`.
+#[derive(Clone, PartialEq, prost::Message)]
+struct HealthCheckRequest {
+    #[prost(string, tag = "1")]
+    service: String,
+}
+
+/// `grpc.health.v1.HealthCheckResponse`.
+#[derive(Clone, PartialEq, prost::Message)]
+struct HealthCheckResponse {
+    /// A `ServingStatus`.
+    #[prost(int32, tag = "1")]
+    status: i32,
+}
+
+const GRPC_HEALTH_CHECK: &str = "/grpc.health.v1.Health/Check";
+
+/// `grpc.health.v1.HealthCheckResponse.ServingStatus.SERVING`.
+const SERVING: i32 = 1;
+
+// === impl Probe ===
+
+impl Probe {
+    /// Probes the endpoint at `addr`.
+    pub(super) async fn probe(&self, addr: SocketAddr) -> Result<(), ProbeError> {
+        let io = TcpStream::connect(addr)
+            .await
+            .map_err(ProbeError::Connect)?;
+        match self {
+            Self::Tcp => Ok(()),
+            Self::Http { path } => http_get(io, addr, path).await,
+            Self::Grpc { service } => grpc_check(io, addr, service).await,
+        }
+    }
+}
+
+impl FromStr for Probe {
+    type Err = InvalidProbe;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "tcp" => Ok(Self::Tcp),
+            None if s == "grpc" => Ok(Self::Grpc {
+                service: String::new(),
+            }),
+            Some(("grpc", service)) => Ok(Self::Grpc {
+                service: service.to_string(),
+            }),
+            Some(("http", path)) if path.starts_with('/') => path
+                .parse()
+                .map(|path| Self::Http { path })
+                .map_err(|_| InvalidProbe(s.to_string())),
+            _ => Err(InvalidProbe(s.to_string())),
+        }
+    }
+}
+
+async fn http_get(
+    io: TcpStream,
+    addr: SocketAddr,
+    path: &http::uri::PathAndQuery,
+) -> Result<(), ProbeError> {
+    let (mut client, conn) = conn::handshake(io).await.map_err(ProbeError::Http)?;
+    tokio::spawn(conn);
+
+    let req = http::Request::get(path.as_str())
+        .header(http::header::HOST, addr.to_string())
+        .body(Body::empty())
+        .expect("request must be valid");
+    let rsp = client.send_request(req).await.map_err(ProbeError::Http)?;
+    if !rsp.status().is_success() {
+        return Err(ProbeError::Status(rsp.status()));
+    }
+    Ok(())
+}
+
+async fn grpc_check(io: TcpStream, addr: SocketAddr, service: &str) -> Result<(), ProbeError> {
+    let (mut client, conn) = conn::Builder::new()
+        .http2_only(true)
+        .handshake(io)
+        .await
+        .map_err(ProbeError::Http)?;
+    tokio::spawn(conn);
+
+    let uri = http::Uri::builder()
+        .scheme("http")
+        .authority(addr.to_string())
+        .path_and_query(GRPC_HEALTH_CHECK)
+        .build()
+        .expect("URI must be valid");
+    let req = http::Request::post(uri)
+        .header(http::header::CONTENT_TYPE, "application/grpc")
+        .header(http::header::TE, "trailers")
+        .body(Body::from(encode_request(service)))
+        .expect("request must be valid");
+    let mut rsp = client.send_request(req).await.map_err(ProbeError::Http)?;
+    if rsp.status() != http::StatusCode::OK {
+        return Err(ProbeError::Status(rsp.status()));
+    }
+
+    let body = rsp.body_mut();
+    let mut data = BytesMut::new();
+    while let Some(chunk) = body.data().await {
+        data.put(chunk.map_err(ProbeError::Http)?);
+    }
+    // The status may be returned in the headers of a trailers-only response.
+    let trailers = body.trailers().await.map_err(ProbeError::Http)?;
+    let status = trailers
+        .as_ref()
+        .and_then(|t| t.get("grpc-status"))
+        .or_else(|| rsp.headers().get("grpc-status"));
+    match status.and_then(|s| s.to_str().ok()) {
+        Some("0") => {}
+        status => {
+            return Err(ProbeError::GrpcStatus(
+                status.unwrap_or("missing").to_string(),
+            ))
+        }
+    }
+
+    match decode_status(data.freeze()) {
+        Some(SERVING) => Ok(()),
+        status => {
+            debug!(?status, "Service is not serving");
+            Err(ProbeError::NotServing)
+        }
+    }
+}
+
+/// Encodes a length-prefixed `grpc.health.v1.HealthCheckRequest` message.
+fn encode_request(service: &str) -> Bytes {
+    let msg = HealthCheckRequest {
+        service: service.to_string(),
+    }
+    .encode_to_vec();
+    let mut frame = BytesMut::with_capacity(5 + msg.len());
+    frame.put_u8(0); // Uncompressed.
+    frame.put_u32(msg.len() as u32);
+    frame.put_slice(&msg);
+    frame.freeze()
+}
+
+/// Decodes the status of a length-prefixed
+/// `grpc.health.v1.HealthCheckResponse` message. The status defaults to
+/// `UNKNOWN` (0) when it is omitted.
+fn decode_status(mut frame: Bytes) -> Option<i32> {
+    if frame.remaining() < 5 || frame.get_u8() != 0 {
+        return None;
+    }
+    let len = frame.get_u32() as usize;
+    if frame.remaining() < len {
+        return None;
+    }
+    let rsp = HealthCheckResponse::decode(frame.split_to(len)).ok()?;
+    Some(rsp.status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_probes() {
+        assert_eq!("tcp".parse::<Probe>().unwrap(), Probe::Tcp);
+        assert_eq!(
+            "http:/ready".parse::<Probe>().unwrap(),
+            Probe::Http {
+                path: "/ready".parse().unwrap()
+            }
+        );
+        assert_eq!(
+            "grpc".parse::<Probe>().unwrap(),
+            Probe::Grpc {
+                service: String::new()
+            }
+        );
+        assert_eq!(
+            "grpc:foo.Bar".parse::<Probe>().unwrap(),
+            Probe::Grpc {
+                service: "foo.Bar".to_string()
+            }
+        );
+        assert!("http:ready".parse::<Probe>().is_err());
+        assert!("udp".parse::<Probe>().is_err());
+    }
+
+    #[test]
+    fn grpc_health_messages() {
+        assert_eq!(encode_request("").as_ref(), &[0, 0, 0, 0, 0]);
+        assert_eq!(
+            encode_request("svc").as_ref(),
+            &[0, 0, 0, 0, 5, 0x0a, 3, b's', b'v', b'c']
+        );
+
+        // An empty response has an UNKNOWN status.
+        assert_eq!(decode_status(Bytes::from_static(&[0, 0, 0, 0, 0])), Some(0));
+        assert_eq!(
+            decode_status(Bytes::from_static(&[0, 0, 0, 0, 2, 0x08, 1])),
+            Some(SERVING)
+        );
+        assert_eq!(
+            decode_status(Bytes::from_static(&[0, 0, 0, 0, 2, 0x08, 2])),
+            Some(2)
+        );
+        // Truncated messages and compressed messages are not decoded.
+        assert_eq!(
+            decode_status(Bytes::from_static(&[0, 0, 0, 0, 2, 0x08])),
+            None
+        );
+        assert_eq!(decode_status(Bytes::from_static(&[1, 0, 0, 0, 0])), None);
+    }
+}
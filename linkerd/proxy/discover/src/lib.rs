@@ -5,10 +5,12 @@ use linkerd_proxy_core::Resolve;
 
 pub mod buffer;
 pub mod from_resolve;
+pub mod health;
 pub mod make_endpoint;
 
 pub use self::buffer::Buffer;
 pub use self::from_resolve::FromResolve;
+pub use self::health::NewHealthCheck;
 pub use self::make_endpoint::MakeEndpoint;
 
 pub type Stack<N, R, E> = MakeEndpoint<FromResolve<R, E>, N>;
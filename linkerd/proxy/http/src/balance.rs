@@ -1,6 +1,14 @@
+pub use self::{
+    failure_accrual::{ConsecutiveFailures, FailureAccrual, IsFailure, NewFailureAccrual},
+    hash::RequestHasher,
+};
 use crate::Error;
 use hyper::body::HttpBody;
 pub use hyper_balance::{PendingUntilFirstData, PendingUntilFirstDataBody};
+pub use linkerd_proxy_balance::{
+    EndpointZone, HashKey, LoadBalancer, LoadHandle, NewZoned, PreferLocalZone, SlowStart,
+    ZoneAware,
+};
 use rand::thread_rng;
 use std::{hash::Hash, marker::PhantomData, time::Duration};
 use tower::discover::Discover;
@@ -9,6 +17,17 @@ pub use tower::{
     load::{Load, PeakEwmaDiscover},
 };
 
+pub mod failure_accrual;
+mod hash;
+
+/// Builds a balancer over each discovered set of HTTP endpoints, using the
+/// algorithm configured for the target.
+pub type MakeBalance<M, A> =
+    linkerd_proxy_balance::MakeBalance<M, PendingUntilFirstData, RequestHasher, http::Request<A>>;
+
+/// The body of responses from balancers built by [`MakeBalance`].
+pub type BalanceBody<B> = PendingUntilFirstDataBody<LoadHandle, B>;
+
 /// Configures a stack to resolve `T` typed targets to balance requests over
 /// `M`-typed endpoint stacks.
 #[derive(Debug)]
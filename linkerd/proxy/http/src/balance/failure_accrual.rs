@@ -0,0 +1,524 @@
+//! Ejects balancer endpoints that fail consecutive requests.
+//!
+//! The balancer only dispatches requests to endpoints that are ready, so an
+//! ejected endpoint simply reports that it is not ready until its ejection
+//! elapses. The endpoint is then placed on probation: a single failure ejects
+//! it again for an exponentially longer period, while a success restores it.
+//!
+//! So that a widespread failure does not leave a balancer without endpoints,
+//! endpoints are not ejected once a configured fraction of the balancer's
+//! endpoints has been ejected.
+
+use futures::{ready, StreamExt, TryFuture};
+use hyper::body::HttpBody;
+use linkerd_error::Error;
+use linkerd_exp_backoff::{ExponentialBackoff, ExponentialBackoffStream};
+use linkerd_http_classify::{ClassifyEos, ClassifyResponse};
+use linkerd_http_metrics::ejections::{Ejected, Ejections, Handle, Registered};
+use linkerd_stack::{layer, NewService, Param};
+use pin_project::{pin_project, pinned_drop};
+use std::{
+    future::Future,
+    hash::Hash,
+    marker::PhantomData,
+    num::NonZeroUsize,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+use tracing::debug;
+
+/// Configures when endpoints are ejected and for how long.
+#[derive(Copy, Clone, Debug)]
+pub struct ConsecutiveFailures {
+    /// The number of consecutive failed requests after which an endpoint is
+    /// ejected.
+    pub max_failures: NonZeroUsize,
+
+    /// Determines how long an endpoint is ejected. Each time an endpoint on
+    /// probation fails, it is ejected for longer.
+    pub backoff: ExponentialBackoff,
+
+    /// The maximum fraction, in `(0, 1]`, of a balancer's endpoints that may
+    /// be ejected at once. Endpoints that fail while this many are ejected
+    /// remain in the balancer.
+    pub max_ejected_ratio: f64,
+}
+
+/// Determines whether a response class indicates that an endpoint failed.
+pub trait IsFailure {
+    fn is_failure(&self) -> bool;
+}
+
+/// Wraps endpoint services so that they are ejected from the balancer after
+/// consecutive failures.
+///
+/// Responses are classified by the `C`-typed classifier set on each request,
+/// if any. Ejections are recorded and limited per `K`-typed target label, which
+/// is expected to identify the endpoints' balancer.
+#[derive(Debug)]
+pub struct NewFailureAccrual<C, K: Hash + Eq, N> {
+    config: Option<ConsecutiveFailures>,
+    ejections: Ejections<K>,
+    inner: N,
+    _marker: PhantomData<fn() -> C>,
+}
+
+#[derive(Debug)]
+pub struct FailureAccrual<C, S> {
+    accrual: Option<Accrual>,
+    inner: S,
+    _marker: PhantomData<fn() -> C>,
+}
+
+#[derive(Debug)]
+struct Accrual {
+    config: ConsecutiveFailures,
+    failures: Arc<AtomicUsize>,
+    backoff: Option<ExponentialBackoffStream>,
+    ejected: Option<Ejected>,
+    metrics: Handle,
+    _registered: Registered,
+}
+
+#[pin_project]
+#[derive(Debug)]
+pub struct ResponseFuture<F, C> {
+    classify: Option<C>,
+    failures: Option<Arc<AtomicUsize>>,
+    #[pin]
+    inner: F,
+}
+
+#[pin_project(PinnedDrop)]
+#[derive(Debug)]
+pub struct ResponseBody<B, C>
+where
+    C: ClassifyEos,
+    C::Class: IsFailure,
+{
+    classify: Option<C>,
+    failures: Option<Arc<AtomicUsize>>,
+    #[pin]
+    inner: B,
+}
+
+// === impl NewFailureAccrual ===
+
+impl<C, K: Hash + Eq, N> NewFailureAccrual<C, K, N> {
+    pub fn layer(
+        config: Option<ConsecutiveFailures>,
+        ejections: Ejections<K>,
+    ) -> impl layer::Layer<N, Service = Self> + Clone {
+        layer::mk(move |inner| Self {
+            config,
+            ejections: ejections.clone(),
+            inner,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<C, K, T, N> NewService<T> for NewFailureAccrual<C, K, N>
+where
+    T: Param<K>,
+    K: Hash + Eq,
+    N: NewService<T>,
+{
+    type Service = FailureAccrual<C, N::Service>;
+
+    fn new_service(&self, target: T) -> Self::Service {
+        let accrual = self.config.map(|config| {
+            let metrics = self.ejections.get_handle(target.param());
+            Accrual {
+                config,
+                failures: Arc::new(AtomicUsize::new(0)),
+                backoff: None,
+                ejected: None,
+                _registered: metrics.register(),
+                metrics,
+            }
+        });
+        FailureAccrual {
+            accrual,
+            inner: self.inner.new_service(target),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C, K: Hash + Eq, N: Clone> Clone for NewFailureAccrual<C, K, N> {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config,
+            ejections: self.ejections.clone(),
+            inner: self.inner.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+// === impl FailureAccrual ===
+
+impl<C, S, A, B> tower::Service<http::Request<A>> for FailureAccrual<C, S>
+where
+    S: tower::Service<http::Request<A>, Response = http::Response<B>>,
+    S::Error: Into<Error>,
+    C: ClassifyResponse + Clone + Default + Send + Sync + 'static,
+    C::Class: IsFailure,
+{
+    type Response = http::Response<ResponseBody<B, C::ClassifyEos>>;
+    type Error = Error;
+    type Future = ResponseFuture<S::Future, C>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if let Some(accrual) = self.accrual.as_mut() {
+            ready!(accrual.poll_restored(cx));
+        }
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<A>) -> Self::Future {
+        let failures = self.accrual.as_ref().map(|a| a.failures.clone());
+        let classify = failures
+            .as_ref()
+            .map(|_| req.extensions().get::<C>().cloned().unwrap_or_default());
+        ResponseFuture {
+            classify,
+            failures,
+            inner: self.inner.call(req),
+        }
+    }
+}
+
+// === impl Accrual ===
+
+impl Accrual {
+    /// Ejects the endpoint if it has failed too many consecutive requests,
+    /// returning `Pending` until the ejection elapses.
+    fn poll_restored(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        loop {
+            if self.ejected.is_some() {
+                let backoff = self
+                    .backoff
+                    .get_or_insert_with(|| self.config.backoff.stream());
+                // The stream only ends once its iterations are exhausted, in
+                // which case the endpoint is simply restored.
+                let _ = ready!(backoff.poll_next_unpin(cx));
+                debug!("Endpoint on probation");
+                self.ejected = None;
+                // A single failure on probation ejects the endpoint again.
+                self.failures
+                    .store(self.config.max_failures.get() - 1, Ordering::Release);
+                return Poll::Ready(());
+            }
+
+            let failures = self.failures.load(Ordering::Acquire);
+            if failures < self.config.max_failures.get() {
+                if failures == 0 {
+                    // The endpoint has succeeded, so its next ejection starts
+                    // with the minimum backoff.
+                    self.backoff = None;
+                }
+                return Poll::Ready(());
+            }
+
+            match self.metrics.try_eject(self.config.max_ejected_ratio) {
+                Some(ejected) => {
+                    debug!(failures, "Ejecting endpoint");
+                    self.ejected = Some(ejected);
+                }
+                None => {
+                    tracing::trace!(failures, "Too many endpoints ejected; not ejecting");
+                    return Poll::Ready(());
+                }
+            }
+        }
+    }
+}
+
+// === impl ResponseFuture ===
+
+impl<F, C, B> Future for ResponseFuture<F, C>
+where
+    F: TryFuture<Ok = http::Response<B>>,
+    F::Error: Into<Error>,
+    C: ClassifyResponse,
+    C::Class: IsFailure,
+{
+    type Output = Result<http::Response<ResponseBody<B, C::ClassifyEos>>, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let res = ready!(this.inner.try_poll(cx)).map_err(Into::into);
+
+        let classify = this.classify.take();
+        let failures = this.failures.take();
+        Poll::Ready(match res {
+            Ok(rsp) => {
+                let classify = classify.map(|c| c.start(&rsp));
+                Ok(rsp.map(|inner| ResponseBody {
+                    classify,
+                    failures,
+                    inner,
+                }))
+            }
+            Err(error) => {
+                if let (Some(classify), Some(failures)) = (classify, failures) {
+                    record(&failures, classify.error(&error));
+                }
+                Err(error)
+            }
+        })
+    }
+}
+
+// === impl ResponseBody ===
+
+impl<B, C> ResponseBody<B, C>
+where
+    C: ClassifyEos,
+    C::Class: IsFailure,
+{
+    fn classify(self: Pin<&mut Self>, classify: impl FnOnce(C) -> C::Class) {
+        let this = self.project();
+        if let (Some(c), Some(failures)) = (this.classify.take(), this.failures.take()) {
+            record(&failures, classify(c));
+        }
+    }
+}
+
+impl<B, C> HttpBody for ResponseBody<B, C>
+where
+    B: HttpBody,
+    B::Error: Into<Error>,
+    C: ClassifyEos,
+    C::Class: IsFailure,
+{
+    type Data = B::Data;
+    type Error = Error;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        match ready!(self.as_mut().project().inner.poll_data(cx)) {
+            Some(Err(e)) => {
+                let error = e.into();
+                self.classify(|c| c.error(&error));
+                Poll::Ready(Some(Err(error)))
+            }
+            frame => Poll::Ready(frame.map(|f| f.map_err(Into::into))),
+        }
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        match ready!(self.as_mut().project().inner.poll_trailers(cx)) {
+            Ok(trailers) => {
+                self.classify(|c| c.eos(trailers.as_ref()));
+                Poll::Ready(Ok(trailers))
+            }
+            Err(e) => {
+                let error = e.into();
+                self.classify(|c| c.error(&error));
+                Poll::Ready(Err(error))
+            }
+        }
+    }
+
+    #[inline]
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[pinned_drop]
+impl<B, C> PinnedDrop for ResponseBody<B, C>
+where
+    C: ClassifyEos,
+    C::Class: IsFailure,
+{
+    fn drop(self: Pin<&mut Self>) {
+        self.classify(|c| c.eos(None));
+    }
+}
+
+fn record(failures: &AtomicUsize, class: impl IsFailure) {
+    if class.is_failure() {
+        failures.fetch_add(1, Ordering::AcqRel);
+    } else {
+        failures.store(0, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use linkerd_stack::service_fn;
+    use std::time::Duration;
+    use tower::{Layer, Service};
+
+    #[derive(Clone, Debug, Default)]
+    struct Classify;
+
+    #[derive(Debug)]
+    struct Failed(bool);
+
+    impl ClassifyResponse for Classify {
+        type Class = Failed;
+        type ClassifyEos = Failed;
+
+        fn start<B>(self, rsp: &http::Response<B>) -> Failed {
+            Failed(rsp.status().is_server_error())
+        }
+
+        fn error(self, _: &Error) -> Failed {
+            Failed(true)
+        }
+    }
+
+    impl ClassifyEos for Failed {
+        type Class = Failed;
+
+        fn eos(self, _: Option<&http::HeaderMap>) -> Failed {
+            self
+        }
+
+        fn error(self, _: &Error) -> Failed {
+            Failed(true)
+        }
+    }
+
+    impl IsFailure for Failed {
+        fn is_failure(&self) -> bool {
+            self.0
+        }
+    }
+
+    fn status(req: http::Request<()>) -> future::Ready<Result<http::Response<()>, Error>> {
+        let status = req.uri().path()[1..].parse().unwrap();
+        let mut rsp = http::Response::new(());
+        *rsp.status_mut() = status;
+        future::ok(rsp)
+    }
+
+    fn is_ready<S>(svc: &mut S) -> bool
+    where
+        S: Service<http::Request<()>>,
+        S::Error: std::fmt::Debug,
+    {
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        match svc.poll_ready(&mut cx) {
+            Poll::Ready(res) => {
+                res.unwrap();
+                true
+            }
+            Poll::Pending => false,
+        }
+    }
+
+    fn req(status: u16) -> http::Request<()> {
+        http::Request::builder()
+            .uri(format!("/{}", status))
+            .body(())
+            .unwrap()
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn ejects_after_consecutive_failures() {
+        let ejections = Ejections::<()>::default();
+        let handle = ejections.get_handle(());
+        let config = ConsecutiveFailures {
+            max_failures: NonZeroUsize::new(2).unwrap(),
+            backoff: ExponentialBackoff::try_new(
+                Duration::from_secs(1),
+                Duration::from_secs(10),
+                0.0,
+            )
+            .unwrap(),
+            max_ejected_ratio: 1.0,
+        };
+        let mut svc = NewFailureAccrual::<Classify, (), _>::layer(Some(config), ejections)
+            .layer(move |()| service_fn(status))
+            .new_service(());
+
+        // Successes interrupt consecutive failures.
+        for code in [500, 200, 500] {
+            future::poll_fn(|cx| svc.poll_ready(cx)).await.unwrap();
+            svc.call(req(code)).await.unwrap();
+        }
+        assert!(is_ready(&mut svc));
+
+        // A second consecutive failure ejects the endpoint.
+        svc.call(req(500)).await.unwrap();
+        assert!(!is_ready(&mut svc));
+        assert_eq!(handle.ejected(), 1);
+
+        // Once the ejection elapses, a single failure ejects the endpoint for
+        // longer.
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        future::poll_fn(|cx| svc.poll_ready(cx)).await.unwrap();
+        assert_eq!(handle.ejected(), 0);
+        svc.call(req(503)).await.unwrap();
+        assert!(!is_ready(&mut svc));
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(!is_ready(&mut svc));
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        // A success on probation restores the endpoint.
+        future::poll_fn(|cx| svc.poll_ready(cx)).await.unwrap();
+        svc.call(req(200)).await.unwrap();
+        svc.call(req(500)).await.unwrap();
+        assert!(is_ready(&mut svc));
+        assert_eq!(handle.ejected(), 0);
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn limits_ejected_endpoints() {
+        let ejections = Ejections::<()>::default();
+        let handle = ejections.get_handle(());
+        let config = ConsecutiveFailures {
+            max_failures: NonZeroUsize::new(1).unwrap(),
+            backoff: ExponentialBackoff::try_new(
+                Duration::from_secs(1),
+                Duration::from_secs(10),
+                0.0,
+            )
+            .unwrap(),
+            max_ejected_ratio: 0.5,
+        };
+        let new_svc = NewFailureAccrual::<Classify, (), _>::layer(Some(config), ejections)
+            .layer(move |()| service_fn(status));
+        let mut svcs = (0..4).map(|_| new_svc.new_service(())).collect::<Vec<_>>();
+
+        // Only half of the endpoints are ejected when all of them fail.
+        for svc in svcs.iter_mut() {
+            future::poll_fn(|cx| svc.poll_ready(cx)).await.unwrap();
+            svc.call(req(500)).await.unwrap();
+        }
+        let ready = svcs.iter_mut().map(is_ready).filter(|ready| *ready).count();
+        assert_eq!(ready, 2);
+        assert_eq!(handle.ejected(), 2);
+
+        // Once endpoints are removed, the remaining endpoints are limited
+        // accordingly.
+        drop(svcs);
+        let mut svc = new_svc.new_service(());
+        future::poll_fn(|cx| svc.poll_ready(cx)).await.unwrap();
+        svc.call(req(500)).await.unwrap();
+        assert!(is_ready(&mut svc));
+        assert_eq!(handle.ejected(), 0);
+    }
+}
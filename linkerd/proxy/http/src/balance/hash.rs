@@ -0,0 +1,89 @@
+use crate::ClientHandle;
+use linkerd_proxy_balance::{HashKey, HashRequest};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+/// Hashes HTTP requests for ring-hash balancers.
+///
+/// Requests that lack the configured key (e.g. because the header is not set)
+/// are not hashed, so that they are balanced randomly.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestHasher(HashKey);
+
+// === impl RequestHasher ===
+
+impl From<HashKey> for RequestHasher {
+    fn from(key: HashKey) -> Self {
+        Self(key)
+    }
+}
+
+impl<B> HashRequest<http::Request<B>> for RequestHasher {
+    fn hash_request(&self, req: &http::Request<B>) -> Option<u64> {
+        let mut hasher = DefaultHasher::new();
+        match &self.0 {
+            HashKey::Header(name) => {
+                req.headers().get(name)?.as_bytes().hash(&mut hasher);
+            }
+            HashKey::Cookie(name) => {
+                cookie(req.headers(), name)?.hash(&mut hasher);
+            }
+            HashKey::ClientAddr => {
+                req.extensions()
+                    .get::<ClientHandle>()?
+                    .addr
+                    .ip()
+                    .hash(&mut hasher);
+            }
+        }
+        Some(hasher.finish())
+    }
+}
+
+/// Returns the value of the first cookie with the given name.
+fn cookie<'h>(headers: &'h http::HeaderMap, name: &str) -> Option<&'h str> {
+    headers
+        .get_all(http::header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .find_map(|pair| {
+            let (k, v) = pair.trim().split_once('=')?;
+            if k == name {
+                Some(v)
+            } else {
+                None
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_cookie_values() {
+        let hasher = RequestHasher::from(HashKey::Cookie("session".to_string()));
+        let req = |cookie: &'static str| {
+            http::Request::builder()
+                .header(http::header::COOKIE, cookie)
+                .body(())
+                .unwrap()
+        };
+
+        let a = hasher.hash_request(&req("theme=dark; session=abc"));
+        assert!(a.is_some());
+        assert_eq!(a, hasher.hash_request(&req("session=abc")));
+        assert_ne!(a, hasher.hash_request(&req("session=xyz")));
+        assert_eq!(hasher.hash_request(&req("theme=dark")), None);
+    }
+
+    #[test]
+    fn unhashed_without_client_handle() {
+        let hasher = RequestHasher::from(HashKey::ClientAddr);
+        let req = http::Request::builder().body(()).unwrap();
+        assert_eq!(hasher.hash_request(&req), None);
+    }
+}
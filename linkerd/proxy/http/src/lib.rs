@@ -17,6 +17,7 @@ pub mod normalize_uri;
 pub mod orig_proto;
 mod override_authority;
 mod retain;
+pub mod route_filter;
 mod server;
 pub mod strip_header;
 pub mod timeout;
@@ -32,9 +33,13 @@ pub use self::{
     normalize_uri::{MarkAbsoluteForm, NewNormalizeUri},
     override_authority::{AuthorityOverride, NewOverrideAuthority},
     retain::Retain,
+    route_filter::{NewApplyFilters, RouteFilters},
     server::NewServeHttp,
     strip_header::StripHeader,
-    timeout::{NewTimeout, ResponseTimeout, ResponseTimeoutError},
+    timeout::{
+        Deadline, DeadlineExceededError, EnforceDeadline, NewTimeout, ResponseTimeout,
+        ResponseTimeoutError, RewriteDeadline,
+    },
     version::Version,
 };
 pub use http::{
@@ -0,0 +1,250 @@
+//! Applies a matched route's filters to requests and their responses.
+
+use futures::{future, TryFutureExt};
+use linkerd_error::Error;
+use linkerd_stack::{layer, NewService, Param};
+use pin_project::pin_project;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use thiserror::Error;
+use tracing::debug;
+
+pub use linkerd_http_route::http::filter::{
+    FaultAbort, FaultDelay, Filter, InjectFault, InvalidRedirect, ModifyHeader, ModifyPath, Ratio,
+    RedirectRequest, Redirection,
+};
+
+/// The filters configured on a route, in the order in which they are applied.
+#[derive(Clone, Debug, Default)]
+pub struct RouteFilters(pub Arc<Vec<Filter>>);
+
+#[derive(Clone, Debug)]
+pub struct NewApplyFilters<N> {
+    inner: N,
+}
+
+#[derive(Clone, Debug)]
+pub struct ApplyFilters<S> {
+    filters: Arc<Vec<Filter>>,
+    inner: S,
+}
+
+#[pin_project]
+#[derive(Debug)]
+pub struct ResponseFuture<F> {
+    filters: Arc<Vec<Filter>>,
+    #[pin]
+    inner: F,
+}
+
+#[derive(Debug, Error)]
+#[error("invalid rewritten path: {0}")]
+pub struct InvalidRewrite(#[source] http::Error);
+
+// === impl NewApplyFilters ===
+
+impl<N> NewApplyFilters<N> {
+    pub fn layer() -> impl layer::Layer<N, Service = Self> + Clone {
+        layer::mk(|inner| Self { inner })
+    }
+}
+
+impl<T, N> NewService<T> for NewApplyFilters<N>
+where
+    T: Param<RouteFilters>,
+    N: NewService<T>,
+{
+    type Service = ApplyFilters<N::Service>;
+
+    fn new_service(&self, target: T) -> Self::Service {
+        let RouteFilters(filters) = target.param();
+        ApplyFilters {
+            filters,
+            inner: self.inner.new_service(target),
+        }
+    }
+}
+
+// === impl ApplyFilters ===
+
+impl<S> ApplyFilters<S> {
+    pub fn new(filters: Arc<Vec<Filter>>, inner: S) -> Self {
+        Self { filters, inner }
+    }
+}
+
+impl<S, B, RspB> tower::Service<http::Request<B>> for ApplyFilters<S>
+where
+    S: tower::Service<http::Request<B>, Response = http::Response<RspB>>,
+    S::Error: Into<Error>,
+    RspB: Default,
+{
+    type Response = http::Response<RspB>;
+    type Error = Error;
+    type Future = future::Either<
+        ResponseFuture<future::MapErr<S::Future, fn(S::Error) -> Error>>,
+        future::Ready<Result<http::Response<RspB>, Error>>,
+    >;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        for filter in self.filters.iter() {
+            match filter {
+                Filter::RequestHeaders(modify) => modify.apply(req.headers_mut()),
+
+                Filter::RewritePath(modify) => {
+                    if let Some(path) = modify.apply(req.uri().path()) {
+                        debug!(%path, "Rewriting request path");
+                        if let Err(e) = rewrite_path(req.uri_mut(), path) {
+                            return future::Either::Right(future::err(e.into()));
+                        }
+                    }
+                }
+
+                Filter::Redirect(redirect) => {
+                    let rsp = redirect.redirect(&req).map_err(Into::into).and_then(
+                        |Redirection { status, location }| {
+                            debug!(%status, %location, "Redirecting request");
+                            http::Response::builder()
+                                .status(status)
+                                .header(http::header::LOCATION, location.to_string())
+                                .body(RspB::default())
+                                .map_err(Into::into)
+                        },
+                    );
+                    return future::Either::Right(future::ready(rsp));
+                }
+
+                // Applied to the response.
+                Filter::ResponseHeaders(_) => {}
+
+                // Faults are injected by the application, so that they may be
+                // observed by retries and timeouts.
+                Filter::InjectFault(_) => {}
+            }
+        }
+
+        future::Either::Left(ResponseFuture {
+            filters: self.filters.clone(),
+            inner: self
+                .inner
+                .call(req)
+                .map_err(Into::into as fn(S::Error) -> Error),
+        })
+    }
+}
+
+fn rewrite_path(uri: &mut http::Uri, path: String) -> Result<(), InvalidRewrite> {
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path,
+    };
+    let mut parts = std::mem::take(uri).into_parts();
+    parts.path_and_query = Some(
+        path_and_query
+            .parse()
+            .map_err(|e: http::uri::InvalidUri| InvalidRewrite(e.into()))?,
+    );
+    *uri = http::Uri::from_parts(parts).map_err(|e| InvalidRewrite(e.into()))?;
+    Ok(())
+}
+
+// === impl ResponseFuture ===
+
+impl<F, B> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<http::Response<B>, Error>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let mut rsp = futures::ready!(this.inner.poll(cx))?;
+        for filter in this.filters.iter() {
+            if let Filter::ResponseHeaders(modify) = filter {
+                modify.apply(rsp.headers_mut());
+            }
+        }
+        Poll::Ready(Ok(rsp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::{header::HeaderValue, StatusCode};
+    use linkerd_stack::service_fn;
+    use tower::Service;
+
+    fn echo_uri(req: http::Request<()>) -> future::Ready<Result<http::Response<String>, Error>> {
+        let mut rsp = http::Response::new(req.uri().to_string());
+        *rsp.headers_mut() = req.headers().clone();
+        future::ok(rsp)
+    }
+
+    #[test]
+    fn modifies_request_and_response() {
+        let filters = vec![
+            Filter::RequestHeaders(ModifyHeader {
+                set: vec![("x-req".parse().unwrap(), HeaderValue::from_static("a"))],
+                ..ModifyHeader::default()
+            }),
+            Filter::RewritePath(ModifyPath::ReplacePrefixMatch {
+                prefix: "/foo".to_string(),
+                replacement: "/bar".to_string(),
+            }),
+            Filter::ResponseHeaders(ModifyHeader {
+                remove: vec!["x-req".parse().unwrap()],
+                set: vec![("x-rsp".parse().unwrap(), HeaderValue::from_static("b"))],
+                ..ModifyHeader::default()
+            }),
+        ];
+        let mut svc = ApplyFilters::new(Arc::new(filters), service_fn(echo_uri));
+
+        let req = http::Request::builder()
+            .uri("http://example.com/foo/baz?qux")
+            .body(())
+            .unwrap();
+        let rsp = tokio_test::block_on(svc.call(req)).expect("request must succeed");
+        assert_eq!(rsp.body(), "http://example.com/bar/baz?qux");
+        assert!(!rsp.headers().contains_key("x-req"));
+        assert_eq!(rsp.headers().get("x-rsp").unwrap(), "b");
+    }
+
+    #[test]
+    fn redirects() {
+        let filters = vec![Filter::Redirect(RedirectRequest {
+            host: Some("example.org".to_string()),
+            status: Some(StatusCode::MOVED_PERMANENTLY),
+            ..RedirectRequest::default()
+        })];
+        let mut svc = ApplyFilters::new(
+            Arc::new(filters),
+            service_fn(
+                |_: http::Request<()>| -> future::Ready<Result<http::Response<String>, Error>> {
+                    panic!("redirected requests must not be dispatched")
+                },
+            ),
+        );
+
+        let req = http::Request::builder()
+            .uri("/foo")
+            .header("host", "example.com")
+            .body(())
+            .unwrap();
+        let rsp = tokio_test::block_on(svc.call(req)).expect("request must succeed");
+        assert_eq!(rsp.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(
+            rsp.headers().get(http::header::LOCATION).unwrap(),
+            "http://example.org/foo"
+        );
+    }
+}
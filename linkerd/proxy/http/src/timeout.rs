@@ -1,7 +1,29 @@
+//! Response timeouts and request deadlines.
+//!
+//! Clients may bound a request with a deadline, either with a `grpc-timeout`
+//! header or, for plain HTTP, an `l5d-deadline` header holding the number of
+//! milliseconds remaining. The proxy reads and enforces the deadline as soon as
+//! a request is received (see [`EnforceDeadline`]), and a route's timeout is
+//! bounded by it. Each attempt's deadline headers are rewritten with the
+//! remaining budget when the request is dispatched (see [`RewriteDeadline`]),
+//! so that time spent in the proxy, including in prior attempts, is deducted
+//! from the budget that the next hop receives.
+
+use http::{HeaderMap, HeaderValue};
 use linkerd_error::Error;
-use linkerd_stack::{layer, MapErr, NewService, Param, Timeout, TimeoutError};
-use std::time::Duration;
+use linkerd_stack::{layer, NewService, Param, Service};
+use pin_project::pin_project;
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
 use thiserror::Error;
+use tokio::time::{self, Instant};
+
+pub const GRPC_TIMEOUT: &str = "grpc-timeout";
+pub const L5D_DEADLINE: &str = "l5d-deadline";
 
 #[derive(Clone, Debug)]
 pub struct ResponseTimeout(pub Option<Duration>);
@@ -10,10 +32,36 @@ pub struct ResponseTimeout(pub Option<Duration>);
 #[error("HTTP response timeout after {0:?}")]
 pub struct ResponseTimeoutError(Duration);
 
+#[derive(Clone, Debug, Error)]
+#[error("request deadline exceeded after {0:?}")]
+pub struct DeadlineExceededError(Duration);
+
+/// A request extension recording the time at which a request's deadline
+/// expires.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Deadline(Instant);
+
+/// Records the deadline of requests that carry a deadline header as a
+/// [`Deadline`] extension, and fails requests whose responses are not received
+/// before their deadline.
+#[derive(Clone, Debug)]
+pub struct EnforceDeadline<S> {
+    inner: S,
+}
+
+/// Rewrites the deadline headers of requests with a [`Deadline`] extension
+/// with the remaining budget.
+#[derive(Clone, Debug)]
+pub struct RewriteDeadline<S> {
+    inner: S,
+}
+
 /// An HTTP-specific optional timeout layer.
 ///
 /// The stack target must implement `HasTimeout`, and if a duration is
 /// specified for the target, a timeout is applied waiting for HTTP responses.
+/// Requests with a deadline are bounded by whichever of the deadline and the
+/// target's timeout expires first.
 ///
 /// Timeout errors are translated into `http::Response`s with appropiate
 /// status codes.
@@ -22,6 +70,131 @@ pub struct NewTimeout<M> {
     inner: M,
 }
 
+#[derive(Clone, Debug)]
+pub struct Timeout<S> {
+    inner: S,
+    timeout: Option<Duration>,
+}
+
+#[pin_project(project = ResponseFutureProj)]
+pub enum ResponseFuture<F> {
+    Passthru(#[pin] F),
+    Timeout(#[pin] time::Timeout<F>, Expiry),
+    Expired(Option<Expiry>),
+}
+
+/// Describes which bound on a request's response elapsed.
+#[derive(Copy, Clone, Debug)]
+pub enum Expiry {
+    Timeout(Duration),
+    Deadline(Duration),
+}
+
+// === impl Deadline ===
+
+impl Deadline {
+    /// Returns the deadline of a request with a deadline header, relative to
+    /// `now`.
+    pub fn from_headers(headers: &HeaderMap, now: Instant) -> Option<Self> {
+        let timeout = headers
+            .get(GRPC_TIMEOUT)
+            .and_then(|v| parse_grpc_timeout(v.to_str().ok()?));
+        let deadline = headers
+            .get(L5D_DEADLINE)
+            .and_then(|v| v.to_str().ok()?.parse().ok())
+            .map(Duration::from_millis);
+        let remaining = match (timeout, deadline) {
+            (Some(t), Some(d)) => t.min(d),
+            (t, d) => t.or(d)?,
+        };
+        Some(Self(now + remaining))
+    }
+
+    pub fn remaining(&self, now: Instant) -> Duration {
+        self.0.saturating_duration_since(now)
+    }
+}
+
+// === impl EnforceDeadline ===
+
+impl<S> EnforceDeadline<S> {
+    pub fn layer() -> impl tower::layer::Layer<S, Service = Self> + Clone {
+        layer::mk(|inner| Self { inner })
+    }
+}
+
+impl<B, S> Service<http::Request<B>> for EnforceDeadline<S>
+where
+    S: Service<http::Request<B>>,
+    S::Error: Into<Error>,
+{
+    type Response = S::Response;
+    type Error = Error;
+    type Future = ResponseFuture<S::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        let now = Instant::now();
+        let deadline = match req.extensions().get::<Deadline>().copied() {
+            Some(deadline) => deadline,
+            None => match Deadline::from_headers(req.headers(), now) {
+                Some(deadline) => {
+                    tracing::trace!(?deadline, "Request has a deadline");
+                    req.extensions_mut().insert(deadline);
+                    deadline
+                }
+                None => return ResponseFuture::Passthru(self.inner.call(req)),
+            },
+        };
+
+        let expiry = Expiry::Deadline(deadline.remaining(now));
+        if expiry.duration().is_zero() {
+            tracing::debug!("Request deadline exceeded before dispatch");
+            return ResponseFuture::Expired(Some(expiry));
+        }
+        ResponseFuture::Timeout(
+            time::timeout(expiry.duration(), self.inner.call(req)),
+            expiry,
+        )
+    }
+}
+
+// === impl RewriteDeadline ===
+
+impl<S> RewriteDeadline<S> {
+    pub fn layer() -> impl tower::layer::Layer<S, Service = Self> + Clone {
+        layer::mk(|inner| Self { inner })
+    }
+}
+
+impl<B, S> Service<http::Request<B>> for RewriteDeadline<S>
+where
+    S: Service<http::Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        if let Some(deadline) = req.extensions().get::<Deadline>().copied() {
+            let remaining = deadline.remaining(Instant::now());
+            set_deadline_headers(req.headers_mut(), remaining);
+        }
+        self.inner.call(req)
+    }
+}
+
+// === impl NewTimeout ===
+
 impl<N> NewTimeout<N> {
     pub fn layer() -> impl tower::layer::Layer<N, Service = Self> + Clone {
         layer::mk(|inner| Self { inner })
@@ -33,19 +206,269 @@ where
     T: Param<ResponseTimeout>,
     M: NewService<T>,
 {
-    type Service = MapErr<Timeout<M::Service>, fn(Error) -> Error>;
+    type Service = Timeout<M::Service>;
 
     fn new_service(&self, target: T) -> Self::Service {
-        let svc = match target.param() {
-            ResponseTimeout(Some(t)) => Timeout::new(self.inner.new_service(target), t),
-            ResponseTimeout(None) => Timeout::passthru(self.inner.new_service(target)),
+        let ResponseTimeout(timeout) = target.param();
+        Timeout {
+            inner: self.inner.new_service(target),
+            timeout,
+        }
+    }
+}
+
+// === impl Timeout ===
+
+impl<B, S> Service<http::Request<B>> for Timeout<S>
+where
+    S: Service<http::Request<B>>,
+    S::Error: Into<Error>,
+{
+    type Response = S::Response;
+    type Error = Error;
+    type Future = ResponseFuture<S::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let now = Instant::now();
+        let deadline = req.extensions().get::<Deadline>().map(|d| d.remaining(now));
+
+        let expiry = match (self.timeout, deadline) {
+            (Some(t), Some(d)) if t <= d => Expiry::Timeout(t),
+            (_, Some(d)) => Expiry::Deadline(d),
+            (Some(t), None) => Expiry::Timeout(t),
+            (None, None) => return ResponseFuture::Passthru(self.inner.call(req)),
         };
-        MapErr::new(svc, |error| {
-            if let Some(t) = error.downcast_ref::<TimeoutError>() {
-                ResponseTimeoutError(t.duration()).into()
-            } else {
-                error
+
+        if expiry.duration().is_zero() {
+            tracing::debug!("Request deadline exceeded before dispatch");
+            return ResponseFuture::Expired(Some(expiry));
+        }
+        ResponseFuture::Timeout(
+            time::timeout(expiry.duration(), self.inner.call(req)),
+            expiry,
+        )
+    }
+}
+
+// === impl ResponseFuture ===
+
+impl<F, T, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<T, E>>,
+    E: Into<Error>,
+{
+    type Output = Result<T, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            ResponseFutureProj::Passthru(f) => f.poll(cx).map_err(Into::into),
+            ResponseFutureProj::Timeout(f, expiry) => {
+                let res = futures::ready!(f.poll(cx)).map_err(|_| expiry.error())?;
+                Poll::Ready(res.map_err(Into::into))
+            }
+            ResponseFutureProj::Expired(expiry) => {
+                Poll::Ready(Err(expiry.take().expect("polled after completion").error()))
             }
-        })
+        }
+    }
+}
+
+// === impl Expiry ===
+
+impl Expiry {
+    fn duration(&self) -> Duration {
+        match *self {
+            Self::Timeout(t) | Self::Deadline(t) => t,
+        }
+    }
+
+    fn error(&self) -> Error {
+        match *self {
+            Self::Timeout(t) => ResponseTimeoutError(t).into(),
+            Self::Deadline(t) => DeadlineExceededError(t).into(),
+        }
+    }
+}
+
+/// Rewrites the deadline headers that are present on a request with the
+/// remaining budget.
+fn set_deadline_headers(headers: &mut HeaderMap, remaining: Duration) {
+    if headers.contains_key(GRPC_TIMEOUT) {
+        headers.insert(GRPC_TIMEOUT, encode_grpc_timeout(remaining));
+    }
+    if headers.contains_key(L5D_DEADLINE) {
+        headers.insert(
+            L5D_DEADLINE,
+            HeaderValue::from(remaining.as_millis() as u64),
+        );
+    }
+}
+
+/// Parses a `grpc-timeout` value: at most 8 digits followed by a unit.
+fn parse_grpc_timeout(s: &str) -> Option<Duration> {
+    if s.len() < 2 || s.len() > 9 {
+        return None;
+    }
+    let (value, unit) = s.split_at(s.len() - 1);
+    if !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let value = value.parse::<u64>().ok()?;
+    let duration = match unit {
+        "H" => Duration::from_secs(value * 60 * 60),
+        "M" => Duration::from_secs(value * 60),
+        "S" => Duration::from_secs(value),
+        "m" => Duration::from_millis(value),
+        "u" => Duration::from_micros(value),
+        "n" => Duration::from_nanos(value),
+        _ => return None,
+    };
+    Some(duration)
+}
+
+/// Encodes a `grpc-timeout` value with the most precise unit that fits in 8
+/// digits.
+fn encode_grpc_timeout(timeout: Duration) -> HeaderValue {
+    const MAX: u128 = 99_999_999;
+    let nanos = timeout.as_nanos();
+    let (value, unit) = if nanos <= MAX {
+        (nanos, "n")
+    } else if nanos / 1_000 <= MAX {
+        (nanos / 1_000, "u")
+    } else if nanos / 1_000_000 <= MAX {
+        (nanos / 1_000_000, "m")
+    } else if timeout.as_secs() as u128 <= MAX {
+        (timeout.as_secs() as u128, "S")
+    } else if timeout.as_secs() as u128 / 60 <= MAX {
+        (timeout.as_secs() as u128 / 60, "M")
+    } else {
+        ((timeout.as_secs() as u128 / 3600).min(MAX), "H")
+    };
+    HeaderValue::from_str(&format!("{}{}", value, unit)).expect("timeout must be a valid header")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use linkerd_stack::service_fn;
+
+    #[test]
+    fn grpc_timeouts() {
+        assert_eq!(parse_grpc_timeout("1H"), Some(Duration::from_secs(3600)));
+        assert_eq!(parse_grpc_timeout("250m"), Some(Duration::from_millis(250)));
+        assert_eq!(
+            parse_grpc_timeout("99999999n"),
+            Some(Duration::from_nanos(99_999_999))
+        );
+        assert_eq!(parse_grpc_timeout("100"), None);
+        assert_eq!(parse_grpc_timeout("123456789S"), None);
+        assert_eq!(parse_grpc_timeout("-1S"), None);
+
+        assert_eq!(encode_grpc_timeout(Duration::from_millis(250)), "250000u");
+        assert_eq!(encode_grpc_timeout(Duration::from_secs(500)), "500000m");
+        assert_eq!(encode_grpc_timeout(Duration::from_secs(200_000)), "200000S");
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn enforces_deadlines() {
+        let mut svc = EnforceDeadline {
+            inner: service_fn(|req: http::Request<()>| async move {
+                time::sleep(Duration::from_secs(1)).await;
+                Ok::<_, Error>(req.extensions().get::<Deadline>().copied())
+            }),
+        };
+
+        let req = http::Request::builder()
+            .header(L5D_DEADLINE, "500")
+            .body(())
+            .unwrap();
+        let err = svc.call(req).await.unwrap_err();
+        assert!(err.is::<DeadlineExceededError>(), "{}", err);
+
+        // The deadline is recorded for the inner stack.
+        let req = http::Request::builder()
+            .header(GRPC_TIMEOUT, "5S")
+            .body(())
+            .unwrap();
+        let deadline = svc.call(req).await.unwrap();
+        assert!(deadline.is_some());
+
+        // Requests without a deadline are not bounded.
+        let deadline = svc.call(http::Request::new(())).await.unwrap();
+        assert!(deadline.is_none());
+
+        // Expired requests are not dispatched.
+        let req = http::Request::builder()
+            .header(L5D_DEADLINE, "0")
+            .body(())
+            .unwrap();
+        let err = svc.call(req).await.unwrap_err();
+        assert!(err.is::<DeadlineExceededError>(), "{}", err);
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn bounds_timeouts_by_deadlines() {
+        let mut svc = Timeout {
+            inner: service_fn(|_: http::Request<()>| async move {
+                time::sleep(Duration::from_secs(1)).await;
+                Ok::<_, Error>(())
+            }),
+            timeout: Some(Duration::from_secs(10)),
+        };
+
+        // The deadline is shorter than the route's timeout.
+        let mut req = http::Request::new(());
+        req.extensions_mut()
+            .insert(Deadline(Instant::now() + Duration::from_millis(500)));
+        let err = svc.call(req).await.unwrap_err();
+        assert!(err.is::<DeadlineExceededError>(), "{}", err);
+
+        // The route's timeout bounds requests without a deadline.
+        svc.timeout = Some(Duration::from_millis(100));
+        let err = svc.call(http::Request::new(())).await.unwrap_err();
+        assert!(err.is::<ResponseTimeoutError>(), "{}", err);
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn rewrites_deadlines_per_attempt() {
+        let mut svc = RewriteDeadline {
+            inner: service_fn(|req: http::Request<()>| {
+                future::ok::<_, Error>(req.headers().clone())
+            }),
+        };
+        let deadline = Deadline(Instant::now() + Duration::from_secs(5));
+        let mk_req = || {
+            let mut req = http::Request::builder()
+                .header(GRPC_TIMEOUT, "5S")
+                .header(L5D_DEADLINE, "5000")
+                .body(())
+                .unwrap();
+            req.extensions_mut().insert(deadline);
+            req
+        };
+
+        // Each attempt is sent with the budget that remains when it is
+        // dispatched.
+        time::advance(Duration::from_secs(1)).await;
+        let headers = svc.call(mk_req()).await.unwrap();
+        assert_eq!(headers[GRPC_TIMEOUT], "4000000u");
+        assert_eq!(headers[L5D_DEADLINE], "4000");
+
+        time::advance(Duration::from_secs(2)).await;
+        let headers = svc.call(mk_req()).await.unwrap();
+        assert_eq!(headers[GRPC_TIMEOUT], "2000000u");
+        assert_eq!(headers[L5D_DEADLINE], "2000");
+
+        // Headers are not added to requests without them.
+        let mut req = http::Request::new(());
+        req.extensions_mut().insert(deadline);
+        let headers = svc.call(req).await.unwrap();
+        assert!(headers.is_empty());
     }
 }
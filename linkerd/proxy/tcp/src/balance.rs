@@ -1,12 +1,22 @@
 use linkerd_error::Error;
+pub use linkerd_proxy_balance::{
+    EndpointZone, LoadBalancer, NewZoned, NoHash, PreferLocalZone, SlowStart, ZoneAware,
+};
 use linkerd_stack::layer;
 use rand::thread_rng;
 use std::{hash::Hash, time::Duration};
+use tower::discover::Discover;
 pub use tower::{
     balance::p2c::Balance,
-    load::{Load, PeakEwmaDiscover},
+    load::{CompleteOnResponse, Load, PeakEwmaDiscover},
 };
-use tower::{discover::Discover, load::CompleteOnResponse};
+
+/// Builds a balancer over each discovered set of endpoints, using the
+/// algorithm configured for the target.
+///
+/// Connections carry no hash key, so ring-hash balancers distribute
+/// connections randomly.
+pub type MakeBalance<M, T> = linkerd_proxy_balance::MakeBalance<M, CompleteOnResponse, NoHash, T>;
 
 /// Produces a PeakEWMA balancer that uses connect latency (and pending
 /// connections) as its load metric.
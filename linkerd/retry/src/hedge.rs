@@ -0,0 +1,299 @@
+//! Hedges requests that have not received a response within a delay.
+//!
+//! A hedged request is a second attempt of a request that is sent while the
+//! first attempt is still in flight. Whichever attempt responds first is used
+//! and the other is cancelled. Hedges are dispatched through the same
+//! balancer as the first attempt, so a load-aware balancer prefers endpoints
+//! other than the one that is already processing the request.
+
+use futures::ready;
+use linkerd_stack::{Oneshot, Service, ServiceExt};
+use parking_lot::Mutex;
+use pin_project::pin_project;
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time::{self, Instant, Sleep};
+use tracing::trace;
+
+/// Determines whether and when requests are hedged.
+pub trait HedgePolicy<Req> {
+    /// Returns how long to wait for a response before the request is hedged,
+    /// if it may be hedged.
+    fn hedge_delay(&self, req: &Req) -> Option<Duration>;
+
+    /// Clones a request so that it may be hedged.
+    fn clone_hedge(&self, req: &Req) -> Option<Req>;
+
+    /// Returns whether a hedge may be sent when its delay elapses, e.g.
+    /// because the request's body may be replayed and a budget permits it.
+    fn can_hedge(&self, req: &Req) -> bool;
+
+    /// Records the latency of a request that received a response.
+    fn record_latency(&self, _latency: Duration) {}
+}
+
+/// Hedges requests to an inner service according to a `P`-typed policy.
+#[derive(Clone, Debug)]
+pub struct Hedge<P, S> {
+    policy: P,
+    inner: S,
+}
+
+#[pin_project]
+pub struct HedgeFuture<P, S, Req>
+where
+    S: Service<Req>,
+{
+    policy: P,
+    start: Instant,
+
+    #[pin]
+    original: Option<S::Future>,
+
+    #[pin]
+    hedge: Option<Oneshot<S, Req>>,
+
+    /// The service, request, and delay for a hedge that has not yet been
+    /// sent.
+    pending: Option<(S, Req, Pin<Box<Sleep>>)>,
+}
+
+/// Estimates a percentile of the latencies of recent requests.
+#[derive(Debug)]
+pub struct Latencies {
+    percentile: f64,
+    inner: Mutex<Window>,
+}
+
+#[derive(Debug, Default)]
+struct Window {
+    samples: VecDeque<Duration>,
+    since_estimate: usize,
+    estimate: Option<Duration>,
+}
+
+/// The number of recent latencies from which percentiles are estimated.
+const MAX_SAMPLES: usize = 1_000;
+
+/// The number of latencies that must be observed before a percentile is
+/// estimated, and between updates to the estimate.
+const MIN_SAMPLES: usize = 20;
+
+// === impl Hedge ===
+
+impl<P, S> Hedge<P, S> {
+    pub fn new(policy: P, inner: S) -> Self {
+        Self { policy, inner }
+    }
+}
+
+impl<P, S, Req> Service<Req> for Hedge<P, S>
+where
+    P: HedgePolicy<Req> + Clone,
+    S: Service<Req> + Clone,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = HedgeFuture<P, S, Req>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let pending = self.policy.hedge_delay(&req).and_then(|delay| {
+            let hedge = self.policy.clone_hedge(&req)?;
+            trace!(?delay, "Request may be hedged");
+            Some((self.inner.clone(), hedge, Box::pin(time::sleep(delay))))
+        });
+        HedgeFuture {
+            policy: self.policy.clone(),
+            start: Instant::now(),
+            original: Some(self.inner.call(req)),
+            hedge: None,
+            pending,
+        }
+    }
+}
+
+// === impl HedgeFuture ===
+
+impl<P, S, Req> Future for HedgeFuture<P, S, Req>
+where
+    P: HedgePolicy<Req>,
+    S: Service<Req>,
+{
+    type Output = Result<S::Response, S::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        loop {
+            // Use whichever attempt responds first. If an attempt fails while
+            // the other is in flight, wait for the other attempt.
+            if let Some(f) = this.original.as_mut().as_pin_mut() {
+                if let Poll::Ready(res) = f.poll(cx) {
+                    this.original.set(None);
+                    *this.pending = None;
+                    match res {
+                        Ok(rsp) => {
+                            this.policy.record_latency(this.start.elapsed());
+                            return Poll::Ready(Ok(rsp));
+                        }
+                        Err(e) if this.hedge.is_none() => return Poll::Ready(Err(e)),
+                        Err(_) => trace!("Original request failed; awaiting hedge"),
+                    }
+                }
+            }
+
+            if let Some(f) = this.hedge.as_mut().as_pin_mut() {
+                if let Poll::Ready(res) = f.poll(cx) {
+                    this.hedge.set(None);
+                    match res {
+                        Ok(rsp) => {
+                            trace!("Hedge responded first");
+                            this.policy.record_latency(this.start.elapsed());
+                            return Poll::Ready(Ok(rsp));
+                        }
+                        Err(e) if this.original.is_none() => return Poll::Ready(Err(e)),
+                        Err(_) => trace!("Hedge failed; awaiting original request"),
+                    }
+                }
+            }
+
+            let sleep = match this.pending.as_mut() {
+                Some((_, _, sleep)) => sleep,
+                None => return Poll::Pending,
+            };
+            ready!(sleep.as_mut().poll(cx));
+            let (svc, req, _) = this.pending.take().expect("pending hedge must be set");
+            if !this.policy.can_hedge(&req) {
+                trace!("Request may not be hedged");
+                return Poll::Pending;
+            }
+            trace!("Hedging request");
+            this.hedge.set(Some(svc.oneshot(req)));
+        }
+    }
+}
+
+// === impl Latencies ===
+
+impl Latencies {
+    /// Estimates the given percentile, in `(0, 100]`, of latencies.
+    pub fn new(percentile: f64) -> Self {
+        Self {
+            percentile,
+            inner: Mutex::new(Window::default()),
+        }
+    }
+
+    /// Returns the estimated percentile, if enough latencies have been
+    /// observed.
+    pub fn estimate(&self) -> Option<Duration> {
+        self.inner.lock().estimate
+    }
+
+    pub fn record(&self, latency: Duration) {
+        let mut window = self.inner.lock();
+        if window.samples.len() == MAX_SAMPLES {
+            window.samples.pop_front();
+        }
+        window.samples.push_back(latency);
+        window.since_estimate += 1;
+        if window.since_estimate < MIN_SAMPLES {
+            return;
+        }
+
+        let mut sorted = window.samples.iter().copied().collect::<Vec<_>>();
+        sorted.sort_unstable();
+        let rank = (self.percentile / 100.0 * sorted.len() as f64).ceil() as usize;
+        window.estimate = Some(sorted[rank.clamp(1, sorted.len()) - 1]);
+        window.since_estimate = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[derive(Clone)]
+    struct Policy(Duration);
+
+    impl HedgePolicy<u64> for Policy {
+        fn hedge_delay(&self, _: &u64) -> Option<Duration> {
+            Some(self.0)
+        }
+
+        fn clone_hedge(&self, req: &u64) -> Option<u64> {
+            Some(*req)
+        }
+
+        fn can_hedge(&self, _: &u64) -> bool {
+            true
+        }
+    }
+
+    /// Responds after a delay that grows with each call, so that the first
+    /// attempt is the slowest.
+    #[derive(Clone)]
+    struct Slow(Arc<AtomicUsize>);
+
+    impl Service<u64> for Slow {
+        type Response = usize;
+        type Error = ();
+        type Future = future::BoxFuture<'static, Result<usize, ()>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), ()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, ms: u64) -> Self::Future {
+            let call = self.0.fetch_add(1, Ordering::SeqCst);
+            let delay = if call == 0 { ms } else { 1 };
+            Box::pin(async move {
+                time::sleep(Duration::from_millis(delay)).await;
+                Ok(call)
+            })
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn hedges_slow_requests() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut svc = Hedge::new(Policy(Duration::from_millis(10)), Slow(calls.clone()));
+
+        // The hedge responds before the original request.
+        let rsp = svc.ready().await.unwrap().call(1_000).await;
+        assert_eq!(rsp, Ok(1));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Requests that respond within the delay are not hedged.
+        calls.store(0, Ordering::SeqCst);
+        let rsp = svc.ready().await.unwrap().call(5).await;
+        assert_eq!(rsp, Ok(0));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn estimates_percentile() {
+        let latencies = Latencies::new(90.0);
+        for ms in 1..MIN_SAMPLES as u64 {
+            latencies.record(Duration::from_millis(ms));
+        }
+        assert_eq!(latencies.estimate(), None);
+
+        latencies.record(Duration::from_millis(MIN_SAMPLES as u64));
+        assert_eq!(latencies.estimate(), Some(Duration::from_millis(18)));
+    }
+}
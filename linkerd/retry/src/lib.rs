@@ -1,6 +1,9 @@
 #![deny(rust_2018_idioms, clippy::disallowed_methods, clippy::disallowed_types)]
 #![forbid(unsafe_code)]
 
+pub mod hedge;
+
+pub use self::hedge::{Hedge, HedgePolicy, Latencies};
 use futures::future;
 use linkerd_error::Error;
 use linkerd_stack::{
@@ -159,12 +162,13 @@ where
 
 // === impl Retry ===
 
-// The inner service created for requests that are retryable.
-type RetrySvc<P, S, R, F> = tower::retry::Retry<P, AndThen<S, fn(R) -> F>>;
+// The inner service created for requests that are retryable. Each attempt may
+// be hedged.
+type RetrySvc<P, S, R, F> = tower::retry::Retry<P, Hedge<P, AndThen<S, fn(R) -> F>>>;
 
 impl<P, S, O, Req, Fut, Rsp> Service<Req> for Retry<P, S, O>
 where
-    P: PrepareRetry<Req, Rsp, Error> + Clone,
+    P: PrepareRetry<Req, Rsp, Error> + HedgePolicy<P::RetryRequest> + Clone,
     S: Service<Req, Response = Rsp, Future = Fut, Error = Error>,
     S: Service<P::RetryRequest, Response = Rsp, Future = Fut, Error = Error>,
     S: Clone,
@@ -208,7 +212,7 @@ where
             self.inner.clone(),
             P::prepare_response as fn(Rsp) -> P::ResponseFuture,
         );
-        let retry = tower::retry::Retry::new(policy.clone(), inner);
+        let retry = tower::retry::Retry::new(policy.clone(), Hedge::new(policy.clone(), inner));
         let retry = self.proxy.clone().into_service(retry);
         future::Either::Right(retry.oneshot(retry_req))
     }
@@ -1,5 +1,5 @@
 use super::Meta;
-use std::{collections::BTreeSet, sync::Arc};
+use std::{collections::BTreeSet, fmt, sync::Arc};
 
 mod network;
 
@@ -47,6 +47,12 @@ impl Suffix {
     }
 }
 
+impl fmt::Display for Suffix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.ends_with.strip_prefix('.').unwrap_or(&self.ends_with))
+    }
+}
+
 #[cfg(feature = "proto")]
 pub mod proto {
     use super::*;
@@ -0,0 +1,9 @@
+use crate::RoutePolicy;
+pub use linkerd_http_route::grpc::{
+    find,
+    r#match::{MatchRoute, MatchRpc},
+    RouteMatch,
+};
+
+pub type Route = linkerd_http_route::grpc::Route<RoutePolicy>;
+pub type Rule = linkerd_http_route::grpc::Rule<RoutePolicy>;
@@ -0,0 +1,9 @@
+use crate::RoutePolicy;
+pub use linkerd_http_route::http::{
+    find,
+    r#match::{MatchHeader, MatchHost, MatchPath, MatchQueryParam, MatchRequest},
+    RouteMatch,
+};
+
+pub type Route = linkerd_http_route::http::Route<RoutePolicy>;
+pub type Rule = linkerd_http_route::http::Rule<RoutePolicy>;
@@ -2,17 +2,52 @@
 #![forbid(unsafe_code)]
 
 pub mod authz;
-
-pub use self::authz::{Authentication, Authorization};
+pub mod grpc;
+pub mod http;
+pub mod priority;
+pub mod rate_limit;
+
+pub use self::{
+    authz::{Authentication, Authorization},
+    priority::Priority,
+    rate_limit::{RateLimit, RateLimitKey},
+};
+pub use linkerd_http_route as route;
 use std::{borrow::Cow, hash::Hash, sync::Arc, time};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ServerPolicy {
     pub protocol: Protocol,
     pub authorizations: Arc<[Authorization]>,
+
+    /// Routes that authorize HTTP requests independently of the server's
+    /// `authorizations`.
+    ///
+    /// When no routes are configured, all requests are authorized against the
+    /// server's `authorizations`.
+    pub http_routes: Arc<[http::Route]>,
+
+    /// Routes that authorize gRPC requests on servers that use the `Grpc`
+    /// protocol. When these are set, they are used instead of `http_routes`.
+    pub grpc_routes: Arc<[grpc::Route]>,
+
+    /// Limits the rate of requests on the server, when set.
+    pub rate_limit: Option<RateLimit>,
+
     pub meta: Arc<Meta>,
 }
 
+/// Policy applied to requests matched by a route rule.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RoutePolicy {
+    pub meta: Arc<Meta>,
+    pub authorizations: Arc<[Authorization]>,
+
+    /// The priority of requests matched by the route. When unset, a request's
+    /// priority may be set by the client.
+    pub priority: Option<Priority>,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Protocol {
     Detect { timeout: time::Duration },
@@ -143,6 +178,12 @@ pub mod proto {
             Ok(ServerPolicy {
                 protocol,
                 authorizations,
+                // The v0.5 API does not describe routes, so all requests are
+                // authorized by the server's authorizations.
+                http_routes: Arc::new([]),
+                grpc_routes: Arc::new([]),
+                // Nor does it describe rate limits.
+                rate_limit: None,
                 meta,
             })
         }
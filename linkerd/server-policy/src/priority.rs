@@ -0,0 +1,57 @@
+use std::{fmt, str::FromStr};
+
+/// The priority class of a request.
+///
+/// When a server is overloaded, requests in higher classes are served before
+/// those in lower classes, and requests in lower classes are shed first.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    Normal,
+    High,
+    Critical,
+}
+
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("invalid priority: {0}")]
+pub struct InvalidPriority(String);
+
+// === impl Priority ===
+
+impl Priority {
+    /// All priorities, from lowest to highest.
+    pub const ALL: [Self; 4] = [Self::Low, Self::Normal, Self::High, Self::Critical];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Normal => "normal",
+            Self::High => "high",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
+impl FromStr for Priority {
+    type Err = InvalidPriority;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|p| p.as_str().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| InvalidPriority(s.to_string()))
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,41 @@
+use std::num::NonZeroU32;
+
+/// Limits the rate at which a server permits requests.
+///
+/// Rates are enforced with a token bucket that holds up to `burst` tokens and
+/// is refilled at `requests_per_second`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RateLimit {
+    pub requests_per_second: NonZeroU32,
+
+    /// The number of requests that may be permitted at once, i.e. the size of
+    /// the token bucket. Once the bucket is empty, requests are permitted at
+    /// `requests_per_second`. A burst of zero permits a single request at a
+    /// time.
+    pub burst: u32,
+
+    pub key: RateLimitKey,
+}
+
+/// Determines how requests are grouped when enforcing a rate limit.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    /// All requests to the server share a single limit.
+    Server,
+
+    /// Each client identity has its own limit. Clients without an identity
+    /// are limited by their source IP address.
+    ClientIdentity,
+
+    /// Each client source IP address has its own limit.
+    ClientAddr,
+}
+
+// === impl RateLimit ===
+
+impl RateLimit {
+    /// The maximum number of requests that may be permitted at once.
+    pub fn capacity(&self) -> u32 {
+        self.burst.max(1)
+    }
+}
@@ -1,10 +1,15 @@
 mod proxy;
 mod service;
 
+use crate::Backends;
+use linkerd_addr::NameAddr;
+use linkerd_exp_backoff::ExponentialBackoff;
 use regex::Regex;
 use std::{
+    collections::HashSet,
     fmt,
     hash::{Hash, Hasher},
+    num::NonZeroU32,
     ops::Deref,
     sync::Arc,
     time::Duration,
@@ -12,6 +17,16 @@ use std::{
 use tower::retry::budget::Budget;
 
 pub use self::{proxy::NewProxyRouter, service::NewServiceRouter};
+pub use linkerd_http_route::http::{
+    filter::{self, Filter},
+    r#match::{MatchHeader, MatchHost, MatchPath, MatchQueryParam, MatchRequest},
+};
+
+/// A Gateway API-style route, grouping rules that apply to a set of hosts.
+pub type HttpRoute = linkerd_http_route::http::Route<Route>;
+
+/// A Gateway API-style route rule.
+pub type HttpRule = linkerd_http_route::http::Rule<Route>;
 
 #[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub struct Route {
@@ -19,6 +34,18 @@ pub struct Route {
     response_classes: ResponseClasses,
     retries: Option<Retries>,
     timeout: Option<Duration>,
+    backends: Option<Backends>,
+    filters: Arc<Vec<Filter>>,
+    mirror: Option<RequestMirror>,
+}
+
+/// Mirrors a fraction of a route's requests to another backend.
+///
+/// Responses from the mirror are discarded.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RequestMirror {
+    pub backend: NameAddr,
+    pub ratio: filter::Ratio,
 }
 
 #[derive(Clone, Debug)]
@@ -53,15 +80,41 @@ pub enum ResponseMatch {
 #[derive(Clone, Debug)]
 pub struct Retries {
     budget: Arc<Budget>,
+    per_try_timeout: Option<Duration>,
+    backoff: Option<ExponentialBackoff>,
+    max_attempts: Option<NonZeroU32>,
+    hedge: Option<Hedge>,
+    max_buffered_bytes: Option<usize>,
+}
+
+/// Configures when idempotent requests on a retryable route are hedged, i.e.
+/// when a second attempt is sent while the first is still in flight.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Hedge {
+    /// Hedges requests that have not received a response after a fixed delay.
+    Delay(Duration),
+
+    /// Hedges requests that have not received a response within the given
+    /// percentile, in `(0, 100]`, of the route's observed latency.
+    Percentile(f64),
 }
 
 #[derive(Clone, Default)]
 struct Labels(Arc<std::collections::BTreeMap<String, String>>);
 
+/// Finds the route for a request.
+///
+/// The best-matching Gateway API-style route is preferred. Otherwise, the
+/// first matching legacy route is used.
 fn route_for_request<'r, B>(
+    gateway_routes: &'r [HttpRoute],
     http_routes: &'r [(RequestMatch, Route)],
     request: &http::Request<B>,
 ) -> Option<&'r Route> {
+    if let Some((_, route)) = linkerd_http_route::http::find(gateway_routes, request) {
+        return Some(route);
+    }
+
     for (request_match, route) in http_routes {
         if request_match.is_match(request) {
             return Some(route);
@@ -84,6 +137,9 @@ impl Route {
             response_classes: ResponseClasses(response_classes.into()),
             retries: None,
             timeout: None,
+            backends: None,
+            filters: Arc::new(Vec::new()),
+            mirror: None,
         }
     }
 
@@ -103,13 +159,49 @@ impl Route {
         self.timeout
     }
 
-    pub fn set_retries(&mut self, budget: Arc<Budget>) {
-        self.retries = Some(Retries { budget });
+    /// Returns the backends that requests on this route are distributed over,
+    /// if the route overrides the profile's targets.
+    pub fn backends(&self) -> Option<&Backends> {
+        self.backends.as_ref()
+    }
+
+    /// Returns the filters applied to requests on this route, in order.
+    pub fn filters(&self) -> &Arc<Vec<Filter>> {
+        &self.filters
+    }
+
+    pub fn mirror(&self) -> Option<&RequestMirror> {
+        self.mirror.as_ref()
+    }
+
+    /// Marks the route as retryable, returning its retry configuration so
+    /// that it may be further configured.
+    pub fn set_retries(&mut self, budget: Arc<Budget>) -> &mut Retries {
+        self.retries.insert(Retries {
+            budget,
+            per_try_timeout: None,
+            backoff: None,
+            max_attempts: None,
+            hedge: None,
+            max_buffered_bytes: None,
+        })
     }
 
     pub fn set_timeout(&mut self, timeout: Duration) {
         self.timeout = Some(timeout);
     }
+
+    pub fn set_backends(&mut self, backends: impl Into<Backends>) {
+        self.backends = Some(backends.into());
+    }
+
+    pub fn set_filters(&mut self, filters: impl IntoIterator<Item = Filter>) {
+        self.filters = Arc::new(filters.into_iter().collect());
+    }
+
+    pub fn set_mirror(&mut self, mirror: RequestMirror) {
+        self.mirror = Some(mirror);
+    }
 }
 
 // === impl RequestMatch ===
@@ -193,11 +285,71 @@ impl Retries {
     pub fn budget(&self) -> &Arc<Budget> {
         &self.budget
     }
+
+    /// Returns the timeout applied to each attempt of a request, distinct
+    /// from the route's timeout, which bounds all attempts.
+    pub fn per_try_timeout(&self) -> Option<Duration> {
+        self.per_try_timeout
+    }
+
+    /// Returns the backoff applied between attempts. Requests are retried
+    /// immediately when unset.
+    pub fn backoff(&self) -> Option<&ExponentialBackoff> {
+        self.backoff.as_ref()
+    }
+
+    /// Returns the maximum number of attempts of a request, including the
+    /// original request. Retries are only limited by the budget when unset.
+    pub fn max_attempts(&self) -> Option<NonZeroU32> {
+        self.max_attempts
+    }
+
+    /// Returns the route's hedging policy. Requests are not hedged when unset.
+    pub fn hedge(&self) -> Option<Hedge> {
+        self.hedge
+    }
+
+    /// Returns the maximum number of bytes of a request body that may be
+    /// buffered so that the request can be retried. The proxy's default
+    /// limit applies when unset.
+    pub fn max_buffered_bytes(&self) -> Option<usize> {
+        self.max_buffered_bytes
+    }
+
+    pub fn set_per_try_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.per_try_timeout = Some(timeout);
+        self
+    }
+
+    pub fn set_backoff(&mut self, backoff: ExponentialBackoff) -> &mut Self {
+        self.backoff = Some(backoff);
+        self
+    }
+
+    pub fn set_max_attempts(&mut self, max_attempts: NonZeroU32) -> &mut Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    pub fn set_hedge(&mut self, hedge: Hedge) -> &mut Self {
+        self.hedge = Some(hedge);
+        self
+    }
+
+    pub fn set_max_buffered_bytes(&mut self, max_buffered_bytes: usize) -> &mut Self {
+        self.max_buffered_bytes = Some(max_buffered_bytes);
+        self
+    }
 }
 
 impl PartialEq for Retries {
     fn eq(&self, other: &Self) -> bool {
         Arc::ptr_eq(&self.budget, &other.budget)
+            && self.per_try_timeout == other.per_try_timeout
+            && self.backoff == other.backoff
+            && self.max_attempts == other.max_attempts
+            && self.hedge == other.hedge
+            && self.max_buffered_bytes == other.max_buffered_bytes
     }
 }
 
@@ -206,9 +358,26 @@ impl Eq for Retries {}
 impl Hash for Retries {
     fn hash<H: Hasher>(&self, state: &mut H) {
         state.write_usize(Arc::as_ref(&self.budget) as *const _ as usize);
+        // Backoffs and hedges are not hashable, so they are only compared for
+        // equality.
+        self.per_try_timeout.hash(state);
+        self.max_attempts.hash(state);
+        self.max_buffered_bytes.hash(state);
     }
 }
 
+/// Returns all routes referenced by a profile's route tables.
+fn all_routes(
+    gateway_routes: &[HttpRoute],
+    http_routes: &[(RequestMatch, Route)],
+) -> HashSet<Route> {
+    gateway_routes
+        .iter()
+        .flat_map(|rt| rt.rules.iter().map(|rule| rule.policy.clone()))
+        .chain(http_routes.iter().map(|(_, r)| r.clone()))
+        .collect()
+}
+
 // === impl Labels ===
 
 impl PartialEq for Labels {
@@ -230,3 +399,94 @@ impl fmt::Debug for Labels {
         self.0.fmt(f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Target;
+    use http::header::{HeaderName, HeaderValue};
+
+    #[test]
+    fn gateway_routes_preferred() {
+        let legacy = Route::new(
+            vec![("route".to_string(), "legacy".to_string())].into_iter(),
+            vec![],
+        );
+        let stable = Route::new(
+            vec![("route".to_string(), "stable".to_string())].into_iter(),
+            vec![],
+        );
+        let mut canary = Route::new(
+            vec![("route".to_string(), "canary".to_string())].into_iter(),
+            vec![],
+        );
+        canary.set_backends(vec![Target {
+            addr: "v2.example.com:8080".parse().unwrap(),
+            weight: 1,
+            priority: 0,
+        }]);
+
+        let gateway_routes = vec![HttpRoute {
+            hosts: vec![],
+            rules: vec![
+                HttpRule {
+                    matches: vec![MatchRequest {
+                        path: Some(MatchPath::Prefix("/api".to_string())),
+                        ..MatchRequest::default()
+                    }],
+                    policy: stable.clone(),
+                },
+                HttpRule {
+                    matches: vec![MatchRequest {
+                        path: Some(MatchPath::Prefix("/api".to_string())),
+                        headers: vec![MatchHeader::Exact(
+                            HeaderName::from_static("x-canary"),
+                            HeaderValue::from_static("true"),
+                        )],
+                        ..MatchRequest::default()
+                    }],
+                    policy: canary.clone(),
+                },
+            ],
+        }];
+        let http_routes = vec![(
+            RequestMatch::Path(Box::new(Regex::new("^/.*$").unwrap())),
+            legacy.clone(),
+        )];
+
+        let req = http::Request::builder()
+            .uri("http://example.com/api/users")
+            .body(())
+            .unwrap();
+        assert_eq!(
+            route_for_request(&gateway_routes, &http_routes, &req),
+            Some(&stable)
+        );
+
+        let req = http::Request::builder()
+            .uri("http://example.com/api/users")
+            .header("x-canary", "true")
+            .body(())
+            .unwrap();
+        let route =
+            route_for_request(&gateway_routes, &http_routes, &req).expect("route must match");
+        assert_eq!(
+            route, &canary,
+            "the route with the most specific match must be used"
+        );
+        assert_eq!(
+            route.backends().expect("route must have backends")[0].addr,
+            "v2.example.com:8080".parse().unwrap()
+        );
+
+        let req = http::Request::builder()
+            .uri("http://example.com/other")
+            .body(())
+            .unwrap();
+        assert_eq!(
+            route_for_request(&gateway_routes, &http_routes, &req),
+            Some(&legacy),
+            "legacy routes must be used when no gateway route matches"
+        );
+    }
+}
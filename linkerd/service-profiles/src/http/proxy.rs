@@ -1,10 +1,10 @@
-use super::{RequestMatch, Route};
+use super::{HttpRoute, RequestMatch, Route};
 use crate::{Profile, Receiver, ReceiverStream};
 use futures::{future, prelude::*};
 use linkerd_error::{Error, Result};
 use linkerd_stack::{layer, NewService, Param, Proxy, Service};
 use std::{
-    collections::{hash_map, HashMap, HashSet},
+    collections::{hash_map, HashMap},
     task::{Context, Poll},
 };
 use tracing::{debug, trace};
@@ -31,6 +31,7 @@ pub struct ProxyRouter<T, N, P, S> {
     inner: S,
     target: T,
     rx: ReceiverStream,
+    gateway_routes: Vec<HttpRoute>,
     http_routes: Vec<(RequestMatch, Route)>,
     proxies: HashMap<Route, P>,
 }
@@ -61,6 +62,7 @@ where
             inner,
             target,
             rx: rx.into(),
+            gateway_routes: Vec::new(),
             http_routes: Vec::new(),
             proxies: HashMap::new(),
             new_proxy: self.new_proxy.clone(),
@@ -91,12 +93,19 @@ where
         futures::ready!(self.inner.poll_ready(cx).map_err(Into::into))?;
 
         // If the routes have been updated, update the cache.
-        if let Poll::Ready(Some(Profile { http_routes, .. })) = self.rx.poll_next_unpin(cx) {
-            debug!(routes = %http_routes.len(), "Updating HTTP routes");
-            let routes = http_routes
-                .iter()
-                .map(|(_, r)| r.clone())
-                .collect::<HashSet<_>>();
+        if let Poll::Ready(Some(Profile {
+            gateway_routes,
+            http_routes,
+            ..
+        })) = self.rx.poll_next_unpin(cx)
+        {
+            debug!(
+                gateway_routes = %gateway_routes.len(),
+                routes = %http_routes.len(),
+                "Updating HTTP routes"
+            );
+            let routes = super::all_routes(&gateway_routes, &http_routes);
+            self.gateway_routes = gateway_routes;
             self.http_routes = http_routes;
 
             // Clear out defunct routes before building any missing routes.
@@ -115,7 +124,7 @@ where
     }
 
     fn call(&mut self, req: http::Request<B>) -> Self::Future {
-        match super::route_for_request(&self.http_routes, &req) {
+        match super::route_for_request(&self.gateway_routes, &self.http_routes, &req) {
             None => future::Either::Left({
                 // Use the inner service directly if no route matches the
                 // request.
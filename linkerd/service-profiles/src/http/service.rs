@@ -1,9 +1,9 @@
-use super::{RequestMatch, Route};
+use super::{HttpRoute, RequestMatch, Route};
 use crate::{Profile, Receiver, ReceiverStream};
 use futures::prelude::*;
 use linkerd_stack::{layer, NewService, Oneshot, Param, Service, ServiceExt};
 use std::{
-    collections::{hash_map, HashMap, HashSet},
+    collections::{hash_map, HashMap},
     task::{Context, Poll},
 };
 use tracing::{debug, trace};
@@ -25,6 +25,7 @@ pub struct ServiceRouter<T, N, S> {
     new_route: N,
     target: T,
     rx: ReceiverStream,
+    gateway_routes: Vec<HttpRoute>,
     http_routes: Vec<(RequestMatch, Route)>,
     services: HashMap<Route, S>,
     default: S,
@@ -52,6 +53,7 @@ where
             default,
             target,
             rx: rx.into(),
+            gateway_routes: Vec::new(),
             http_routes: Vec::new(),
             services: HashMap::new(),
             new_route: self.0.clone(),
@@ -73,12 +75,19 @@ where
 
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
         // If the routes have been updated, update the cache.
-        if let Poll::Ready(Some(Profile { http_routes, .. })) = self.rx.poll_next_unpin(cx) {
-            debug!(routes = %http_routes.len(), "Updating HTTP routes");
-            let routes = http_routes
-                .iter()
-                .map(|(_, r)| r.clone())
-                .collect::<HashSet<_>>();
+        if let Poll::Ready(Some(Profile {
+            gateway_routes,
+            http_routes,
+            ..
+        })) = self.rx.poll_next_unpin(cx)
+        {
+            debug!(
+                gateway_routes = %gateway_routes.len(),
+                routes = %http_routes.len(),
+                "Updating HTTP routes"
+            );
+            let routes = super::all_routes(&gateway_routes, &http_routes);
+            self.gateway_routes = gateway_routes;
             self.http_routes = http_routes;
 
             // Clear out defunct routes before building any missing routes.
@@ -98,7 +107,7 @@ where
     }
 
     fn call(&mut self, req: http::Request<B>) -> Self::Future {
-        let inner = match super::route_for_request(&self.http_routes, &req) {
+        let inner = match super::route_for_request(&self.gateway_routes, &self.http_routes, &req) {
             Some(route) => {
                 // If the request matches a route, use the route's service.
                 trace!(?route, "Using route service");
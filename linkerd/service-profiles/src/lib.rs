@@ -8,9 +8,12 @@ use linkerd_proxy_api_resolve::Metadata;
 use std::{
     fmt,
     future::Future,
+    hash::{Hash, Hasher},
     net::SocketAddr,
+    ops::Deref,
     pin::Pin,
     str::FromStr,
+    sync::Arc,
     task::{Context, Poll},
 };
 use thiserror::Error;
@@ -25,6 +28,7 @@ mod proto;
 pub mod split;
 
 pub use self::client::Client;
+pub use linkerd_proxy_balance::{HashKey, LoadBalancer};
 
 #[derive(Clone, Debug)]
 pub struct Receiver {
@@ -40,9 +44,16 @@ struct ReceiverStream {
 pub struct Profile {
     pub addr: Option<LogicalAddr>,
     pub http_routes: Vec<(self::http::RequestMatch, self::http::Route)>,
+
+    /// Gateway API-style routes. These are matched by best-match precedence
+    /// and take precedence over `http_routes`.
+    pub gateway_routes: Vec<self::http::HttpRoute>,
     pub targets: Vec<Target>,
     pub opaque_protocol: bool,
     pub endpoint: Option<(SocketAddr, Metadata)>,
+
+    /// Determines how requests are balanced over the service's endpoints.
+    pub load_balancer: LoadBalancer,
 }
 
 /// A profile lookup target.
@@ -57,8 +68,17 @@ pub struct LogicalAddr(pub NameAddr);
 pub struct Target {
     pub addr: NameAddr,
     pub weight: u32,
+
+    /// The target's priority tier. Requests are dispatched to the tier with
+    /// the lowest value that has ready targets and, if failover is configured,
+    /// that is not failing too many requests.
+    pub priority: u32,
 }
 
+/// A set of weighted targets that overrides a profile's `targets`.
+#[derive(Clone)]
+pub struct Backends(Arc<[Target]>);
+
 #[derive(Clone, Debug)]
 pub struct GetProfileService<P>(P);
 
@@ -129,6 +149,12 @@ impl From<watch::Receiver<Profile>> for Receiver {
     }
 }
 
+impl From<Receiver> for watch::Receiver<Profile> {
+    fn from(Receiver { inner }: Receiver) -> Self {
+        inner
+    }
+}
+
 impl Receiver {
     pub fn logical_addr(&self) -> Option<LogicalAddr> {
         self.inner.borrow().addr.clone()
@@ -142,6 +168,15 @@ impl Receiver {
         self.inner.borrow().endpoint.clone()
     }
 
+    pub fn load_balancer(&self) -> LoadBalancer {
+        self.inner.borrow().load_balancer.clone()
+    }
+
+    /// Returns true if the profile's targets have more than one priority.
+    pub fn is_tiered(&self) -> bool {
+        is_tiered(&self.inner.borrow().targets)
+    }
+
     fn targets(&self) -> Vec<Target> {
         self.inner.borrow().targets.clone()
     }
@@ -240,10 +275,54 @@ impl fmt::Debug for Target {
         f.debug_struct("Target")
             .field("addr", &format_args!("{}", self.addr))
             .field("weight", &self.weight)
+            .field("priority", &self.priority)
             .finish()
     }
 }
 
+// === impl Backends ===
+
+impl Backends {
+    /// Returns true if the backends have more than one priority.
+    pub fn is_tiered(&self) -> bool {
+        is_tiered(&*self.0)
+    }
+}
+
+impl From<Vec<Target>> for Backends {
+    fn from(targets: Vec<Target>) -> Self {
+        Self(targets.into())
+    }
+}
+
+impl Deref for Backends {
+    type Target = [Target];
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+impl PartialEq for Backends {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Backends {}
+
+impl Hash for Backends {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(Arc::as_ref(&self.0) as *const _ as *const () as usize);
+    }
+}
+
+impl fmt::Debug for Backends {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 // === impl DiscoveryRejected ===
 
 impl DiscoveryRejected {
@@ -272,3 +351,7 @@ impl DiscoveryRejected {
         false
     }
 }
+
+fn is_tiered(targets: &[Target]) -> bool {
+    targets.iter().any(|t| t.priority != targets[0].priority)
+}
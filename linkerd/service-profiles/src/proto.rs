@@ -1,4 +1,4 @@
-use crate::{http, LogicalAddr, Profile, Target};
+use crate::{http, LoadBalancer, LogicalAddr, Profile, Target};
 use linkerd2_proxy_api::destination as api;
 use linkerd_addr::NameAddr;
 use linkerd_dns_name::Name;
@@ -28,9 +28,13 @@ pub(super) fn convert_profile(proto: api::DestinationProfile, port: u16) -> Prof
     Profile {
         addr: name.map(move |n| LogicalAddr(NameAddr::from((n, port)))),
         http_routes,
+        // The destination API does not yet describe Gateway API-style routes.
+        gateway_routes: Vec::new(),
         targets,
         opaque_protocol: proto.opaque_protocol,
         endpoint,
+        // The destination API does not yet configure load balancers.
+        load_balancer: LoadBalancer::default(),
     }
 }
 
@@ -62,6 +66,7 @@ fn convert_dst_override(orig: api::WeightedDst) -> Option<Target> {
     Some(Target {
         addr,
         weight: orig.weight,
+        priority: 0,
     })
 }
 
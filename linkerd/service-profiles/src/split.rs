@@ -1,94 +1,195 @@
-use crate::{LogicalAddr, Profile, Receiver, ReceiverStream, Target};
+use crate::{Backends, LoadBalancer, LogicalAddr, Profile, Receiver, ReceiverStream, Target};
 use futures::{prelude::*, ready};
 use indexmap::IndexSet;
 use linkerd_addr::NameAddr;
 use linkerd_error::Error;
 use linkerd_proxy_api_resolve::ConcreteAddr;
+use linkerd_proxy_balance::HashRequest;
 use linkerd_stack::{layer, NewService, Param};
 use rand::distributions::{Distribution, WeightedIndex};
 use rand::{rngs::SmallRng, thread_rng, SeedableRng};
 use std::{
+    collections::HashMap,
     marker::PhantomData,
     pin::Pin,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
     task::{Context, Poll},
+    time::Duration,
 };
+use tokio::time::Instant;
 use tower::ready_cache::ReadyCache;
 use tracing::{debug, trace};
 
-pub fn layer<N, S, Req>() -> impl layer::Layer<N, Service = NewSplit<N, S, Req>> + Clone {
-    // This RNG doesn't need to be cryptographically secure. Small and fast is
-    // preferable.
+/// Configures when a priority tier of targets fails over to the next tier due
+/// to failed requests.
+///
+/// Regardless of this configuration, requests fail over from a tier when none
+/// of its targets are ready.
+#[derive(Copy, Clone, Debug)]
+pub struct Failover {
+    /// The fraction of a tier's requests that may fail before requests fail
+    /// over to the next tier.
+    pub max_failure_rate: f64,
+
+    /// The minimum number of requests in a window before a tier's failure
+    /// rate is considered.
+    pub min_requests: u32,
+
+    /// The window over which a tier's failure rate is measured.
+    pub window: Duration,
+
+    /// How long requests fail over from a tier before they are returned to it.
+    pub cooldown: Duration,
+}
+
+/// Determines whether a target's response indicates that the target failed.
+///
+/// Errors always indicate failure.
+pub trait IsFailure {
+    fn is_failure(&self) -> bool;
+}
+
+/// Returns a layer that splits requests over a profile's targets.
+///
+/// When a `hasher` is set, requests that it hashes are split
+/// deterministically, so that requests with the same hash are dispatched to
+/// the same target while the targets and their weights are unchanged. Other
+/// requests are split randomly.
+pub fn layer<N, S, Req, H: Clone>(
+    failover: Option<Failover>,
+    hasher: Option<H>,
+) -> impl layer::Layer<N, Service = NewSplit<N, S, Req, H>> + Clone {
     layer::mk(move |inner| NewSplit {
         inner,
+        failover,
+        hasher: hasher.clone(),
         _service: PhantomData,
     })
 }
 
 #[derive(Debug)]
-pub struct NewSplit<N, S, Req> {
+pub struct NewSplit<N, S, Req, H> {
     inner: N,
+    failover: Option<Failover>,
+    hasher: Option<H>,
     _service: PhantomData<fn(Req) -> S>,
 }
 
-pub struct Split<T, N, S, Req> {
+pub struct Split<T, N, S, Req, H> {
     rng: SmallRng,
+    hasher: Option<H>,
     rx: ReceiverStream,
+    backends: Option<Backends>,
     target: T,
     new_service: N,
-    distribution: WeightedIndex<u32>,
-    addrs: IndexSet<NameAddr>,
+    failover: Option<Failover>,
+
+    /// The algorithm with which the targets' balancers were built.
+    load_balancer: LoadBalancer,
+
+    /// Targets grouped by priority, in order of preference.
+    tiers: Vec<Tier>,
+
+    /// The tier, and the ready targets within it, chosen by `poll_ready`.
+    selected: Option<Selected>,
     services: ReadyCache<NameAddr, S, Req>,
 }
 
+struct Tier {
+    priority: u32,
+    addrs: IndexSet<NameAddr>,
+    weights: Vec<u32>,
+    distribution: WeightedIndex<u32>,
+    health: Health,
+}
+
+struct Selected {
+    tier: usize,
+    ready: Vec<usize>,
+    distribution: Option<WeightedIndex<u32>>,
+}
+
+/// Tracks the outcomes of a tier's requests.
+struct Health {
+    outcomes: Arc<Outcomes>,
+    window_start: Instant,
+    failed_over_until: Option<Instant>,
+}
+
+#[derive(Debug, Default)]
+struct Outcomes {
+    requests: AtomicU32,
+    failures: AtomicU32,
+}
+
 // === impl NewSplit ===
 
-impl<N: Clone, S, Req> Clone for NewSplit<N, S, Req> {
+impl<N: Clone, S, Req, H: Clone> Clone for NewSplit<N, S, Req, H> {
     fn clone(&self) -> Self {
         Self {
             inner: self.inner.clone(),
+            failover: self.failover,
+            hasher: self.hasher.clone(),
             _service: self._service,
         }
     }
 }
 
-impl<T, N, S, Req> NewService<T> for NewSplit<N, S, Req>
+impl<T, N, S, Req, H> NewService<T> for NewSplit<N, S, Req, H>
 where
-    T: Clone + Param<LogicalAddr> + Param<Receiver>,
+    H: Clone,
+    T: Clone + Param<LogicalAddr> + Param<Receiver> + Param<Option<Backends>>,
     N: NewService<(ConcreteAddr, T), Service = S> + Clone,
     S: tower::Service<Req>,
     S::Error: Into<Error>,
 {
-    type Service = Split<T, N, S, Req>;
+    type Service = Split<T, N, S, Req, H>;
 
     fn new_service(&self, target: T) -> Self::Service {
         let rx: Receiver = target.param();
-        let mut targets = rx.targets();
+        let load_balancer = rx.load_balancer();
+        // If the target overrides the profile's targets (e.g. for a route with
+        // its own backends), the split is fixed to those targets.
+        let backends: Option<Backends> = target.param();
+        let mut targets = match backends {
+            Some(ref b) => b.to_vec(),
+            None => rx.targets(),
+        };
         if targets.is_empty() {
             let LogicalAddr(addr) = target.param();
-            targets.push(Target { addr, weight: 1 })
+            targets.push(Target {
+                addr,
+                weight: 1,
+                priority: 0,
+            })
         }
         trace!(?targets, "Building split service");
 
-        let mut addrs = IndexSet::with_capacity(targets.len());
-        let mut weights = Vec::with_capacity(targets.len());
         let mut services = ReadyCache::default();
         let new_service = self.inner.clone();
-        for Target { weight, addr } in targets.into_iter() {
+        for Target { addr, .. } in targets.iter() {
             services.push(
                 addr.clone(),
                 new_service.new_service((ConcreteAddr(addr.clone()), target.clone())),
             );
-            addrs.insert(addr);
-            weights.push(weight);
         }
 
         Split {
             rx: rx.into(),
+            backends,
             target,
             new_service,
+            failover: self.failover,
+            load_balancer,
+            hasher: self.hasher.clone(),
+            tiers: Tier::group(targets, HashMap::new()),
+            selected: None,
             services,
-            addrs,
-            distribution: WeightedIndex::new(weights).unwrap(),
+            // This RNG doesn't need to be cryptographically secure. Small and
+            // fast is preferable.
             rng: SmallRng::from_rng(&mut thread_rng()).expect("RNG must initialize"),
         }
     }
@@ -96,13 +197,14 @@ where
 
 // === impl Split ===
 
-impl<T, N, S, Req> tower::Service<Req> for Split<T, N, S, Req>
+impl<T, N, S, Req, H> tower::Service<Req> for Split<T, N, S, Req, H>
 where
+    H: HashRequest<Req>,
     Req: Send + 'static,
     T: Clone + Param<LogicalAddr>,
     N: NewService<(ConcreteAddr, T), Service = S> + Clone,
     S: tower::Service<Req> + Send + 'static,
-    S::Response: Send + 'static,
+    S::Response: IsFailure + Send + 'static,
     S::Error: Into<Error>,
     S::Future: Send,
 {
@@ -112,32 +214,67 @@ where
 
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         let mut update = None;
-        while let Poll::Ready(Some(up)) = self.rx.poll_next_unpin(cx) {
-            update = Some(up);
+        // Overridden targets do not change with the profile.
+        if self.backends.is_none() {
+            while let Poll::Ready(Some(up)) = self.rx.poll_next_unpin(cx) {
+                update = Some(up);
+            }
         }
 
         // Every time the profile updates, rebuild the distribution, reusing
         // services that existed in the prior state.
-        if let Some(Profile { mut targets, .. }) = update {
+        if let Some(Profile {
+            mut targets,
+            load_balancer,
+            ..
+        }) = update
+        {
             if targets.is_empty() {
                 let LogicalAddr(addr) = self.target.param();
-                targets.push(Target { addr, weight: 1 })
+                targets.push(Target {
+                    addr,
+                    weight: 1,
+                    priority: 0,
+                })
             }
             debug!(?targets, "Updating");
 
-            // Replace the old set of addresses with an empty set. The
-            // prior set is used to determine whether a new service
-            // needs to be created and what stale services should be
+            // Balancers are configured when they are built, so every target is
+            // rebuilt when the profile's algorithm changes.
+            let rebuild = load_balancer != self.load_balancer;
+            if rebuild {
+                debug!(?load_balancer, "Load balancer changed");
+                self.load_balancer = load_balancer;
+            }
+
+            // Targets are also rebuilt when the profile gains or loses tiers,
+            // since only the balancers of untiered targets fail fast. Prior
+            // services are evicted so that a balancer in failfast does not
+            // accept requests that could fail over.
+            let retier = crate::is_tiered(&targets) != (self.tiers.len() > 1);
+            if retier {
+                debug!("Tiers changed");
+            }
+
+            // The prior set of addresses is used to determine whether a new
+            // service needs to be created and what stale services should be
             // removed.
-            let mut prior_addrs =
-                std::mem::replace(&mut self.addrs, IndexSet::with_capacity(targets.len()));
-            let mut weights = Vec::with_capacity(targets.len());
-
-            // Create an updated distribution and set of services.
-            for Target { weight, addr } in targets.into_iter() {
-                // Reuse the prior services whenever possible.
-                if !prior_addrs.remove(&addr) {
+            let mut prior_addrs = self
+                .tiers
+                .iter()
+                .flat_map(|t| t.addrs.iter().cloned())
+                .collect::<IndexSet<_>>();
+            for Target { addr, .. } in targets.iter() {
+                // Reuse the prior services whenever possible. Replaced
+                // services are used until their replacements are ready.
+                // Services that failed have been removed and are recreated.
+                let failed = self.services.get_ready(addr).is_none()
+                    && !self.services.pending_contains(addr);
+                if !prior_addrs.remove(addr) || rebuild || retier || failed {
                     debug!(%addr, "Creating target");
+                    if retier {
+                        self.services.evict(addr);
+                    }
                     let svc = self
                         .new_service
                         .new_service((ConcreteAddr(addr.clone()), self.target.clone()));
@@ -145,11 +282,16 @@ where
                 } else {
                     trace!(%addr, "Target already exists");
                 }
-                self.addrs.insert(addr);
-                weights.push(weight);
             }
 
-            self.distribution = WeightedIndex::new(weights).unwrap();
+            // Tiers that persist retain their health.
+            let prior_health = self
+                .tiers
+                .drain(..)
+                .map(|t| (t.priority, t.health))
+                .collect();
+            self.tiers = Tier::group(targets, prior_health);
+            self.selected = None;
 
             // Remove all prior services that did not exist in the new
             // set of targets.
@@ -158,19 +300,529 @@ where
             }
         }
 
-        // Wait for all target services to be ready. If any services fail, then
-        // the whole service fails.
-        Poll::Ready(ready!(self.services.poll_pending(cx)).map_err(Into::into))
+        // When all targets have the same priority, wait for all target
+        // services to be ready. If any services fail, then the whole service
+        // fails.
+        if self.tiers.len() == 1 {
+            return Poll::Ready(ready!(self.services.poll_pending(cx)).map_err(Into::into));
+        }
+
+        // Otherwise, requests are dispatched to the ready targets of the most
+        // preferred tier that has any. Ready services are checked so that
+        // those that are no longer ready are driven with the pending services.
+        //
+        // Services that fail are removed so that requests fail over to the
+        // remaining tiers. The split only fails once no targets remain.
+        let mut failed = None;
+        for tier in self.tiers.iter() {
+            for addr in tier.addrs.iter() {
+                if self.services.get_ready(addr).is_some() {
+                    if let Err(error) = self.services.check_ready(cx, addr) {
+                        debug!(addr = %error.0, error = %error.1, "Target failed");
+                        failed = Some(error);
+                    }
+                }
+            }
+        }
+        while let Poll::Ready(Err(error)) = self.services.poll_pending(cx) {
+            debug!(addr = %error.0, error = %error.1, "Target failed");
+            failed = Some(error);
+        }
+        if self.services.is_empty() {
+            if let Some(error) = failed {
+                return Poll::Ready(Err(error.into()));
+            }
+        }
+
+        // Tiers that are failing too many requests are only used when no other
+        // tier is ready.
+        let now = Instant::now();
+        let failover = self.failover;
+        let failed_over = self
+            .tiers
+            .iter_mut()
+            .map(|tier| match failover.as_ref() {
+                Some(f) => tier.health.is_failed_over(tier.priority, f, now),
+                None => false,
+            })
+            .collect::<Vec<_>>();
+        let candidates = (0..self.tiers.len())
+            .filter(|i| !failed_over[*i])
+            .chain((0..self.tiers.len()).filter(|i| failed_over[*i]));
+        for idx in candidates {
+            let tier = &self.tiers[idx];
+            let ready = (0..tier.addrs.len())
+                .filter(|i| {
+                    let addr = tier.addrs.get_index(*i).expect("index must be valid");
+                    self.services.get_ready(addr).is_some()
+                })
+                .collect::<Vec<_>>();
+            if ready.is_empty() {
+                continue;
+            }
+
+            let prior = self.selected.as_ref().map(|s| s.tier);
+            if prior != Some(idx) {
+                debug!(priority = tier.priority, "Dispatching to tier");
+            }
+            if prior != Some(idx) || self.selected.as_ref().map(|s| &s.ready) != Some(&ready) {
+                // Weights are only used to choose among a tier's ready
+                // targets when some of its targets are not ready.
+                let distribution = if ready.len() == tier.addrs.len() {
+                    None
+                } else {
+                    WeightedIndex::new(ready.iter().map(|i| tier.weights[*i])).ok()
+                };
+                self.selected = Some(Selected {
+                    tier: idx,
+                    ready,
+                    distribution,
+                });
+            }
+            return Poll::Ready(Ok(()));
+        }
+
+        self.selected = None;
+        Poll::Pending
     }
 
     fn call(&mut self, req: Req) -> Self::Future {
-        let idx = if self.addrs.len() == 1 {
-            0
-        } else {
-            self.distribution.sample(&mut self.rng)
+        let hash = self.hasher.as_ref().and_then(|h| h.hash_request(&req));
+        let (tier, idx) = match self.selected.as_ref() {
+            // Only some of the tier's targets are ready.
+            Some(Selected {
+                tier,
+                ready,
+                distribution,
+            }) if ready.len() < self.tiers[*tier].addrs.len() => {
+                let tier = &self.tiers[*tier];
+                let i = match hash {
+                    Some(hash) => {
+                        let weights = ready.iter().map(|i| tier.weights[*i]).collect::<Vec<_>>();
+                        pick(&weights, hash)
+                    }
+                    None => distribution.as_ref().map_or(0, |d| d.sample(&mut self.rng)),
+                };
+                (tier, ready[i])
+            }
+            Some(Selected { tier, .. }) => {
+                let tier = &self.tiers[*tier];
+                (tier, tier.select(hash, &mut self.rng))
+            }
+            // All targets have the same priority and are ready.
+            None => (&self.tiers[0], self.tiers[0].select(hash, &mut self.rng)),
         };
-        let addr = self.addrs.get_index(idx).expect("invalid index");
-        trace!(?addr, "Dispatching");
-        Box::pin(self.services.call_ready(addr, req).err_into::<Error>())
+        let addr = tier.addrs.get_index(idx).expect("invalid index");
+        trace!(?addr, priority = tier.priority, "Dispatching");
+
+        let call = self.services.call_ready(addr, req).err_into::<Error>();
+        if self.failover.is_none() || self.tiers.len() == 1 {
+            return Box::pin(call);
+        }
+        let outcomes = tier.health.outcomes.clone();
+        Box::pin(call.map(move |res| {
+            outcomes.record(res.as_ref().map(IsFailure::is_failure).unwrap_or(true));
+            res
+        }))
+    }
+}
+
+// === impl Tier ===
+
+impl Tier {
+    /// Groups targets into tiers in order of priority, retaining the health of
+    /// tiers that existed previously.
+    fn group(targets: Vec<Target>, mut prior: HashMap<u32, Health>) -> Vec<Self> {
+        let mut tiers = Vec::<Self>::new();
+        for Target {
+            addr,
+            weight,
+            priority,
+        } in targets.into_iter()
+        {
+            let idx = match tiers.binary_search_by_key(&priority, |t| t.priority) {
+                Ok(idx) => idx,
+                Err(idx) => {
+                    let health = prior.remove(&priority).unwrap_or_default();
+                    tiers.insert(
+                        idx,
+                        Tier {
+                            priority,
+                            addrs: IndexSet::new(),
+                            weights: Vec::new(),
+                            distribution: WeightedIndex::new([1]).unwrap(),
+                            health,
+                        },
+                    );
+                    idx
+                }
+            };
+            let tier = &mut tiers[idx];
+            if tier.addrs.insert(addr) {
+                tier.weights.push(weight);
+            }
+        }
+        for tier in tiers.iter_mut() {
+            // If all of a tier's targets have no weight, they are weighted
+            // equally.
+            tier.distribution = WeightedIndex::new(tier.weights.iter().copied())
+                .unwrap_or_else(|_| WeightedIndex::new(tier.weights.iter().map(|_| 1)).unwrap());
+        }
+        tiers
+    }
+
+    /// Selects a target by its hash, if any, or randomly.
+    fn select(&self, hash: Option<u64>, rng: &mut SmallRng) -> usize {
+        if self.addrs.len() == 1 {
+            return 0;
+        }
+        match hash {
+            Some(hash) => pick(&self.weights, hash),
+            None => self.distribution.sample(rng),
+        }
+    }
+}
+
+/// Maps a hash onto weighted targets, so that each target is selected by a
+/// share of hashes proportional to its weight. If no target has weight, the
+/// targets are weighted equally.
+fn pick(weights: &[u32], hash: u64) -> usize {
+    let total = weights.iter().copied().map(u64::from).sum::<u64>();
+    if total == 0 {
+        return (hash % weights.len() as u64) as usize;
+    }
+    let mut point = hash % total;
+    for (i, weight) in weights.iter().copied().map(u64::from).enumerate() {
+        if point < weight {
+            return i;
+        }
+        point -= weight;
+    }
+    unreachable!("the point must be less than the total weight")
+}
+
+// === impl Health ===
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            outcomes: Arc::default(),
+            window_start: Instant::now(),
+            failed_over_until: None,
+        }
+    }
+}
+
+impl Health {
+    /// Returns true if requests should fail over from this tier because too
+    /// many of its requests have failed recently.
+    fn is_failed_over(&mut self, priority: u32, failover: &Failover, now: Instant) -> bool {
+        if let Some(until) = self.failed_over_until {
+            if now < until {
+                return true;
+            }
+            debug!(priority, "Returning requests to tier");
+            self.failed_over_until = None;
+            self.reset(now);
+        }
+
+        let requests = self.outcomes.requests.load(Ordering::Acquire);
+        let failures = self.outcomes.failures.load(Ordering::Acquire);
+        if requests >= failover.min_requests.max(1)
+            && f64::from(failures) > f64::from(requests) * failover.max_failure_rate
+        {
+            debug!(priority, requests, failures, "Failing over from tier");
+            self.failed_over_until = Some(now + failover.cooldown);
+            self.reset(now);
+            return true;
+        }
+
+        if now.saturating_duration_since(self.window_start) >= failover.window {
+            self.reset(now);
+        }
+        false
+    }
+
+    fn reset(&mut self, now: Instant) {
+        self.outcomes.requests.store(0, Ordering::Release);
+        self.outcomes.failures.store(0, Ordering::Release);
+        self.window_start = now;
+    }
+}
+
+// === impl Outcomes ===
+
+impl Outcomes {
+    fn record(&self, failed: bool) {
+        self.requests.fetch_add(1, Ordering::AcqRel);
+        if failed {
+            self.failures.fetch_add(1, Ordering::AcqRel);
+        }
+    }
+}
+
+// === impl IsFailure ===
+
+impl<B> IsFailure for http::Response<B> {
+    fn is_failure(&self) -> bool {
+        self.status().is_server_error()
+    }
+}
+
+impl IsFailure for () {
+    fn is_failure(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linkerd_proxy_balance::NoHash;
+    use std::sync::atomic::AtomicBool;
+    use tokio::sync::watch;
+    use tower::{Layer, ServiceExt};
+
+    #[derive(Clone)]
+    struct Logical(Receiver);
+
+    impl Param<LogicalAddr> for Logical {
+        fn param(&self) -> LogicalAddr {
+            LogicalAddr("logical.example.com:80".parse().unwrap())
+        }
+    }
+
+    impl Param<Receiver> for Logical {
+        fn param(&self) -> Receiver {
+            self.0.clone()
+        }
+    }
+
+    impl Param<Option<Backends>> for Logical {
+        fn param(&self) -> Option<Backends> {
+            None
+        }
+    }
+
+    /// A target service that is ready only while `ready` is set, or that
+    /// fails if `fail` is set.
+    struct Svc {
+        addr: NameAddr,
+        ready: Arc<AtomicBool>,
+        fail: bool,
+    }
+
+    impl tower::Service<()> for Svc {
+        type Response = http::Response<NameAddr>;
+        type Error = Error;
+        type Future = future::Ready<Result<Self::Response, Error>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Error>> {
+            if self.fail {
+                Poll::Ready(Err("target failed".into()))
+            } else if self.ready.load(Ordering::SeqCst) {
+                Poll::Ready(Ok(()))
+            } else {
+                Poll::Pending
+            }
+        }
+
+        fn call(&mut self, (): ()) -> Self::Future {
+            future::ok(http::Response::new(self.addr.clone()))
+        }
+    }
+
+    fn target(addr: &str, weight: u32, priority: u32) -> Target {
+        Target {
+            addr: addr.parse().unwrap(),
+            weight,
+            priority,
+        }
+    }
+
+    #[test]
+    fn groups_targets_by_priority() {
+        let tiers = Tier::group(
+            vec![
+                target("remote.example.com:80", 1, 1),
+                target("a.example.com:80", 3, 0),
+                target("b.example.com:80", 0, 0),
+            ],
+            HashMap::new(),
+        );
+        assert_eq!(
+            tiers.iter().map(|t| t.priority).collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        assert_eq!(tiers[0].addrs.len(), 2);
+        assert_eq!(tiers[0].weights, vec![3, 0]);
+        assert_eq!(tiers[1].addrs.len(), 1);
+    }
+
+    #[test]
+    fn picks_targets_by_weight() {
+        let weights = [1, 0, 3];
+        let picks = (0..400).map(|h| pick(&weights, h)).collect::<Vec<_>>();
+        assert_eq!(picks.iter().filter(|i| **i == 0).count(), 100);
+        assert_eq!(picks.iter().filter(|i| **i == 1).count(), 0);
+        assert_eq!(picks.iter().filter(|i| **i == 2).count(), 300);
+
+        // The same hash always picks the same target.
+        assert_eq!(pick(&weights, 12345), pick(&weights, 12345));
+        assert_eq!(pick(&[0, 0], 3), 1);
+    }
+
+    #[test]
+    fn fails_over_from_failing_tier() {
+        let failover = Failover {
+            max_failure_rate: 0.5,
+            min_requests: 4,
+            window: Duration::from_secs(10),
+            cooldown: Duration::from_secs(30),
+        };
+        let t0 = Instant::now();
+        let mut health = Health::default();
+
+        // Too few requests have been observed to fail over.
+        health.outcomes.record(false);
+        health.outcomes.record(true);
+        health.outcomes.record(true);
+        assert!(!health.is_failed_over(0, &failover, t0));
+
+        // Half of the requests have failed.
+        health.outcomes.record(false);
+        assert!(!health.is_failed_over(0, &failover, t0));
+
+        health.outcomes.record(true);
+        assert!(health.is_failed_over(0, &failover, t0));
+
+        // Requests return to the tier once the cooldown elapses.
+        assert!(health.is_failed_over(0, &failover, t0 + Duration::from_secs(29)));
+        assert!(!health.is_failed_over(0, &failover, t0 + Duration::from_secs(30)));
+    }
+
+    #[tokio::test]
+    async fn fails_over_from_unready_tier() {
+        let profile = Profile {
+            targets: vec![
+                target("local.example.com:80", 1, 0),
+                target("remote.example.com:80", 1, 1),
+            ],
+            ..Default::default()
+        };
+        let (_tx, rx) = watch::channel(profile);
+        let local_ready = Arc::new(AtomicBool::new(false));
+        let new_target = {
+            let local_ready = local_ready.clone();
+            move |(ConcreteAddr(addr), _): (ConcreteAddr, Logical)| {
+                let ready = if addr.to_string() == "local.example.com:80" {
+                    local_ready.clone()
+                } else {
+                    Arc::new(AtomicBool::new(true))
+                };
+                Svc {
+                    addr,
+                    ready,
+                    fail: false,
+                }
+            }
+        };
+        let mut split = layer::<_, _, (), NoHash>(None, None)
+            .layer(new_target)
+            .new_service(Logical(rx.into()));
+
+        // Requests fail over while the preferred tier is not ready.
+        for _ in 0..10 {
+            let rsp = split.ready().await.unwrap().call(()).await.unwrap();
+            assert_eq!(rsp.body().to_string(), "remote.example.com:80");
+        }
+
+        // Requests return to the preferred tier once it becomes ready.
+        local_ready.store(true, Ordering::SeqCst);
+        for _ in 0..10 {
+            let rsp = split.ready().await.unwrap().call(()).await.unwrap();
+            assert_eq!(rsp.body().to_string(), "local.example.com:80");
+        }
+    }
+
+    #[tokio::test]
+    async fn fails_over_from_failed_tier() {
+        let profile = Profile {
+            targets: vec![
+                target("local.example.com:80", 1, 0),
+                target("remote.example.com:80", 1, 1),
+            ],
+            ..Default::default()
+        };
+        let (tx, rx) = watch::channel(profile);
+        let remote_fails = Arc::new(AtomicBool::new(false));
+        let new_target = {
+            let remote_fails = remote_fails.clone();
+            move |(ConcreteAddr(addr), _): (ConcreteAddr, Logical)| {
+                let fail = addr.to_string() == "local.example.com:80"
+                    || remote_fails.load(Ordering::SeqCst);
+                Svc {
+                    addr,
+                    ready: Arc::new(AtomicBool::new(true)),
+                    fail,
+                }
+            }
+        };
+        let mut split = layer::<_, _, (), NoHash>(None, None)
+            .layer(new_target)
+            .new_service(Logical(rx.into()));
+
+        // Requests fail over while the preferred tier's targets fail.
+        for _ in 0..10 {
+            let rsp = split.ready().await.unwrap().call(()).await.unwrap();
+            assert_eq!(rsp.body().to_string(), "remote.example.com:80");
+        }
+
+        // The split fails once no tier has targets that haven't failed.
+        remote_fails.store(true, Ordering::SeqCst);
+        tx.send(Profile {
+            targets: vec![
+                target("local.example.com:80", 1, 0),
+                target("other.example.com:80", 1, 1),
+            ],
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(split.ready().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rebuilds_targets_when_load_balancer_changes() {
+        let profile = Profile {
+            targets: vec![target("a.example.com:80", 1, 0)],
+            ..Default::default()
+        };
+        let (tx, rx) = watch::channel(profile.clone());
+        let built = Arc::new(AtomicU32::new(0));
+        let new_target = {
+            let built = built.clone();
+            move |_: (ConcreteAddr, Logical)| {
+                built.fetch_add(1, Ordering::SeqCst);
+                tower::service_fn(|()| future::ok::<_, Error>(()))
+            }
+        };
+        let mut split = layer::<_, _, (), NoHash>(None, None)
+            .layer(new_target)
+            .new_service(Logical(rx.into()));
+        split.ready().await.unwrap();
+        assert_eq!(built.load(Ordering::SeqCst), 1);
+
+        // Targets are reused while the algorithm is unchanged.
+        tx.send(profile.clone()).unwrap();
+        split.ready().await.unwrap();
+        assert_eq!(built.load(Ordering::SeqCst), 1);
+
+        tx.send(Profile {
+            load_balancer: LoadBalancer::RoundRobin,
+            ..profile
+        })
+        .unwrap();
+        split.ready().await.unwrap();
+        assert_eq!(built.load(Ordering::SeqCst), 2);
     }
 }
@@ -87,13 +87,15 @@ fn main() {
             }
         }
 
-        let dst_addr = app.dst_addr();
-        match dst_addr.identity.value() {
-            None => info!("Destinations resolved via {}", dst_addr.addr),
-            Some(tls) => info!(
-                "Destinations resolved via {} ({})",
-                dst_addr.addr, tls.server_id
-            ),
+        match app.dst_addr() {
+            None => info!("Destinations resolved from a local file"),
+            Some(dst_addr) => match dst_addr.identity.value() {
+                None => info!("Destinations resolved via {}", dst_addr.addr),
+                Some(tls) => info!(
+                    "Destinations resolved via {} ({})",
+                    dst_addr.addr, tls.server_id
+                ),
+            },
         }
 
         if let Some(oc) = app.opencensus_addr() {